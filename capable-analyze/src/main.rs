@@ -0,0 +1,230 @@
+//! Offline analysis of the captures of `capable`, away from the host where they were
+//! collected. It needs neither privileges nor eBPF, so a production host only has to
+//! capture while the profile and the policies are generated on another machine.
+//!
+//! A capture is a recording (`capable --record FILE`) or a debug dump directory
+//! (`capable --debug-dump DIR`). The profiles of several captures are merged.
+
+use std::fs::{read_dir, read_to_string, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use capable_common::{Nsid, Pid};
+use capable_core::policy::Policy;
+use capable_core::profile::{ProgramResult, TimeWindow};
+use capable_core::recording::{read_recording, Recording};
+use capable_core::replay::Replay;
+use capable_core::{containers, phase};
+use tracing::{debug, info};
+use tracing_subscriber::filter::LevelFilter;
+
+/// Files of a --debug-dump directory
+const DUMP_REQUESTS_NAME: &str = "requests.jsonl";
+const DUMP_DBUS_NAME: &str = "dbus.json";
+const DUMP_STRACE_PREFIX: &str = "strace-";
+const DUMP_STRACE_SUFFIX: &str = ".log";
+
+/// Stands for the command of a debug dump in the policies, a dump does not record it
+const UNKNOWN_COMMAND: &str = "COMMAND";
+
+struct Cli {
+    captures: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    policy: Option<Policy>,
+    /// Capabilities last requested within this time after the start are startup ones
+    startup: Duration,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Cli {
+            captures: Vec::new(),
+            output: None,
+            policy: None,
+            startup: phase::DEFAULT_STARTUP,
+        }
+    }
+}
+
+fn getopt<S, I>(s: I) -> Result<Cli, anyhow::Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut args = Cli::default();
+    let mut iter = s.into_iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_ref() {
+            "-o" | "--output" => {
+                args.output = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--policy" => {
+                let policy = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--policy requires a sandbox name"))?;
+                args.policy = Some(policy.as_ref().parse()?);
+            }
+            "--startup" => {
+                let seconds = iter
+                    .next()
+                    .and_then(|s| s.as_ref().parse().ok())
+                    .ok_or(anyhow::anyhow!("--startup requires a number of seconds"))?;
+                args.startup = Duration::from_secs(seconds);
+            }
+            "-h" | "--help" => {
+                println!("Usage: capable-analyze [-o FILE] [--policy NAME] [--startup SECONDS] CAPTURE...");
+                println!("A capture is a recording of capable --record or a directory of capable --debug-dump.");
+                std::process::exit(0);
+            }
+            capture => args.captures.push(PathBuf::from(capture)),
+        }
+    }
+    if args.captures.is_empty() {
+        return Err(anyhow::anyhow!("capable-analyze requires a capture"));
+    }
+    Ok(args)
+}
+
+/// Profile of a capture, with the commands it ran and its strace log
+struct Analysis {
+    commands: Vec<Vec<String>>,
+    strace_logs: Vec<String>,
+    result: ProgramResult,
+}
+
+/// Namespace of the first request of the replay and its first process
+fn root(replay: &Replay, path: &Path) -> Result<(Nsid, Pid), anyhow::Error> {
+    let ns = replay
+        .root()
+        .ok_or(anyhow::anyhow!("No request in {}", path.display()))?;
+    let pid = replay
+        .entries()
+        .iter()
+        .filter(|entry| entry.ns == ns)
+        .map(|entry| entry.pid)
+        .min()
+        .unwrap_or_default();
+    Ok((ns, pid))
+}
+
+fn analyze(
+    replay: Replay,
+    commands: Vec<Vec<String>>,
+    ns: Nsid,
+    window: Option<TimeWindow>,
+) -> Analysis {
+    Analysis {
+        commands,
+        strace_logs: replay.strace().map(str::to_string).into_iter().collect(),
+        result: replay.result(ns, window),
+    }
+}
+
+fn analyze_recording(path: &Path) -> Result<Analysis, anyhow::Error> {
+    let Recording { replay, run } = read_recording(path)?;
+    match run {
+        Some(run) => Ok(analyze(replay, vec![run.command], run.ns, Some(run.window))),
+        // recordings of daemon mode have no run, the first namespace is analyzed
+        None => {
+            let (ns, pid) = root(&replay, path)?;
+            debug!(
+                "{} has no run, analyzing the namespace of {}",
+                path.display(),
+                pid
+            );
+            Ok(analyze(replay, Vec::new(), ns, None))
+        }
+    }
+}
+
+/// strace logs of a debug dump, in the order of the commands
+fn dump_strace_logs(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut logs: Vec<(usize, PathBuf)> = Vec::new();
+    for entry in read_dir(dir).with_context(|| format!("Unable to read {}", dir.display()))? {
+        let path = entry?.path();
+        let index = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(DUMP_STRACE_PREFIX))
+            .and_then(|name| name.strip_suffix(DUMP_STRACE_SUFFIX))
+            .and_then(|index| index.parse().ok());
+        if let Some(index) = index {
+            logs.push((index, path));
+        }
+    }
+    logs.sort();
+    Ok(logs.into_iter().map(|(_, path)| path).collect())
+}
+
+fn analyze_dump(dir: &Path) -> Result<Analysis, anyhow::Error> {
+    let mut replay = Replay::new();
+    replay.requests_file(dir.join(DUMP_REQUESTS_NAME))?;
+    let mut strace_log = String::new();
+    for path in dump_strace_logs(dir)? {
+        strace_log.push_str(
+            &read_to_string(&path)
+                .with_context(|| format!("Unable to read strace log {}", path.display()))?,
+        );
+    }
+    if !strace_log.is_empty() {
+        replay.strace_log(strace_log);
+    }
+    let dbus = dir.join(DUMP_DBUS_NAME);
+    if dbus.exists() {
+        replay.dbus_file(dbus)?;
+    }
+    let (ns, _) = root(&replay, dir)?;
+    let command = vec![UNKNOWN_COMMAND.to_string()];
+    Ok(analyze(replay, vec![command], ns, None))
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    let cli_args = getopt(std::env::args()).context("Arguments error")?;
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(LevelFilter::INFO)
+        .init();
+
+    let mut merged: Option<Analysis> = None;
+    for capture in &cli_args.captures {
+        let analysis = if capture.is_dir() {
+            analyze_dump(capture)?
+        } else {
+            analyze_recording(capture)?
+        };
+        info!(
+            "{}: {} capabilities",
+            capture.display(),
+            analysis.result.capabilities.len()
+        );
+        match merged.as_mut() {
+            Some(merged) => {
+                merged.commands.extend(analysis.commands);
+                merged.strace_logs.extend(analysis.strace_logs);
+                merged.result.merge(analysis.result);
+            }
+            None => merged = Some(analysis),
+        }
+    }
+    let mut analysis = merged.expect("getopt requires a capture");
+    analysis.result.containers = containers::compare(&analysis.result);
+    phase::classify(&mut analysis.result, cli_args.startup);
+
+    let json = serde_json::to_string_pretty(&analysis.result)?;
+    if let Some(output) = &cli_args.output {
+        let mut file = File::create(output)
+            .with_context(|| format!("Unable to create {}", output.display()))?;
+        writeln!(file, "{}", json)?;
+    } else {
+        println!("{}", json);
+    }
+    if let Some(policy) = cli_args.policy {
+        eprint!(
+            "{}",
+            policy.render(&analysis.commands, &analysis.strace_logs, &analysis.result)
+        );
+    }
+    Ok(())
+}
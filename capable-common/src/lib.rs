@@ -1,4 +1,17 @@
-#![no_std]
+//! Types shared by the eBPF programs and `capable`.
+//!
+//! With the `client` feature, [`profile`] also holds the profiles `capable` writes, for
+//! the programs consuming its output:
+//!
+//! ```ignore
+//! let profile: capable_common::profile::ProgramResult = serde_json::from_str(&output)?;
+//! ```
+#![cfg_attr(not(feature = "client"), no_std)]
+
+#[cfg(feature = "client")]
+pub mod profile;
+#[cfg(feature = "client")]
+pub mod stats;
 
 #[cfg(feature = "aya")]
 use aya::Pod;
@@ -22,8 +35,163 @@ pub struct Request {
     pub uid_gid : UidGid,
     pub pnsid_nsid : PnsidNsid,
     pub capability : u8,
+    /// Set by the return probe when the kernel denied the capability
+    pub denied : u8,
     pub stackid : StackId,
+    /// bpf_ktime_get_ns when the capability was checked, nanoseconds since boot
+    pub time : u64,
+    /// cgroup v2 id of the process, the inode of its cgroup directory
+    pub cgroup : u64,
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for Request {}
+
+/// Counters of the eBPF program, one per cpu
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    /// Requests stored in the requests map
+    pub requests : u64,
+    /// Requests lost as the requests map was full
+    pub drops : u64,
+    /// Bitmask of the requested capabilities
+    pub capabilities : Capabilities,
+    /// Requests of kernel threads attributed to the process of their cred
+    pub deferred : u64,
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for Stats {}
+
+/// Length of a module name in the kernel, MODULE_NAME_LEN
+pub const MODULE_NAME_LEN : usize = 56;
+
+/// Module the kernel requested while handling a syscall of a process, with request_module
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ModuleRequest {
+    /// PID namespace of the process
+    pub ns : Nsid,
+    /// NUL terminated name, as given to request_module (e.g. netdev-wg0)
+    pub name : [u8; MODULE_NAME_LEN],
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for ModuleRequest {}
+
+pub const SYSCTL_NAME_LEN : usize = 64;
+pub const SYSCTL_VALUE_LEN : usize = 64;
+
+/// Access to a sysctl by a process of the profiled cgroup
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SysctlRequest {
+    /// 1 for a write, 0 for a read
+    pub write : u8,
+    /// NUL terminated name, with / separators (e.g. net/ipv4/ip_forward)
+    pub name : [u8; SYSCTL_NAME_LEN],
+    /// NUL terminated value written, empty for a read
+    pub value : [u8; SYSCTL_VALUE_LEN],
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for SysctlRequest {}
+
+/// libcap function a process called to change its own capabilities
+pub const LIBCAP_CAP_SET_PROC : u8 = 0;
+/// libcap-ng function a process called to change its own capabilities
+pub const LIBCAP_CAPNG_APPLY : u8 = 1;
+
+/// Capability sets of a thread when it returns from cap_set_proc or capng_apply
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LibcapCall {
+    pub pid : Pid,
+    /// PID namespace of the process
+    pub ns : Nsid,
+    /// LIBCAP_CAP_SET_PROC or LIBCAP_CAPNG_APPLY
+    pub function : u8,
+    /// 1 when the function returned an error
+    pub failed : u8,
+    pub effective : Capabilities,
+    pub permitted : Capabilities,
+    pub inheritable : Capabilities,
+    pub ambient : Capabilities,
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for LibcapCall {}
+
+/// Number of path components recorded with the DAC capability checks, from the file up
+pub const DAC_PATH_COMPONENTS : usize = 4;
+/// Length of each recorded component, longer names are truncated
+pub const DAC_COMPONENT_LEN : usize = 32;
+/// Length of the paths recorded with the DAC capability checks
+pub const DAC_PATH_LEN : usize = DAC_PATH_COMPONENTS * DAC_COMPONENT_LEN;
+
+/// File checked by the LSM hook following a check of CAP_DAC_OVERRIDE or
+/// CAP_DAC_READ_SEARCH
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DacAccess {
+    /// PID namespace of the process
+    pub ns : Nsid,
+    /// u32 rather than u8, the key of the set has no padding
+    pub capability : u32,
+    /// DAC_PATH_COMPONENTS NUL terminated names of DAC_COMPONENT_LEN bytes, the file first
+    /// then its parents, up to the root of its mount
+    pub path : [u8; DAC_PATH_LEN],
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for DacAccess {}
+
+/// Number of components recorded with the cgroup of a request, from the cgroup up
+pub const CGROUP_PATH_COMPONENTS : usize = 6;
+/// Length of each recorded component, longer names are truncated
+pub const CGROUP_COMPONENT_LEN : usize = 64;
+/// Length of the recorded cgroup paths
+pub const CGROUP_PATH_LEN : usize = CGROUP_PATH_COMPONENTS * CGROUP_COMPONENT_LEN;
+
+/// Path of a cgroup in the cgroup v2 hierarchy, recorded with its first request as the
+/// cgroup of a short-lived unit may already be removed when the requests are read
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CgroupPath {
+    /// CGROUP_PATH_COMPONENTS NUL terminated names of CGROUP_COMPONENT_LEN bytes, the
+    /// cgroup first then its parents, below the root of the hierarchy
+    pub path : [u8; CGROUP_PATH_LEN],
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for CgroupPath {}
+
+/// Process stopped by the probe on a capability its namespace has not decided yet, with
+/// --interactive
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ApprovalRequest {
+    /// Thread group of the process, the one to resume
+    pub pid : Pid,
+    /// Interactive PID namespace the process belongs to
+    pub ns : Nsid,
+    /// u32 rather than u8, the key of the set has no padding
+    pub capability : u32,
+}
+
+#[cfg(feature = "aya")]
+unsafe impl Pod for ApprovalRequest {}
+
+/// Capability denied by the BPF LSM enforcement to a process of an enforced cgroup
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LsmDenial {
+    /// cgroup v2 id of the process
+    pub cgroup : u64,
+    /// u64 rather than u8, the key of the map has no padding
+    pub capability : u64,
 }
 
 #[cfg(feature = "aya")]
-unsafe impl Pod for Request {}
\ No newline at end of file
+unsafe impl Pod for LsmDenial {}
@@ -0,0 +1,693 @@
+//! Profile written by `capable`, the schema of its JSON output. Consumers parse it with
+//! serde_json into a [`ProgramResult`].
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+
+use crate::stats::CollectorStats;
+
+bitflags! {
+    /// Rights on a file, serialized as the letters R, W and X
+    #[derive(Clone, Copy, Debug)]
+    pub struct Access: u8 {
+        const R   = 0b100;
+        const W   = 0b010;
+        const X   = 0b001;
+        const RW  = 0b110;
+        const RX  = 0b101;
+        const WX  = 0b011;
+        const RWX = 0b111;
+    }
+}
+
+impl PartialEq for Access {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl std::fmt::Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut access = String::new();
+        if self.contains(Access::R) {
+            access.push('R');
+        }
+        if self.contains(Access::W) {
+            access.push('W');
+        }
+        if self.contains(Access::X) {
+            access.push('X');
+        }
+        write!(f, "{}", access)
+    }
+}
+
+impl Serialize for Access {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Access {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let mut access = Access::empty();
+        for c in s.chars() {
+            match c {
+                'R' => access |= Access::R,
+                'W' => access |= Access::W,
+                'X' => access |= Access::X,
+                _ => {
+                    return Err(serde::de::Error::custom(format!(
+                        "Unknown access right: {}",
+                        c
+                    )))
+                }
+            }
+        }
+        Ok(access)
+    }
+}
+
+/// Start and end of a profiling run, in seconds since the Unix epoch
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct TimeWindow {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Set-ID bits of the profiled binary, the owner and group granted at exec
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SetuidTransition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// Whether the kernel changed the credentials at exec, the capabilities observed
+    /// are then the ones of the elevated process
+    pub elevated: bool,
+}
+
+/// Binary or script executed by the command
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ExecutedFile {
+    pub path: String,
+    /// SHA-256 of the content at the end of the run, None when it could not be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Ports below this one require CAP_NET_BIND_SERVICE, unless the sysctl lowers it
+const UNPRIVILEGED_PORT_START: u16 = 1024;
+
+/// Internet socket the command listened on
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ListeningSocket {
+    /// tcp, udp, tcp6 or udp6
+    pub protocol: String,
+    /// IPv4 address bound, None when strace did not decode it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub port: u16,
+}
+
+impl ListeningSocket {
+    /// Whether binding the port requires CAP_NET_BIND_SERVICE by default
+    pub fn is_privileged(&self) -> bool {
+        self.port < UNPRIVILEGED_PORT_START
+    }
+}
+
+/// Unix socket of a privileged service the command connected to, e.g. /run/docker.sock
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ControlSocket {
+    pub path: String,
+    pub service: String,
+    /// Group owning the socket when the profile was analyzed, None when it is gone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Whether the members of the group may connect, without any capability
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub group_access: bool,
+}
+
+/// Mode, owner or timestamps of a file changed by the command, the operations checking
+/// CAP_FOWNER and CAP_CHOWN when the command does not own the file
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OwnershipChange {
+    pub path: String,
+    /// Change requested, e.g. chmod 0600, chown 1000:-1 or utimensat
+    pub change: String,
+    /// Owner of the file when the profile was analyzed, user:group, None when it is gone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+/// Memory the command locked, CAP_IPC_LOCK allows locking beyond RLIMIT_MEMLOCK
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct MemoryLock {
+    /// Bytes requested by mlock, mlock2, mmap(MAP_LOCKED) and the io_uring registered
+    /// buffers, over the run
+    pub requested: u64,
+    /// Whether the command locked all its memory with mlockall, of unknown size
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub all: bool,
+    /// Locking calls that failed, e.g. mlock of 8388608 bytes (failed with ENOMEM)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<String>,
+    /// Soft RLIMIT_MEMLOCK of the command in bytes, None when unlimited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
+impl MemoryLock {
+    /// Value of LimitMEMLOCK= allowing the locked memory without CAP_IPC_LOCK
+    pub fn limit_setting(&self) -> String {
+        if self.all {
+            String::from("infinity")
+        } else {
+            format!("{}M", self.requested.div_ceil(1 << 20))
+        }
+    }
+}
+
+/// A capability the command requires, with the evidence of its requests
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CapabilityFinding {
+    pub name: String,
+    /// Checks of the capability by the kernel, 0 when it was not observed by the eBPF
+    /// program (e.g. CAP_SYS_PTRACE deduced from the strace log)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub count: u64,
+    /// Processes that requested it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<i32>,
+    /// Syscalls during which it was checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub syscalls: Vec<String>,
+    /// Kernel functions that checked it, e.g. do_mount for CAP_SYS_ADMIN
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reason_symbols: Vec<String>,
+    /// First and last checks, in milliseconds since the start of the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase: Option<Phase>,
+}
+
+/// When the command needs a capability, see phase::classify
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Only during the initialization of the command
+    Startup,
+    /// Also once the command is initialized
+    Continuous,
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+impl CapabilityFinding {
+    pub fn new(name: String) -> CapabilityFinding {
+        CapabilityFinding {
+            name,
+            count: 0,
+            processes: Vec::new(),
+            syscalls: Vec::new(),
+            reason_symbols: Vec::new(),
+            first_seen: None,
+            last_seen: None,
+            phase: None,
+        }
+    }
+}
+
+impl CapabilityFinding {
+    /// Move the first and last checks `offset` milliseconds later
+    fn shift(&mut self, offset: u64) {
+        self.first_seen = self.first_seen.map(|time| time + offset);
+        self.last_seen = self.last_seen.map(|time| time + offset);
+    }
+
+    /// Add the evidence of the same capability in another run
+    fn merge(&mut self, other: CapabilityFinding) {
+        self.count += other.count;
+        extend_unique(&mut self.processes, other.processes);
+        extend_unique(&mut self.syscalls, other.syscalls);
+        extend_unique(&mut self.reason_symbols, other.reason_symbols);
+        self.first_seen = match (self.first_seen, other.first_seen) {
+            (Some(first), Some(other)) => Some(first.min(other)),
+            (first, other) => first.or(other),
+        };
+        self.last_seen = self.last_seen.max(other.last_seen);
+        if other.phase == Some(Phase::Continuous) || self.phase.is_none() {
+            self.phase = other.phase;
+        }
+    }
+}
+
+/// Append the values of `other` missing from `values`, keeping their order
+fn extend_unique<T: PartialEq>(values: &mut Vec<T>, other: Vec<T>) {
+    for value in other {
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+}
+
+/// Findings as stored by any version, profiles used to list the capability names only
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredFinding {
+    Name(String),
+    Finding(CapabilityFinding),
+}
+
+fn deserialize_findings<'de, D>(deserializer: D) -> Result<Vec<CapabilityFinding>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let stored: Vec<StoredFinding> = Deserialize::deserialize(deserializer)?;
+    Ok(stored
+        .into_iter()
+        .map(|finding| match finding {
+            StoredFinding::Name(name) => CapabilityFinding::new(name),
+            StoredFinding::Finding(finding) => finding,
+        })
+        .collect())
+}
+
+/// Least-privilege alternative to a capability finding
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Suggestion {
+    pub capability: String,
+    pub alternative: String,
+    /// Setting implementing the alternative, e.g. a sysctl or a unit file directive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setting: Option<String>,
+}
+
+/// Difference between the profile and the default capabilities of a container runtime
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ContainerComparison {
+    /// Required capabilities outside of the default set, to pass with --cap-add
+    pub cap_add: Vec<String>,
+    /// Default capabilities the command does not need, to pass with --cap-drop
+    pub cap_drop: Vec<String>,
+}
+
+/// Access of the command to a sysctl, observed by the cgroup/sysctl program
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SysctlAccess {
+    pub access: Access,
+    /// Values the command attempted to write
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+}
+
+/// Capability sets of a process after it changed them with libcap or libcap-ng
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CapabilityChange {
+    pub pid: i32,
+    /// cap_set_proc or capng_apply
+    pub function: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub failed: bool,
+    pub effective: Vec<String>,
+    pub permitted: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inheritable: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ambient: Vec<String>,
+}
+
+/// PID namespace created below the profiled one, e.g. by a container or a sandbox started
+/// by the command
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct NestedNamespace {
+    pub ns: u32,
+    /// The profiled namespace or another nested one
+    pub parent: u32,
+    /// 1 for the namespaces created in the profiled one
+    pub depth: u32,
+    /// Capabilities requested by the processes of this namespace only
+    pub capabilities: Vec<String>,
+}
+
+/// Capability sets the processes held while they ran, compared with what they requested
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HeldCapabilities {
+    pub effective: Vec<String>,
+    pub permitted: Vec<String>,
+    pub bounding: Vec<String>,
+    /// Permitted but never requested, the command is over-provisioned with them
+    pub unused: Vec<String>,
+    /// Requested but never seen effective, the requests likely failed
+    pub not_held: Vec<String>,
+}
+
+/// Run in which an entry of a learned profile was observed, see --merge-into
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Observation {
+    /// Id of the run in the history, None when it was not stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<u64>,
+    /// End of the run, in seconds since the Unix epoch
+    pub date: u64,
+}
+
+/// Result of a profiling run, also used as input profile by enforce and assert
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProgramResult {
+    #[serde(deserialize_with = "deserialize_findings")]
+    pub capabilities: Vec<CapabilityFinding>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggestion>,
+    /// Comparison with the default sets of docker and Kubernetes, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub containers: BTreeMap<String, ContainerComparison>,
+    pub files: BTreeMap<String, Access>,
+    /// Files of the dynamic loader, apart from `files`: the libraries mapped, the ld cache
+    /// and the ELF interpreters
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub runtime: BTreeMap<String, Access>,
+    /// Manifest of the binaries and scripts executed, with their hash
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub executed: Vec<ExecutedFile>,
+    /// Whether the command opened internet sockets, from its syscalls
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub network: bool,
+    /// Internet sockets the command listened on, from its syscalls
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listening: Vec<ListeningSocket>,
+    /// Control sockets of privileged services the command connected to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub control_sockets: Vec<ControlSocket>,
+    /// Files whose mode, owner or timestamps the command changed, with CAP_FOWNER or
+    /// CAP_CHOWN
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ownership: Vec<OwnershipChange>,
+    /// Memory the command locked, with CAP_IPC_LOCK or when locking failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memlock: Option<MemoryLock>,
+    pub dbus: Vec<String>,
+    /// Well-known names of the services the command called on the system bus
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<String>,
+    /// Well-known names owned on the system bus, for BusName= and the D-Bus policy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bus_names: Vec<String>,
+    /// Services the bus activated because of calls of the command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub activated: Vec<String>,
+    /// polkit action IDs checked on behalf of the command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub polkit: Vec<String>,
+    /// Only observed when the command runs in its own cgroup (--cgroup)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sysctls: BTreeMap<String, SysctlAccess>,
+    /// Changes of its own capabilities by the command, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub self_managed: Vec<CapabilityChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setuid: Option<SetuidTransition>,
+    /// Metadata given with --label key=value
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Notes per finding, explaining its root cause or added by plugins
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, Vec<String>>,
+    /// Runs in which each entry was observed, by the key of its annotations
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provenance: BTreeMap<String, Vec<Observation>>,
+    /// Statistics of the collectors, with --stats
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<CollectorStats>,
+    /// Namespaces nested in the profiled one, their requests are part of the profile
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub namespaces: Vec<NestedNamespace>,
+    /// Capability sets sampled from /proc/PID/status while the command ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub held: Option<HeldCapabilities>,
+}
+
+/// Key of a capability finding, as used for annotations
+pub fn capability_key(capability: &str) -> String {
+    format!("capability:{}", capability)
+}
+
+/// Key of a file, as used for annotations
+pub fn file_key(path: &str) -> String {
+    format!("file:{}", path)
+}
+
+/// Key of a D-Bus method, as used for annotations
+pub fn dbus_key(method: &str) -> String {
+    format!("dbus:{}", method)
+}
+
+/// Everything a profile requires beyond a baseline
+#[derive(Default)]
+pub struct ProfileDiff {
+    pub capabilities: Vec<String>,
+    pub files: BTreeMap<String, Access>,
+    pub dbus: Vec<String>,
+    pub polkit: Vec<String>,
+}
+
+impl ProfileDiff {
+    pub fn is_empty(&self) -> bool {
+        self.capabilities.is_empty()
+            && self.files.is_empty()
+            && self.dbus.is_empty()
+            && self.polkit.is_empty()
+    }
+}
+
+impl Display for ProfileDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for cap in &self.capabilities {
+            writeln!(f, "+ capability {}", cap)?;
+        }
+        for (path, access) in &self.files {
+            writeln!(f, "+ file {} {}", path, access)?;
+        }
+        for method in &self.dbus {
+            writeln!(f, "+ dbus {}", method)?;
+        }
+        for action in &self.polkit {
+            writeln!(f, "+ polkit {}", action)?;
+        }
+        Ok(())
+    }
+}
+
+impl ProgramResult {
+    pub fn capability_names(&self) -> impl Iterator<Item = &str> {
+        self.capabilities.iter().map(|cap| cap.name.as_str())
+    }
+
+    /// Files of the program and of the loader
+    pub fn all_files(&self) -> impl Iterator<Item = (&String, &Access)> {
+        self.files.iter().chain(self.runtime.iter())
+    }
+
+    pub fn has_capability(&self, name: &str) -> bool {
+        self.capability_names().any(|cap| cap == name)
+    }
+
+    /// Keys of the capabilities, files and D-Bus methods of the profile
+    pub fn entry_keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.capability_names()
+            .map(capability_key)
+            .chain(self.all_files().map(|(path, _)| file_key(path)))
+            .chain(self.dbus.iter().map(|method| dbus_key(method)))
+    }
+
+    /// Date of the latest run in which the entry of `key` was observed
+    pub fn last_observed(&self, key: &str) -> Option<u64> {
+        self.provenance
+            .get(key)?
+            .iter()
+            .map(|observation| observation.date)
+            .max()
+    }
+
+    /// Remove the capability, file or D-Bus method of `key`, with its notes and provenance
+    pub fn remove_entry(&mut self, key: &str) {
+        if let Some(name) = key.strip_prefix("capability:") {
+            self.capabilities.retain(|finding| finding.name != name);
+            self.suggestions
+                .retain(|suggestion| suggestion.capability != name);
+        } else if let Some(path) = key.strip_prefix("file:") {
+            self.files.remove(path);
+            self.runtime.remove(path);
+        } else if let Some(method) = key.strip_prefix("dbus:") {
+            self.dbus.retain(|existing| existing != method);
+        }
+        self.annotations.remove(key);
+        self.provenance.remove(key);
+    }
+
+    /// Record that every entry of the profile was observed in `observation`
+    pub fn observe(&mut self, observation: Observation) {
+        let keys: Vec<String> = self.entry_keys().collect();
+        for key in keys {
+            extend_unique(self.provenance.entry(key).or_default(), vec![observation]);
+        }
+    }
+
+    /// Add what `other` requires, as if both runs were one. Times of the findings are
+    /// rebased on the start of the merged window. The container comparisons are left to
+    /// compute again.
+    pub fn merge(&mut self, other: ProgramResult) {
+        let (shift, other_shift) = match (self.window, other.window) {
+            (Some(window), Some(other_window)) => {
+                let start = window.start.min(other_window.start);
+                self.window = Some(TimeWindow {
+                    start,
+                    end: window.end.max(other_window.end),
+                });
+                (
+                    (window.start - start) * 1000,
+                    (other_window.start - start) * 1000,
+                )
+            }
+            (None, window) => {
+                self.window = window;
+                (0, 0)
+            }
+            _ => (0, 0),
+        };
+        if shift > 0 {
+            for finding in self.capabilities.iter_mut() {
+                finding.shift(shift);
+            }
+        }
+        for mut finding in other.capabilities {
+            finding.shift(other_shift);
+            match self
+                .capabilities
+                .iter_mut()
+                .find(|existing| existing.name == finding.name)
+            {
+                Some(existing) => existing.merge(finding),
+                None => self.capabilities.push(finding),
+            }
+        }
+        for suggestion in other.suggestions {
+            if !self.suggestions.iter().any(|existing| {
+                existing.capability == suggestion.capability
+                    && existing.alternative == suggestion.alternative
+            }) {
+                self.suggestions.push(suggestion);
+            }
+        }
+        for (path, access) in other.files {
+            *self.files.entry(path).or_insert(access) |= access;
+        }
+        for (path, access) in other.runtime {
+            *self.runtime.entry(path).or_insert(access) |= access;
+        }
+        extend_unique(&mut self.executed, other.executed);
+        self.network |= other.network;
+        extend_unique(&mut self.listening, other.listening);
+        extend_unique(&mut self.control_sockets, other.control_sockets);
+        extend_unique(&mut self.ownership, other.ownership);
+        if let Some(other_memlock) = other.memlock {
+            let memlock = self.memlock.get_or_insert_with(MemoryLock::default);
+            memlock.requested = memlock.requested.max(other_memlock.requested);
+            memlock.all |= other_memlock.all;
+            extend_unique(&mut memlock.failures, other_memlock.failures);
+            memlock.limit = memlock.limit.or(other_memlock.limit);
+        }
+        extend_unique(&mut self.dbus, other.dbus);
+        extend_unique(&mut self.destinations, other.destinations);
+        extend_unique(&mut self.bus_names, other.bus_names);
+        extend_unique(&mut self.activated, other.activated);
+        extend_unique(&mut self.polkit, other.polkit);
+        for (name, access) in other.sysctls {
+            match self.sysctls.get_mut(&name) {
+                Some(existing) => {
+                    existing.access |= access.access;
+                    extend_unique(&mut existing.values, access.values);
+                }
+                None => {
+                    self.sysctls.insert(name, access);
+                }
+            }
+        }
+        self.self_managed.extend(other.self_managed);
+        if self.setuid.is_none() {
+            self.setuid = other.setuid;
+        }
+        self.labels.extend(other.labels);
+        extend_unique(&mut self.namespaces, other.namespaces);
+        if let Some(other_held) = other.held {
+            let held = self.held.get_or_insert_with(HeldCapabilities::default);
+            extend_unique(&mut held.effective, other_held.effective);
+            extend_unique(&mut held.permitted, other_held.permitted);
+            extend_unique(&mut held.bounding, other_held.bounding);
+            extend_unique(&mut held.unused, other_held.unused);
+            extend_unique(&mut held.not_held, other_held.not_held);
+        }
+        if let Some(held) = self.held.as_mut() {
+            // requested by one of the runs, held by one of them
+            let capabilities = &self.capabilities;
+            held.unused
+                .retain(|cap| !capabilities.iter().any(|finding| &finding.name == cap));
+            let effective = held.effective.clone();
+            held.not_held.retain(|cap| !effective.contains(cap));
+        }
+        for (key, notes) in other.annotations {
+            extend_unique(self.annotations.entry(key).or_default(), notes);
+        }
+        for (key, observations) in other.provenance {
+            extend_unique(self.provenance.entry(key).or_default(), observations);
+        }
+    }
+
+    /// Return what this profile requires that the baseline does not allow.
+    /// File accesses are compared per path, only the missing rights are reported.
+    pub fn beyond(&self, baseline: &ProgramResult) -> ProfileDiff {
+        let mut diff = ProfileDiff::default();
+        for cap in self.capability_names() {
+            if !baseline.has_capability(cap) {
+                diff.capabilities.push(cap.to_string());
+            }
+        }
+        for (path, access) in &self.files {
+            let allowed = baseline.files.get(path).copied().unwrap_or(Access::empty());
+            let missing = access.difference(allowed);
+            if !missing.is_empty() {
+                diff.files.insert(path.clone(), missing);
+            }
+        }
+        for method in &self.dbus {
+            if !baseline.dbus.contains(method) && !diff.dbus.contains(method) {
+                diff.dbus.push(method.clone());
+            }
+        }
+        for action in &self.polkit {
+            if !baseline.polkit.contains(action) {
+                diff.polkit.push(action.clone());
+            }
+        }
+        diff
+    }
+}
@@ -0,0 +1,128 @@
+//! Counters of the collectors of a run, to judge how complete its profile is.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// Entries of a map of the probes when it was read, out of its capacity
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapOccupancy {
+    pub entries: u32,
+    pub capacity: u32,
+}
+
+/// Notifications of the seccomp collector, received or lost
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeccompStats {
+    /// Notified syscalls written to the log
+    pub received: u64,
+    /// Notifications the collector failed to receive, e.g. of a killed process
+    pub failed: u64,
+    /// Notifications invalidated while their arguments were read, as the process was gone
+    pub invalidated: u64,
+    /// Syscalls of processes of another architecture, e.g. 32-bit ones, run uncollected
+    #[serde(default)]
+    pub foreign: u64,
+}
+
+impl SeccompStats {
+    pub fn add(&mut self, other: &SeccompStats) {
+        self.received += other.received;
+        self.failed += other.failed;
+        self.invalidated += other.invalidated;
+        self.foreign += other.foreign;
+    }
+}
+
+/// Statistics of the collectors, printed and stored in the profile with --stats
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollectorStats {
+    /// Capability checks recorded by the probes, system-wide before filtering
+    pub requests: u64,
+    /// Capability checks lost as the requests map was full
+    pub drops: u64,
+    /// Capability checks of kernel threads, attributed to the process of their cred
+    #[serde(default)]
+    pub deferred: u64,
+    /// Maps of the probes by name. The requests map is drained while collecting, its
+    /// occupancy is the highest one seen.
+    pub maps: BTreeMap<String, MapOccupancy>,
+    /// Lines of the strace log parsed as syscalls
+    pub strace_parsed: usize,
+    /// Lines of the strace log without a syscall: signals, exits and resumed syscalls
+    pub strace_skipped: usize,
+    /// Lines of the strace log the parser rejected, their syscalls are missing
+    #[serde(default)]
+    pub strace_failed: usize,
+    /// Notifications of the seccomp collector, when it replaced strace
+    #[serde(default)]
+    pub seccomp: Option<SeccompStats>,
+    /// Messages of the D-Bus monitor, for every namespace
+    pub dbus_messages: usize,
+    /// Kernel stacks symbolized from kallsyms
+    pub stacks_resolved: u64,
+    /// Kernel stacks served from the symbolization cache
+    pub stack_cache_hits: u64,
+}
+
+/// Whether a strace line is a syscall, and not a signal, an exit or the end of a syscall
+/// reported as unfinished
+fn is_syscall(line: &str) -> bool {
+    // lines are prefixed with the pid when following forks
+    let line = line.trim_start_matches(|c: char| c.is_ascii_digit() || c == ' ');
+    !(line.starts_with("---") || line.starts_with("+++") || line.starts_with("<..."))
+}
+
+impl CollectorStats {
+    /// Count the lines of a strace log, of which `failed` were rejected by the parser, in
+    /// addition to the logs counted before
+    pub fn strace(&mut self, log: &str, failed: usize) {
+        let mut syscalls: usize = 0;
+        for line in log.lines().filter(|line| !line.trim().is_empty()) {
+            if is_syscall(line) {
+                syscalls += 1;
+            } else {
+                self.strace_skipped += 1;
+            }
+        }
+        self.strace_parsed += syscalls.saturating_sub(failed);
+        self.strace_failed += failed;
+    }
+
+    /// Count the messages of the D-Bus monitor, per PID namespace
+    pub fn dbus<T>(&mut self, messages: &HashMap<u32, Vec<T>>) {
+        self.dbus_messages = messages.values().map(Vec::len).sum();
+    }
+}
+
+impl Display for CollectorStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "probes: {} requests, {} dropped, {} deferred to kernel threads",
+            self.requests, self.drops, self.deferred
+        )?;
+        for (name, map) in &self.maps {
+            writeln!(f, "  {}: {}/{}", name, map.entries, map.capacity)?;
+        }
+        writeln!(
+            f,
+            "strace: {} lines parsed, {} skipped, {} failed to parse",
+            self.strace_parsed, self.strace_skipped, self.strace_failed
+        )?;
+        if let Some(seccomp) = &self.seccomp {
+            writeln!(
+                f,
+                "seccomp: {} notifications received, {} failed, {} invalidated, {} syscalls of another architecture uncollected",
+                seccomp.received, seccomp.failed, seccomp.invalidated, seccomp.foreign
+            )?;
+        }
+        writeln!(f, "dbus: {} messages", self.dbus_messages)?;
+        writeln!(
+            f,
+            "symbolization: {} stacks resolved, {} cache hits",
+            self.stacks_resolved, self.stack_cache_hits
+        )
+    }
+}
@@ -1,7 +1,9 @@
 use std::{error::Error, fs};
 
 use aya::util::KernelVersion;
+#[cfg(feature = "probes")]
 use anyhow::{anyhow, Context as _};
+#[cfg(feature = "probes")]
 use aya_build::cargo_metadata;
 
 fn main() -> anyhow::Result<()> {
@@ -18,6 +20,17 @@ fn main() -> anyhow::Result<()> {
         }
         Err(e) => eprintln!("Error: {}", e),
     };
+    build_ebpf()
+}
+
+// Only the probes need the eBPF programs, the analysis of recordings builds without them
+#[cfg(not(feature = "probes"))]
+fn build_ebpf() -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(feature = "probes")]
+fn build_ebpf() -> anyhow::Result<()> {
     let cargo_metadata::Metadata { packages, .. } = cargo_metadata::MetadataCommand::new()
     .no_deps()
     .exec()
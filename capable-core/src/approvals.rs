@@ -0,0 +1,56 @@
+//! Maps of the interactive mode, where the user decides each capability of a run.
+
+use aya::maps::{HashMap, MapData};
+use capable_common::{ApprovalRequest, Capabilities, Nsid};
+
+/// The processes of a watched PID namespace are stopped by the probe on each capability
+/// the namespace has not decided yet, and listed as pending requests. Once decided, the
+/// capability no longer stops them and the caller resumes the stopped processes.
+pub struct Approvals {
+    decided: HashMap<MapData, Nsid, Capabilities>,
+    requests: HashMap<MapData, ApprovalRequest, u8>,
+}
+
+impl Approvals {
+    pub fn new(
+        decided: HashMap<MapData, Nsid, Capabilities>,
+        requests: HashMap<MapData, ApprovalRequest, u8>,
+    ) -> Approvals {
+        Approvals { decided, requests }
+    }
+
+    /// Stop the processes of `ns` on every capability until it is decided
+    pub fn watch(&mut self, ns: Nsid) -> Result<(), anyhow::Error> {
+        self.decided.insert(ns, 0, 0)?;
+        Ok(())
+    }
+
+    /// Stop watching `ns`, its pending requests are dropped
+    pub fn unwatch(&mut self, ns: Nsid) -> Result<(), anyhow::Error> {
+        // not watched when the run failed before its namespace was known
+        let _ = self.decided.remove(&ns);
+        for request in self
+            .pending()
+            .into_iter()
+            .filter(|request| request.ns == ns)
+        {
+            self.requests.remove(&request)?;
+        }
+        Ok(())
+    }
+
+    /// Processes stopped on a capability not decided yet
+    pub fn pending(&self) -> Vec<ApprovalRequest> {
+        self.requests.keys().filter_map(Result::ok).collect()
+    }
+
+    /// Mark the capability of the request as decided in its namespace. Allowed or not, the
+    /// processes are no longer stopped on it.
+    pub fn decide(&mut self, request: &ApprovalRequest) -> Result<(), anyhow::Error> {
+        let decided = self.decided.get(&request.ns, 0).unwrap_or_default();
+        self.decided
+            .insert(request.ns, decided | 1 << request.capability, 0)?;
+        self.requests.remove(request)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+//! Architecture of the running kernel, against the one the embedded probes were built for.
+//!
+//! The kprobes read the arguments of the kernel functions from the registers of the kernel
+//! architecture. aya-build builds the probes for the target of capable, so a 32-bit armv7
+//! capable on a 64-bit kernel would read the wrong registers.
+
+use std::ffi::CStr;
+
+/// Architecture the embedded probes were built for
+pub const PROBES_ARCH: &str = std::env::consts::ARCH;
+
+/// Architecture of the running kernel, in the names of Rust, e.g. arm for armv7l
+pub fn kernel_arch() -> Option<String> {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return None;
+    }
+    let machine = unsafe { CStr::from_ptr(uts.machine.as_ptr()) }.to_string_lossy();
+    Some(
+        match machine.as_ref() {
+            "arm64" => "aarch64",
+            "i386" | "i486" | "i586" | "i686" => "x86",
+            machine if machine.starts_with("armv") => "arm",
+            machine => machine,
+        }
+        .to_string(),
+    )
+}
+
+/// Fail when the embedded probes would read the arguments from the registers of another
+/// architecture than the one of the kernel
+pub fn check_probes_arch() -> Result<(), anyhow::Error> {
+    match kernel_arch() {
+        Some(kernel) if kernel != PROBES_ARCH => Err(anyhow::anyhow!(
+            "The probes are built for {} but the kernel is {}, build capable for {} or load probes built for it with --bpf-object",
+            PROBES_ARCH,
+            kernel,
+            kernel
+        )),
+        _ => Ok(()),
+    }
+}
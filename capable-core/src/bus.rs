@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+use std::fs::{metadata, read_to_string, rename, File};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Error;
+use dashmap::DashMap;
+use dbus::arg::{self, RefArg, Variant};
+use dbus::message::MatchRule;
+use dbus::{blocking::Connection, channel::MatchingReceiver};
+use dbus::{Message, MessageType};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Polling interval of the monitor loop, bounds how long the cancel flag may go unnoticed
+const DBUS_PROCESS_TIMEOUT_MS: u64 = 200;
+
+const POLKIT_AUTHORITY: &str = "org.freedesktop.PolicyKit1.Authority";
+const POLKIT_CHECK: &str = "CheckAuthorization";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DbusMsg {
+    #[serde(
+        rename = "type",
+        serialize_with = "msg_type_to_string",
+        deserialize_with = "msg_type_from_string"
+    )]
+    msg_type: MessageType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sender: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    serial: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interface: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Vec<String>>,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct MsgKey {
+    sender: String,
+    serial: u32,
+}
+
+#[derive(Debug)]
+pub struct Memory {
+    pub cancel: Arc<AtomicBool>,
+    //                            "Systemd (1.7, 21)", "1.21"
+    pub credentials_requests: DashMap<MsgKey, String>, // Conversation_key -> Requested Credentials
+    pub messages: Mutex<Vec<DbusMsg>>,
+    //      "namespace_id" => [ "1.21", "1.22" ]
+    pub owners: DashMap<u32, Vec<String>>,
+    //                "1.21"  [ "org.freedesktop.systemd1.Manager.Reboot" ]
+    pub requests: DashMap<String, Vec<DbusMsg>>,
+    //                "namespace_id" or "1.21"  [ CheckAuthorization calls ]
+    pub polkit: DashMap<PolkitSubject, Vec<DbusMsg>>,
+    //                "org.freedesktop.hostname1"  [ "1.21" ] callers since its last owner change
+    pub activation_callers: DashMap<String, Vec<String>>,
+    //                "1.21"  [ NameOwnerChanged signals of the services it activated ]
+    pub activations: DashMap<String, Vec<DbusMsg>>,
+}
+
+/// Subject of a polkit authorization check, a process is attributed when the check is seen
+/// as it may exit before the end of the run, a bus name once its owner namespace is known
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum PolkitSubject {
+    Namespace(u32),
+    BusName(String),
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory {
+            cancel: Arc::new(AtomicBool::new(false)),
+            credentials_requests: DashMap::new(),
+            messages: Mutex::new(Vec::new()),
+            owners: DashMap::new(),
+            requests: DashMap::new(),
+            polkit: DashMap::new(),
+            activation_callers: DashMap::new(),
+            activations: DashMap::new(),
+        }
+    }
+}
+
+fn msg_type_to_string<S>(msg_type: &MessageType, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&format!("{:?}", msg_type))
+}
+
+fn msg_type_from_string<'de, D>(deserializer: D) -> Result<MessageType, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    match s.as_str() {
+        "MethodCall" => Ok(MessageType::MethodCall),
+        "MethodReturn" => Ok(MessageType::MethodReturn),
+        "Error" => Ok(MessageType::Error),
+        "Signal" => Ok(MessageType::Signal),
+        _ => Err(serde::de::Error::custom(format!(
+            "Unknown message type: {}",
+            s
+        ))),
+    }
+}
+
+// This programs implements the equivalent of running the "dbus-monitor" tool
+pub fn run_dbus_monitor(d_data: Arc<Memory>) -> Result<HashMap<u32, Vec<DbusMsg>>, Error> {
+    // First open up a connection to the desired bus.
+    let conn = Connection::new_system()?;
+
+    // Second create a rule to match messages we want to receive; in this example we add no
+    // further requirements, so all messages will match
+    let rule = MatchRule::new();
+
+    // Try matching using new scheme
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(5000),
+    );
+    let result: Result<(), dbus::Error> = proxy.method_call(
+        "org.freedesktop.DBus.Monitoring",
+        "BecomeMonitor",
+        (vec![rule.match_str()], 0u32),
+    );
+    match result {
+        // BecomeMonitor was successful, start listening for messages
+        Ok(_) => {
+            let data = d_data.clone();
+            conn.start_receive(
+                rule,
+                Box::new(move |msg, _| {
+                    handle_message(data.clone(), &msg);
+                    true
+                }),
+            );
+        }
+        // BecomeMonitor failed, fallback to using the old scheme
+        Err(e) => {
+            eprintln!(
+                "Failed to BecomeMonitor: '{}', falling back to eavesdrop",
+                e
+            );
+
+            // First, we'll try "eavesdrop", which as the name implies lets us receive
+            // *all* messages, not just ours.
+            let rule_with_eavesdrop = {
+                let mut rule = rule.clone();
+                rule.eavesdrop = true;
+                rule
+            };
+            let data = d_data.clone();
+            let result = conn.add_match(rule_with_eavesdrop, move |_: (), _, msg| {
+                handle_message(data.clone(), msg);
+                true
+            });
+            let data = d_data.clone();
+            match result {
+                Ok(_) => {
+                    // success, we're now listening
+                }
+                // This can sometimes fail, for example when listening to the system bus as a non-root user.
+                // So, just like `dbus-monitor`, we attempt to fallback without `eavesdrop=true`:
+                Err(e) => {
+                    eprintln!("Failed to eavesdrop: '{}', trying without it", e);
+                    conn.add_match(rule, move |_: (), _, msg| {
+                        handle_message(data.clone(), msg);
+                        true
+                    })
+                    .expect("add_match failed");
+                }
+            }
+        }
+    }
+
+    // Loop and print out all messages received (using handle_message()) as they come.
+    // Some can be quite large, e.g. if they contain embedded images..
+    while !d_data.cancel.load(std::sync::atomic::Ordering::Relaxed) {
+        if let Err(e) = conn.process(Duration::from_millis(DBUS_PROCESS_TIMEOUT_MS)) {
+            // stop listening but still report what was collected so far
+            warn!("dbus process() failed: {}", e);
+            break;
+        }
+    }
+
+    // join d_data.owners and d_data.requests
+    let mut nsid_to_requests = HashMap::new();
+    d_data.owners.iter().for_each(|x| {
+        let nsid = x.key();
+        let owners = x.value();
+        for owner in owners {
+            if let Some(requests) = d_data.requests.get(owner) {
+                debug!("extend requests: {:?}", requests.value());
+                if !nsid_to_requests.contains_key(nsid) {
+                    nsid_to_requests.insert(*nsid, Vec::new());
+                }
+                nsid_to_requests
+                    .get_mut(nsid)
+                    .unwrap_or_else(|| panic!("Failed to get nsid {}", nsid))
+                    .extend(requests.value().clone());
+            }
+            if let Some(activations) = d_data.activations.get(owner) {
+                debug!("extend activations: {:?}", activations.value());
+                nsid_to_requests
+                    .entry(*nsid)
+                    .or_insert_with(Vec::new)
+                    .extend(activations.value().clone());
+            }
+        }
+    });
+    // polkit checks are sent by the services on behalf of the target, not by the target itself
+    d_data.polkit.iter().for_each(|x| {
+        let (nsid, owners) = match x.key() {
+            PolkitSubject::Namespace(nsid) => (
+                *nsid,
+                d_data.owners.get(nsid).map(|owners| owners.value().clone()),
+            ),
+            PolkitSubject::BusName(name) => match d_data
+                .owners
+                .iter()
+                .find(|owners| owners.value().contains(name))
+            {
+                Some(owners) => (*owners.key(), Some(owners.value().clone())),
+                None => {
+                    debug!("polkit subject {} is not in a known namespace", name);
+                    return;
+                }
+            },
+        };
+        for check in x.value() {
+            // a check sent by the target is already one of its requests
+            let sent_by_target = owners.as_ref().is_some_and(|owners| {
+                check
+                    .sender
+                    .as_ref()
+                    .is_some_and(|sender| owners.contains(sender))
+            });
+            if !sent_by_target {
+                nsid_to_requests
+                    .entry(nsid)
+                    .or_insert_with(Vec::new)
+                    .push(check.clone());
+            }
+        }
+    });
+    debug!("nsid_to_requests: {:?}", nsid_to_requests);
+    Ok(nsid_to_requests)
+}
+
+/// Write the monitor result atomically, so the parent never reads a partially written file
+pub fn write_dbus_result<P: AsRef<Path>>(
+    path: P,
+    result: &HashMap<u32, Vec<DbusMsg>>,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    write!(file, "{}", serde_json::to_string(result)?)?;
+    file.sync_all()?;
+    rename(tmp_path, path)?;
+    Ok(())
+}
+
+pub fn read_dbus_result<P: AsRef<Path>>(path: P) -> Result<HashMap<u32, Vec<DbusMsg>>, Error> {
+    //read json file
+    let content = read_to_string(path.as_ref())?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Methods called by the processes of the namespace `nsid`
+pub fn get_dbus_methods(content: &HashMap<u32, Vec<DbusMsg>>, nsid: u32) -> Vec<String> {
+    let default = Vec::new();
+    let requests = content.get(&nsid).unwrap_or(&default);
+    requests.iter().filter_map(method_name).collect()
+}
+
+/// Interface and name of the method called by the message, if it is a call
+pub fn method_name(request: &DbusMsg) -> Option<String> {
+    // polkit checks are reported as actions, most are sent on behalf of the target
+    if request.msg_type != MessageType::MethodCall || is_polkit_check(request) {
+        return None;
+    }
+    Some(format!(
+        "{}.{}",
+        request
+            .interface
+            .as_ref()
+            .unwrap_or_else(|| panic!("Unknown interface for {:?}", request)),
+        request
+            .method
+            .as_ref()
+            .unwrap_or_else(|| panic!("Unknown method for {:?}", request))
+    ))
+}
+
+fn is_polkit_check(msg: &DbusMsg) -> bool {
+    msg.msg_type == MessageType::MethodCall
+        && msg.interface.as_deref() == Some(POLKIT_AUTHORITY)
+        && msg.method.as_deref() == Some(POLKIT_CHECK)
+}
+
+/// polkit action IDs checked for the processes of the namespace `nsid`
+pub fn get_polkit_actions(content: &HashMap<u32, Vec<DbusMsg>>, nsid: u32) -> Vec<String> {
+    let mut actions = Vec::new();
+    for request in content.get(&nsid).into_iter().flatten() {
+        // CheckAuthorization((sa{sv}) subject, s action_id, a{ss} details, u flags, s cancellation_id)
+        let action = request
+            .arguments
+            .as_ref()
+            .and_then(|arguments| arguments.get(1));
+        if let Some(action) = action.filter(|_| is_polkit_check(request)) {
+            if !actions.contains(action) {
+                actions.push(action.clone());
+            }
+        }
+    }
+    actions
+}
+
+/// Namespace or bus name of the subject of a CheckAuthorization call
+fn polkit_subject(msg: &Message) -> Option<PolkitSubject> {
+    let ((kind, details), _action): ((String, arg::PropMap), String) = msg.read2().ok()?;
+    match kind.as_str() {
+        "unix-process" => {
+            let pid = details.get("pid")?.0.as_u64()?;
+            match metadata(format!("/proc/{}/ns/pid", pid)) {
+                Ok(metadata) => Some(PolkitSubject::Namespace(metadata.ino() as u32)),
+                Err(e) => {
+                    debug!("failed to open pid ns of polkit subject {}: {}", pid, e);
+                    None
+                }
+            }
+        }
+        "system-bus-name" => Some(PolkitSubject::BusName(
+            details.get("name")?.0.as_str()?.to_string(),
+        )),
+        _ => None,
+    }
+}
+
+fn add_owner(data: &Memory, process_id: i32, nspid: u32, dbus_id: String) {
+    let array = data.owners.get_mut(&nspid);
+    match array {
+        Some(mut array) => {
+            if !array.contains(&dbus_id) {
+                debug!(
+                    "We know that ProcessID: {} is DbusID: {}, which is under {} namespace",
+                    process_id, dbus_id, nspid
+                );
+                array.push(dbus_id);
+            }
+        }
+        None => {
+            debug!(
+                "We know that ProcessID: {} is DbusID: {}, which is under {} namespace",
+                process_id, dbus_id, nspid
+            );
+            data.owners.insert(nspid, vec![dbus_id]);
+        }
+    }
+}
+
+/// Attribute a connection nobody asked the credentials of. The bus daemon checks
+/// RequestName against its own policy, so no service does it for us.
+fn attribute(data: &Memory, dbus_id: &str) {
+    if data.owners.iter().any(|owners| owners.value().iter().any(|x| x == dbus_id)) {
+        return;
+    }
+    // the monitoring connection can no longer send method calls
+    let process_id = Connection::new_system().and_then(|conn| {
+        let proxy = conn.with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            Duration::from_millis(DBUS_PROCESS_TIMEOUT_MS),
+        );
+        let (pid,): (u32,) = proxy.method_call(
+            "org.freedesktop.DBus",
+            "GetConnectionUnixProcessID",
+            (dbus_id,),
+        )?;
+        Ok(pid as i32)
+    });
+    let process_id = match process_id {
+        Ok(process_id) => process_id,
+        Err(e) => {
+            debug!("failed to get the process of {}: {}", dbus_id, e);
+            return;
+        }
+    };
+    match metadata(format!("/proc/{}/ns/pid", process_id)) {
+        Ok(metadata) => add_owner(data, process_id, metadata.ino() as u32, dbus_id.to_string()),
+        Err(e) => debug!("failed to open pid ns of {}: {}", process_id, e),
+    }
+}
+
+fn is_name_owner_changed(msg: &DbusMsg) -> bool {
+    msg.msg_type == MessageType::Signal
+        && msg.interface.as_deref() == Some("org.freedesktop.DBus")
+        && msg.method.as_deref() == Some("NameOwnerChanged")
+}
+
+/// Services activated by the bus because of calls from the processes of the namespace `nsid`
+pub fn get_activations(content: &HashMap<u32, Vec<DbusMsg>>, nsid: u32) -> Vec<String> {
+    let mut services = Vec::new();
+    for signal in content.get(&nsid).into_iter().flatten() {
+        // NameOwnerChanged(s name, s old_owner, s new_owner)
+        let name = signal
+            .arguments
+            .as_ref()
+            .and_then(|arguments| arguments.first());
+        if let Some(name) = name.filter(|_| is_name_owner_changed(signal)) {
+            if !services.contains(name) {
+                services.push(name.clone());
+            }
+        }
+    }
+    services
+}
+
+/// A well-known name acquired while it had callers waiting was activated by the bus,
+/// either implicitly by the calls or explicitly with StartServiceByName
+fn track_activation(data: &Memory, signal: &DbusMsg) {
+    let arguments = signal.arguments.as_deref().unwrap_or_default();
+    let [name, old_owner, new_owner] = arguments else {
+        return;
+    };
+    if name.starts_with(':') {
+        return;
+    }
+    // any owner change ends the calls the name was waiting for
+    let Some((_, callers)) = data.activation_callers.remove(name) else {
+        return;
+    };
+    if !old_owner.is_empty() || new_owner.is_empty() {
+        return;
+    }
+    for caller in callers {
+        debug!("{} activated {}", caller, name);
+        attribute(data, &caller);
+        data.activations
+            .entry(caller)
+            .or_default()
+            .push(signal.clone());
+    }
+}
+
+fn wait_activation(data: &Memory, name: &str, caller: &str) {
+    if name.starts_with(':') || name == "org.freedesktop.DBus" {
+        return;
+    }
+    let mut callers = data.activation_callers.entry(name.to_string()).or_default();
+    if !callers.iter().any(|x| x == caller) {
+        callers.push(caller.to_string());
+    }
+}
+
+fn is_name_request(msg: &DbusMsg) -> bool {
+    msg.msg_type == MessageType::MethodCall
+        && msg.interface.as_deref() == Some("org.freedesktop.DBus")
+        && msg.method.as_deref() == Some("RequestName")
+}
+
+/// Well-known names the processes of the namespace `nsid` called methods on, unique
+/// connection names and the bus itself are left out
+pub fn get_destinations(content: &HashMap<u32, Vec<DbusMsg>>, nsid: u32) -> Vec<String> {
+    let mut destinations = Vec::new();
+    for request in content.get(&nsid).into_iter().flatten() {
+        if request.msg_type != MessageType::MethodCall {
+            continue;
+        }
+        let Some(destination) = request.destination.as_ref() else {
+            continue;
+        };
+        if destination.starts_with(':') || destination == "org.freedesktop.DBus" {
+            continue;
+        }
+        if !destinations.contains(destination) {
+            destinations.push(destination.clone());
+        }
+    }
+    destinations
+}
+
+/// Well-known bus names requested by the processes of the namespace `nsid`. A name released
+/// afterwards is still reported, the D-Bus policy must allow owning it.
+pub fn get_bus_names(content: &HashMap<u32, Vec<DbusMsg>>, nsid: u32) -> Vec<String> {
+    let mut names = Vec::new();
+    for request in content.get(&nsid).into_iter().flatten() {
+        // RequestName(s name, u flags)
+        let name = request
+            .arguments
+            .as_ref()
+            .and_then(|arguments| arguments.first());
+        if let Some(name) = name.filter(|_| is_name_request(request)) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+fn handle_message(data: Arc<Memory>, msg: &Message) {
+    let sender = msg.sender().map(|x| x.to_string());
+    let dest = msg.destination().map(|x| x.to_string());
+    let dbus_msg = DbusMsg {
+        msg_type: msg.msg_type(),
+        sender: sender.clone(),
+        destination: dest.clone(),
+        serial: if msg.msg_type() == MessageType::MethodReturn {
+            msg.get_reply_serial()
+        } else {
+            msg.get_serial()
+        },
+        interface: msg
+            .interface()
+            .map(|x| x.to_string().trim_matches('"').to_string()),
+        method: msg.member().map(|x| x.to_string()),
+        path: msg.path().map(|x| x.to_string()),
+        arguments: if msg.iter_init().count() > 0 {
+            Some(
+                msg.iter_init()
+                    .map(|arg| format!("{:?}", arg).trim_matches('"').to_string())
+                    .collect(),
+            )
+        } else {
+            None
+        },
+    };
+
+    let key = dest.map(|dest| MsgKey {
+        sender: dest,
+        serial: dbus_msg
+            .serial
+            .unwrap_or_else(|| panic!("No serial for {:?}", dbus_msg)),
+    });
+
+    if dbus_msg.msg_type == MessageType::MethodCall
+        && dbus_msg
+            .method
+            .as_ref()
+            .is_some_and(|x| x == "GetConnectionCredentials")
+    {
+        let key = MsgKey {
+            sender: sender
+                .clone()
+                .unwrap_or_else(|| panic!("No sender for {:?}", dbus_msg)),
+            serial: dbus_msg
+                .serial
+                .unwrap_or_else(|| panic!("No serial for {:?}", dbus_msg)),
+        };
+        data.credentials_requests.insert(
+            key,
+            msg.get1().unwrap_or_else(|| panic!("No get1() for {:?}", dbus_msg)),
+        );
+    } else if dbus_msg.msg_type == MessageType::MethodReturn
+        && key
+            .as_ref()
+            .is_some_and(|key| data.credentials_requests.contains_key(key))
+    {
+        let map: HashMap<String, Variant<Box<dyn RefArg>>> =
+            msg.get1().expect("Impossible error get1()");
+        let process_id = map
+            .get("ProcessID")
+            .expect("Unable to get ProcessID")
+            .0
+            .as_u64()
+            .expect("Unable to convert ProcessID to u64") as i32;
+        // read /proc/<pid>/name to get the path of the socket
+        let nspid = match metadata(format!("/proc/{}/ns/pid", process_id)) {
+            Ok(metadata) => metadata.ino() as u32,
+            Err(e) => {
+                // the process may already be gone, we cannot attribute it
+                debug!("failed to open pid ns of {}: {}", process_id, e);
+                return;
+            }
+        };
+        let dbus_id = data
+            .credentials_requests
+            .get(key.as_ref().expect("Unable to get the key (Impossible)"))
+            .unwrap_or_else(|| panic!("Unable to get the creential_request for key {:?}", key.as_ref()))
+            .to_string();
+        add_owner(&data, process_id, nspid, dbus_id);
+    } else if is_name_owner_changed(&dbus_msg) {
+        track_activation(&data, &dbus_msg);
+    } else if dbus_msg.msg_type == MessageType::MethodCall {
+        if let Some(sender) = sender.as_ref() {
+            if is_name_request(&dbus_msg) {
+                attribute(&data, sender);
+            }
+            let activated = if dbus_msg.method.as_deref() == Some("StartServiceByName") {
+                // StartServiceByName(s name, u flags)
+                dbus_msg.arguments.as_ref().and_then(|arguments| arguments.first())
+            } else {
+                dbus_msg.destination.as_ref()
+            };
+            if let Some(name) = activated {
+                wait_activation(&data, name, sender);
+            }
+        }
+        if is_polkit_check(&dbus_msg) {
+            if let Some(subject) = polkit_subject(msg) {
+                data.polkit.entry(subject).or_default().push(dbus_msg.clone());
+            }
+        }
+        data.requests
+            .entry(sender.expect("No sender for the message"))
+            .or_default()
+            .push(dbus_msg.clone());
+    }
+    data.messages.lock().expect("unable to lock Mutex Memory messages").push(dbus_msg);
+}
@@ -0,0 +1,142 @@
+//! bubblewrap invocation running a command with only the environment a profile observed.
+
+use std::collections::BTreeMap;
+
+use crate::paths::{collapse, parent};
+use crate::profile::ProgramResult;
+use crate::syscalls::Access;
+
+/// Directories of the system, bound read-only when they exist
+const SYSTEM: [&str; 6] = ["/usr", "/lib", "/lib32", "/lib64", "/bin", "/sbin"];
+
+/// Devices created by `--dev`
+const DEVICES: [&str; 11] = [
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+    "/dev/ptmx",
+    "/dev/pts/",
+    "/dev/shm/",
+    "/dev/fd/",
+    "/dev/std",
+];
+
+const SYSTEM_BUS: &str = "/run/dbus/system_bus_socket";
+
+/// Mounts of a sandbox with fresh /proc, /dev and /tmp and the system directories
+/// read-only, for the other files the profile observed
+pub struct Mounts {
+    /// Paths bound at the same place, and whether they are writable
+    pub binds: Vec<(String, bool)>,
+    /// Devices bound besides the ones of a fresh /dev
+    pub devices: Vec<String>,
+}
+
+impl Mounts {
+    pub fn new(result: &ProgramResult) -> Mounts {
+        // a written file may be created by the command, its directory is bound
+        let mut binds = BTreeMap::new();
+        let mut devices = Vec::new();
+        for (path, access) in result.all_files() {
+            let writable = access.contains(Access::W);
+            if SYSTEM
+                .iter()
+                .any(|dir| path == dir || path.starts_with(&format!("{}/", dir)))
+                || path.starts_with("/proc/")
+                || DEVICES.iter().any(|device| path.starts_with(device))
+                || (writable && path.starts_with("/tmp/"))
+            {
+                continue;
+            }
+            if path.starts_with("/dev/") {
+                devices.push(path.clone());
+                continue;
+            }
+            let target = if writable { parent(path) } else { path };
+            *binds.entry(target.to_string()).or_insert(false) |= writable;
+        }
+        if !result.destinations.is_empty() || !result.bus_names.is_empty() {
+            binds.entry(SYSTEM_BUS.to_string()).or_insert(true);
+        }
+        Mounts {
+            binds: collapse(binds),
+            devices,
+        }
+    }
+}
+
+/// Options of bwrap reproducing what the profile observed
+pub struct BwrapSandbox {
+    /// Each option with its values, in the order bwrap applies them
+    pub options: Vec<Vec<String>>,
+    /// Whether the sandbox adds capabilities, bwrap then has to run as root
+    pub privileged: bool,
+}
+
+fn option(name: &str, values: &[&str]) -> Vec<String> {
+    std::iter::once(name)
+        .chain(values.iter().copied())
+        .map(str::to_string)
+        .collect()
+}
+
+impl BwrapSandbox {
+    pub fn new(result: &ProgramResult) -> BwrapSandbox {
+        let mut options = vec![
+            option("--unshare-all", &[]),
+            option("--die-with-parent", &[]),
+        ];
+        if result.network {
+            options.push(option("--share-net", &[]));
+        }
+        options.push(option("--ro-bind", &["/usr", "/usr"]));
+        for dir in &SYSTEM[1..] {
+            options.push(option("--ro-bind-try", &[dir, dir]));
+        }
+        options.push(option("--proc", &["/proc"]));
+        options.push(option("--dev", &["/dev"]));
+        options.push(option("--tmpfs", &["/tmp"]));
+
+        let mounts = Mounts::new(result);
+        for (path, writable) in &mounts.binds {
+            let name = if *writable {
+                "--bind-try"
+            } else {
+                "--ro-bind-try"
+            };
+            options.push(option(name, &[path, path]));
+        }
+        for device in &mounts.devices {
+            options.push(option("--dev-bind-try", &[device, device]));
+        }
+
+        let privileged = !result.capabilities.is_empty();
+        if privileged {
+            options.push(option("--cap-drop", &["ALL"]));
+            for name in result.capability_names() {
+                options.push(option("--cap-add", &[name]));
+            }
+        }
+        BwrapSandbox {
+            options,
+            privileged,
+        }
+    }
+
+    /// Shell command line running `command` in the sandbox
+    pub fn command_line(&self, command: &[String]) -> String {
+        let mut line = String::new();
+        if self.privileged {
+            line.push_str("# --cap-add requires running bwrap as root\n");
+        }
+        line.push_str("bwrap \\\n");
+        for option in &self.options {
+            line.push_str(&format!("  {} \\\n", shell_words::join(option)));
+        }
+        line.push_str(&format!("  -- {}\n", shell_words::join(command)));
+        line
+    }
+}
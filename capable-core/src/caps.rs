@@ -0,0 +1,160 @@
+//! Capability sets and the effective capabilities of the profiler itself.
+
+use capctl::{Cap, CapSet, CapState, ParseCapError};
+
+pub fn capset_to_vec(set: &CapSet) -> Vec<String> {
+    set.iter().map(|c| format!("CAP_{:?}", c)).collect()
+}
+
+pub fn capset_to_string(set: &CapSet) -> String {
+    if set == &!CapSet::empty() {
+        return String::from("ALL");
+    }
+    set.iter()
+        .fold(String::new(), |mut acc, cap| {
+            acc.push_str(&format!("CAP_{:?} ", cap));
+            acc
+        })
+        .trim_end()
+        .to_string()
+}
+
+pub fn get_cap(val: u8) -> Option<Cap> {
+    match val {
+        0 => Some(Cap::CHOWN),
+        1 => Some(Cap::DAC_OVERRIDE),
+        2 => Some(Cap::DAC_READ_SEARCH),
+        3 => Some(Cap::FOWNER),
+        4 => Some(Cap::FSETID),
+        5 => Some(Cap::KILL),
+        6 => Some(Cap::SETGID),
+        7 => Some(Cap::SETUID),
+        8 => Some(Cap::SETPCAP),
+        9 => Some(Cap::LINUX_IMMUTABLE),
+        10 => Some(Cap::NET_BIND_SERVICE),
+        11 => Some(Cap::NET_BROADCAST),
+        12 => Some(Cap::NET_ADMIN),
+        13 => Some(Cap::NET_RAW),
+        14 => Some(Cap::IPC_LOCK),
+        15 => Some(Cap::IPC_OWNER),
+        16 => Some(Cap::SYS_MODULE),
+        17 => Some(Cap::SYS_RAWIO),
+        18 => Some(Cap::SYS_CHROOT),
+        19 => Some(Cap::SYS_PTRACE),
+        20 => Some(Cap::SYS_PACCT),
+        21 => Some(Cap::SYS_ADMIN),
+        22 => Some(Cap::SYS_BOOT),
+        23 => Some(Cap::SYS_NICE),
+        24 => Some(Cap::SYS_RESOURCE),
+        25 => Some(Cap::SYS_TIME),
+        26 => Some(Cap::SYS_TTY_CONFIG),
+        27 => Some(Cap::MKNOD),
+        28 => Some(Cap::LEASE),
+        29 => Some(Cap::AUDIT_WRITE),
+        30 => Some(Cap::AUDIT_CONTROL),
+        31 => Some(Cap::SETFCAP),
+        32 => Some(Cap::MAC_OVERRIDE),
+        33 => Some(Cap::MAC_ADMIN),
+        34 => Some(Cap::SYSLOG),
+        35 => Some(Cap::WAKE_ALARM),
+        36 => Some(Cap::BLOCK_SUSPEND),
+        37 => Some(Cap::AUDIT_READ),
+        38 => Some(Cap::PERFMON),
+        39 => Some(Cap::BPF),
+        40 => Some(Cap::CHECKPOINT_RESTORE),
+        _ => None,
+    }
+}
+
+pub fn parse_capset_iter<'a, I>(iter: I) -> Result<CapSet, ParseCapError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut res = CapSet::empty();
+
+    for part in iter {
+        match part.parse() {
+            Ok(cap) => res.add(cap),
+            Err(error) => {
+                return Err(error);
+            }
+        }
+    }
+    Ok(res)
+}
+
+const CAPABILITIES_ERROR: &str =
+    "You need at least bpf, perfmon and sys_ptrace capabilities to run capable, plus setpcap and sys_admin to profile a command. Before Linux 5.8, sys_admin replaces bpf and perfmon";
+fn cap_effective_error(caplist: &str) -> String {
+    format!(
+        "Unable to toggle {} privilege. {}",
+        caplist, CAPABILITIES_ERROR
+    )
+}
+
+pub fn cap_effective(cap: Cap, enable: bool) -> Result<(), capctl::Error> {
+    let mut current = CapState::get_current()?;
+    current.effective.set_state(cap, enable);
+    current.set_current()
+}
+
+pub fn dac_read_search_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_effective(Cap::DAC_READ_SEARCH, enable).inspect_err(|_| {
+        eprintln!("{}", cap_effective_error("DAC_READ_SEARCH"));
+    })
+}
+
+pub fn setpcap_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_effective(Cap::SETPCAP, enable).inspect_err(|_| {
+        eprintln!("{}", cap_effective_error("SETPCAP"));
+    })
+}
+
+/// Whether `cap` is in the permitted set of capable
+pub fn is_permitted(cap: Cap) -> bool {
+    CapState::get_current().is_ok_and(|state| state.permitted.has(cap))
+}
+
+/// Toggle `cap`, or SYS_ADMIN when the kernel does not know `cap` or it is not permitted.
+/// BPF, PERFMON and NET_ADMIN are split out of SYS_ADMIN for the eBPF operations.
+fn cap_or_admin_effective(cap: Cap, name: &str, enable: bool) -> Result<(), capctl::Error> {
+    if !cap.is_supported() || !is_permitted(cap) {
+        return setadmin_effective(enable);
+    }
+    cap_effective(cap, enable).inspect_err(|_| {
+        eprintln!("{}", cap_effective_error(name));
+    })
+}
+
+pub fn setbpf_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_or_admin_effective(Cap::BPF, "BPF", enable)
+}
+
+/// Loading the tracing programs and opening the perf events of the kprobes, tracepoints and
+/// uprobes, PERFMON since Linux 5.8
+pub fn setperfmon_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_or_admin_effective(Cap::PERFMON, "PERFMON", enable)
+}
+
+/// Loading and attaching the cgroup programs
+pub fn setnetadmin_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_or_admin_effective(Cap::NET_ADMIN, "NET_ADMIN", enable)
+}
+
+pub fn setadmin_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_effective(Cap::SYS_ADMIN, enable).inspect_err(|_| {
+        eprintln!("{}", cap_effective_error("SYS_ADMIN"));
+    })
+}
+
+pub fn setresource_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_effective(Cap::SYS_RESOURCE, enable).inspect_err(|_| {
+        eprintln!("{}", cap_effective_error("SYS_RESOURCE"));
+    })
+}
+
+pub fn setptrace_effective(enable: bool) -> Result<(), capctl::Error> {
+    cap_effective(Cap::SYS_PTRACE, enable).inspect_err(|_| {
+        eprintln!("{}", cap_effective_error("SYS_PTRACE"));
+    })
+}
@@ -0,0 +1,62 @@
+//! The cgroup array the probes check the current task against, see [`CgroupFilter`].
+
+use std::fs::File;
+use std::mem::size_of;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+
+use aya::maps::Map;
+
+/// bpf() command number of BPF_MAP_UPDATE_ELEM
+const BPF_MAP_UPDATE_ELEM: libc::c_long = 2;
+
+/// Element commands member of `union bpf_attr`
+#[repr(C)]
+#[derive(Default)]
+struct ElemAttr {
+    map_fd: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+/// BPF_MAP_TYPE_CGROUP_ARRAY of a single cgroup. The probes only record the tasks of this
+/// cgroup and of its descendants, with bpf_current_task_under_cgroup. Empty, they record
+/// every task.
+pub struct CgroupFilter {
+    fd: OwnedFd,
+}
+
+impl CgroupFilter {
+    pub fn new(map: &Map) -> Result<CgroupFilter, anyhow::Error> {
+        // aya has no type for the cgroup arrays
+        let fd = match map {
+            Map::Unsupported(data) => data.fd().as_fd().try_clone_to_owned()?,
+            _ => return Err(anyhow::anyhow!("cgroup filter map is not a cgroup array")),
+        };
+        Ok(CgroupFilter { fd })
+    }
+
+    /// Only record the tasks under the cgroup opened as `directory`
+    pub fn set(&self, directory: &File) -> std::io::Result<()> {
+        let index: u32 = 0;
+        let value: u32 = directory.as_raw_fd() as u32;
+        let mut attr = ElemAttr {
+            map_fd: self.fd.as_raw_fd() as u32,
+            key: &index as *const u32 as u64,
+            value: &value as *const u32 as u64,
+            ..Default::default()
+        };
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_bpf,
+                BPF_MAP_UPDATE_ELEM,
+                &mut attr as *mut ElemAttr,
+                size_of::<ElemAttr>(),
+            )
+        };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,457 @@
+//! Aggregation of the capability requests per process and namespace, and the resulting profile.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::rc::Rc;
+
+use capable_common::{Nsid, Pid};
+use capctl::{Cap, CapSet};
+use log::{debug, log_enabled};
+use rayon::prelude::*;
+use tracing::info_span;
+
+use crate::bus::{self, DbusMsg};
+use crate::caps::{capset_to_vec, get_cap};
+use crate::explain;
+use crate::loader;
+use crate::manifest;
+use crate::memlock;
+use crate::ownership::{self, OWNERSHIP_CAPABILITIES};
+use crate::profile::{capability_key, NestedNamespace, ProgramResult, TimeWindow};
+use crate::reasons::Reasons;
+use crate::sockets;
+use crate::strace::{parse_strace, Parameter, Syscall};
+use crate::suggest;
+use crate::syscalls::{self, SyscallAccessEntry};
+
+/// Capabilities requested by a process, aggregated from its requests
+#[derive(Clone, Debug)]
+pub struct CapSetEntry {
+    pub pid: Pid,
+    pub ppid: Pid,
+    pub uid: capable_common::Uid,
+    pub gid: capable_common::Gid,
+    pub ns: Nsid,
+    pub parent_ns: Nsid,
+    /// cgroup of the process when it first requested a capability, 0 when unknown
+    pub cgroup: u64,
+    pub capabilities: CapSet,
+    /// Requested capabilities the kernel denied
+    pub denied: CapSet,
+    pub reasons: Reasons,
+}
+
+impl CapSetEntry {
+    pub fn new(
+        pid: Pid,
+        ppid: Pid,
+        uid: capable_common::Uid,
+        gid: capable_common::Gid,
+        parent_ns: Nsid,
+        ns: Nsid,
+    ) -> CapSetEntry {
+        CapSetEntry {
+            pid,
+            ppid,
+            uid,
+            gid,
+            parent_ns,
+            ns,
+            cgroup: 0,
+            capabilities: CapSet::empty(),
+            denied: CapSet::empty(),
+            reasons: Reasons::new(),
+        }
+    }
+    pub fn add(&mut self, cap: Cap) {
+        self.capabilities.add(cap);
+    }
+}
+
+impl Hash for CapSetEntry {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.pid.hash(state);
+        self.ppid.hash(state);
+        self.uid.hash(state);
+        self.gid.hash(state);
+        self.parent_ns.hash(state);
+        self.ns.hash(state);
+    }
+}
+
+impl PartialEq for CapSetEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.pid == other.pid
+            && self.ppid == other.ppid
+            && self.uid == other.uid
+            && self.parent_ns == other.parent_ns
+            && self.ns == other.ns
+    }
+}
+
+impl Eq for CapSetEntry {}
+
+/// Receives every capability request drained from the kernel, with its symbolized kernel
+/// stack, e.g. to record the raw events of a run
+pub trait RequestObserver {
+    fn observe(
+        &mut self,
+        entry: &CapSetEntry,
+        capability: u8,
+        denied: bool,
+        time: u64,
+        stack: &[String],
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// Namespace and parent namespace of a request, packed by the eBPF program
+pub fn extract_ns(pinum_inum: u64) -> (u32, u32) {
+    let ns = (pinum_inum & 0xffffffff) as u32;
+    let parent_ns = (pinum_inum >> 32) as u32;
+    (ns, parent_ns)
+}
+
+/// Add a capability request to the entry of its process, unless it is a known false
+/// positive. `frames` symbolizes the kernel stack of the request, only when needed.
+pub fn add_request<F>(
+    set_entry: &mut HashSet<CapSetEntry>,
+    mut entry: CapSetEntry,
+    capability: u8,
+    denied: bool,
+    time: u64,
+    mut frames: F,
+) -> Result<(), anyhow::Error>
+where
+    F: FnMut() -> Result<Rc<[String]>, anyhow::Error>,
+{
+    let mut binding = set_entry.take(&entry);
+    let entry = binding.as_mut().unwrap_or(&mut entry);
+    // DAC capabilities are reported like the others, the files of their checks are added
+    // from dac_accesses when the LSM hooks found them
+    if !((capability == Cap::SETUID as u8 && skip_priv_sym(&frames()?, "cap_bprm_creds_from_file"))
+        || capability == Cap::SYS_PTRACE as u8)
+    {
+        let cap = get_cap(capability).unwrap_or_else(|| panic!("Unknown capability: {}", capability));
+        entry.add(cap);
+        if denied {
+            entry.denied.add(cap);
+        }
+        let name = format!("CAP_{:?}", cap);
+        let stack = frames()?;
+        entry
+            .reasons
+            .entry(name.clone())
+            .or_default()
+            .observe(&name, &stack, time);
+        if log_enabled!(log::Level::Debug) {
+            for sym in stack.iter() {
+                debug!("{}()", sym);
+            }
+        }
+    }
+
+    //debug!("new entry: {:?}", entry);
+
+    set_entry.insert(entry.clone());
+    Ok(())
+}
+
+fn skip_priv_sym(frames: &[String], symbol: &str) -> bool {
+    frames.iter().any(|sym| sym == symbol)
+}
+
+/// Build the profile of a run from its capabilities, strace log and dbus monitor result
+/// socket(AF_INET, ...) or socket(AF_INET6, ...)
+fn opens_internet_socket(syscall: &Syscall) -> bool {
+    syscall.syscall.trim() == "socket"
+        && matches!(
+            syscall.args.first(),
+            Some(Parameter::Constant(family)) if family == "AF_INET" || family == "AF_INET6"
+        )
+}
+
+pub fn build_result(
+    mut capset: CapSet,
+    strace_log: Option<&str>,
+    dbus_result: Option<&std::collections::HashMap<u32, Vec<DbusMsg>>>,
+    nsinode: u32,
+    window: Option<TimeWindow>,
+    mut reasons: Reasons,
+) -> ProgramResult {
+    let strace_span = info_span!("strace").entered();
+    let syscalls = strace_log.map(parse_strace).unwrap_or_default();
+    if syscalls
+        .par_iter()
+        .any(|syscall| syscall.syscall.trim() == "ptrace")
+    {
+        capset.add(Cap::SYS_PTRACE);
+    }
+    // capabilities are per-thread, each worker toggles its own DAC_READ_SEARCH
+    let access: Vec<SyscallAccessEntry> = syscalls
+        .par_iter()
+        .filter_map(syscalls::syscall_to_entry)
+        .flatten()
+        .collect();
+    let mut map = BTreeMap::new();
+    for entry in access {
+        let key = entry.path.clone();
+        let value = entry.access;
+        *map.entry(key).or_insert(value) |= entry.access;
+    }
+    let runtime = loader::split_runtime(&syscalls, &mut map);
+    let executed = manifest::executed_files(&syscalls);
+    let network = syscalls.par_iter().any(opens_internet_socket);
+    let listening = sockets::listening_sockets(&syscalls);
+    let control_sockets = sockets::control_sockets(&syscalls);
+    drop(strace_span);
+    let dbus_span = info_span!("dbus").entered();
+    let method_list = dbus_result
+        .map(|content| bus::get_dbus_methods(content, nsinode))
+        .unwrap_or_default();
+    let destinations = dbus_result
+        .map(|content| bus::get_destinations(content, nsinode))
+        .unwrap_or_default();
+    let bus_names = dbus_result
+        .map(|content| bus::get_bus_names(content, nsinode))
+        .unwrap_or_default();
+    let activated = dbus_result
+        .map(|content| bus::get_activations(content, nsinode))
+        .unwrap_or_default();
+    let polkit = dbus_result
+        .map(|content| bus::get_polkit_actions(content, nsinode))
+        .unwrap_or_default();
+    drop(dbus_span);
+    let _analysis_span = info_span!("analysis").entered();
+    let names = capset_to_vec(&capset);
+    let mut capabilities = Vec::new();
+    let mut stack_explanations = Vec::new();
+    for name in names {
+        let mut evidence = reasons.remove(&name).unwrap_or_default();
+        if !evidence.explanations.is_empty() {
+            stack_explanations.push((
+                capability_key(&name),
+                std::mem::take(&mut evidence.explanations),
+            ));
+        }
+        capabilities.push(evidence.finding(name, window.map(|window| window.start)));
+    }
+    let mut annotations = explain::explain(&syscalls, &capabilities);
+    for (key, explanations) in stack_explanations {
+        annotations.entry(key).or_default().extend(explanations);
+    }
+    let ownership = if capabilities
+        .iter()
+        .any(|finding| OWNERSHIP_CAPABILITIES.contains(&finding.name.as_str()))
+    {
+        ownership::ownership_changes(&syscalls)
+    } else {
+        Vec::new()
+    };
+    let memlock = memlock::memory_lock(&syscalls, &capabilities, memlock::memlock_limit());
+    let suggestions = suggest::suggest(
+        &capabilities,
+        &syscalls,
+        &listening,
+        &control_sockets,
+        &ownership,
+        memlock.as_ref(),
+    );
+    let mut result = ProgramResult {
+        capabilities,
+        suggestions,
+        containers: BTreeMap::new(),
+        files: map,
+        runtime,
+        executed,
+        network,
+        listening,
+        control_sockets,
+        ownership,
+        memlock,
+        dbus: method_list,
+        destinations,
+        bus_names,
+        activated,
+        polkit,
+        sysctls: BTreeMap::new(),
+        self_managed: Vec::new(),
+        window,
+        setuid: None,
+        labels: BTreeMap::new(),
+        annotations,
+        provenance: BTreeMap::new(),
+        stats: None,
+        namespaces: Vec::new(),
+        held: None,
+    };
+    explain::explain_yama(&mut result);
+    explain::explain_keyring(&mut result);
+    result
+}
+
+/// Hierarchy of the PID namespaces of the requests. Each request links the namespace of
+/// the process with the one of its parent process; namespaces without any request, such as
+/// the one of a container runtime between the command and a nested sandbox, are linked
+/// with the parents read from the kernel.
+#[derive(Default, Debug, Clone)]
+pub struct NamespaceTree {
+    children: HashMap<Nsid, BTreeSet<Nsid>>,
+    /// Parent namespace of each namespace, as known to the kernel
+    parents: HashMap<Nsid, Nsid>,
+}
+
+/// Namespace below the root of `NamespaceTree::descendants`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Descendant {
+    pub ns: Nsid,
+    pub parent: Nsid,
+    /// 0 for the root itself
+    pub depth: u32,
+}
+
+impl NamespaceTree {
+    pub fn new(set_entry: &HashSet<CapSetEntry>) -> NamespaceTree {
+        let mut tree = NamespaceTree::default();
+        for entry in set_entry {
+            tree.children
+                .entry(entry.parent_ns)
+                .or_default()
+                .insert(entry.ns);
+        }
+        tree
+    }
+
+    /// Record that the kernel created `ns` below `parent`
+    pub fn link(&mut self, parent: Nsid, ns: Nsid) {
+        self.children.entry(parent).or_default().insert(ns);
+        self.parents.insert(ns, parent);
+    }
+
+    /// Namespaces whose requests belong to `root`, at any depth, parents first. `root`
+    /// itself is part of them when one of its processes has a parent in it.
+    pub fn descendants(&self, root: Nsid) -> Vec<Descendant> {
+        let mut parents: HashMap<Nsid, Nsid> = HashMap::new();
+        let mut order = Vec::new();
+        let mut pending = VecDeque::from([root]);
+        while let Some(parent) = pending.pop_front() {
+            for &ns in self.children.get(&parent).into_iter().flatten() {
+                if ns == root {
+                    if !order.contains(&root) {
+                        order.push(root);
+                    }
+                } else if let Entry::Vacant(vacant) = parents.entry(ns) {
+                    vacant.insert(parent);
+                    order.push(ns);
+                    pending.push_back(ns);
+                }
+            }
+        }
+        // a process may have a parent in an ancestor of its namespace, e.g. once reparented,
+        // the parent known to the kernel is kept when it is part of the tree
+        for (ns, parent) in parents.iter_mut() {
+            if let Some(known) = self.parents.get(ns) {
+                if *known == root || order.contains(known) {
+                    *parent = *known;
+                }
+            }
+        }
+        let bound = order.len() as u32;
+        let depth = |mut ns: Nsid| {
+            let mut depth = 0;
+            while ns != root && depth <= bound {
+                ns = parents[&ns];
+                depth += 1;
+            }
+            depth
+        };
+        order
+            .into_iter()
+            .map(|ns| Descendant {
+                ns,
+                parent: parents.get(&ns).copied().unwrap_or(root),
+                depth: depth(ns),
+            })
+            .collect()
+    }
+}
+
+/// Capabilities selected by `select` in the namespace `nsinode` and its descendants
+pub fn namespace_capabilities<F>(
+    tree: &NamespaceTree,
+    nsinode: u32,
+    set_entry: &HashSet<CapSetEntry>,
+    select: F,
+) -> CapSet
+where
+    F: Fn(&CapSetEntry) -> CapSet,
+{
+    let namespaces: HashSet<Nsid> = tree
+        .descendants(nsinode)
+        .into_iter()
+        .map(|descendant| descendant.ns)
+        .collect();
+    set_entry
+        .iter()
+        .filter(|entry| namespaces.contains(&entry.ns))
+        .fold(CapSet::empty(), |capset, entry| capset | select(entry))
+}
+
+/// Reasons of the capabilities requested in the namespace `nsinode` and its descendants,
+/// the same entries as namespace_capabilities
+pub fn namespace_reasons(
+    tree: &NamespaceTree,
+    nsinode: u32,
+    set_entry: &HashSet<CapSetEntry>,
+) -> Reasons {
+    let namespaces: HashSet<Nsid> = tree
+        .descendants(nsinode)
+        .into_iter()
+        .map(|descendant| descendant.ns)
+        .collect();
+    entries_reasons(
+        set_entry
+            .iter()
+            .filter(|entry| namespaces.contains(&entry.ns)),
+    )
+}
+
+/// Namespaces nested in `nsinode`, with the capabilities requested in each of them
+pub fn nested_namespaces(
+    tree: &NamespaceTree,
+    nsinode: u32,
+    set_entry: &HashSet<CapSetEntry>,
+) -> Vec<NestedNamespace> {
+    tree.descendants(nsinode)
+        .into_iter()
+        .filter(|descendant| descendant.depth > 0)
+        .map(|descendant| {
+            let capabilities = set_entry
+                .iter()
+                .filter(|entry| entry.ns == descendant.ns)
+                .fold(CapSet::empty(), |capset, entry| capset | entry.capabilities);
+            NestedNamespace {
+                ns: descendant.ns,
+                parent: descendant.parent,
+                depth: descendant.depth,
+                capabilities: capset_to_vec(&capabilities),
+            }
+        })
+        .collect()
+}
+
+/// Reasons of the capabilities requested by `entries`, with the processes requesting them
+pub fn entries_reasons<'a, I>(entries: I) -> Reasons
+where
+    I: IntoIterator<Item = &'a CapSetEntry>,
+{
+    let mut reasons = Reasons::new();
+    for entry in entries {
+        for (capability, evidence) in &entry.reasons {
+            let reason = reasons.entry(capability.clone()).or_default();
+            reason.extend(evidence);
+            reason.processes.insert(entry.pid);
+        }
+    }
+    reasons
+}
@@ -0,0 +1,87 @@
+//! zstd compression of the files written by `capable`, chosen by their `.zst` extension.
+//! Daemon-mode captures and the history of busy hosts get large as plain JSON.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::Context;
+
+/// zstd level of the outputs, written once at the end of a run
+const OUTPUT_LEVEL: i32 = 9;
+
+/// Extension of the compressed files, after the one of their content
+pub const EXTENSION: &str = "zst";
+
+pub fn is_compressed(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == EXTENSION)
+}
+
+/// Writer of an output, compressed when its path ends with .zst. [`Output::finish`] must
+/// be called to write the end of the compressed frame.
+pub enum Output {
+    Plain(BufWriter<File>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Output {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Output, anyhow::Error> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("Unable to create {}", path.display()))?;
+        Output::new(file, is_compressed(path))
+    }
+
+    pub fn new(file: File, compressed: bool) -> Result<Output, anyhow::Error> {
+        let file = BufWriter::new(file);
+        Ok(if compressed {
+            Output::Zstd(zstd::Encoder::new(file, OUTPUT_LEVEL)?)
+        } else {
+            Output::Plain(file)
+        })
+    }
+
+    pub fn finish(self) -> Result<(), anyhow::Error> {
+        let mut file = match self {
+            Output::Plain(file) => file,
+            Output::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Plain(file) => file.write(buf),
+            Output::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Plain(file) => file.flush(),
+            Output::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Write `content` to `path`, compressed when it ends with .zst
+pub fn write<P: AsRef<Path>>(path: P, content: &str) -> Result<(), anyhow::Error> {
+    let mut output = Output::create(path)?;
+    writeln!(output, "{}", content)?;
+    output.finish()
+}
+
+/// Content of `path`, decompressed when it ends with .zst
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> std::io::Result<String> {
+    let path = path.as_ref();
+    if !is_compressed(path) {
+        return std::fs::read_to_string(path);
+    }
+    let mut content = String::new();
+    zstd::Decoder::new(File::open(path)?)?.read_to_string(&mut content)?;
+    Ok(content)
+}
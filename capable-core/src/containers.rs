@@ -0,0 +1,70 @@
+//! Comparison of the profile with the default capabilities of container runtimes.
+
+use std::collections::BTreeMap;
+
+use crate::profile::{ContainerComparison, ProgramResult};
+
+/// Default capabilities of Docker and containerd containers
+const DOCKER_DEFAULT: &[&str] = &[
+    "CAP_AUDIT_WRITE",
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_MKNOD",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_RAW",
+    "CAP_SETFCAP",
+    "CAP_SETGID",
+    "CAP_SETPCAP",
+    "CAP_SETUID",
+    "CAP_SYS_CHROOT",
+];
+
+/// Capabilities a pod may have under the baseline Pod Security Standard, the runtime
+/// defaults without CAP_NET_RAW
+const KUBERNETES_BASELINE: &[&str] = &[
+    "CAP_AUDIT_WRITE",
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_MKNOD",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_SETFCAP",
+    "CAP_SETGID",
+    "CAP_SETPCAP",
+    "CAP_SETUID",
+    "CAP_SYS_CHROOT",
+];
+
+/// The restricted Pod Security Standard drops all capabilities but this one
+const KUBERNETES_RESTRICTED: &[&str] = &["CAP_NET_BIND_SERVICE"];
+
+const DEFAULT_SETS: &[(&str, &[&str])] = &[
+    ("docker", DOCKER_DEFAULT),
+    ("kubernetes-baseline", KUBERNETES_BASELINE),
+    ("kubernetes-restricted", KUBERNETES_RESTRICTED),
+];
+
+/// What to add to and drop from each default set to match the profile
+pub fn compare(result: &ProgramResult) -> BTreeMap<String, ContainerComparison> {
+    DEFAULT_SETS
+        .iter()
+        .map(|(name, default)| {
+            let cap_add = result
+                .capability_names()
+                .filter(|cap| !default.contains(cap))
+                .map(String::from)
+                .collect();
+            let cap_drop = default
+                .iter()
+                .filter(|cap| !result.has_capability(cap))
+                .map(|cap| cap.to_string())
+                .collect();
+            (name.to_string(), ContainerComparison { cap_add, cap_drop })
+        })
+        .collect()
+}
@@ -0,0 +1,618 @@
+//! eBPF programs observing the capability checks of the kernel, and their maps.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{read_to_string, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use aya::maps::{Array, HashMap, Map, MapData, PerCpuArray, StackTraceMap};
+use aya::programs::{CgroupAttachMode, CgroupSysctl, KProbe, TracePoint};
+use aya::util::KernelVersion;
+use aya::Ebpf;
+use aya_log::EbpfLogger;
+use capable_common::{
+    CgroupPath, DacAccess, LibcapCall, ModuleRequest, Nsid, Request, Stats, SysctlRequest,
+    CGROUP_COMPONENT_LEN, CGROUP_PATH_COMPONENTS, CGROUP_PATH_LEN, DAC_COMPONENT_LEN,
+    DAC_PATH_COMPONENTS, DAC_PATH_LEN,
+};
+use capctl::{Cap, CapSet};
+use log::{debug, warn};
+use tracing::info_span;
+
+use crate::approvals::Approvals;
+use crate::arch::check_probes_arch;
+use crate::caps::{
+    get_cap, is_permitted, setbpf_effective, setnetadmin_effective, setperfmon_effective,
+};
+use crate::cgroup_filter::CgroupFilter;
+use crate::collect::{
+    add_request, extract_ns, namespace_capabilities, namespace_reasons, nested_namespaces,
+    CapSetEntry, NamespaceTree, RequestObserver,
+};
+use crate::ksyms::KernelSymbols;
+use crate::memlock::remove_memlock_limit;
+use crate::profile::{CapabilityChange, NestedNamespace, SysctlAccess};
+use crate::reasons::Reasons;
+use crate::requests::RequestMap;
+use crate::stats::{CollectorStats, MapOccupancy};
+use crate::time::boot_time;
+use crate::{libcap, sysctl, version};
+
+/// The eBPF programs observing the capability checks of the kernel, and their maps.
+/// The programs stay attached until it is dropped.
+pub struct Probes {
+    bpf: Ebpf,
+    requests: RequestMap,
+    /// Most requests drained at once, the occupancy of the requests map
+    peak_requests: u32,
+    stats: Arc<PerCpuArray<MapData, Stats>>,
+    module_requests: HashMap<MapData, ModuleRequest, u8>,
+    sysctl_requests: HashMap<MapData, SysctlRequest, u8>,
+    dac_accesses: HashMap<MapData, DacAccess, u8>,
+    libcap_calls: HashMap<MapData, u64, LibcapCall>,
+    ns_parents: HashMap<MapData, Nsid, Nsid>,
+    cgroup_paths: HashMap<MapData, u64, CgroupPath>,
+    cgroup_filter: CgroupFilter,
+    approvals: Arc<Mutex<Approvals>>,
+    stack_traces: StackTraceMap<MapData>,
+    // kallsyms is parsed on the first stack to symbolize
+    ksyms: KernelSymbols,
+    /// max_entries of the maps reported by --stats
+    capacities: BTreeMap<&'static str, u32>,
+}
+
+/// What the processes of a namespace and of its descendants requested
+pub struct NamespaceCapabilities {
+    pub capabilities: CapSet,
+    /// Requested capabilities the kernel denied
+    pub denied: CapSet,
+    pub reasons: Reasons,
+    pub nested: Vec<NestedNamespace>,
+}
+
+/// Maps whose occupancy is reported in the collector statistics
+const REPORTED_MAPS: [&str; 6] = [
+    "ENTRY_MAP",
+    "MODULE_REQUESTS",
+    "SYSCTL_REQUESTS",
+    "DAC_ACCESSES",
+    "LIBCAP_CALLS",
+    "NS_PARENTS",
+];
+
+/// Format of the module:module_request tracepoint, in tracefs or below debugfs
+const MODULE_REQUEST_FORMATS: [&str; 2] = [
+    "/sys/kernel/tracing/events/module/module_request/format",
+    "/sys/kernel/debug/tracing/events/module/module_request/format",
+];
+
+/// Programs recording the file of the DAC capability checks, and the LSM hook each follows
+const DAC_HOOKS: [(&str, &str); 2] = [
+    ("dac_inode", "security_inode_permission"),
+    ("dac_file", "security_file_open"),
+];
+
+fn check_kernel_version() -> Result<(), anyhow::Error> {
+    let current = KernelVersion::current()
+        .context("Unable to get kernel version")?
+        .code();
+    if current != version::LINUX_VERSION_CODE {
+        let major = version::LINUX_VERSION_CODE >> 16;
+        let minor = (version::LINUX_VERSION_CODE >> 8) & 0xff;
+        let patch = version::LINUX_VERSION_CODE & 0xff;
+        let current_major = current >> 16;
+        let current_minor = (current >> 8) & 0xff;
+        let current_patch = current & 0xff;
+        warn!("This program was compiled for kernel version {}.{}.{}, but the current kernel version is {}.{}.{}",
+              major, minor, patch, current_major, current_minor, current_patch);
+        warn!("This may cause the program to fail or behave unexpectedly");
+    }
+    Ok(())
+}
+
+/// Sum the per-CPU counters of the programs
+pub fn read_counters(stats: &PerCpuArray<MapData, Stats>) -> Stats {
+    match stats.get(&0, 0) {
+        Ok(values) => values.iter().fold(Stats::default(), |acc, cpu| Stats {
+            requests: acc.requests + cpu.requests,
+            drops: acc.drops + cpu.drops,
+            capabilities: acc.capabilities | cpu.capabilities,
+            deferred: acc.deferred + cpu.deferred,
+        }),
+        Err(e) => {
+            debug!("unable to read eBPF stats: {}", e);
+            Stats::default()
+        }
+    }
+}
+
+fn map_capacity(bpf: &Ebpf, name: &str) -> Option<u32> {
+    match bpf.map(name)? {
+        Map::HashMap(data) => data.info().ok().map(|info| info.max_entries()),
+        _ => None,
+    }
+}
+
+fn take_hash_map<K: aya::Pod, V: aya::Pod>(
+    bpf: &mut Ebpf,
+    name: &str,
+) -> Result<HashMap<MapData, K, V>, anyhow::Error> {
+    let map = bpf
+        .take_map(name)
+        .with_context(|| format!("Unable to obtain map {}", name))?;
+    Ok(HashMap::try_from(map)?)
+}
+
+/// Parse the eBPF object embedded at build time, or `object` when given, e.g. probes
+/// modified by a distribution or built for another architecture. A replacement must define
+/// the programs and maps of the embedded one, with the same names and types.
+pub fn load_object(object: Option<&Path>) -> Result<Ebpf, anyhow::Error> {
+    match object {
+        Some(path) => Ebpf::load_file(path)
+            .with_context(|| format!("Unable to load the eBPF object {}", path.display())),
+        // This will include your eBPF object file as raw bytes at compile-time and load it at
+        // runtime.
+        None => {
+            check_probes_arch()?;
+            Ok(Ebpf::load(aya::include_bytes_aligned!(concat!(
+                env!("OUT_DIR"),
+                "/capable"
+            )))?)
+        }
+    }
+}
+
+/// Merge requests drained from a requests map into `set_entry`, their stacks are symbolized
+/// from `stack_traces`
+pub fn merge_requests(
+    requests: Vec<Request>,
+    stack_traces: &StackTraceMap<MapData>,
+    ksyms: &mut KernelSymbols,
+    set_entry: &mut HashSet<CapSetEntry>,
+    mut observer: Option<&mut dyn RequestObserver>,
+) -> Result<(), anyhow::Error> {
+    let boot = boot_time();
+    for Request {
+        pid,
+        ppid,
+        uid_gid,
+        pnsid_nsid,
+        capability,
+        denied,
+        stackid,
+        time,
+        cgroup,
+    } in requests
+    {
+        let time = boot + time;
+        assert!(stackid <= i32::MAX as i64); // Inconsistent StackTraceMap key type
+        let (ns, parent_ns) = extract_ns(pnsid_nsid);
+        let uid = uid_gid as u32 as capable_common::Uid;
+        let gid = (uid_gid >> 32) as capable_common::Gid;
+        let mut entry = CapSetEntry::new(pid, ppid, uid, gid, parent_ns, ns);
+        entry.cgroup = cgroup;
+        let stackid = stackid as u32;
+        if let Some(observer) = observer.as_deref_mut() {
+            // the stack map does not outlive the run, give the symbolized stack
+            let stack = ksyms.resolve(stack_traces, stackid)?;
+            observer.observe(&entry, capability, denied != 0, time, &stack)?;
+        }
+        add_request(set_entry, entry, capability, denied != 0, time, || {
+            ksyms.resolve(stack_traces, stackid)
+        })?;
+    }
+    Ok(())
+}
+
+/// Files of the DAC capability checks, from the LSM hooks following them. Without them, the
+/// checks are reported without their file.
+fn attach_dac_hooks(bpf: &mut Ebpf) -> Result<(), anyhow::Error> {
+    for (name, hook) in DAC_HOOKS {
+        let program: &mut KProbe = bpf
+            .program_mut(name)
+            .expect("failed to get Kprobe DAC program")
+            .try_into()
+            .context("Failed to get Kprobe")?;
+        program.load()?;
+        if let Err(e) = program.attach(hook, 0) {
+            warn!(
+                "{} not probed, DAC capabilities are reported without their file: {}",
+                hook, e
+            );
+        }
+    }
+    Ok(())
+}
+
+/// NUL terminated components of `component_len` bytes recorded by the programs, up to the
+/// first empty one
+fn recorded_components(path: &[u8], component_len: usize) -> Vec<String> {
+    path.chunks(component_len)
+        .map(|component| {
+            let len = component
+                .iter()
+                .position(|c| *c == 0)
+                .unwrap_or(component.len());
+            String::from_utf8_lossy(&component[..len]).into_owned()
+        })
+        .take_while(|component| !component.is_empty())
+        .collect()
+}
+
+/// Path of the recorded components, the file first. A full record may be cut from the root
+/// of its mount.
+fn dac_path(path: &[u8; DAC_PATH_LEN]) -> String {
+    let components = recorded_components(path, DAC_COMPONENT_LEN);
+    let prefix = if components.len() == DAC_PATH_COMPONENTS {
+        "..."
+    } else {
+        ""
+    };
+    let mut path = prefix.to_string();
+    for component in components.iter().rev() {
+        path.push('/');
+        path.push_str(component);
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    path
+}
+
+/// Path of a recorded cgroup relative to the root of the hierarchy, the cgroup being the
+/// first component. A full record may be cut, it then starts with `...`.
+pub fn cgroup_path(path: &[u8; CGROUP_PATH_LEN]) -> PathBuf {
+    let components = recorded_components(path, CGROUP_COMPONENT_LEN);
+    let mut relative = PathBuf::new();
+    if components.len() == CGROUP_PATH_COMPONENTS {
+        relative.push("...");
+    }
+    relative.extend(components.iter().rev());
+    relative
+}
+
+/// Offset of the name in the records of module:module_request, from the format of the
+/// tracepoint. None when the kernel has no modules or tracefs is not mounted.
+fn module_request_name_offset() -> Option<u32> {
+    let format = MODULE_REQUEST_FORMATS
+        .iter()
+        .find_map(|path| read_to_string(path).ok())?;
+    let field = format
+        .lines()
+        .find(|line| line.contains("__data_loc") && line.contains(" name;"))?;
+    field
+        .split(';')
+        .find_map(|part| part.trim().strip_prefix("offset:"))?
+        .parse()
+        .ok()
+}
+
+/// Requested modules, without them when the kernel has no modules
+fn attach_module_request(bpf: &mut Ebpf) -> Result<(), anyhow::Error> {
+    let Some(offset) = module_request_name_offset() else {
+        warn!("module:module_request format not found, requested modules are not recorded");
+        return Ok(());
+    };
+    let mut offsets: Array<&mut MapData, u32> = Array::try_from(
+        bpf.map_mut("MODULE_REQUEST_NAME_OFFSET")
+            .expect("Unable to obtain module request name offset map"),
+    )?;
+    offsets.set(0, offset, 0)?;
+    let program: &mut TracePoint = bpf
+        .program_mut("module_request")
+        .expect("failed to get TracePoint module_request program")
+        .try_into()
+        .context("Failed to get TracePoint")?;
+    program.load()?;
+    if let Err(e) = program.attach("module", "module_request") {
+        warn!(
+            "module:module_request not traced, requested modules are not recorded: {}",
+            e
+        );
+    }
+    Ok(())
+}
+
+impl Probes {
+    /// Load the programs and attach them system-wide, except the sysctl one which is only
+    /// attached to a cgroup. Requires BPF, PERFMON and NET_ADMIN in the permitted set, or
+    /// SYS_ADMIN before Linux 5.8. `object` replaces the embedded programs, see `load_object`.
+    pub fn load(object: Option<&Path>) -> Result<Probes, anyhow::Error> {
+        check_kernel_version()?;
+        debug!("setting capabilities");
+        remove_memlock_limit()?;
+        setbpf_effective(true)?;
+        setperfmon_effective(true)?;
+
+        let mut bpf = load_object(object)?;
+
+        if let Err(e) = EbpfLogger::init(&mut bpf) {
+            // This can happen if you remove all log statements from your eBPF program.
+            warn!("failed to initialize eBPF {}", e);
+        }
+        debug!("loading and attaching program {}", "capable");
+        let program: &mut KProbe = bpf
+            .program_mut("capable")
+            .expect("failed to get Kprobe capable program")
+            .try_into()
+            .context("Failed to get Kprobe")?;
+        program.load()?;
+        program.attach("cap_capable", 0)?;
+        let program: &mut KProbe = bpf
+            .program_mut("capable_ret")
+            .expect("failed to get Kretprobe capable_ret program")
+            .try_into()
+            .context("Failed to get Kretprobe")?;
+        program.load()?;
+        program.attach("cap_capable", 0)?;
+        attach_dac_hooks(&mut bpf)?;
+        attach_module_request(&mut bpf)?;
+        // attached to the cgroup of the command, once created. Without NET_ADMIN, only the
+        // daemon mode can run, it has no command.
+        let program: &mut CgroupSysctl = bpf
+            .program_mut("sysctl")
+            .expect("failed to get CgroupSysctl sysctl program")
+            .try_into()
+            .context("Failed to get CgroupSysctl")?;
+        if is_permitted(Cap::NET_ADMIN) || is_permitted(Cap::SYS_ADMIN) {
+            setnetadmin_effective(true)?;
+            program.load()?;
+            setnetadmin_effective(false)?;
+        } else {
+            debug!("NET_ADMIN is not permitted, sysctl program not loaded");
+        }
+        libcap::attach(&mut bpf)?;
+        let capacities = REPORTED_MAPS
+            .iter()
+            .filter_map(|name| Some((*name, map_capacity(&bpf, name)?)))
+            .collect();
+        setbpf_effective(false)?;
+        setperfmon_effective(false)?;
+        debug!("program {} loaded and attached", "capable");
+
+        let requests = RequestMap::new(
+            bpf.take_map("ENTRY_MAP")
+                .expect("Unable to obtain requests map"),
+        )?;
+        let stats =
+            PerCpuArray::try_from(bpf.take_map("STATS").expect("Unable to obtain stats map"))?;
+        let module_requests = take_hash_map(&mut bpf, "MODULE_REQUESTS")?;
+        let sysctl_requests = take_hash_map(&mut bpf, "SYSCTL_REQUESTS")?;
+        let dac_accesses = take_hash_map(&mut bpf, "DAC_ACCESSES")?;
+        let libcap_calls = take_hash_map(&mut bpf, "LIBCAP_CALLS")?;
+        let ns_parents = take_hash_map(&mut bpf, "NS_PARENTS")?;
+        let cgroup_paths = take_hash_map(&mut bpf, "CGROUP_PATHS")?;
+        let approvals = Approvals::new(
+            take_hash_map(&mut bpf, "APPROVALS")?,
+            take_hash_map(&mut bpf, "APPROVAL_REQUESTS")?,
+        );
+        let cgroup_filter = CgroupFilter::new(
+            &bpf.take_map("CGROUP_FILTER")
+                .expect("Unable to obtain cgroup filter map"),
+        )?;
+        let stack_traces = StackTraceMap::try_from(
+            bpf.take_map("STACKTRACE_MAP")
+                .expect("unable to get Stacktrace map"),
+        )?;
+        Ok(Probes {
+            bpf,
+            requests,
+            peak_requests: 0,
+            stats: Arc::new(stats),
+            module_requests,
+            sysctl_requests,
+            dac_accesses,
+            libcap_calls,
+            ns_parents,
+            cgroup_paths,
+            cgroup_filter,
+            approvals: Arc::new(Mutex::new(approvals)),
+            stack_traces,
+            ksyms: KernelSymbols::default(),
+            capacities,
+        })
+    }
+
+    /// Per-CPU counters of the programs, see `read_counters`
+    pub fn counters(&self) -> Arc<PerCpuArray<MapData, Stats>> {
+        self.stats.clone()
+    }
+
+    /// Maps of the interactive mode, shared with the thread asking the user
+    pub fn approvals(&self) -> Arc<Mutex<Approvals>> {
+        self.approvals.clone()
+    }
+
+    /// Statistics of the probes: counters, occupancy of their maps and symbolization.
+    /// The strace and D-Bus ones are left to the caller.
+    pub fn collector_stats(&self) -> Result<CollectorStats, anyhow::Error> {
+        setbpf_effective(true)?;
+        let counters = read_counters(&self.stats);
+        let entries = [
+            ("ENTRY_MAP", self.peak_requests),
+            (
+                "MODULE_REQUESTS",
+                self.module_requests.keys().count() as u32,
+            ),
+            (
+                "SYSCTL_REQUESTS",
+                self.sysctl_requests.keys().count() as u32,
+            ),
+            ("DAC_ACCESSES", self.dac_accesses.keys().count() as u32),
+            ("LIBCAP_CALLS", self.libcap_calls.keys().count() as u32),
+            ("NS_PARENTS", self.ns_parents.keys().count() as u32),
+        ];
+        setbpf_effective(false)?;
+        let maps = entries
+            .into_iter()
+            .filter_map(|(name, entries)| {
+                let capacity = *self.capacities.get(name)?;
+                Some((name.to_string(), MapOccupancy { entries, capacity }))
+            })
+            .collect();
+        let (stacks_resolved, stack_cache_hits) = self.ksyms.cache_stats();
+        Ok(CollectorStats {
+            requests: counters.requests,
+            drops: counters.drops,
+            deferred: counters.deferred,
+            maps,
+            stacks_resolved,
+            stack_cache_hits,
+            ..Default::default()
+        })
+    }
+
+    /// Only record the requests of the processes of the cgroup opened as `directory` and of
+    /// its descendants, and attach the sysctl program to it
+    pub fn restrict_to_cgroup(&mut self, directory: File) -> Result<(), anyhow::Error> {
+        setbpf_effective(true)?;
+        setnetadmin_effective(true)?;
+        self.cgroup_filter.set(&directory)?;
+        let program: &mut CgroupSysctl = self
+            .bpf
+            .program_mut("sysctl")
+            .expect("failed to get CgroupSysctl sysctl program")
+            .try_into()?;
+        program.attach(directory, CgroupAttachMode::Single)?;
+        setnetadmin_effective(false)?;
+        setbpf_effective(false)?;
+        Ok(())
+    }
+
+    /// Drain the pending requests of the kernel map and merge them into `set_entry`.
+    /// Raw requests are discarded once merged, so it can be called repeatedly while collecting.
+    pub fn drain(
+        &mut self,
+        set_entry: &mut HashSet<CapSetEntry>,
+        observer: Option<&mut dyn RequestObserver>,
+    ) -> Result<(), anyhow::Error> {
+        let requests = self.requests.drain()?;
+        self.peak_requests = self.peak_requests.max(requests.len() as u32);
+        merge_requests(
+            requests,
+            &self.stack_traces,
+            &mut self.ksyms,
+            set_entry,
+            observer,
+        )
+    }
+
+    /// Hierarchy of the namespaces of `set_entry`, with the parents of the namespaces
+    /// recorded by the programs
+    pub fn namespace_tree(
+        &self,
+        set_entry: &HashSet<CapSetEntry>,
+    ) -> Result<NamespaceTree, anyhow::Error> {
+        let mut tree = NamespaceTree::new(set_entry);
+        setbpf_effective(true)?;
+        for (ns, parent) in self.ns_parents.iter().filter_map(Result::ok) {
+            tree.link(parent, ns);
+        }
+        setbpf_effective(false)?;
+        Ok(tree)
+    }
+
+    /// Requested and denied capabilities of the namespace `nsinode` and its descendants,
+    /// with the reasons of the requested ones. `set_entry` holds the requests already
+    /// drained while the program ran, the pending ones are added to it.
+    pub fn program_capabilities(
+        &mut self,
+        nsinode: Nsid,
+        mut set_entry: HashSet<CapSetEntry>,
+        observer: Option<&mut dyn RequestObserver>,
+    ) -> Result<NamespaceCapabilities, anyhow::Error> {
+        let _span = info_span!("ebpf", ns = nsinode).entered();
+        setbpf_effective(true)?;
+        self.drain(&mut set_entry, observer)?;
+        setbpf_effective(false)?;
+        let tree = self.namespace_tree(&set_entry)?;
+        Ok(NamespaceCapabilities {
+            capabilities: namespace_capabilities(&tree, nsinode, &set_entry, |entry| {
+                entry.capabilities
+            }),
+            denied: namespace_capabilities(&tree, nsinode, &set_entry, |entry| entry.denied),
+            reasons: namespace_reasons(&tree, nsinode, &set_entry),
+            nested: nested_namespaces(&tree, nsinode, &set_entry),
+        })
+    }
+
+    /// Modules the kernel requested for the processes of the namespace `ns`. They are
+    /// removed from the map, a later run in a namespace with the same inode starts empty.
+    pub fn requested_modules(&mut self, ns: Nsid) -> Result<BTreeSet<String>, anyhow::Error> {
+        setbpf_effective(true)?;
+        let requests: Vec<ModuleRequest> = self
+            .module_requests
+            .keys()
+            .filter_map(|request| request.ok())
+            .filter(|request| request.ns == ns)
+            .collect();
+        let mut modules = BTreeSet::new();
+        for request in requests {
+            let _ = self.module_requests.remove(&request);
+            let len = request
+                .name
+                .iter()
+                .position(|c| *c == 0)
+                .unwrap_or(request.name.len());
+            modules.insert(String::from_utf8_lossy(&request.name[..len]).into_owned());
+        }
+        setbpf_effective(false)?;
+        Ok(modules)
+    }
+
+    /// Path of the cgroup of the requests, relative to the root of the hierarchy, by cgroup
+    /// id. They are recorded with the requests, so the cgroups already removed are known.
+    pub fn cgroup_paths(&self) -> Result<Vec<(u64, PathBuf)>, anyhow::Error> {
+        setbpf_effective(true)?;
+        let paths = self
+            .cgroup_paths
+            .iter()
+            .filter_map(Result::ok)
+            .map(|(id, path)| (id, cgroup_path(&path.path)))
+            .collect();
+        setbpf_effective(false)?;
+        Ok(paths)
+    }
+
+    /// Files the kernel checked a DAC capability on in the namespace, per capability
+    pub fn dac_accesses(
+        &self,
+        ns: Nsid,
+    ) -> Result<BTreeMap<String, BTreeSet<String>>, anyhow::Error> {
+        setbpf_effective(true)?;
+        let mut accesses: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for access in self
+            .dac_accesses
+            .keys()
+            .filter_map(|access| access.ok())
+            .filter(|access| access.ns == ns)
+        {
+            let Some(cap) = get_cap(access.capability as u8) else {
+                continue;
+            };
+            accesses
+                .entry(format!("CAP_{:?}", cap))
+                .or_default()
+                .insert(dac_path(&access.path));
+        }
+        setbpf_effective(false)?;
+        Ok(accesses)
+    }
+
+    /// Sysctls accessed by the cgroup given to `restrict_to_cgroup`
+    pub fn sysctls(&self) -> Result<BTreeMap<String, SysctlAccess>, anyhow::Error> {
+        setbpf_effective(true)?;
+        let sysctls = sysctl::read_sysctls(&self.sysctl_requests);
+        setbpf_effective(false)?;
+        Ok(sysctls)
+    }
+
+    /// Changes of their own capabilities by the processes of the namespace `ns`
+    pub fn self_managed(&self, ns: Nsid) -> Result<Vec<CapabilityChange>, anyhow::Error> {
+        setbpf_effective(true)?;
+        let calls = libcap::read_calls(&self.libcap_calls, ns);
+        setbpf_effective(false)?;
+        Ok(calls)
+    }
+}
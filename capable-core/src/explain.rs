@@ -0,0 +1,800 @@
+//! Root causes of capability findings, from the syscalls traced by strace.
+//!
+//! A capability alone does not tell why the command required it. The syscalls listed in
+//! [`EXPLAINED_SYSCALLS`] are traced with the file ones and each of them is turned into a
+//! note on the findings it can explain, stored as an annotation of the profile.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::read_to_string;
+
+use crate::ksyms::base_symbol;
+use crate::profile::{capability_key, CapabilityFinding, ProgramResult};
+use crate::strace::{Parameter, Syscall};
+use crate::time::{format_time, unix_time};
+
+/// Syscalls traced only to explain findings or suggest alternatives. The ? prefix tells
+/// strace to ignore the ones this architecture does not have.
+pub const EXPLAINED_SYSCALLS: &[&str] = &[
+    "?add_key",
+    "?keyctl",
+    "?request_key",
+    "?setrlimit",
+    "?prlimit64",
+    "?sched_setscheduler",
+    "?sched_setattr",
+    "?setpriority",
+    "?sched_setaffinity",
+    "?clock_settime",
+    "?settimeofday",
+    "?adjtimex",
+    "?clock_adjtime",
+    "?init_module",
+    "?finit_module",
+    "?socket",
+    "?bind",
+    "?listen",
+    "?connect",
+    "?sendto",
+    "?sendmsg",
+    "?bpf",
+    "?perf_event_open",
+    "?io_uring_setup",
+    "?io_uring_register",
+    "?mlock",
+    "?mlock2",
+    "?mlockall",
+];
+
+/// Keyring operations are checked against CAP_SYS_ADMIN, or CAP_SETUID when a key is
+/// handed to a process of another user. The stack of the check tells which one.
+const KEYRING_CAPABILITIES: &[&str] = &["CAP_SYS_ADMIN", "CAP_SETUID"];
+const KEYRING_SYSCALLS: &[&str] = &["add_key", "keyctl", "request_key"];
+
+/// Raising a hard resource limit, or another process limits, requires CAP_SYS_RESOURCE
+const RLIMIT_CAPABILITIES: &[&str] = &["CAP_SYS_RESOURCE"];
+
+/// Raising the priority, or scheduling another process, requires CAP_SYS_NICE
+const SCHED_CAPABILITIES: &[&str] = &["CAP_SYS_NICE"];
+
+/// Setting or adjusting the system clock requires CAP_SYS_TIME
+const TIME_CAPABILITIES: &[&str] = &["CAP_SYS_TIME"];
+
+/// Loading a kernel module requires CAP_SYS_MODULE
+const MODULE_CAPABILITIES: &[&str] = &["CAP_SYS_MODULE"];
+const MODULE_ADVICE: &str =
+    "load it at boot with modules-load.d instead of granting the capability";
+
+/// Opening a raw or packet socket requires CAP_NET_RAW
+const RAW_SOCKET_CAPABILITIES: &[&str] = &["CAP_NET_RAW"];
+const PING_ADVICE: &str = "ping only needs an unprivileged ICMP socket (SOCK_DGRAM), allow \
+    its group with net.ipv4.ping_group_range instead of granting the capability";
+
+/// Changing the network configuration through netlink requires CAP_NET_ADMIN
+const NETLINK_CAPABILITIES: &[&str] = &["CAP_NET_ADMIN"];
+
+/// What a netlink message changes, by a prefix of its type. Requests reading the
+/// configuration (RTM_GET...) need no capability and are left out.
+const NETLINK_OPERATIONS: &[(&str, &str)] = &[
+    ("RTM_NEWLINK", "configures network interfaces"),
+    ("RTM_SETLINK", "configures network interfaces"),
+    ("RTM_DELLINK", "removes network interfaces"),
+    ("RTM_NEWADDR", "assigns addresses"),
+    ("RTM_DELADDR", "removes addresses"),
+    ("RTM_NEWROUTE", "modifies routes"),
+    ("RTM_DELROUTE", "modifies routes"),
+    ("RTM_NEWRULE", "modifies routing rules"),
+    ("RTM_DELRULE", "modifies routing rules"),
+    ("RTM_NEWNEIGH", "modifies the neighbour table"),
+    ("RTM_DELNEIGH", "modifies the neighbour table"),
+    ("RTM_NEWQDISC", "configures traffic control"),
+    ("RTM_DELQDISC", "configures traffic control"),
+    ("RTM_NEWTCLASS", "configures traffic control"),
+    ("RTM_DELTCLASS", "configures traffic control"),
+    ("RTM_NEWTFILTER", "configures traffic control"),
+    ("RTM_DELTFILTER", "configures traffic control"),
+    ("NFT_MSG_NEW", "configures nftables"),
+    ("NFT_MSG_DEL", "configures nftables"),
+    ("IPCTNL_MSG_CT_NEW", "manages connection tracking"),
+    ("IPCTNL_MSG_CT_DELETE", "manages connection tracking"),
+    ("XFRM_MSG_NEW", "configures IPsec"),
+    ("XFRM_MSG_UPD", "configures IPsec"),
+    ("XFRM_MSG_DEL", "configures IPsec"),
+];
+
+/// Loading programs and creating maps requires CAP_BPF, and CAP_PERFMON for the tracing
+/// programs or CAP_NET_ADMIN for the networking ones. CAP_SYS_ADMIN replaces them before
+/// Linux 5.8.
+const BPF_CAPABILITIES: &[&str] = &["CAP_BPF", "CAP_PERFMON", "CAP_NET_ADMIN", "CAP_SYS_ADMIN"];
+/// Opening a perf event of another process, of every process of a CPU or of the kernel
+/// requires CAP_PERFMON, or CAP_SYS_ADMIN before Linux 5.8
+const PERF_CAPABILITIES: &[&str] = &["CAP_PERFMON", "CAP_SYS_ADMIN"];
+const BPF_ADVICE: &str = "a privileged helper loading the programs and pinning them to bpffs \
+    avoids granting the capability to the command";
+
+/// Without CAP_IPC_LOCK the memory of an io_uring instance is charged to RLIMIT_MEMLOCK,
+/// and CAP_SYS_ADMIN allows creating one when kernel.io_uring_disabled is 1
+const IO_URING_CAPABILITIES: &[&str] = &["CAP_IPC_LOCK", "CAP_SYS_ADMIN"];
+/// The polling thread of SQPOLL requires CAP_SYS_ADMIN before Linux 5.11 and no capability
+/// since, but CAP_SYS_NICE to pin it to a CPU (SQ_AFF)
+const SQPOLL_CAPABILITIES: &[&str] = &["CAP_SYS_ADMIN", "CAP_IPC_LOCK"];
+const SQ_AFF_CAPABILITIES: &[&str] = &["CAP_SYS_NICE", "CAP_SYS_ADMIN", "CAP_IPC_LOCK"];
+/// Registered buffers are pinned, and charged to RLIMIT_MEMLOCK without CAP_IPC_LOCK
+const BUFFER_CAPABILITIES: &[&str] = &["CAP_IPC_LOCK"];
+const SQPOLL_FLAG: &str = "IORING_SETUP_SQPOLL";
+const SQ_AFF_FLAG: &str = "IORING_SETUP_SQ_AFF";
+const IO_URING_ADVICE: &str = "without CAP_IPC_LOCK its memory counts towards the locked \
+    memory limit, raise LimitMEMLOCK= instead of granting the capability";
+
+/// Yama restricts ptrace beyond the permissions of the user, CAP_SYS_PTRACE passes its check
+const YAMA_PTRACE_SCOPE: &str = "/proc/sys/kernel/yama/ptrace_scope";
+const PTRACE_CAPABILITY: &str = "CAP_SYS_PTRACE";
+/// Kernel function of the Yama check, in the stacks of the requests it made
+const YAMA_CHECK: &str = "yama_ptrace_access_check";
+
+/// Note on the findings a syscall may explain
+struct Explanation {
+    capabilities: &'static [&'static str],
+    note: String,
+}
+
+fn string_arg(syscall: &Syscall, n: usize) -> Option<&str> {
+    match syscall.args.get(n) {
+        Some(Parameter::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn arg(syscall: &Syscall, n: usize) -> String {
+    syscall
+        .args
+        .get(n)
+        .map(|arg| arg.to_string())
+        .unwrap_or_default()
+}
+
+/// How the syscall ended, when it failed
+fn failure(syscall: &Syscall) -> String {
+    match &syscall.return_code.constant {
+        Some(error) if syscall.return_code.code < 0 => format!(" (failed with {})", error),
+        _ => String::new(),
+    }
+}
+
+fn keyring(syscall: &Syscall) -> Option<Explanation> {
+    let note = match syscall.syscall.trim() {
+        "add_key" => format!(
+            "keyring: add_key of a \"{}\" key to {}",
+            string_arg(syscall, 0)?,
+            arg(syscall, syscall.args.len().checked_sub(1)?)
+        ),
+        "request_key" => format!(
+            "keyring: request_key of a \"{}\" key",
+            string_arg(syscall, 0)?
+        ),
+        "keyctl" => format!("keyring: keyctl {}", arg(syscall, 0)),
+        _ => return None,
+    };
+    Some(Explanation {
+        capabilities: KEYRING_CAPABILITIES,
+        note: note + &failure(syscall),
+    })
+}
+
+/// A limit as strace prints it (e.g. 8192*1024 or RLIM64_INFINITY), in the unit file format
+fn limit_value(value: &str) -> String {
+    if value.starts_with("RLIM") && value.ends_with("_INFINITY") {
+        return String::from("infinity");
+    }
+    value
+        .split('*')
+        .map(|factor| factor.parse::<u64>().ok())
+        .try_fold(1u64, |acc, factor| acc.checked_mul(factor?))
+        .map_or(value.to_string(), |limit| limit.to_string())
+}
+
+fn rlimit(syscall: &Syscall) -> Option<Explanation> {
+    let (pid, resource, limit) = match syscall.syscall.trim() {
+        "setrlimit" => ("0".to_string(), arg(syscall, 0), syscall.args.get(1)?),
+        "prlimit64" => (arg(syscall, 0), arg(syscall, 1), syscall.args.get(2)?),
+        _ => return None,
+    };
+    // prlimit64 without a new limit only reads it
+    let Parameter::Dict(limit) = limit else {
+        return None;
+    };
+    let soft = limit_value(limit.get("rlim_cur")?);
+    let hard = limit_value(limit.get("rlim_max")?);
+    let target = if pid == "0" {
+        String::new()
+    } else {
+        format!(" of process {}", pid)
+    };
+    // systemd names the limits after the resource, RLIMIT_NOFILE is LimitNOFILE
+    let directive = resource.strip_prefix("RLIMIT_").unwrap_or(&resource);
+    Some(Explanation {
+        capabilities: RLIMIT_CAPABILITIES,
+        note: format!(
+            "rlimit: {} set to {} soft, {} hard{}{}, Limit{}={}:{} in the unit file avoids the capability",
+            resource,
+            soft,
+            hard,
+            target,
+            failure(syscall),
+            directive,
+            soft,
+            hard
+        ),
+    })
+}
+
+/// CPUSchedulingPolicy of a policy as strace prints it, e.g. SCHED_FIFO|SCHED_RESET_ON_FORK
+fn sched_policy(policy: &str) -> Option<(String, bool)> {
+    let reset_on_fork = policy.contains("SCHED_RESET_ON_FORK");
+    let policy = policy
+        .split('|')
+        .find(|flag| *flag != "SCHED_RESET_ON_FORK")?
+        .strip_prefix("SCHED_")?
+        .to_lowercase();
+    Some((policy, reset_on_fork))
+}
+
+fn sched_directives(policy: &str, priority: &str) -> Option<String> {
+    let (policy, reset_on_fork) = sched_policy(policy)?;
+    let mut directives = format!("CPUSchedulingPolicy={}", policy);
+    if priority != "0" {
+        directives += &format!(" / CPUSchedulingPriority={}", priority);
+    }
+    if reset_on_fork {
+        directives += " / CPUSchedulingResetOnFork=yes";
+    }
+    Some(directives)
+}
+
+fn sched(syscall: &Syscall) -> Option<Explanation> {
+    let (call, pid, directives) = match syscall.syscall.trim() {
+        "sched_setscheduler" => {
+            let priority = match syscall.args.get(2)? {
+                Parameter::Array(priority) => priority.first()?.clone(),
+                _ => return None,
+            };
+            (
+                "sched_setscheduler",
+                arg(syscall, 0),
+                sched_directives(&arg(syscall, 1), &priority)?,
+            )
+        }
+        "sched_setattr" => {
+            let Parameter::Dict(attr) = syscall.args.get(1)? else {
+                return None;
+            };
+            let mut directives =
+                sched_directives(attr.get("sched_policy")?, attr.get("sched_priority")?)?;
+            if let Some(nice) = attr.get("sched_nice").filter(|nice| *nice != "0") {
+                directives += &format!(" / Nice={}", nice);
+            }
+            ("sched_setattr", arg(syscall, 0), directives)
+        }
+        "setpriority" => (
+            "setpriority",
+            arg(syscall, 1),
+            format!("Nice={}", arg(syscall, 2)),
+        ),
+        "sched_setaffinity" => {
+            let cpus = match syscall.args.get(2)? {
+                Parameter::Array(cpus) => cpus.join(" "),
+                _ => return None,
+            };
+            (
+                "sched_setaffinity",
+                arg(syscall, 0),
+                format!("CPUAffinity={}", cpus),
+            )
+        }
+        _ => return None,
+    };
+    let target = if pid == "0" {
+        String::new()
+    } else {
+        format!(" on process {}", pid)
+    };
+    Some(Explanation {
+        capabilities: SCHED_CAPABILITIES,
+        note: format!(
+            "scheduling: {}{}{}, {} in the unit file avoids the capability",
+            call,
+            target,
+            failure(syscall),
+            directives
+        ),
+    })
+}
+
+/// Time set by a clock syscall, with its distance to the time of the analysis
+fn time_value(time: &HashMap<String, String>) -> Option<String> {
+    let seconds: i64 = time.get("tv_sec")?.parse().ok()?;
+    let now = unix_time() as i64;
+    Some(format!(
+        "{} ({:+} s from the current time)",
+        format_time(seconds.max(0) as u64),
+        seconds - now
+    ))
+}
+
+/// Description of the adjustment requested from the kernel clock discipline
+fn adjustment(timex: &HashMap<String, String>) -> Option<String> {
+    let modes = timex.get("modes")?;
+    // adjtimex with no mode only reads the clock state, which is not privileged
+    if modes == "0" {
+        return None;
+    }
+    let mut adjustment = format!("modes {}", modes);
+    for field in ["offset", "freq", "status"] {
+        if modes.contains(&format!("ADJ_{}", field.to_uppercase())) {
+            if let Some(value) = timex.get(field) {
+                adjustment += &format!(", {} {}", field, value);
+            }
+        }
+    }
+    Some(adjustment)
+}
+
+fn time(syscall: &Syscall) -> Option<Explanation> {
+    let change = match (syscall.syscall.trim(), syscall.args.as_slice()) {
+        ("clock_settime", [clock, Parameter::Dict(time), ..]) => {
+            format!("clock_settime of {} to {}", clock, time_value(time)?)
+        }
+        ("settimeofday", [Parameter::Dict(time), ..]) => {
+            format!("settimeofday to {}", time_value(time)?)
+        }
+        ("adjtimex", [Parameter::Dict(timex), ..]) => {
+            format!("adjtimex with {}", adjustment(timex)?)
+        }
+        ("clock_adjtime", [clock, Parameter::Dict(timex), ..]) => {
+            format!("clock_adjtime of {} with {}", clock, adjustment(timex)?)
+        }
+        _ => return None,
+    };
+    Some(Explanation {
+        capabilities: TIME_CAPABILITIES,
+        note: format!(
+            "time: {}{}, unless the command synchronizes the clock, leave it to a time daemon such as systemd-timesyncd",
+            change,
+            failure(syscall)
+        ),
+    })
+}
+
+/// Module files opened by the command, loaded by the next init_module or finit_module
+#[derive(Default)]
+struct ModuleFiles {
+    /// Module file of each file descriptor
+    fds: HashMap<String, String>,
+    /// init_module takes the module image, it is usually the last module file read
+    last: Option<String>,
+}
+
+/// Module name of a module file, e.g. wireguard for .../wireguard.ko.zst
+fn module_name(path: &str) -> &str {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    file.split(".ko").next().unwrap_or(file)
+}
+
+impl ModuleFiles {
+    fn observe(&mut self, syscall: &Syscall) {
+        if !matches!(syscall.syscall.trim(), "open" | "openat" | "openat2") {
+            return;
+        }
+        let Some(path) = syscall.args.iter().find_map(|arg| match arg {
+            Parameter::String(path) if path.contains(".ko") => Some(path),
+            _ => None,
+        }) else {
+            return;
+        };
+        if syscall.return_code.code >= 0 {
+            self.fds
+                .insert(syscall.return_code.code.to_string(), path.clone());
+            self.last = Some(path.clone());
+        }
+    }
+
+    fn explain(&self, syscall: &Syscall) -> Option<Explanation> {
+        let path = match syscall.syscall.trim() {
+            "finit_module" => self.fds.get(&arg(syscall, 0)),
+            "init_module" => self.last.as_ref(),
+            _ => return None,
+        };
+        let module = match path {
+            Some(path) => format!("of {} ({})", module_name(path), path),
+            None => String::from("of an unknown module"),
+        };
+        Some(Explanation {
+            capabilities: MODULE_CAPABILITIES,
+            note: format!(
+                "module: {} {}{}, {}",
+                syscall.syscall.trim(),
+                module,
+                failure(syscall),
+                MODULE_ADVICE
+            ),
+        })
+    }
+}
+
+/// Annotate CAP_SYS_MODULE with the modules the kernel requested (request_module) while
+/// handling the syscalls of the command
+pub fn explain_requested_modules(result: &mut ProgramResult, modules: &BTreeSet<String>) {
+    let cap = MODULE_CAPABILITIES[0];
+    if modules.is_empty() || !result.has_capability(cap) {
+        return;
+    }
+    let notes = result.annotations.entry(capability_key(cap)).or_default();
+    for module in modules {
+        notes.push(format!(
+            "module: the kernel requested {}, {}",
+            module, MODULE_ADVICE
+        ));
+    }
+}
+
+/// ptrace scope of Yama on this host, None when Yama is not enabled
+pub fn yama_ptrace_scope() -> Option<u8> {
+    read_to_string(YAMA_PTRACE_SCOPE).ok()?.trim().parse().ok()
+}
+
+/// Annotate CAP_SYS_PTRACE when Yama restricts ptrace, the command may only need the
+/// capability to pass its check
+pub fn explain_yama(result: &mut ProgramResult) {
+    let Some(finding) = result
+        .capabilities
+        .iter()
+        .find(|finding| finding.name == PTRACE_CAPABILITY)
+    else {
+        return;
+    };
+    let origin = if finding
+        .reason_symbols
+        .iter()
+        .any(|symbol| base_symbol(symbol) == YAMA_CHECK)
+    {
+        "the capability was checked by Yama"
+    } else {
+        "the capability may stem from Yama"
+    };
+    let note = match yama_ptrace_scope() {
+        Some(1) => format!(
+            "yama: kernel.yama.ptrace_scope is 1, only the ancestors of a process and the tracer it declares with prctl(PR_SET_PTRACER) may trace it without the capability, {}",
+            origin
+        ),
+        Some(2) => format!(
+            "yama: kernel.yama.ptrace_scope is 2, tracing any process requires the capability, {}",
+            origin
+        ),
+        Some(3) => String::from(
+            "yama: kernel.yama.ptrace_scope is 3, no process may be traced even with the capability",
+        ),
+        _ => return,
+    };
+    result
+        .annotations
+        .entry(capability_key(PTRACE_CAPABILITY))
+        .or_default()
+        .push(note);
+}
+
+fn raw_socket(syscall: &Syscall) -> Option<Explanation> {
+    if syscall.syscall.trim() != "socket" {
+        return None;
+    }
+    let family = arg(syscall, 0);
+    let flags = arg(syscall, 1);
+    // SOCK_CLOEXEC and SOCK_NONBLOCK are or-ed with the type
+    let kind = flags.split('|').next().unwrap_or_default();
+    if family != "AF_PACKET" && kind != "SOCK_RAW" && kind != "SOCK_PACKET" {
+        return None;
+    }
+    let protocol = arg(syscall, 2);
+    let mut note = format!(
+        "socket: {}, {}, {}{}",
+        family,
+        kind,
+        protocol,
+        failure(syscall)
+    );
+    if matches!(family.as_str(), "AF_INET" | "AF_INET6") && protocol.starts_with("IPPROTO_ICMP") {
+        note = format!("{}, {}", note, PING_ADVICE);
+    }
+    Some(Explanation {
+        capabilities: RAW_SOCKET_CAPABILITIES,
+        note,
+    })
+}
+
+/// Types of the netlink messages sent, as strace decodes them (nlmsg_type=RTM_NEWROUTE).
+/// nftables batches prefix them with their subsystem, NFNL_SUBSYS_NFTABLES<<8|NFT_MSG_...
+fn netlink_types(syscall: &Syscall) -> BTreeSet<String> {
+    let mut types = BTreeSet::new();
+    for arg in &syscall.args {
+        let text = arg.to_string();
+        for (index, field) in text.match_indices("nlmsg_type=") {
+            let kind = text[index + field.len()..]
+                .split(|c: char| c == ',' || c == '}' || c == '"' || c.is_whitespace())
+                .next()
+                .unwrap_or_default();
+            let kind = kind.rsplit('|').next().unwrap_or(kind);
+            types.insert(kind.to_string());
+        }
+    }
+    types
+}
+
+fn netlink(syscall: &Syscall) -> Option<Explanation> {
+    if !matches!(syscall.syscall.trim(), "sendto" | "sendmsg") {
+        return None;
+    }
+    let operations: BTreeSet<String> = netlink_types(syscall)
+        .iter()
+        .filter_map(|kind| {
+            NETLINK_OPERATIONS
+                .iter()
+                .find(|(prefix, _)| kind.starts_with(prefix))
+                .map(|(_, operation)| format!("{} ({})", operation, kind))
+        })
+        .collect();
+    if operations.is_empty() {
+        return None;
+    }
+    Some(Explanation {
+        capabilities: NETLINK_CAPABILITIES,
+        note: format!(
+            "netlink: {}{}",
+            operations.into_iter().collect::<Vec<_>>().join(", "),
+            failure(syscall)
+        ),
+    })
+}
+
+/// Value of a field of a decoded structure, without the quotes of a string
+fn field<'a>(fields: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    fields
+        .get(name)
+        .map(|value| value.trim_end_matches('.').trim_matches('"'))
+}
+
+fn bpf(syscall: &Syscall) -> Option<Explanation> {
+    if syscall.syscall.trim() != "bpf" {
+        return None;
+    }
+    let command = arg(syscall, 0);
+    let attr = match syscall.args.get(1) {
+        Some(Parameter::Dict(attr)) => Some(attr),
+        _ => None,
+    };
+    let object = match (command.as_str(), attr) {
+        ("BPF_PROG_LOAD", Some(attr)) => {
+            let mut program = format!(" of a {} program", field(attr, "prog_type")?);
+            if let Some(name) = field(attr, "prog_name").filter(|name| !name.is_empty()) {
+                program += &format!(" \"{}\"", name);
+            }
+            program
+        }
+        ("BPF_MAP_CREATE", Some(attr)) => {
+            let mut map = format!(" of a {} map", field(attr, "map_type")?);
+            if let Some(name) = field(attr, "map_name").filter(|name| !name.is_empty()) {
+                map += &format!(" \"{}\"", name);
+            }
+            map
+        }
+        ("BPF_OBJ_PIN" | "BPF_OBJ_GET", Some(attr)) => format!(" of {}", field(attr, "pathname")?),
+        _ => String::new(),
+    };
+    Some(Explanation {
+        capabilities: BPF_CAPABILITIES,
+        note: format!(
+            "bpf: {}{}{}, {}",
+            command,
+            object,
+            failure(syscall),
+            BPF_ADVICE
+        ),
+    })
+}
+
+/// Events the perf event observes, the process, every process of a CPU or the kernel
+fn perf_target(pid: &str, cpu: &str) -> String {
+    match (pid, cpu) {
+        ("-1", "-1") => String::from("every process"),
+        ("-1", cpu) => format!("every process of CPU {}", cpu),
+        ("0", _) => String::from("the command itself"),
+        (pid, _) => format!("process {}", pid),
+    }
+}
+
+fn perf_event(syscall: &Syscall) -> Option<Explanation> {
+    if syscall.syscall.trim() != "perf_event_open" {
+        return None;
+    }
+    let Some(Parameter::Dict(attr)) = syscall.args.first() else {
+        return None;
+    };
+    let mut event = format!("{} event", field(attr, "type")?);
+    if let Some(config) = field(attr, "config") {
+        event += &format!(" (config {})", config);
+    }
+    Some(Explanation {
+        capabilities: PERF_CAPABILITIES,
+        note: format!(
+            "perf: perf_event_open of a {} on {}{}",
+            event,
+            perf_target(&arg(syscall, 1), &arg(syscall, 2)),
+            failure(syscall)
+        ),
+    })
+}
+
+/// io_uring instance created with a polling thread (IORING_SETUP_SQPOLL)
+pub fn io_uring_sqpoll(syscall: &Syscall) -> bool {
+    syscall.syscall.trim() == "io_uring_setup" && arg(syscall, 1).contains(SQPOLL_FLAG)
+}
+
+/// io_uring instance whose polling thread is pinned to a CPU (IORING_SETUP_SQ_AFF)
+pub fn io_uring_sq_affinity(syscall: &Syscall) -> bool {
+    io_uring_sqpoll(syscall) && arg(syscall, 1).contains(SQ_AFF_FLAG)
+}
+
+/// Number and total size of the buffers registered to an io_uring instance
+pub fn registered_buffers(syscall: &Syscall) -> Option<(usize, u64)> {
+    if syscall.syscall.trim() != "io_uring_register"
+        || !arg(syscall, 1).starts_with("IORING_REGISTER_BUFFERS")
+    {
+        return None;
+    }
+    let buffers = arg(syscall, 2);
+    let lengths: Vec<u64> = buffers
+        .match_indices("iov_len=")
+        .filter_map(|(index, field)| {
+            buffers[index + field.len()..]
+                .split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()
+        })
+        .collect();
+    Some((lengths.len(), lengths.iter().sum()))
+}
+
+fn io_uring(syscall: &Syscall) -> Option<Explanation> {
+    if let Some((count, size)) = registered_buffers(syscall) {
+        return Some(Explanation {
+            capabilities: BUFFER_CAPABILITIES,
+            note: format!(
+                "io_uring: {} of {} buffers ({} bytes) pinned in memory{}, {}",
+                arg(syscall, 1),
+                count,
+                size,
+                failure(syscall),
+                IO_URING_ADVICE
+            ),
+        });
+    }
+    if syscall.syscall.trim() != "io_uring_setup" {
+        return None;
+    }
+    let (capabilities, polling) = if io_uring_sq_affinity(syscall) {
+        (
+            SQ_AFF_CAPABILITIES,
+            " with a polling thread (IORING_SETUP_SQPOLL, CAP_SYS_ADMIN before Linux 5.11) pinned to a CPU (IORING_SETUP_SQ_AFF, CAP_SYS_NICE since Linux 5.11)",
+        )
+    } else if io_uring_sqpoll(syscall) {
+        (
+            SQPOLL_CAPABILITIES,
+            " with a polling thread (IORING_SETUP_SQPOLL, CAP_SYS_ADMIN before Linux 5.11)",
+        )
+    } else {
+        (IO_URING_CAPABILITIES, "")
+    };
+    Some(Explanation {
+        capabilities,
+        note: format!(
+            "io_uring: io_uring_setup of {} entries{}{}, {}; kernel.io_uring_disabled=1 requires CAP_SYS_ADMIN outside kernel.io_uring_group",
+            arg(syscall, 0),
+            polling,
+            failure(syscall),
+            IO_URING_ADVICE
+        ),
+    })
+}
+
+/// Annotate the DAC capabilities with the files the kernel checked them on
+pub fn explain_dac_accesses(
+    result: &mut ProgramResult,
+    accesses: &BTreeMap<String, BTreeSet<String>>,
+) {
+    for (cap, paths) in accesses {
+        if !result.has_capability(cap) {
+            continue;
+        }
+        let notes = result.annotations.entry(capability_key(cap)).or_default();
+        notes.extend(paths.iter().map(|path| format!("dac: checked on {}", path)));
+    }
+}
+
+fn explain_syscall(syscall: &Syscall) -> Option<Explanation> {
+    keyring(syscall)
+        .or_else(|| rlimit(syscall))
+        .or_else(|| sched(syscall))
+        .or_else(|| time(syscall))
+        .or_else(|| raw_socket(syscall))
+        .or_else(|| netlink(syscall))
+        .or_else(|| bpf(syscall))
+        .or_else(|| perf_event(syscall))
+        .or_else(|| io_uring(syscall))
+}
+
+/// Whether `finding` may have been checked during `syscall`, always when its stacks are
+/// unknown
+pub fn checked_during(finding: &CapabilityFinding, syscall: &str) -> bool {
+    finding.syscalls.is_empty() || finding.syscalls.iter().any(|name| name == syscall)
+}
+
+/// Annotate the keyring capabilities checked during a keyring syscall from their stacks,
+/// when strace did not trace the operation, e.g. with --collector seccomp
+pub fn explain_keyring(result: &mut ProgramResult) {
+    for finding in &result.capabilities {
+        if !KEYRING_CAPABILITIES.contains(&finding.name.as_str()) {
+            continue;
+        }
+        let key = capability_key(&finding.name);
+        if result
+            .annotations
+            .get(&key)
+            .is_some_and(|notes| notes.iter().any(|note| note.starts_with("keyring: ")))
+        {
+            continue;
+        }
+        let keyring: Vec<String> = finding
+            .syscalls
+            .iter()
+            .filter(|syscall| KEYRING_SYSCALLS.contains(&syscall.as_str()))
+            .map(|syscall| format!("keyring: checked during {}", syscall))
+            .collect();
+        if !keyring.is_empty() {
+            result.annotations.entry(key).or_default().extend(keyring);
+        }
+    }
+}
+
+/// Notes per finding key, only for the capabilities of the profile
+pub fn explain(
+    syscalls: &[Syscall],
+    capabilities: &[CapabilityFinding],
+) -> BTreeMap<String, Vec<String>> {
+    let mut notes: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    let mut modules = ModuleFiles::default();
+    for syscall in syscalls {
+        modules.observe(syscall);
+        let Some(explanation) = explain_syscall(syscall).or_else(|| modules.explain(syscall))
+        else {
+            continue;
+        };
+        let name = syscall.syscall.trim();
+        let several = explanation.capabilities.len() > 1;
+        for cap in explanation.capabilities {
+            let Some(finding) = capabilities.iter().find(|finding| finding.name == *cap) else {
+                continue;
+            };
+            // a syscall checking one of several capabilities, e.g. CAP_BPF or CAP_SYS_ADMIN
+            // for bpf(), only explains the ones checked during it
+            if several && !checked_during(finding, name) {
+                continue;
+            }
+            notes
+                .entry(cap)
+                .or_default()
+                .insert(explanation.note.clone());
+        }
+    }
+    notes
+        .into_iter()
+        .map(|(cap, notes)| (capability_key(cap), notes.into_iter().collect()))
+        .collect()
+}
@@ -0,0 +1,148 @@
+//! firejail profile allowing what a profile observed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+use crate::paths::{collapse, first_component, home_path, parent};
+use crate::profile::ProgramResult;
+use crate::syscalls::Access;
+
+/// Directories where firejail accepts `whitelist`, anything else stays visible
+const WHITELISTED: [&str; 8] = [
+    "/opt/",
+    "/srv/",
+    "/var/",
+    "/media/",
+    "/mnt/",
+    "/run/user/",
+    "/usr/share/",
+    "/dev/",
+];
+
+/// Devices kept by `private-dev`
+const PRIVATE_DEV: [&str; 13] = [
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+    "/dev/ptmx",
+    "/dev/pts/",
+    "/dev/shm/",
+    "/dev/dri/",
+    "/dev/snd/",
+    "/dev/video",
+    "/dev/fd/",
+];
+
+/// Profile of firejail reproducing what the profile observed
+pub struct FirejailProfile {
+    /// Lines of the profile, in order
+    pub lines: Vec<String>,
+    /// What the profile cannot express, with why
+    pub unsupported: Vec<String>,
+}
+
+impl FirejailProfile {
+    pub fn new(result: &ProgramResult) -> FirejailProfile {
+        let mut lines = Vec::new();
+        let mut unsupported = Vec::new();
+        let mut whitelist = BTreeMap::new();
+        let mut etc = BTreeSet::new();
+        let mut private_dev = true;
+        let mut private_tmp = true;
+        for (path, access) in result.all_files() {
+            let writable = access.contains(Access::W);
+            if let Some(rest) = home_path(path) {
+                let grant = format!("${{HOME}}/{}", first_component(rest));
+                *whitelist.entry(grant).or_insert(false) |= writable;
+                continue;
+            }
+            if let Some(rest) = path.strip_prefix("/etc/") {
+                if writable {
+                    unsupported.push(format!(
+                        "{} ({}): private-etc discards the changes",
+                        path, access
+                    ));
+                }
+                etc.insert(first_component(rest).to_string());
+                continue;
+            }
+            if path.starts_with("/dev/") {
+                private_dev &= PRIVATE_DEV.iter().any(|device| path.starts_with(device));
+                continue;
+            }
+            if path.starts_with("/tmp/") {
+                // private-tmp only keeps the X11 sockets, the files read must exist
+                private_tmp &= writable || path.starts_with("/tmp/.X11-unix/");
+                continue;
+            }
+            if WHITELISTED.iter().any(|dir| path.starts_with(dir)) {
+                let grant = if writable { parent(path) } else { path };
+                *whitelist.entry(grant.to_string()).or_insert(false) |= writable;
+            }
+        }
+
+        let capabilities: Vec<String> = result
+            .capability_names()
+            .map(|name| name.trim_start_matches("CAP_").to_lowercase())
+            .collect();
+        if capabilities.is_empty() {
+            lines.push("caps.drop all".to_string());
+            lines.push("nonewprivs".to_string());
+            lines.push("noroot".to_string());
+        } else {
+            lines.push(format!("caps.keep {}", capabilities.join(",")));
+        }
+        if result.network {
+            lines.push("protocol unix,inet,inet6".to_string());
+        } else {
+            lines.push("protocol unix".to_string());
+            lines.push("net none".to_string());
+        }
+        if private_dev {
+            lines.push("private-dev".to_string());
+        }
+        if private_tmp {
+            lines.push("private-tmp".to_string());
+        }
+        if !etc.is_empty() {
+            let files: Vec<String> = etc.into_iter().collect();
+            lines.push(format!("private-etc {}", files.join(",")));
+        }
+        for (path, writable) in collapse(whitelist) {
+            lines.push(format!("whitelist {}", path));
+            if !writable {
+                lines.push(format!("read-only {}", path));
+            }
+        }
+        if result.destinations.is_empty() && result.bus_names.is_empty() {
+            lines.push("dbus-system none".to_string());
+        } else {
+            lines.push("dbus-system filter".to_string());
+            for name in &result.bus_names {
+                lines.push(format!("dbus-system.own {}", name));
+            }
+            for destination in &result.destinations {
+                lines.push(format!("dbus-system.talk {}", destination));
+            }
+        }
+        FirejailProfile { lines, unsupported }
+    }
+}
+
+impl Display for FirejailProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        if !self.unsupported.is_empty() {
+            writeln!(f, "# not allowed by the profile:")?;
+            for reason in &self.unsupported {
+                writeln!(f, "#   {}", reason)?;
+            }
+        }
+        Ok(())
+    }
+}
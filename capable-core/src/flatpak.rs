@@ -0,0 +1,181 @@
+//! Flatpak sandbox permissions of a profile, as the `finish-args` of a manifest.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+use crate::paths::{collapse, first_component, home_path, parent};
+use crate::profile::ProgramResult;
+use crate::syscalls::Access;
+
+/// Files the sandbox provides from the runtime or the host, never granted
+const PROVIDED: [&str; 20] = [
+    "/usr/",
+    "/lib/",
+    "/lib32/",
+    "/lib64/",
+    "/bin/",
+    "/sbin/",
+    "/app/",
+    "/proc/",
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+    "/dev/pts/",
+    "/dev/shm/",
+    "/etc/ld.so.",
+    "/etc/resolv.conf",
+    "/etc/hosts",
+    "/etc/localtime",
+];
+
+/// Files of /etc the sandbox copies from the host
+const HOST_ETC_PROVIDED: [&str; 6] = [
+    "/etc/passwd",
+    "/etc/group",
+    "/etc/machine-id",
+    "/etc/host.conf",
+    "/etc/nsswitch.conf",
+    "/etc/gai.conf",
+];
+
+/// Directories Flatpak refuses to expose, the files below them cannot be granted
+const RESERVED: [&str; 6] = ["/boot/", "/run/", "/sys/", "/var/", "/dev/", "/etc/"];
+
+/// Sockets and devices of the host, by a prefix of their path. A path of the session
+/// (/run/user/UID/...) is matched on its part below the runtime directory.
+const SOCKETS: [(&str, &str); 6] = [
+    ("/tmp/.X11-unix/", "--socket=x11"),
+    ("wayland-", "--socket=wayland"),
+    ("pulse/", "--socket=pulseaudio"),
+    ("pipewire-", "--filesystem=xdg-run/pipewire-0"),
+    ("gnupg/", "--socket=gpg-agent"),
+    ("/run/cups/", "--socket=cups"),
+];
+
+const DEVICES: [(&str, &str); 2] = [("/dev/dri/", "--device=dri"), ("/dev/kvm", "--device=kvm")];
+
+/// XDG directories of the home, granted by their name
+const XDG_DIRS: [(&str, &str); 3] = [
+    (".config/", "xdg-config/"),
+    (".local/share/", "xdg-data/"),
+    (".cache/", "xdg-cache/"),
+];
+
+/// Permissions of a Flatpak application doing what the profile observed, and what no
+/// permission allows
+pub struct FlatpakPermissions {
+    pub finish_args: BTreeSet<String>,
+    /// What the sandbox cannot allow, with why
+    pub unsupported: Vec<String>,
+}
+
+/// Path below the runtime directory of the session, /run/user/UID/
+fn session_path(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix("/run/user/")?;
+    rest.split_once('/').map(|(_, rest)| rest)
+}
+
+/// Filesystem grant of `path`, None when it cannot be granted
+fn filesystem(path: &str) -> Option<String> {
+    if let Some(rest) = home_path(path) {
+        for (dir, name) in XDG_DIRS {
+            if let Some(rest) = rest.strip_prefix(dir) {
+                return Some(format!("{}{}", name, first_component(rest)));
+            }
+        }
+        return Some(format!("~/{}", first_component(rest)));
+    }
+    if RESERVED.iter().any(|reserved| path.starts_with(reserved)) {
+        return None;
+    }
+    Some(parent(path).to_string())
+}
+
+impl FlatpakPermissions {
+    pub fn new(result: &ProgramResult) -> FlatpakPermissions {
+        let mut finish_args = BTreeSet::new();
+        let mut unsupported = Vec::new();
+        if result.network {
+            finish_args.insert("--share=network".to_string());
+        }
+        for name in result.capability_names() {
+            unsupported.push(format!("{}: a Flatpak application has no capability", name));
+        }
+        let mut grants = BTreeMap::new();
+        let mut host_etc = false;
+        for (path, access) in result.all_files() {
+            let writable = access.contains(Access::W);
+            let session = session_path(path);
+            if let Some((_, arg)) = SOCKETS.iter().find(|(prefix, _)| {
+                path.starts_with(prefix) || session.is_some_and(|rest| rest.starts_with(prefix))
+            }) {
+                finish_args.insert(arg.to_string());
+                continue;
+            }
+            if let Some((_, arg)) = DEVICES.iter().find(|(prefix, _)| path.starts_with(prefix)) {
+                finish_args.insert(arg.to_string());
+                continue;
+            }
+            if PROVIDED.iter().any(|prefix| path.starts_with(prefix))
+                || (!writable && HOST_ETC_PROVIDED.contains(&path.as_str()))
+                || session.is_some()
+            {
+                continue;
+            }
+            if path.starts_with("/dev/") {
+                finish_args.insert("--device=all".to_string());
+                continue;
+            }
+            if path.starts_with("/etc/") && !writable {
+                host_etc = true;
+                continue;
+            }
+            match filesystem(path) {
+                Some(grant) => *grants.entry(grant).or_insert(false) |= writable,
+                None => unsupported.push(format!(
+                    "{} ({}): Flatpak cannot expose this location",
+                    path, access
+                )),
+            }
+        }
+        if host_etc {
+            finish_args.insert("--filesystem=host-etc".to_string());
+        }
+        for (grant, writable) in collapse(grants) {
+            finish_args.insert(if writable {
+                format!("--filesystem={}", grant)
+            } else {
+                format!("--filesystem={}:ro", grant)
+            });
+        }
+        for destination in &result.destinations {
+            finish_args.insert(format!("--system-talk-name={}", destination));
+        }
+        for name in &result.bus_names {
+            finish_args.insert(format!("--system-own-name={}", name));
+        }
+        FlatpakPermissions {
+            finish_args,
+            unsupported,
+        }
+    }
+}
+
+impl Display for FlatpakPermissions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "finish-args:")?;
+        for arg in &self.finish_args {
+            writeln!(f, "  - {}", arg)?;
+        }
+        if !self.unsupported.is_empty() {
+            writeln!(f, "# not allowed by any permission:")?;
+            for reason in &self.unsupported {
+                writeln!(f, "#   {}", reason)?;
+            }
+        }
+        Ok(())
+    }
+}
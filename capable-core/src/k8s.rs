@@ -0,0 +1,97 @@
+//! securityContext of a Kubernetes container allowing what a profile observed.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use crate::paths::{collapse, parent};
+use crate::profile::ProgramResult;
+use crate::syscalls::Access;
+
+/// Filesystems a container gets besides its root
+const NOT_ROOTFS: [&str; 3] = ["/dev/", "/proc/", "/sys/"];
+
+/// securityContext fragment, to merge into the container spec
+pub struct SecurityContext {
+    /// Capabilities added after dropping ALL, without the CAP_ prefix
+    pub add: Vec<String>,
+    pub run_as_non_root: bool,
+    pub allow_privilege_escalation: bool,
+    /// Directories written by the command, which need a volume when the root filesystem
+    /// is read-only
+    pub writable: Vec<String>,
+    /// Most restrictive Pod Security Standard the capabilities fit in
+    pub pod_security: &'static str,
+}
+
+impl SecurityContext {
+    pub fn new(result: &ProgramResult) -> SecurityContext {
+        let add = result
+            .capability_names()
+            .map(|name| name.trim_start_matches("CAP_").to_string())
+            .collect();
+        let mut written = BTreeMap::new();
+        for (path, access) in result.all_files() {
+            if access.contains(Access::W)
+                && !NOT_ROOTFS.iter().any(|prefix| path.starts_with(prefix))
+            {
+                written.insert(parent(path).to_string(), true);
+            }
+        }
+        let fits = |name: &str| {
+            result
+                .containers
+                .get(name)
+                .is_some_and(|comparison| comparison.cap_add.is_empty())
+        };
+        // restricted also forbids privilege escalation
+        let pod_security = if fits("kubernetes-restricted") && result.setuid.is_none() {
+            "restricted"
+        } else if fits("kubernetes-baseline") {
+            "baseline"
+        } else {
+            "privileged"
+        };
+        SecurityContext {
+            add,
+            // added capabilities only reach the process of a non-root user with file
+            // capabilities on its executable
+            run_as_non_root: result.capabilities.is_empty(),
+            allow_privilege_escalation: result.setuid.is_some(),
+            writable: collapse(written).into_iter().map(|(dir, _)| dir).collect(),
+            pod_security,
+        }
+    }
+}
+
+impl Display for SecurityContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# fits the {} Pod Security Standard", self.pod_security)?;
+        writeln!(f, "securityContext:")?;
+        writeln!(f, "  runAsNonRoot: {}", self.run_as_non_root)?;
+        writeln!(
+            f,
+            "  allowPrivilegeEscalation: {}",
+            self.allow_privilege_escalation
+        )?;
+        writeln!(f, "  readOnlyRootFilesystem: true")?;
+        writeln!(f, "  capabilities:")?;
+        writeln!(f, "    drop:")?;
+        writeln!(f, "      - ALL")?;
+        if !self.add.is_empty() {
+            writeln!(f, "    add:")?;
+            for name in &self.add {
+                writeln!(f, "      - {}", name)?;
+            }
+        }
+        if !self.writable.is_empty() {
+            writeln!(
+                f,
+                "# mount a volume, e.g. an emptyDir, on the directories written:"
+            )?;
+            for dir in &self.writable {
+                writeln!(f, "#   {}", dir)?;
+            }
+        }
+        Ok(())
+    }
+}
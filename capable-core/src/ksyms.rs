@@ -0,0 +1,121 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use aya::maps::{MapData, StackTraceMap};
+use aya::util::kernel_symbols;
+use tracing::debug;
+
+/// Functions checking a capability on behalf of their caller, skipped to find the reason
+const CAPABILITY_INFRASTRUCTURE: &[&str] = &[
+    "cap_capable",
+    "security_capable",
+    "ns_capable",
+    "ns_capable_common",
+    "ns_capable_noaudit",
+    "ns_capable_setid",
+    "capable",
+    "capable_wrt_inode_uidgid",
+    "privileged_wrt_inode_uidgid",
+    "inode_owner_or_capable",
+    "file_ns_capable",
+    "has_ns_capability",
+    "has_ns_capability_noaudit",
+    "has_capability",
+    "has_capability_noaudit",
+    "sk_ns_capable",
+    "sk_capable",
+    "sk_net_capable",
+    "netlink_ns_capable",
+    "netlink_capable",
+    "netlink_net_capable",
+    "bpf_capable",
+    "perfmon_capable",
+    "checkpoint_restore_ns_capable",
+];
+
+/// Symbol without its compiler suffix, ns_capable_common.constprop.0 is ns_capable_common
+pub fn base_symbol(symbol: &str) -> &str {
+    symbol.split_once('.').map_or(symbol, |(name, _)| name)
+}
+
+/// First frame of the stack that is not part of the capability checks, the kernel function
+/// that required the capability (e.g. do_mount)
+pub fn reason_symbol(frames: &[String]) -> Option<&str> {
+    frames
+        .iter()
+        .map(String::as_str)
+        .find(|frame| !CAPABILITY_INFRASTRUCTURE.contains(&base_symbol(frame)))
+}
+
+/// Syscall entry points of each architecture, followed by the syscall name
+const SYSCALL_PREFIXES: &[&str] = &[
+    "__x64_sys_",
+    "__ia32_sys_",
+    "__arm64_sys_",
+    "__riscv_sys_",
+    "__do_sys_",
+];
+
+/// Syscall during which the capability was checked, from its entry point in the stack
+pub fn syscall_name(frames: &[String]) -> Option<&str> {
+    frames.iter().rev().find_map(|frame| {
+        SYSCALL_PREFIXES
+            .iter()
+            .find_map(|prefix| base_symbol(frame).strip_prefix(prefix))
+    })
+}
+
+/// Kernel stack symbolizer.
+/// kallsyms is only parsed when the first stack needs to be resolved, and every stack id
+/// is resolved once then served from cache, as the same kernel paths are hit over and over.
+#[derive(Default)]
+pub struct KernelSymbols {
+    symbols: Option<BTreeMap<u64, String>>,
+    stacks: HashMap<u32, Rc<[String]>>,
+    hits: u64,
+}
+
+impl KernelSymbols {
+    fn symbols(&mut self) -> Result<&BTreeMap<u64, String>, anyhow::Error> {
+        if self.symbols.is_none() {
+            debug!("loading kernel symbols");
+            self.symbols = Some(kernel_symbols()?);
+        }
+        Ok(self.symbols.as_ref().expect("kernel symbols are loaded"))
+    }
+
+    /// Return the symbol of each frame of the stack, innermost first
+    pub fn resolve<T>(
+        &mut self,
+        stacktrace_map: &StackTraceMap<T>,
+        stackid: u32,
+    ) -> Result<Rc<[String]>, anyhow::Error>
+    where
+        T: Borrow<MapData>,
+    {
+        if let Some(frames) = self.stacks.get(&stackid) {
+            self.hits += 1;
+            return Ok(frames.clone());
+        }
+        let stack = stacktrace_map.get(&stackid, 0)?;
+        let symbols = self.symbols()?;
+        let frames: Rc<[String]> = stack
+            .frames()
+            .iter()
+            .filter_map(|frame| {
+                symbols
+                    .range(..=frame.ip)
+                    .next_back()
+                    .map(|(_, s)| s.clone())
+            })
+            .collect();
+        self.stacks.insert(stackid, frames.clone());
+        Ok(frames)
+    }
+
+    /// Stacks symbolized from kallsyms, and stacks served from cache
+    pub fn cache_stats(&self) -> (u64, u64) {
+        (self.stacks.len() as u64, self.hits)
+    }
+}
@@ -0,0 +1,70 @@
+//! Collection and analysis behind `capable`.
+//!
+//! A profile is built in three steps:
+//! - [`ebpf::Probes`] observes the capability checks of the kernel and aggregates them per
+//!   process into [`collect::CapSetEntry`], [`ebpf::Probes::program_capabilities`] then gives
+//!   the capabilities requested in a PID namespace;
+//! - [`strace::parse_strace`] reads the syscalls of the command, and [`bus`] the D-Bus
+//!   messages sent on its behalf;
+//! - [`collect::build_result`] merges both into a [`profile::ProgramResult`], which
+//!   [`rootasrole`] and [`wrapper`] turn into policies.
+//!
+//! [`profiler::Profiler`] streams the findings while the processes run instead, and
+//! [`replay::Replay`] injects recorded or fixture requests in place of the probes.
+//!
+//! The modules loading the eBPF programs are behind the default `probes` feature, which
+//! builds capable-ebpf. Without it, the crate only analyses recordings and profiles.
+
+pub mod approvals;
+pub mod arch;
+pub mod bus;
+pub mod bwrap;
+pub mod caps;
+#[cfg(feature = "probes")]
+pub mod cgroup_filter;
+pub mod collect;
+pub mod compress;
+pub mod containers;
+#[cfg(feature = "probes")]
+pub mod ebpf;
+pub mod explain;
+pub mod firejail;
+pub mod flatpak;
+pub mod k8s;
+pub mod ksyms;
+pub mod libcap;
+pub mod loader;
+pub mod manifest;
+pub mod memlock;
+#[cfg(feature = "probes")]
+pub mod lsm;
+pub mod minijail;
+pub mod nspawn;
+pub mod ownership;
+pub mod paths;
+pub mod phase;
+#[cfg(feature = "probes")]
+pub mod pinned;
+pub mod policy;
+pub mod profile;
+#[cfg(feature = "probes")]
+pub mod profiler;
+pub mod reasons;
+pub mod recording;
+pub mod replay;
+#[cfg(feature = "probes")]
+pub mod requests;
+pub mod risk;
+pub mod rootasrole;
+pub mod snap;
+pub mod sockets;
+pub mod strace;
+pub mod suggest;
+pub mod syscalls;
+pub mod sysctl;
+pub mod systemd;
+pub mod time;
+pub mod version;
+pub mod wrapper;
+
+pub use capable_common::stats;
@@ -0,0 +1,74 @@
+//! Capabilities the command manages itself, observed with uprobes on libcap and libcap-ng.
+//!
+//! A service often starts with its capabilities and drops them once it bound its sockets,
+//! so the capabilities it requested do not tell which ones it keeps. The sets of the thread
+//! are read when cap_set_proc or capng_apply returns.
+
+use aya::maps::{HashMap, MapData};
+use aya::programs::UProbe;
+use aya::Ebpf;
+use capable_common::{LibcapCall, Nsid, LIBCAP_CAPNG_APPLY, LIBCAP_CAP_SET_PROC};
+use capctl::CapSet;
+use tracing::debug;
+
+use crate::caps::capset_to_vec;
+use crate::profile::CapabilityChange;
+
+/// Function probed on return, also the name of its program, and the library defining it
+const LIBCAP_PROBES: &[(&str, &str)] = &[
+    ("cap_set_proc", "libcap.so.2"),
+    ("capng_apply", "libcap-ng.so.0"),
+];
+
+/// Attach the uprobes to the libraries installed on the system, the others are skipped
+pub fn attach(bpf: &mut Ebpf) -> Result<(), anyhow::Error> {
+    for (function, library) in LIBCAP_PROBES {
+        let program: &mut UProbe = bpf
+            .program_mut(function)
+            .expect("failed to get UProbe libcap program")
+            .try_into()?;
+        program.load()?;
+        if let Err(e) = program.attach(Some(function), 0, library, None) {
+            debug!(
+                "{} not probed, {} may not be installed: {}",
+                function, library, e
+            );
+        }
+    }
+    Ok(())
+}
+
+fn function_name(function: u8) -> String {
+    match function {
+        LIBCAP_CAP_SET_PROC => "cap_set_proc",
+        LIBCAP_CAPNG_APPLY => "capng_apply",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn names(set: u64) -> Vec<String> {
+    capset_to_vec(&CapSet::from_bitmask_truncate(set))
+}
+
+/// Capability changes of the processes of the namespace, in the order they happened
+pub fn read_calls(calls: &HashMap<MapData, u64, LibcapCall>, ns: Nsid) -> Vec<CapabilityChange> {
+    let mut calls: Vec<(u64, LibcapCall)> = calls
+        .iter()
+        .filter_map(|call| call.ok())
+        .filter(|(_, call)| call.ns == ns)
+        .collect();
+    calls.sort_unstable_by_key(|(time, _)| *time);
+    calls
+        .into_iter()
+        .map(|(_, call)| CapabilityChange {
+            pid: call.pid,
+            function: function_name(call.function),
+            failed: call.failed != 0,
+            effective: names(call.effective),
+            permitted: names(call.permitted),
+            inheritable: names(call.inheritable),
+            ambient: names(call.ambient),
+        })
+        .collect()
+}
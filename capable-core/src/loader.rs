@@ -0,0 +1,115 @@
+//! Files accessed by the dynamic loader, reported apart from the files of the program.
+//!
+//! A library is recognized from the syscalls: a file opened then mapped executable
+//! (`mmap(..., PROT_READ|PROT_EXEC, ..., fd, ...)`), with the descriptors strace gives as
+//! the results of the opens. The seccomp collector knows neither, its libraries are only
+//! recognized by name. The ld cache and configuration, the ELF interpreters and the
+//! library names the loader probed along its search path complete them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::strace::{Parameter, Syscall};
+use crate::syscalls::Access;
+
+/// Syscalls traced to recognize the mapped libraries
+pub const LOADER_SYSCALLS: [&str; 2] = ["?mmap", "?mmap2"];
+
+/// Files of the loader configuration, by a prefix of their path
+const LOADER_CONFIG: [&str; 3] = ["/etc/ld.so.cache", "/etc/ld.so.preload", "/etc/ld.so.conf"];
+
+/// ELF interpreters, by a prefix of their file name
+const INTERPRETERS: [&str; 3] = ["ld-linux", "ld-musl", "ld64.so"];
+
+/// Whether the file name is the one of a shared library, lib.so or lib.so.1.2. Other names
+/// containing .so., like ld.so.cache or a backup lib.so.old, are not.
+fn is_library_name(name: &str) -> bool {
+    if name.ends_with(".so") {
+        return true;
+    }
+    name.split_once(".so.").is_some_and(|(_, version)| {
+        version.starts_with(|c: char| c.is_ascii_digit())
+            && version.chars().all(|c| c.is_ascii_digit() || c == '.')
+    })
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn constant(syscall: &Syscall, index: usize) -> Option<&str> {
+    match syscall.args.get(index) {
+        Some(Parameter::Constant(value)) => Some(value),
+        _ => None,
+    }
+}
+
+/// Paths mapped executable by the syscalls, in log order
+fn mapped_executable(syscalls: &[Syscall]) -> HashSet<String> {
+    let mut opened: HashMap<i32, String> = HashMap::new();
+    let mut mapped = HashSet::new();
+    for syscall in syscalls {
+        match syscall.syscall.trim() {
+            "open" | "openat" | "openat2" if syscall.return_code.code >= 0 => {
+                let index = if syscall.syscall.trim() == "open" {
+                    0
+                } else {
+                    1
+                };
+                if let Some(Parameter::String(path)) = syscall.args.get(index) {
+                    opened.insert(syscall.return_code.code, path.clone());
+                }
+            }
+            "mmap" | "mmap2" => {
+                let executable =
+                    constant(syscall, 2).is_some_and(|prot| prot.contains("PROT_EXEC"));
+                let fd = constant(syscall, 4).and_then(|fd| fd.parse::<i32>().ok());
+                if let (true, Some(path)) = (executable, fd.and_then(|fd| opened.get(&fd))) {
+                    mapped.insert(path.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    mapped
+}
+
+/// Move the files of the loader out of `files`. Directories only traversed to reach
+/// them, and not to reach a file of the program, are moved with them.
+pub fn split_runtime(
+    syscalls: &[Syscall],
+    files: &mut BTreeMap<String, Access>,
+) -> BTreeMap<String, Access> {
+    let mapped = mapped_executable(syscalls);
+    let (mut runtime, rest): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(files)
+        .into_iter()
+        .partition(|(path, access)| {
+            let name = file_name(path);
+            !access.contains(Access::W)
+                && (mapped.contains(path.as_str())
+                    || LOADER_CONFIG.iter().any(|config| path.starts_with(config))
+                    || INTERPRETERS
+                        .iter()
+                        .any(|interpreter| name.starts_with(interpreter))
+                    || is_library_name(name))
+        });
+    *files = rest;
+    let traversed: Vec<String> = files
+        .iter()
+        .filter(|(path, access)| {
+            **access == Access::X && {
+                let dir = format!("{}/", path.trim_end_matches('/'));
+                runtime.keys().any(|file| file.starts_with(&dir))
+                    && !files
+                        .iter()
+                        .any(|(file, access)| file.starts_with(&dir) && *access != Access::X)
+            }
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in traversed {
+        if let Some(access) = files.remove(&path) {
+            runtime.insert(path, access);
+        }
+    }
+    runtime
+}
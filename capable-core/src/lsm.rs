@@ -0,0 +1,136 @@
+//! Enforcement of a profile by the BPF LSM, see [`LsmEnforcer`].
+
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_dir, remove_file};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use aya::maps::{HashMap, MapData};
+use aya::programs::Lsm;
+use aya::{Btf, Ebpf};
+use capable_common::LsmDenial;
+use capctl::CapSet;
+use log::{debug, warn};
+
+use crate::caps::{get_cap, setbpf_effective, setperfmon_effective};
+use crate::ebpf::load_object;
+use crate::memlock::remove_memlock_limit;
+
+/// bpffs directory where the profiles map is pinned while enforced, as lsm_profiles-PID,
+/// to be inspected with e.g. `bpftool map dump pinned`
+pub const LSM_PIN_DIR: &str = "/sys/fs/bpf/capable";
+
+/// The lsm/capable program, denying with EPERM the capabilities outside the profile of the
+/// cgroup of the process or of its ancestors. The lsm/file_open one keeps the processes in
+/// their cgroup, they cannot write to the cgroup filesystem. Requires the bpf LSM in the
+/// lsm= boot parameter. The programs are detached and the map unpinned when it is dropped.
+/// When capable is killed before, the programs are detached with its file descriptors but
+/// the pin stays, it is removed by the next load.
+pub struct LsmEnforcer {
+    // owns the program
+    _bpf: Ebpf,
+    profiles: HashMap<MapData, u64, u64>,
+    denials: HashMap<MapData, LsmDenial, u64>,
+    pin: PathBuf,
+}
+
+impl LsmEnforcer {
+    /// Load and attach the program, requires BPF and PERFMON in the permitted set. `object`
+    /// replaces the embedded programs.
+    pub fn load(object: Option<&Path>) -> Result<LsmEnforcer, anyhow::Error> {
+        remove_memlock_limit()?;
+        setbpf_effective(true)?;
+        setperfmon_effective(true)?;
+        let mut bpf = load_object(object)?;
+        let btf = Btf::from_sys_fs().context("BPF LSM requires the kernel BTF")?;
+        let program: &mut Lsm = bpf
+            .program_mut("capable_lsm")
+            .expect("failed to get Lsm capable_lsm program")
+            .try_into()
+            .context("Failed to get Lsm")?;
+        program.load("capable", &btf)?;
+        program
+            .attach()
+            .context("Unable to attach to the BPF LSM, is bpf in the lsm= boot parameter?")?;
+        let program: &mut Lsm = bpf
+            .program_mut("cgroup_write_lsm")
+            .expect("failed to get Lsm cgroup_write_lsm program")
+            .try_into()
+            .context("Failed to get Lsm")?;
+        program.load("file_open", &btf)?;
+        program.attach()?;
+        let map = bpf
+            .take_map("LSM_PROFILES")
+            .context("Unable to obtain map LSM_PROFILES")?;
+        create_dir_all(LSM_PIN_DIR).with_context(|| format!("Unable to create {}", LSM_PIN_DIR))?;
+        remove_stale_pins();
+        let pin = Path::new(LSM_PIN_DIR).join(format!("lsm_profiles-{}", std::process::id()));
+        map.pin(&pin)
+            .with_context(|| format!("Unable to pin the profiles to {}", pin.display()))?;
+        let profiles = HashMap::try_from(map)?;
+        let denials = HashMap::try_from(
+            bpf.take_map("LSM_DENIALS")
+                .context("Unable to obtain map LSM_DENIALS")?,
+        )?;
+        setperfmon_effective(false)?;
+        setbpf_effective(false)?;
+        debug!("program {} loaded and attached", "capable_lsm");
+        Ok(LsmEnforcer {
+            _bpf: bpf,
+            profiles,
+            denials,
+            pin,
+        })
+    }
+
+    /// Deny the capabilities outside `allowed` to the processes of the cgroup `id`
+    pub fn restrict(&mut self, id: u64, allowed: CapSet) -> Result<(), anyhow::Error> {
+        let mask = allowed.iter().fold(0u64, |mask, cap| mask | 1 << cap as u8);
+        self.profiles.insert(id, mask, 0)?;
+        Ok(())
+    }
+
+    /// Capabilities denied to the processes of the cgroup `id`, with the number of checks
+    pub fn denials(&self, id: u64) -> BTreeMap<String, u64> {
+        self.denials
+            .iter()
+            .filter_map(Result::ok)
+            .filter(|(denial, _)| denial.cgroup == id)
+            .filter_map(|(denial, count)| {
+                let cap = get_cap(denial.capability as u8)?;
+                Some((format!("CAP_{:?}", cap), count))
+            })
+            .collect()
+    }
+}
+
+/// Unpin the profiles of the runs whose process is gone
+fn remove_stale_pins() {
+    let Ok(entries) = read_dir(LSM_PIN_DIR) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name();
+        let Some(pid) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("lsm_profiles-"))
+        else {
+            continue;
+        };
+        if Path::new("/proc").join(pid).exists() {
+            continue;
+        }
+        debug!("removing the stale pin {}", entry.path().display());
+        if let Err(e) = remove_file(entry.path()) {
+            warn!("Unable to unpin {}: {}", entry.path().display(), e);
+        }
+    }
+}
+
+impl Drop for LsmEnforcer {
+    fn drop(&mut self) {
+        if let Err(e) = remove_file(&self.pin) {
+            warn!("Unable to unpin {}: {}", self.pin.display(), e);
+        }
+    }
+}
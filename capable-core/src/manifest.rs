@@ -0,0 +1,82 @@
+//! Integrity manifest of the binaries and scripts a command executed, to tie a profile to
+//! the versions it was observed with.
+
+use std::fs::{canonicalize, File};
+use std::io::{BufRead, BufReader, Read};
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::profile::ExecutedFile;
+use crate::strace::{Parameter, Syscall};
+
+/// Path of a successful exec, execveat relative to a directory is skipped
+fn exec_path(syscall: &Syscall) -> Option<&str> {
+    if syscall.return_code.code != 0 {
+        return None;
+    }
+    let index = match syscall.syscall.trim() {
+        "execve" | "execv" => 0,
+        "execveat" => 1,
+        _ => return None,
+    };
+    match syscall.args.get(index) {
+        Some(Parameter::String(path)) if index == 0 || path.starts_with('/') => Some(path),
+        _ => None,
+    }
+}
+
+/// SHA-256 of the content of `path`, in hexadecimal
+fn sha256(path: &str) -> Result<String, std::io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Interpreter of a script, from its #! line
+fn interpreter(path: &str) -> Option<String> {
+    let mut line = String::new();
+    BufReader::new(File::open(path).ok()?)
+        .read_line(&mut line)
+        .ok()?;
+    let interpreter = line.strip_prefix("#!")?.split_whitespace().next()?;
+    Some(interpreter.to_string())
+}
+
+/// Files executed by the syscalls, with the interpreters of the scripts, hashed as they
+/// are on disk now
+pub fn executed_files(syscalls: &[Syscall]) -> Vec<ExecutedFile> {
+    let mut paths: Vec<String> = Vec::new();
+    let mut add = |path: String| {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    };
+    for path in syscalls.iter().filter_map(exec_path) {
+        let path = canonicalize(path)
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| path.to_string());
+        let script = interpreter(&path);
+        add(path);
+        if let Some(interpreter) = script {
+            add(interpreter);
+        }
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let sha256 = sha256(&path)
+                .map_err(|e| warn!("Unable to hash {}: {}", path, e))
+                .ok();
+            ExecutedFile { path, sha256 }
+        })
+        .collect()
+}
@@ -0,0 +1,162 @@
+//! Memory the command locked, the requests checking CAP_IPC_LOCK. A limit on locked memory
+//! (LimitMEMLOCK=) large enough for it makes the capability unnecessary. The limit capable
+//! removes for its programs is given back to the commands it runs.
+
+use std::sync::OnceLock;
+
+use capctl::Cap;
+use log::debug;
+
+use crate::caps::{is_permitted, setresource_effective};
+
+use crate::explain::registered_buffers;
+use crate::profile::{CapabilityFinding, MemoryLock};
+use crate::strace::{Parameter, Syscall};
+
+pub const MEMLOCK_CAPABILITY: &str = "CAP_IPC_LOCK";
+
+/// RLIMIT_MEMLOCK of capable before remove_memlock_limit, the one of the commands it runs
+static MEMLOCK_LIMIT: OnceLock<libc::rlimit> = OnceLock::new();
+
+/// Bump the memlock rlimit. This is needed for older kernels that don't use the
+/// new memcg based accounting, see https://lwn.net/Articles/837122/
+pub fn remove_memlock_limit() -> Result<(), anyhow::Error> {
+    // not needed since Linux 5.11, capable may run without it
+    if !is_permitted(Cap::SYS_RESOURCE) {
+        debug!("SYS_RESOURCE is not permitted, keeping the limit on locked memory");
+        return Ok(());
+    }
+    if MEMLOCK_LIMIT.get().is_none() {
+        let _ = MEMLOCK_LIMIT.set(memlock_limit());
+    }
+    let rlim = libc::rlimit {
+        rlim_cur: libc::RLIM_INFINITY,
+        rlim_max: libc::RLIM_INFINITY,
+    };
+    setresource_effective(true)?;
+    let ret = unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlim) };
+    setresource_effective(false)?;
+    if ret != 0 {
+        debug!("remove limit on locked memory failed, ret is: {}", ret);
+    }
+    Ok(())
+}
+
+/// Limit on locked memory of the commands, the one of capable before it removed it
+pub fn memlock_limit() -> libc::rlimit {
+    if let Some(limit) = MEMLOCK_LIMIT.get() {
+        return *limit;
+    }
+    let mut limit = libc::rlimit {
+        rlim_cur: libc::RLIM_INFINITY,
+        rlim_max: libc::RLIM_INFINITY,
+    };
+    unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) };
+    limit
+}
+
+/// Give the command the limit on locked memory removed for the programs, otherwise
+/// CAP_IPC_LOCK is never checked when it locks memory. Runs in pre_exec.
+pub fn restore_memlock_limit(limit: &libc::rlimit) -> std::io::Result<()> {
+    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Syscalls checking CAP_IPC_LOCK only beyond RLIMIT_MEMLOCK
+const LOCKING_SYSCALLS: [&str; 7] = [
+    "mlock",
+    "mlock2",
+    "mlockall",
+    "mmap",
+    "mmap2",
+    "io_uring_setup",
+    "io_uring_register",
+];
+
+/// Bytes a syscall locks, None when it locks nothing
+fn locked(syscall: &Syscall) -> Option<u64> {
+    let length = |n: usize| match syscall.args.get(n) {
+        Some(Parameter::Constant(length)) => length.parse().ok(),
+        _ => None,
+    };
+    match syscall.syscall.trim() {
+        "mlock" | "mlock2" => length(1),
+        "mmap" | "mmap2" => match syscall.args.get(3) {
+            Some(Parameter::Constant(flags)) if flags.split('|').any(|f| f == "MAP_LOCKED") => {
+                length(1)
+            }
+            _ => None,
+        },
+        _ => registered_buffers(syscall).map(|(_, size)| size),
+    }
+}
+
+/// How a failed syscall ended
+fn failure(syscall: &Syscall) -> Option<&str> {
+    match &syscall.return_code.constant {
+        Some(error) if syscall.return_code.code < 0 => Some(error),
+        _ => None,
+    }
+}
+
+/// Memory the command locked, when it needed CAP_IPC_LOCK or locking failed. `limit` is the
+/// soft RLIMIT_MEMLOCK of the command.
+pub fn memory_lock(
+    syscalls: &[Syscall],
+    capabilities: &[CapabilityFinding],
+    limit: libc::rlimit,
+) -> Option<MemoryLock> {
+    let mut memlock = MemoryLock {
+        limit: (limit.rlim_cur != libc::RLIM_INFINITY).then_some(limit.rlim_cur),
+        ..Default::default()
+    };
+    for syscall in syscalls {
+        let name = syscall.syscall.trim();
+        let size = match (name, locked(syscall)) {
+            ("mlockall", _) => {
+                memlock.all = true;
+                None
+            }
+            (_, Some(size)) => Some(size),
+            (_, None) => continue,
+        };
+        if let Some(size) = size {
+            memlock.requested += size;
+        }
+        if let Some(error) = failure(syscall) {
+            let failure = match size {
+                Some(size) => format!("{} of {} bytes (failed with {})", name, size, error),
+                None => format!("{} (failed with {})", name, error),
+            };
+            if !memlock.failures.contains(&failure) {
+                memlock.failures.push(failure);
+            }
+        }
+    }
+    let needed = capabilities
+        .iter()
+        .any(|finding| finding.name == MEMLOCK_CAPABILITY);
+    ((needed || !memlock.failures.is_empty()) && (memlock.requested > 0 || memlock.all))
+        .then_some(memlock)
+}
+
+/// Locked memory whose limit replaces CAP_IPC_LOCK, when the kernel only checked it during
+/// the syscalls locking memory
+pub fn limit_replacing<'a>(
+    capabilities: &[CapabilityFinding],
+    memlock: Option<&'a MemoryLock>,
+) -> Option<&'a MemoryLock> {
+    let memlock = memlock?;
+    let finding = capabilities
+        .iter()
+        .find(|finding| finding.name == MEMLOCK_CAPABILITY)?;
+    // without the syscalls of the checks, they may come from anything else
+    let locking = !finding.syscalls.is_empty()
+        && finding
+            .syscalls
+            .iter()
+            .all(|syscall| LOCKING_SYSCALLS.contains(&syscall.as_str()));
+    locking.then_some(memlock)
+}
@@ -0,0 +1,107 @@
+//! minijail0 flags running a command with only what a profile observed, and the seccomp
+//! policy of the syscalls it made.
+
+use std::collections::BTreeSet;
+
+use capctl::CapSet;
+
+use crate::bwrap::Mounts;
+use crate::caps::parse_capset_iter;
+use crate::profile::ProgramResult;
+use crate::strace::parse_strace;
+
+/// Name of the seccomp policy file given to -S
+pub const SECCOMP_POLICY_NAME: &str = "seccomp.policy";
+
+/// Syscalls minijail0 itself makes once the filter is installed
+const MINIJAIL_SYSCALLS: [&str; 2] = ["execve", "exit_group"];
+
+/// Syscalls every program makes, only in the logs of strace tracing all syscalls. The
+/// default trace of capable only keeps the file ones.
+const COMPLETE_TRACE_SYSCALLS: [&str; 5] = ["brk", "mmap", "mmap2", "read", "close"];
+
+/// Flags of minijail0 and its seccomp policy
+pub struct MinijailConfig {
+    pub flags: Vec<String>,
+    /// Whether the jail keeps capabilities, minijail0 then has to run as root
+    pub privileged: bool,
+    /// Syscalls allowed by the seccomp policy
+    pub syscalls: BTreeSet<String>,
+    /// Whether the logs traced every syscall. Otherwise the policy would kill the jail at
+    /// its first syscall outside the trace, -L logs them instead.
+    pub complete: bool,
+}
+
+impl MinijailConfig {
+    /// Jail of `result`, allowing the syscalls of `strace_logs`
+    pub fn new(result: &ProgramResult, strace_logs: &[String]) -> MinijailConfig {
+        let mut flags: Vec<String> = ["--profile=minimalistic-mountns", "-p", "-l", "--uts", "-n"]
+            .iter()
+            .map(|flag| flag.to_string())
+            .collect();
+        if !result.network {
+            flags.push("-e".to_string());
+        }
+        let capabilities = parse_capset_iter(result.capability_names()).unwrap_or(CapSet::empty());
+        let mask = capabilities
+            .iter()
+            .fold(0u64, |mask, cap| mask | 1 << cap as u8);
+        flags.push("-c".to_string());
+        flags.push(format!("{:#x}", mask));
+
+        let mounts = Mounts::new(result);
+        for (path, writable) in &mounts.binds {
+            flags.push("-b".to_string());
+            flags.push(if *writable {
+                format!("{},{},1", path, path)
+            } else {
+                format!("{},{}", path, path)
+            });
+        }
+        for device in &mounts.devices {
+            flags.push("-b".to_string());
+            flags.push(format!("{},{},1", device, device));
+        }
+        let syscalls: BTreeSet<String> = strace_logs
+            .iter()
+            .flat_map(|log| parse_strace(log))
+            .map(|syscall| syscall.syscall)
+            .filter(|name| {
+                name.chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            })
+            .chain(MINIJAIL_SYSCALLS.iter().map(|name| name.to_string()))
+            .collect();
+        let complete = COMPLETE_TRACE_SYSCALLS
+            .iter()
+            .any(|name| syscalls.contains(*name));
+        if !complete {
+            flags.push("-L".to_string());
+        }
+        flags.push("-S".to_string());
+        flags.push(SECCOMP_POLICY_NAME.to_string());
+        MinijailConfig {
+            flags,
+            privileged: mask != 0,
+            syscalls,
+            complete,
+        }
+    }
+
+    /// Shell command line running `command` in the jail
+    pub fn command_line(&self, command: &[String]) -> String {
+        format!(
+            "minijail0 {} -- {}\n",
+            shell_words::join(&self.flags),
+            shell_words::join(command)
+        )
+    }
+
+    /// Content of the seccomp policy file, one allowed syscall per line
+    pub fn seccomp_policy(&self) -> String {
+        self.syscalls
+            .iter()
+            .map(|name| format!("{}: 1\n", name))
+            .collect()
+    }
+}
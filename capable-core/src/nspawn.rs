@@ -0,0 +1,95 @@
+//! systemd-nspawn options running a command with only what a profile observed.
+
+use crate::bwrap::Mounts;
+use crate::profile::ProgramResult;
+
+/// Capabilities systemd-nspawn keeps by default
+const DEFAULT_CAPABILITIES: [&str; 26] = [
+    "CAP_AUDIT_CONTROL",
+    "CAP_AUDIT_WRITE",
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_IPC_OWNER",
+    "CAP_KILL",
+    "CAP_LEASE",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_MKNOD",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_RAW",
+    "CAP_SETFCAP",
+    "CAP_SETGID",
+    "CAP_SETPCAP",
+    "CAP_SETUID",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TTY_CONFIG",
+];
+
+/// Options of systemd-nspawn reproducing what the profile observed
+pub struct NspawnOptions {
+    pub options: Vec<String>,
+}
+
+impl NspawnOptions {
+    pub fn new(result: &ProgramResult) -> NspawnOptions {
+        // the root is a tmpfs with /usr of the host, see --volatile=yes
+        let mut options = vec![
+            "--directory=/".to_string(),
+            "--volatile=yes".to_string(),
+            "--as-pid2".to_string(),
+            "--register=no".to_string(),
+        ];
+        if !result.network {
+            options.push("--private-network".to_string());
+        }
+        if result.setuid.is_none() {
+            options.push("--no-new-privileges=yes".to_string());
+        }
+        let added: Vec<&str> = result
+            .capability_names()
+            .filter(|name| !DEFAULT_CAPABILITIES.contains(name))
+            .collect();
+        if !added.is_empty() {
+            options.push(format!("--capability={}", added.join(",")));
+        }
+        let dropped: Vec<&str> = DEFAULT_CAPABILITIES
+            .iter()
+            .copied()
+            .filter(|name| !result.has_capability(name))
+            .collect();
+        if !dropped.is_empty() {
+            options.push(format!("--drop-capability={}", dropped.join(",")));
+        }
+
+        let mounts = Mounts::new(result);
+        for (path, writable) in &mounts.binds {
+            if *writable {
+                options.push(format!("--bind={}", path));
+            } else {
+                options.push(format!("--bind-ro={}", path));
+            }
+        }
+        for device in &mounts.devices {
+            options.push(format!("--bind={}", device));
+        }
+        NspawnOptions { options }
+    }
+
+    /// Shell command line running `command` in the container
+    pub fn command_line(&self, command: &[String]) -> String {
+        let mut line = String::from("systemd-nspawn \\\n");
+        for option in &self.options {
+            line.push_str(&format!("  {} \\\n", shell_words::quote(option)));
+        }
+        line.push_str(&format!("  -- {}\n", shell_words::join(command)));
+        line
+    }
+}
@@ -0,0 +1,89 @@
+//! Files whose mode, owner or timestamps the command changed, the checks behind
+//! CAP_FOWNER and CAP_CHOWN. Owning the files, or their directory, as the user of the
+//! command makes these capabilities unnecessary.
+
+use std::fs::symlink_metadata;
+use std::os::unix::fs::MetadataExt;
+
+use nix::unistd::{Gid, Group, Uid, User};
+
+use crate::profile::OwnershipChange;
+use crate::strace::{Parameter, Syscall};
+
+/// Capabilities checked when changing a file the command does not own
+pub const OWNERSHIP_CAPABILITIES: [&str; 2] = ["CAP_FOWNER", "CAP_CHOWN"];
+
+/// Syscalls changing a file by its path: the position of the path and of the change
+const CHANGES: &[(&str, usize, Option<usize>)] = &[
+    ("chmod", 0, Some(1)),
+    ("fchmodat", 1, Some(2)),
+    ("fchmodat2", 1, Some(2)),
+    ("chown", 0, Some(1)),
+    ("lchown", 0, Some(1)),
+    ("fchownat", 1, Some(2)),
+    ("utime", 0, None),
+    ("utimes", 0, None),
+    ("futimesat", 1, None),
+    ("utimensat", 1, None),
+];
+
+fn constant(syscall: &Syscall, n: usize) -> Option<&str> {
+    match syscall.args.get(n) {
+        Some(Parameter::Constant(value)) => Some(value),
+        _ => None,
+    }
+}
+
+/// Owner of `path` as user:group, by name when known
+fn owner(path: &str) -> Option<String> {
+    let metadata = symlink_metadata(path).ok()?;
+    let user = User::from_uid(Uid::from_raw(metadata.uid()))
+        .ok()
+        .flatten()
+        .map_or(metadata.uid().to_string(), |user| user.name);
+    let group = Group::from_gid(Gid::from_raw(metadata.gid()))
+        .ok()
+        .flatten()
+        .map_or(metadata.gid().to_string(), |group| group.name);
+    Some(format!("{}:{}", user, group))
+}
+
+fn change(syscall: &Syscall) -> Option<(String, String)> {
+    let name = syscall.syscall.trim();
+    let (_, path, change) = CHANGES.iter().find(|(call, _, _)| *call == name)?;
+    // relative paths depend on the directory of the process, utimensat on a descriptor
+    // has no path
+    let path = match syscall.args.get(*path) {
+        Some(Parameter::String(path)) if path.starts_with('/') => path.clone(),
+        _ => return None,
+    };
+    let change = match change {
+        Some(n) if name.contains("chmod") => format!("chmod {}", constant(syscall, *n)?),
+        Some(n) => format!(
+            "chown {}:{}",
+            constant(syscall, *n)?,
+            constant(syscall, n + 1)?
+        ),
+        None => name.to_string(),
+    };
+    Some((path, change))
+}
+
+/// Changes of files by path, in the order of the run, with the current owner of the files
+pub fn ownership_changes(syscalls: &[Syscall]) -> Vec<OwnershipChange> {
+    let mut changes: Vec<OwnershipChange> = Vec::new();
+    for (path, change) in syscalls.iter().filter_map(change) {
+        if changes
+            .iter()
+            .any(|existing| existing.path == path && existing.change == change)
+        {
+            continue;
+        }
+        changes.push(OwnershipChange {
+            owner: owner(&path),
+            path,
+            change,
+        });
+    }
+    changes
+}
@@ -0,0 +1,38 @@
+//! Paths of the files of a profile, shared by the sandbox policies that grant them.
+
+use std::collections::BTreeMap;
+
+/// Path below the home directory, /home/USER/ or /root/
+pub fn home_path(path: &str) -> Option<&str> {
+    if let Some(rest) = path.strip_prefix("/root/") {
+        return Some(rest);
+    }
+    let rest = path.strip_prefix("/home/")?;
+    rest.split_once('/').map(|(_, rest)| rest)
+}
+
+/// First component of `path`, or `path` when it is a file directly inside
+pub fn first_component(path: &str) -> &str {
+    path.split_once('/').map_or(path, |(first, _)| first)
+}
+
+/// Parent directory of `path`, the grants are per directory
+pub fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => path,
+        Some(index) => &path[..index],
+    }
+}
+
+/// Remove the grants below another one with at least the same rights
+pub fn collapse(grants: BTreeMap<String, bool>) -> Vec<(String, bool)> {
+    grants
+        .iter()
+        .filter(|(path, writable)| {
+            !grants.iter().any(|(other, other_writable)| {
+                path.starts_with(&format!("{}/", other)) && (*other_writable || !**writable)
+            })
+        })
+        .map(|(path, writable)| (path.clone(), *writable))
+        .collect()
+}
@@ -0,0 +1,52 @@
+//! Startup versus steady-state capabilities.
+//!
+//! Services often need a capability to initialize, e.g. to bind a port or read a key, and
+//! never again. Such capabilities can be dropped once the service is initialized, or
+//! granted to an initialization step only.
+
+use std::time::Duration;
+
+use crate::profile::{Phase, ProgramResult, Suggestion};
+
+/// Default length of the startup phase (--startup)
+pub const DEFAULT_STARTUP: Duration = Duration::from_secs(10);
+
+/// Tell the findings needed only during the first `startup` of the run from the others,
+/// and suggest how to drop the startup ones. A run that did not last longer than `startup`
+/// has no steady state to compare with, its findings are left without phase.
+pub fn classify(result: &mut ProgramResult, startup: Duration) {
+    let startup_ms = startup.as_millis() as u64;
+    let run_ms = result
+        .window
+        .map_or(0, |window| window.end.saturating_sub(window.start) * 1000);
+    if run_ms <= startup_ms {
+        return;
+    }
+    for finding in &mut result.capabilities {
+        finding.phase = finding.last_seen.map(|last| {
+            if last <= startup_ms {
+                Phase::Startup
+            } else {
+                Phase::Continuous
+            }
+        });
+        if finding.phase != Some(Phase::Startup) {
+            continue;
+        }
+        result.suggestions.push(Suggestion {
+            capability: finding.name.clone(),
+            alternative: format!(
+                "only needed during the first {}s, drop it once initialized with cap_set_proc or capng_apply",
+                startup.as_secs()
+            ),
+            setting: None,
+        });
+        result.suggestions.push(Suggestion {
+            capability: finding.name.clone(),
+            alternative: String::from(
+                "run the initialization with elevated rights before the service",
+            ),
+            setting: Some(String::from("ExecStartPre=+")),
+        });
+    }
+}
@@ -0,0 +1,135 @@
+//! Probes pinned to bpffs, see [`pin`] and [`PinnedProbes`].
+
+use std::collections::HashSet;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use aya::maps::{HashMap, Map, MapData, PerCpuArray, StackTraceMap};
+use aya::programs::links::FdLink;
+use aya::programs::KProbe;
+use capable_common::{CgroupPath, Stats};
+use log::debug;
+
+use crate::caps::{setbpf_effective, setperfmon_effective};
+use crate::collect::CapSetEntry;
+use crate::ebpf::{cgroup_path, load_object, merge_requests, read_counters};
+use crate::ksyms::KernelSymbols;
+use crate::memlock::remove_memlock_limit;
+use crate::requests::RequestMap;
+
+/// Programs pinned with their link, attached until their pin is removed
+const PINNED_PROGRAMS: [&str; 2] = ["capable", "capable_ret"];
+/// Maps read back by `PinnedProbes`
+const PINNED_MAPS: [&str; 4] = ["ENTRY_MAP", "STACKTRACE_MAP", "STATS", "CGROUP_PATHS"];
+
+fn open_pinned(dir: &Path, name: &str) -> Result<MapData, anyhow::Error> {
+    let path = dir.join(name);
+    MapData::from_pin(&path)
+        .with_context(|| format!("Unable to open the pinned map {}", path.display()))
+}
+
+/// Load the capability probes, attach them system-wide and pin their links and maps in
+/// `dir`. They keep collecting after this process exits, until the directory is removed.
+/// Requires BPF and PERFMON in the permitted set, and Linux 5.15 for the links of the
+/// kprobes. `object` replaces the embedded programs.
+pub fn pin(dir: &Path, object: Option<&Path>) -> Result<(), anyhow::Error> {
+    remove_memlock_limit()?;
+    setbpf_effective(true)?;
+    setperfmon_effective(true)?;
+    create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+    let mut bpf = load_object(object)?;
+    for name in PINNED_PROGRAMS {
+        let program: &mut KProbe = bpf
+            .program_mut(name)
+            .with_context(|| format!("failed to get Kprobe {} program", name))?
+            .try_into()
+            .context("Failed to get Kprobe")?;
+        program.load()?;
+        let link_id = program.attach("cap_capable", 0)?;
+        let link = FdLink::try_from(program.take_link(link_id)?)
+            .context("Pinning the probes requires Linux 5.15")?;
+        let path = dir.join(name);
+        link.pin(&path)
+            .with_context(|| format!("Unable to pin {}", path.display()))?;
+    }
+    for name in PINNED_MAPS {
+        let path = dir.join(name);
+        bpf.map(name)
+            .with_context(|| format!("Unable to obtain map {}", name))?
+            .pin(&path)
+            .with_context(|| format!("Unable to pin {}", path.display()))?;
+    }
+    setperfmon_effective(false)?;
+    setbpf_effective(false)?;
+    debug!("probes pinned to {}", dir.display());
+    Ok(())
+}
+
+/// Maps of the probes pinned by [`pin`], read by a later invocation
+pub struct PinnedProbes {
+    requests: RequestMap,
+    stack_traces: StackTraceMap<MapData>,
+    stats: PerCpuArray<MapData, Stats>,
+    /// None when pinned by a version without it
+    cgroup_paths: Option<HashMap<MapData, u64, CgroupPath>>,
+    ksyms: KernelSymbols,
+}
+
+impl PinnedProbes {
+    pub fn open(dir: &Path) -> Result<PinnedProbes, anyhow::Error> {
+        setbpf_effective(true)?;
+        let requests = RequestMap::new(Map::HashMap(open_pinned(dir, "ENTRY_MAP")?))?;
+        let stack_traces =
+            StackTraceMap::try_from(Map::StackTraceMap(open_pinned(dir, "STACKTRACE_MAP")?))?;
+        let stats = PerCpuArray::try_from(Map::PerCpuArray(open_pinned(dir, "STATS")?))?;
+        let cgroup_paths = open_pinned(dir, "CGROUP_PATHS")
+            .inspect_err(|e| debug!("{:#}", e))
+            .ok()
+            .map(|map| HashMap::try_from(Map::LruHashMap(map)))
+            .transpose()?;
+        setbpf_effective(false)?;
+        Ok(PinnedProbes {
+            requests,
+            stack_traces,
+            stats,
+            cgroup_paths,
+            ksyms: KernelSymbols::default(),
+        })
+    }
+
+    /// Drain the requests collected since the previous drain into `set_entry`
+    pub fn drain(&mut self, set_entry: &mut HashSet<CapSetEntry>) -> Result<(), anyhow::Error> {
+        setbpf_effective(true)?;
+        let requests = self.requests.drain()?;
+        setbpf_effective(false)?;
+        merge_requests(
+            requests,
+            &self.stack_traces,
+            &mut self.ksyms,
+            set_entry,
+            None,
+        )
+    }
+
+    /// Path of the cgroup of the requests by cgroup id, see [`crate::ebpf::Probes::cgroup_paths`]. None
+    /// when the pinned probes do not record them.
+    pub fn cgroup_paths(&self) -> Result<Option<Vec<(u64, PathBuf)>>, anyhow::Error> {
+        let Some(cgroup_paths) = &self.cgroup_paths else {
+            return Ok(None);
+        };
+        setbpf_effective(true)?;
+        let paths = cgroup_paths
+            .iter()
+            .filter_map(Result::ok)
+            .map(|(id, path)| (id, cgroup_path(&path.path)))
+            .collect();
+        setbpf_effective(false)?;
+        Ok(Some(paths))
+    }
+
+    /// Requests seen and dropped since the probes were pinned
+    pub fn counters(&self) -> Stats {
+        read_counters(&self.stats)
+    }
+}
@@ -0,0 +1,108 @@
+//! Sandbox policies generated from a profile, selected with --policy.
+
+use std::str::FromStr;
+
+use crate::bwrap::BwrapSandbox;
+use crate::firejail::FirejailProfile;
+use crate::flatpak::FlatpakPermissions;
+use crate::k8s::SecurityContext;
+use crate::minijail::{MinijailConfig, SECCOMP_POLICY_NAME};
+use crate::nspawn::NspawnOptions;
+use crate::profile::ProgramResult;
+use crate::snap::SnapInterfaces;
+use crate::systemd::ServiceSandbox;
+
+/// Sandbox policy derived from the profile with --policy
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Policy {
+    /// finish-args of a Flatpak manifest
+    Flatpak,
+    /// Interfaces a strictly confined snap would plug
+    Snap,
+    /// bubblewrap command line running the commands of the profile
+    Bwrap,
+    /// Profile of firejail
+    Firejail,
+    /// minijail0 command line and its seccomp policy
+    Minijail,
+    /// systemd-nspawn command line running the commands of the profile
+    Nspawn,
+    /// securityContext of a Kubernetes container
+    K8s,
+    /// Drop-in sandboxing a systemd service
+    Systemd,
+}
+
+impl FromStr for Policy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flatpak" => Ok(Policy::Flatpak),
+            "snap" => Ok(Policy::Snap),
+            "bwrap" => Ok(Policy::Bwrap),
+            "firejail" => Ok(Policy::Firejail),
+            "minijail" => Ok(Policy::Minijail),
+            "nspawn" => Ok(Policy::Nspawn),
+            "k8s" => Ok(Policy::K8s),
+            "systemd" => Ok(Policy::Systemd),
+            _ => Err(anyhow::anyhow!(
+                "--policy expects flatpak, snap, bwrap, firejail, minijail, nspawn, k8s or systemd, got {}",
+                s
+            )),
+        }
+    }
+}
+
+impl Policy {
+    /// Policy of `result`, profiled from `commands` which made the syscalls of
+    /// `strace_logs`
+    pub fn render(
+        &self,
+        commands: &[Vec<String>],
+        strace_logs: &[String],
+        result: &ProgramResult,
+    ) -> String {
+        match self {
+            Policy::Flatpak => FlatpakPermissions::new(result).to_string(),
+            Policy::Snap => SnapInterfaces::new(result).to_string(),
+            Policy::Bwrap => {
+                let sandbox = BwrapSandbox::new(result);
+                commands
+                    .iter()
+                    .map(|command| sandbox.command_line(command))
+                    .collect()
+            }
+            Policy::Firejail => FirejailProfile::new(result).to_string(),
+            Policy::Minijail => {
+                let config = MinijailConfig::new(result, strace_logs);
+                let mut rendered = String::new();
+                if config.privileged {
+                    rendered.push_str("# -c requires running minijail0 as root\n");
+                }
+                if !config.complete {
+                    rendered.push_str(
+                        "# WARNING: the trace did not include every syscall, the seccomp policy is\n\
+                         # incomplete and -L only logs the syscalls outside it. Profile the command\n\
+                         # with --policy minijail and the strace collector to trace all of them.\n",
+                    );
+                }
+                for command in commands {
+                    rendered.push_str(&config.command_line(command));
+                }
+                rendered.push_str(&format!("# {}\n", SECCOMP_POLICY_NAME));
+                rendered.push_str(&config.seccomp_policy());
+                rendered
+            }
+            Policy::Nspawn => {
+                let options = NspawnOptions::new(result);
+                commands
+                    .iter()
+                    .map(|command| options.command_line(command))
+                    .collect()
+            }
+            Policy::K8s => SecurityContext::new(result).to_string(),
+            Policy::Systemd => ServiceSandbox::new(result).to_string(),
+        }
+    }
+}
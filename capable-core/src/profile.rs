@@ -0,0 +1,19 @@
+//! Profiles of `capable`, their types are defined in [`capable_common::profile`] for the
+//! consumers of its output.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::compress::read_to_string;
+
+pub use capable_common::profile::*;
+
+/// Read a profile, compressed when its path ends with .zst
+pub fn read_profile<P: AsRef<Path>>(path: P) -> Result<ProgramResult, anyhow::Error> {
+    let path = path.as_ref();
+    let content = read_to_string(path)
+        .with_context(|| format!("Unable to read profile {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Unable to parse profile {}", path.display()))
+}
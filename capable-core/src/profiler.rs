@@ -0,0 +1,489 @@
+//! Findings streamed while the profiled processes run, for frontends that consume them
+//! incrementally instead of waiting for the final report.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{read_to_string, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use capable_common::{Nsid, Pid};
+use capctl::CapSet;
+use futures_core::Stream;
+use log::warn;
+use serde::Serialize;
+
+use crate::bus::{self, DbusMsg, Memory};
+use crate::caps::{get_cap, setbpf_effective};
+use crate::collect::{
+    build_result, namespace_capabilities, namespace_reasons, nested_namespaces, CapSetEntry,
+    NamespaceTree, RequestObserver,
+};
+use crate::ebpf::Probes;
+use crate::profile::{ProgramResult, TimeWindow};
+use crate::reasons::Reasons;
+use crate::strace::parse_strace;
+use crate::syscalls::{syscall_to_entry, Access};
+use crate::time::unix_time;
+
+/// Interval between two reads of the kernel maps, the strace log and the bus monitor
+const POLL_INTERVAL: Duration = Duration::from_millis(400);
+/// Findings kept until they are consumed, the next ones are dropped from the stream but
+/// stay in the report
+const QUEUE_CAPACITY: usize = 65_536;
+
+/// Finding observed while profiling
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Capability checked by the kernel, `denied` when the process did not hold it
+    Capability {
+        capability: String,
+        pid: Pid,
+        ns: Nsid,
+        denied: bool,
+        time: u64,
+    },
+    /// File access traced by strace
+    File { path: String, access: Access },
+    /// D-Bus method called by a process
+    Dbus { method: String },
+}
+
+/// Profiling session, the probes are loaded when the events are requested
+#[derive(Default)]
+pub struct Profiler {
+    namespace: Option<Nsid>,
+    strace_log: Option<PathBuf>,
+    dbus: bool,
+    bpf_object: Option<PathBuf>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Only report the processes of the PID namespace and its descendants
+    pub fn namespace(mut self, ns: Nsid) -> Profiler {
+        self.namespace = Some(ns);
+        self
+    }
+
+    /// Report the file accesses of a strace log, read as it is written
+    pub fn strace_log<P: Into<PathBuf>>(mut self, path: P) -> Profiler {
+        self.strace_log = Some(path.into());
+        self
+    }
+
+    /// Report the method calls on the system bus, monitoring it requires to run as root
+    pub fn dbus(mut self, dbus: bool) -> Profiler {
+        self.dbus = dbus;
+        self
+    }
+
+    /// Load the probes of this eBPF object instead of the embedded ones
+    pub fn bpf_object<P: Into<PathBuf>>(mut self, path: P) -> Profiler {
+        self.bpf_object = Some(path.into());
+        self
+    }
+
+    /// Load the probes and report the findings until the stream is dropped
+    pub fn events(self) -> Result<Events, anyhow::Error> {
+        let shared = Arc::new(Shared::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let restrict = Arc::new(AtomicU32::new(self.namespace.unwrap_or_default()));
+        let worker = Worker {
+            shared: shared.clone(),
+            stop: stop.clone(),
+            restrict: restrict.clone(),
+            namespace: self.namespace,
+            namespaces: self.namespace.map(|ns| HashSet::from([ns])),
+            set_entry: HashSet::new(),
+            strace_log: self.strace_log.map(|path| StraceTail {
+                path,
+                offset: 0,
+                pending: String::new(),
+                unfinished: 0,
+            }),
+            dbus: self.dbus,
+            bpf_object: self.bpf_object,
+            dbus_seen: HashMap::new(),
+            start: unix_time(),
+        };
+        let (loaded, result) = mpsc::channel();
+        // the probes are not Send, they live in the thread polling them
+        let worker = thread::spawn(move || worker.run(loaded));
+        result.recv()??;
+        Ok(Events {
+            shared,
+            stop,
+            restrict,
+            worker: Some(worker),
+        })
+    }
+}
+
+/// Findings of a [`Profiler`] in the order they are observed, then the error that stopped
+/// the collection if any. Dropping it unloads the probes.
+pub struct Events {
+    shared: Arc<Shared>,
+    stop: Arc<AtomicBool>,
+    restrict: Arc<AtomicU32>,
+    worker: Option<JoinHandle<Result<ProgramResult, anyhow::Error>>>,
+}
+
+impl Events {
+    /// Next pending finding, without waiting, for callers without an async runtime
+    pub fn try_next(&self) -> Option<Result<Event, anyhow::Error>> {
+        let mut queue = self.shared.queue.lock().expect("events queue poisoned");
+        queue.next()
+    }
+
+    /// Number of findings dropped from the stream as they were not consumed in time
+    pub fn dropped(&self) -> u64 {
+        let queue = self.shared.queue.lock().expect("events queue poisoned");
+        queue.dropped
+    }
+
+    /// Only report the processes of the PID namespace and its descendants, for a command
+    /// started after the probes. The findings already streamed are not filtered, the report is.
+    pub fn restrict(&self, ns: Nsid) {
+        self.restrict.store(ns, Ordering::Relaxed);
+    }
+
+    /// Stop collecting and build the profile of all the findings, including the ones
+    /// not consumed from the stream
+    pub fn finish(mut self) -> Result<ProgramResult, anyhow::Error> {
+        self.stop.store(true, Ordering::Relaxed);
+        let worker = self.worker.take().expect("profiler already finished");
+        worker
+            .join()
+            .map_err(|_| anyhow!("The profiler thread panicked"))?
+    }
+}
+
+impl Stream for Events {
+    type Item = Result<Event, anyhow::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut queue = self.shared.queue.lock().expect("events queue poisoned");
+        if let Some(next) = queue.next() {
+            return Poll::Ready(Some(next));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<Event>,
+    /// Findings not queued as QUEUE_CAPACITY was reached
+    dropped: u64,
+    /// Error that stopped the worker, given after the pending findings
+    error: Option<anyhow::Error>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl Queue {
+    fn next(&mut self) -> Option<Result<Event, anyhow::Error>> {
+        match self.events.pop_front() {
+            Some(event) => Some(Ok(event)),
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+impl Shared {
+    fn push(&self, event: Event) {
+        let mut queue = self.queue.lock().expect("events queue poisoned");
+        if queue.events.len() >= QUEUE_CAPACITY {
+            if queue.dropped == 0 {
+                warn!("The events are not consumed, the next ones are only in the report");
+            }
+            queue.dropped += 1;
+            return;
+        }
+        queue.events.push_back(event);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// End the stream, after `error` if the worker failed
+    fn close(&self, error: Option<anyhow::Error>) {
+        let mut queue = self.queue.lock().expect("events queue poisoned");
+        queue.error = error;
+        queue.closed = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Strace log, read incrementally
+struct StraceTail {
+    path: PathBuf,
+    offset: u64,
+    // lines kept until their unfinished syscalls are resumed, as they are parsed together
+    pending: String,
+    unfinished: usize,
+}
+
+impl StraceTail {
+    fn update(&mut self, shared: &Shared) -> std::io::Result<()> {
+        let Ok(mut file) = File::open(&self.path) else {
+            // the log is created by strace once started
+            return Ok(());
+        };
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            if !line.ends_with('\n') {
+                // being written, read it again on the next update
+                break;
+            }
+            self.offset += line.len() as u64;
+            if line.contains("<unfinished ...>") {
+                self.unfinished += 1;
+            }
+            if line.contains(" resumed>") {
+                self.unfinished = self.unfinished.saturating_sub(1);
+            }
+            self.pending.push_str(&line);
+            line.clear();
+        }
+        if self.unfinished > 0 || self.pending.is_empty() {
+            return Ok(());
+        }
+        for syscall in parse_strace(&self.pending) {
+            for entry in syscall_to_entry(&syscall).into_iter().flatten() {
+                shared.push(Event::File {
+                    path: entry.path,
+                    access: entry.access,
+                });
+            }
+        }
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// Forwards the capability checks of the namespaces to the stream
+struct Forward<'a> {
+    shared: &'a Shared,
+    namespaces: Option<&'a mut HashSet<Nsid>>,
+}
+
+impl RequestObserver for Forward<'_> {
+    fn observe(
+        &mut self,
+        entry: &CapSetEntry,
+        capability: u8,
+        denied: bool,
+        time: u64,
+        _stack: &[String],
+    ) -> Result<(), anyhow::Error> {
+        if let Some(namespaces) = self.namespaces.as_deref_mut() {
+            // a namespace is seen before its children, which request from its processes
+            if namespaces.contains(&entry.parent_ns) {
+                namespaces.insert(entry.ns);
+            }
+            if !namespaces.contains(&entry.ns) {
+                return Ok(());
+            }
+        }
+        let Some(cap) = get_cap(capability) else {
+            return Ok(());
+        };
+        self.shared.push(Event::Capability {
+            capability: format!("CAP_{:?}", cap),
+            pid: entry.pid,
+            ns: entry.ns,
+            denied,
+            time,
+        });
+        Ok(())
+    }
+}
+
+struct Worker {
+    shared: Arc<Shared>,
+    stop: Arc<AtomicBool>,
+    // namespace given to the events once the profiler started, 0 if none
+    restrict: Arc<AtomicU32>,
+    namespace: Option<Nsid>,
+    // the namespace and its descendants seen so far
+    namespaces: Option<HashSet<Nsid>>,
+    set_entry: HashSet<CapSetEntry>,
+    strace_log: Option<StraceTail>,
+    dbus: bool,
+    bpf_object: Option<PathBuf>,
+    // number of calls of each bus connection already reported
+    dbus_seen: HashMap<String, usize>,
+    start: u64,
+}
+
+impl Worker {
+    fn run(
+        mut self,
+        loaded: mpsc::Sender<Result<(), anyhow::Error>>,
+    ) -> Result<ProgramResult, anyhow::Error> {
+        let mut probes = match Probes::load(self.bpf_object.as_deref()) {
+            Ok(probes) => probes,
+            Err(e) => {
+                let _ = loaded.send(Err(e));
+                return Err(anyhow!("The probes were not loaded"));
+            }
+        };
+        let _ = loaded.send(Ok(()));
+        let monitor = self.dbus.then(|| {
+            let memory = Arc::new(Memory::default());
+            let monitored = memory.clone();
+            let handle = thread::spawn(move || {
+                bus::run_dbus_monitor(monitored)
+                    .inspect_err(|e| warn!("dbus monitor failed: {}", e))
+                    .ok()
+            });
+            (memory, handle)
+        });
+        let collected = loop {
+            // the requests until the stop are collected too
+            let stopping = self.stop.load(Ordering::Relaxed);
+            let collected = self.poll(&mut probes, monitor.as_ref().map(|(memory, _)| &**memory));
+            if let Err(e) = &collected {
+                warn!("Unable to collect the events: {}", e);
+            }
+            if stopping || collected.is_err() {
+                break collected;
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        let dbus = monitor.and_then(|(memory, handle)| {
+            memory.cancel.store(true, Ordering::Relaxed);
+            handle.join().ok().flatten()
+        });
+        // the error is returned by finish too
+        self.shared.close(
+            collected
+                .as_ref()
+                .err()
+                .map(|e| anyhow!("Unable to collect the events: {:#}", e)),
+        );
+        collected?;
+        let tree = probes.namespace_tree(&self.set_entry)?;
+        self.report(&tree, dbus.as_ref())
+    }
+
+    fn report(
+        &self,
+        tree: &NamespaceTree,
+        dbus: Option<&HashMap<u32, Vec<DbusMsg>>>,
+    ) -> Result<ProgramResult, anyhow::Error> {
+        let (capset, reasons) = match self.namespace {
+            Some(ns) => (
+                namespace_capabilities(tree, ns, &self.set_entry, |entry| entry.capabilities),
+                namespace_reasons(tree, ns, &self.set_entry),
+            ),
+            None => (
+                self.set_entry
+                    .iter()
+                    .fold(CapSet::empty(), |capset, entry| capset | entry.capabilities),
+                Reasons::default(),
+            ),
+        };
+        let strace_log = self
+            .strace_log
+            .as_ref()
+            .filter(|tail| tail.path.exists())
+            .map(|tail| read_to_string(&tail.path))
+            .transpose()?;
+        let window = TimeWindow {
+            start: self.start,
+            end: unix_time(),
+        };
+        let mut result = build_result(
+            capset,
+            strace_log.as_deref(),
+            dbus,
+            self.namespace.unwrap_or_default(),
+            Some(window),
+            reasons,
+        );
+        if let Some(ns) = self.namespace {
+            result.namespaces = nested_namespaces(tree, ns, &self.set_entry);
+        }
+        Ok(result)
+    }
+
+    fn poll(&mut self, probes: &mut Probes, memory: Option<&Memory>) -> Result<(), anyhow::Error> {
+        let ns = self.restrict.load(Ordering::Relaxed);
+        if ns != 0 && self.namespace != Some(ns) {
+            self.namespace = Some(ns);
+            self.namespaces = Some(HashSet::from([ns]));
+        }
+        let mut forward = Forward {
+            shared: &self.shared,
+            namespaces: self.namespaces.as_mut(),
+        };
+        setbpf_effective(true)?;
+        probes.drain(&mut self.set_entry, Some(&mut forward))?;
+        setbpf_effective(false)?;
+        if let Some(tail) = self.strace_log.as_mut() {
+            tail.update(&self.shared)?;
+        }
+        if let Some(memory) = memory {
+            self.poll_dbus(memory);
+        }
+        Ok(())
+    }
+
+    fn poll_dbus(&mut self, memory: &Memory) {
+        let connections: Option<HashSet<String>> = self.namespaces.as_ref().map(|namespaces| {
+            namespaces
+                .iter()
+                .filter_map(|ns| memory.owners.get(ns))
+                .flat_map(|owners| owners.clone())
+                .collect()
+        });
+        for requests in memory.requests.iter() {
+            if connections
+                .as_ref()
+                .is_some_and(|connections| !connections.contains(requests.key()))
+            {
+                continue;
+            }
+            let seen = self.dbus_seen.entry(requests.key().clone()).or_default();
+            for method in requests.value()[*seen..]
+                .iter()
+                .filter_map(bus::method_name)
+            {
+                self.shared.push(Event::Dbus { method });
+            }
+            *seen = requests.value().len();
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Human-readable reasons of capability findings, from well-known kernel call paths.
+//!
+//! The stack of a capability check tells what the command was doing, but only to kernel
+//! developers. Each known path lists functions that must all be in the stack of the check,
+//! the first matching path of the capability explains it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use capable_common::Pid;
+
+use crate::ksyms::{base_symbol, reason_symbol, syscall_name};
+use crate::profile::CapabilityFinding;
+
+/// Evidence of the requests of a capability
+#[derive(Clone, Debug, Default)]
+pub struct Evidence {
+    /// Checks of the capability by the kernel
+    pub count: u64,
+    pub processes: BTreeSet<Pid>,
+    /// Syscalls during which it was checked
+    pub syscalls: BTreeSet<String>,
+    /// Kernel functions that checked it, see ksyms::reason_symbol
+    pub symbols: BTreeSet<String>,
+    /// Explanations of the known call paths of the checks
+    pub explanations: BTreeSet<String>,
+    /// Unix time of the first and last checks in nanoseconds, 0 when unknown
+    pub first: u64,
+    pub last: u64,
+}
+
+/// Evidence of each requested capability, keyed by capability name
+pub type Reasons = BTreeMap<String, Evidence>;
+
+impl Evidence {
+    /// Add a check of `capability` with the stack `frames`
+    pub fn observe(&mut self, capability: &str, frames: &[String], time: u64) {
+        self.count += 1;
+        self.seen(time);
+        if let Some(syscall) = syscall_name(frames) {
+            self.syscalls.insert(syscall.to_string());
+        }
+        if let Some(symbol) = reason_symbol(frames) {
+            self.symbols.insert(symbol.to_string());
+        }
+        if let Some(explanation) = explain_stack(capability, frames) {
+            self.explanations.insert(explanation);
+        }
+    }
+
+    fn seen(&mut self, time: u64) {
+        if time == 0 {
+            return;
+        }
+        if self.first == 0 || time < self.first {
+            self.first = time;
+        }
+        self.last = self.last.max(time);
+    }
+
+    pub fn extend(&mut self, other: &Evidence) {
+        self.count += other.count;
+        self.seen(other.first);
+        self.seen(other.last);
+        self.processes.extend(&other.processes);
+        self.syscalls.extend(other.syscalls.iter().cloned());
+        self.symbols.extend(other.symbols.iter().cloned());
+        self.explanations.extend(other.explanations.iter().cloned());
+    }
+
+    /// Finding of the capability `name` in the profile of a run started at `start`, in
+    /// seconds since the Unix epoch
+    pub fn finding(self, name: String, start: Option<u64>) -> CapabilityFinding {
+        let since_start = |time: u64| {
+            let start = start?.checked_mul(1_000_000_000)?;
+            (time != 0).then(|| time.saturating_sub(start) / 1_000_000)
+        };
+        CapabilityFinding {
+            first_seen: since_start(self.first),
+            last_seen: since_start(self.last),
+            phase: None,
+            name,
+            count: self.count,
+            processes: self.processes.into_iter().collect(),
+            syscalls: self.syscalls.into_iter().collect(),
+            reason_symbols: self.symbols.into_iter().collect(),
+        }
+    }
+}
+
+struct KnownPath {
+    capability: &'static str,
+    frames: &'static [&'static str],
+    explanation: &'static str,
+}
+
+const KNOWN_PATHS: &[KnownPath] = &[
+    KnownPath {
+        capability: "CAP_NET_ADMIN",
+        frames: &["netlink_sendmsg", "rtnetlink_rcv_msg"],
+        explanation: "configuring network interfaces, addresses or routes",
+    },
+    KnownPath {
+        capability: "CAP_NET_ADMIN",
+        frames: &["netlink_sendmsg", "nfnetlink_rcv_msg"],
+        explanation: "changing firewall rules",
+    },
+    KnownPath {
+        capability: "CAP_NET_ADMIN",
+        frames: &["netlink_sendmsg", "genl_rcv_msg"],
+        explanation: "configuring a network subsystem through generic netlink",
+    },
+    KnownPath {
+        capability: "CAP_NET_ADMIN",
+        frames: &["dev_ioctl"],
+        explanation: "configuring a network interface with an ioctl",
+    },
+    KnownPath {
+        capability: "CAP_NET_ADMIN",
+        frames: &["sk_setsockopt"],
+        explanation: "setting a privileged socket option, such as SO_MARK or SO_PRIORITY",
+    },
+    KnownPath {
+        capability: "CAP_NET_BIND_SERVICE",
+        frames: &["__inet_bind"],
+        explanation: "binding a port below net.ipv4.ip_unprivileged_port_start",
+    },
+    KnownPath {
+        capability: "CAP_NET_BIND_SERVICE",
+        frames: &["__inet6_bind"],
+        explanation: "binding a port below net.ipv4.ip_unprivileged_port_start",
+    },
+    KnownPath {
+        capability: "CAP_NET_RAW",
+        frames: &["packet_create"],
+        explanation: "opening a packet socket to capture or inject frames",
+    },
+    KnownPath {
+        capability: "CAP_NET_RAW",
+        frames: &["inet_create"],
+        explanation: "opening a raw IP socket",
+    },
+    KnownPath {
+        capability: "CAP_NET_RAW",
+        frames: &["inet6_create"],
+        explanation: "opening a raw IPv6 socket",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["path_mount"],
+        explanation: "mounting a filesystem",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["path_umount"],
+        explanation: "unmounting a filesystem",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["ksys_unshare"],
+        explanation: "creating namespaces with unshare",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["copy_namespaces"],
+        explanation: "creating namespaces with clone",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["__do_sys_setns"],
+        explanation: "entering the namespace of another process",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["__do_sys_sethostname"],
+        explanation: "setting the hostname",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["__sys_bpf"],
+        explanation: "loading eBPF programs or maps",
+    },
+    KnownPath {
+        capability: "CAP_SYS_ADMIN",
+        frames: &["__do_sys_swapon"],
+        explanation: "enabling swap",
+    },
+    KnownPath {
+        capability: "CAP_KILL",
+        frames: &["check_kill_permission"],
+        explanation: "sending a signal to a process of another user",
+    },
+    KnownPath {
+        capability: "CAP_CHOWN",
+        frames: &["chown_common"],
+        explanation: "changing the owner of a file",
+    },
+    KnownPath {
+        capability: "CAP_FOWNER",
+        frames: &["chmod_common"],
+        explanation: "changing the mode of a file owned by another user",
+    },
+    KnownPath {
+        capability: "CAP_FOWNER",
+        frames: &["vfs_utimes"],
+        explanation: "changing the timestamps of a file owned by another user",
+    },
+    KnownPath {
+        capability: "CAP_SETUID",
+        frames: &["__sys_setresuid"],
+        explanation: "switching to another user",
+    },
+    KnownPath {
+        capability: "CAP_SETUID",
+        frames: &["__sys_setuid"],
+        explanation: "switching to another user",
+    },
+    KnownPath {
+        capability: "CAP_SETGID",
+        frames: &["__sys_setresgid"],
+        explanation: "switching to another group",
+    },
+    KnownPath {
+        capability: "CAP_SETGID",
+        frames: &["__sys_setgid"],
+        explanation: "switching to another group",
+    },
+    KnownPath {
+        capability: "CAP_SETGID",
+        frames: &["__do_sys_setgroups"],
+        explanation: "setting the supplementary groups",
+    },
+    KnownPath {
+        capability: "CAP_SYS_CHROOT",
+        frames: &["__do_sys_chroot"],
+        explanation: "changing the root directory",
+    },
+    KnownPath {
+        capability: "CAP_IPC_LOCK",
+        frames: &["do_mlock"],
+        explanation: "locking memory beyond RLIMIT_MEMLOCK",
+    },
+    KnownPath {
+        capability: "CAP_SYS_RESOURCE",
+        frames: &["do_prlimit"],
+        explanation: "raising a hard resource limit",
+    },
+    KnownPath {
+        capability: "CAP_SYS_NICE",
+        frames: &["set_one_prio"],
+        explanation: "raising the priority of a process",
+    },
+    KnownPath {
+        capability: "CAP_MKNOD",
+        frames: &["vfs_mknod"],
+        explanation: "creating a device node",
+    },
+    KnownPath {
+        capability: "CAP_DAC_READ_SEARCH",
+        frames: &["do_handle_open"],
+        explanation: "opening a file by handle",
+    },
+    KnownPath {
+        capability: "CAP_SYS_TIME",
+        frames: &["do_sys_settimeofday64"],
+        explanation: "setting the system clock",
+    },
+    KnownPath {
+        capability: "CAP_SYS_BOOT",
+        frames: &["__do_sys_reboot"],
+        explanation: "rebooting or powering off the system",
+    },
+    KnownPath {
+        capability: "CAP_AUDIT_WRITE",
+        frames: &["audit_receive_msg"],
+        explanation: "writing records to the audit log",
+    },
+    KnownPath {
+        capability: "CAP_SYSLOG",
+        frames: &["do_syslog"],
+        explanation: "reading the kernel log",
+    },
+];
+
+/// Explanation of the capability check of `frames`, innermost first, when its path is known.
+/// The note reads "via <function>: <explanation>", with the outermost function of the path.
+pub fn explain_stack(capability: &str, frames: &[String]) -> Option<String> {
+    KNOWN_PATHS
+        .iter()
+        .filter(|path| path.capability == capability)
+        .find(|path| {
+            path.frames
+                .iter()
+                .all(|known| frames.iter().any(|frame| base_symbol(frame) == *known))
+        })
+        .map(|path| {
+            format!(
+                "via {}: {}",
+                path.frames.first().expect("known paths have frames"),
+                path.explanation
+            )
+        })
+}
@@ -0,0 +1,202 @@
+//! Recordings of the raw events of a run, see `--record`. A recording is replayed
+//! through [`Replay`] to analyze the run again, on this host or another one.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::Context;
+use capable_common::{Gid, Nsid, Pid, Uid};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::bus::DbusMsg;
+use crate::collect::{CapSetEntry, RequestObserver};
+use crate::profile::TimeWindow;
+use crate::replay::Replay;
+
+/// zstd level of recordings, favours speed as events are compressed while collecting
+const RECORD_LEVEL: i32 = 3;
+
+/// Line of a recording. Requests are recorded while collecting, the other records at the
+/// end of a command run.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Record<'a> {
+    Request {
+        pid: Pid,
+        ppid: Pid,
+        uid: Uid,
+        gid: Gid,
+        ns: Nsid,
+        parent_ns: Nsid,
+        capability: u8,
+        #[serde(default)]
+        denied: bool,
+        /// Unix time of the check in nanoseconds, 0 in older recordings
+        #[serde(default)]
+        time: u64,
+        stack: Cow<'a, [String]>,
+    },
+    Run {
+        command: Cow<'a, [String]>,
+        pid: Pid,
+        ns: Nsid,
+        window: TimeWindow,
+    },
+    Strace {
+        log: Cow<'a, str>,
+    },
+    Dbus {
+        messages: Cow<'a, HashMap<u32, Vec<DbusMsg>>>,
+    },
+}
+
+/// Writer of the raw events of a run, as zstd compressed JSON lines
+pub struct Recorder {
+    encoder: zstd::Encoder<'static, BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Recorder, anyhow::Error> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("Unable to create recording {}", path.display()))?;
+        Ok(Recorder {
+            encoder: zstd::Encoder::new(BufWriter::new(file), RECORD_LEVEL)?,
+        })
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), anyhow::Error> {
+        serde_json::to_writer(&mut self.encoder, record)?;
+        self.encoder.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn record_run(
+        &mut self,
+        command: &[String],
+        pid: Pid,
+        ns: Nsid,
+        window: TimeWindow,
+        strace_log: Option<&str>,
+        dbus: Option<&HashMap<u32, Vec<DbusMsg>>>,
+    ) -> Result<(), anyhow::Error> {
+        self.write(&Record::Run {
+            command: Cow::Borrowed(command),
+            pid,
+            ns,
+            window,
+        })?;
+        if let Some(log) = strace_log {
+            self.write(&Record::Strace {
+                log: Cow::Borrowed(log),
+            })?;
+        }
+        if let Some(messages) = dbus {
+            self.write(&Record::Dbus {
+                messages: Cow::Borrowed(messages),
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), anyhow::Error> {
+        self.encoder.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+impl RequestObserver for Recorder {
+    fn observe(
+        &mut self,
+        entry: &CapSetEntry,
+        capability: u8,
+        denied: bool,
+        time: u64,
+        stack: &[String],
+    ) -> Result<(), anyhow::Error> {
+        self.write(&Record::Request {
+            pid: entry.pid,
+            ppid: entry.ppid,
+            uid: entry.uid,
+            gid: entry.gid,
+            ns: entry.ns,
+            parent_ns: entry.parent_ns,
+            capability,
+            denied,
+            time,
+            stack: Cow::Borrowed(stack),
+        })
+    }
+}
+
+/// Command run of a recording
+pub struct RecordedRun {
+    pub command: Vec<String>,
+    pub pid: Pid,
+    pub ns: Nsid,
+    pub window: TimeWindow,
+}
+
+/// Events of a recording, injected into a replay
+pub struct Recording {
+    pub replay: Replay,
+    /// None for the recordings of daemon mode
+    pub run: Option<RecordedRun>,
+}
+
+pub fn read_recording<P: AsRef<Path>>(path: P) -> Result<Recording, anyhow::Error> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("Unable to open recording {}", path.display()))?;
+    let reader = BufReader::new(zstd::Decoder::new(file)?);
+    let mut replay = Replay::new();
+    let mut run = None;
+    for line in reader.lines() {
+        let record: Record = serde_json::from_str(&line?)
+            .with_context(|| format!("Corrupted recording {}", path.display()))?;
+        match record {
+            Record::Request {
+                pid,
+                ppid,
+                uid,
+                gid,
+                ns,
+                parent_ns,
+                capability,
+                denied,
+                time,
+                stack,
+            } => {
+                let entry = CapSetEntry::new(pid, ppid, uid, gid, parent_ns, ns);
+                let stack: Rc<[String]> = Rc::from(stack.into_owned());
+                replay.inject(entry, capability, denied, time, stack)?;
+            }
+            Record::Run {
+                command,
+                pid,
+                ns,
+                window,
+            } => {
+                run = Some(RecordedRun {
+                    command: command.into_owned(),
+                    pid,
+                    ns,
+                    window,
+                })
+            }
+            Record::Strace { log } => replay.strace_log(log.into_owned()),
+            Record::Dbus { messages } => replay.dbus(messages.into_owned()),
+        }
+    }
+    debug!(
+        "{} requests replayed from {}",
+        replay.requests(),
+        path.display()
+    );
+    Ok(Recording { replay, run })
+}
@@ -0,0 +1,206 @@
+//! Replay of capability requests and strace logs through the aggregation of a live run.
+//!
+//! Requests are injected instead of drained from the kernel, so profiles can be generated
+//! without privileges, from recordings or from fixture files. A fixture holds one request
+//! per line as a JSON object, for instance
+//! `{"pid": 2, "ppid": 1, "ns": 4026531836, "capability": "CAP_NET_BIND_SERVICE"}`. The
+//! fixtures of capable-core/tests/fixtures are replayed by the tests of capable-core.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{read_to_string, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::Context;
+use capable_common::{Gid, Nsid, Pid, Uid};
+use capctl::Cap;
+use serde::{Deserialize, Deserializer};
+
+use crate::bus::{read_dbus_result, DbusMsg};
+use crate::collect::{
+    add_request, build_result, namespace_capabilities, namespace_reasons, nested_namespaces,
+    CapSetEntry, NamespaceTree,
+};
+use crate::profile::{ProgramResult, TimeWindow};
+
+/// Capability request of a fixture file, as the probes report it
+#[derive(Debug, Clone, Deserialize)]
+pub struct InjectedRequest {
+    pub pid: Pid,
+    pub ppid: Pid,
+    #[serde(default)]
+    pub uid: Uid,
+    #[serde(default)]
+    pub gid: Gid,
+    pub ns: Nsid,
+    /// 0 when the namespace of the request is the root of the fixture
+    #[serde(default)]
+    pub parent_ns: Nsid,
+    /// Number or name of the capability, `CAP_CHOWN` or `chown`
+    #[serde(deserialize_with = "capability")]
+    pub capability: u8,
+    #[serde(default)]
+    pub denied: bool,
+    /// Unix time of the check in nanoseconds
+    #[serde(default)]
+    pub time: u64,
+    /// Kernel stack of the check, innermost function first
+    #[serde(default)]
+    pub stack: Vec<String>,
+}
+
+fn capability<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Capability {
+        Number(u8),
+        Name(String),
+    }
+    match Capability::deserialize(deserializer)? {
+        Capability::Number(number) => Ok(number),
+        Capability::Name(name) => {
+            let name = name.to_uppercase();
+            let name = if name.starts_with("CAP_") {
+                name
+            } else {
+                format!("CAP_{}", name)
+            };
+            name.parse::<Cap>()
+                .map(|cap| cap as u8)
+                .map_err(|_| serde::de::Error::custom(format!("Unknown capability: {}", name)))
+        }
+    }
+}
+
+/// Requests, strace log and D-Bus messages of a replayed run
+#[derive(Default)]
+pub struct Replay {
+    set_entry: HashSet<CapSetEntry>,
+    /// Namespace of the first request
+    root: Option<Nsid>,
+    requests: usize,
+    strace_log: Option<String>,
+    dbus: Option<HashMap<u32, Vec<DbusMsg>>>,
+}
+
+impl Replay {
+    pub fn new() -> Replay {
+        Replay::default()
+    }
+
+    /// Aggregate a request, as if the probes reported it
+    pub fn inject(
+        &mut self,
+        entry: CapSetEntry,
+        capability: u8,
+        denied: bool,
+        time: u64,
+        stack: Rc<[String]>,
+    ) -> Result<(), anyhow::Error> {
+        self.requests += 1;
+        self.root.get_or_insert(entry.ns);
+        add_request(&mut self.set_entry, entry, capability, denied, time, || {
+            Ok(stack.clone())
+        })
+    }
+
+    pub fn request(&mut self, request: InjectedRequest) -> Result<(), anyhow::Error> {
+        let entry = CapSetEntry::new(
+            request.pid,
+            request.ppid,
+            request.uid,
+            request.gid,
+            request.parent_ns,
+            request.ns,
+        );
+        self.inject(
+            entry,
+            request.capability,
+            request.denied,
+            request.time,
+            Rc::from(request.stack),
+        )
+    }
+
+    /// Inject the requests of a fixture file, empty lines and `#` comments are skipped
+    pub fn requests_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), anyhow::Error> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Unable to open fixture {}", path.display()))?;
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let request = serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: invalid request", path.display(), number + 1))?;
+            self.request(request)?;
+        }
+        Ok(())
+    }
+
+    pub fn strace_log(&mut self, log: String) {
+        self.strace_log = Some(log);
+    }
+
+    /// strace log of the replayed run, if any
+    pub fn strace(&self) -> Option<&str> {
+        self.strace_log.as_deref()
+    }
+
+    pub fn strace_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), anyhow::Error> {
+        let path = path.as_ref();
+        self.strace_log = Some(
+            read_to_string(path)
+                .with_context(|| format!("Unable to read strace log {}", path.display()))?,
+        );
+        Ok(())
+    }
+
+    pub fn dbus(&mut self, messages: HashMap<u32, Vec<DbusMsg>>) {
+        self.dbus = Some(messages);
+    }
+
+    pub fn dbus_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), anyhow::Error> {
+        self.dbus = Some(read_dbus_result(path)?);
+        Ok(())
+    }
+
+    /// Number of injected requests
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+
+    /// Namespace of the first injected request, the one of the command in a fixture
+    pub fn root(&self) -> Option<Nsid> {
+        self.root
+    }
+
+    /// Aggregated requests per process, as drained from the probes
+    pub fn entries(&self) -> &HashSet<CapSetEntry> {
+        &self.set_entry
+    }
+
+    pub fn into_entries(self) -> HashSet<CapSetEntry> {
+        self.set_entry
+    }
+
+    /// Profile of the namespace `ns` and its descendants
+    pub fn result(&self, ns: Nsid, window: Option<TimeWindow>) -> ProgramResult {
+        let tree = NamespaceTree::new(&self.set_entry);
+        let capset = namespace_capabilities(&tree, ns, &self.set_entry, |entry| entry.capabilities);
+        let reasons = namespace_reasons(&tree, ns, &self.set_entry);
+        let mut result = build_result(
+            capset,
+            self.strace_log.as_deref(),
+            self.dbus.as_ref(),
+            ns,
+            window,
+            reasons,
+        );
+        result.namespaces = nested_namespaces(&tree, ns, &self.set_entry);
+        result
+    }
+}
@@ -0,0 +1,133 @@
+use std::mem::size_of;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+
+use aya::maps::{HashMap, Map, MapData};
+use capable_common::Request;
+use tracing::{debug, warn};
+
+/// bpf() command number of BPF_MAP_LOOKUP_AND_DELETE_BATCH, available since Linux 5.6
+const BPF_MAP_LOOKUP_AND_DELETE_BATCH: libc::c_long = 25;
+/// Kernel internal errno returned when a map type has no batch operation
+const ENOTSUPP: i32 = 524;
+/// Number of requests read by a single batch syscall
+const BATCH_SIZE: u32 = 4096;
+
+/// `batch` member of `union bpf_attr`
+#[repr(C)]
+#[derive(Default)]
+struct BatchAttr {
+    in_batch: u64,
+    out_batch: u64,
+    keys: u64,
+    values: u64,
+    count: u32,
+    map_fd: u32,
+    elem_flags: u64,
+    flags: u64,
+}
+
+/// Kernel map where the eBPF program stores capability requests, keyed by a per-cpu sequence.
+/// Requests are drained with batched lookup-and-delete when the kernel supports it, which
+/// costs one syscall per `BATCH_SIZE` requests instead of one per request.
+pub struct RequestMap {
+    fd: OwnedFd,
+    map: HashMap<MapData, u64, Request>,
+    batch: bool,
+}
+
+impl RequestMap {
+    pub fn new(map: Map) -> Result<RequestMap, anyhow::Error> {
+        let fd = match &map {
+            Map::HashMap(data) => data.fd().as_fd().try_clone_to_owned()?,
+            _ => return Err(anyhow::anyhow!("requests map is not a hash map")),
+        };
+        Ok(RequestMap {
+            fd,
+            map: HashMap::try_from(map)?,
+            batch: true,
+        })
+    }
+
+    /// Remove every pending request from the kernel map and return them
+    pub fn drain(&mut self) -> Result<Vec<Request>, anyhow::Error> {
+        let mut requests = Vec::new();
+        if self.batch {
+            match self.drain_batch(&mut requests) {
+                Ok(()) => return Ok(requests),
+                Err(e)
+                    if requests.is_empty()
+                        && matches!(
+                            e.raw_os_error(),
+                            Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) | Some(ENOTSUPP)
+                        ) =>
+                {
+                    debug!("batch map operations unsupported ({}), falling back", e);
+                    self.batch = false;
+                }
+                Err(e) if requests.is_empty() => return Err(e.into()),
+                // the requests read so far are already deleted from the map, keep them
+                Err(e) => {
+                    warn!(
+                        "draining the requests failed after {} of them: {}",
+                        requests.len(),
+                        e
+                    );
+                    return Ok(requests);
+                }
+            }
+        }
+        self.drain_each(&mut requests)?;
+        Ok(requests)
+    }
+
+    fn drain_batch(&self, requests: &mut Vec<Request>) -> std::io::Result<()> {
+        let mut keys = vec![0u64; BATCH_SIZE as usize];
+        let mut values: Vec<Request> = Vec::with_capacity(BATCH_SIZE as usize);
+        // hash maps use a bucket index as batch token
+        let mut token: u32 = 0;
+        let mut first = true;
+        loop {
+            let mut attr = BatchAttr {
+                in_batch: if first { 0 } else { &token as *const u32 as u64 },
+                out_batch: &mut token as *mut u32 as u64,
+                keys: keys.as_mut_ptr() as u64,
+                values: values.as_mut_ptr() as u64,
+                count: BATCH_SIZE,
+                map_fd: self.fd.as_raw_fd() as u32,
+                ..Default::default()
+            };
+            let ret = unsafe {
+                libc::syscall(
+                    libc::SYS_bpf,
+                    BPF_MAP_LOOKUP_AND_DELETE_BATCH,
+                    &mut attr as *mut BatchAttr,
+                    size_of::<BatchAttr>(),
+                )
+            };
+            let err = std::io::Error::last_os_error();
+            // the kernel reports how many elements were copied, even on ENOENT
+            let count = (attr.count as usize).min(BATCH_SIZE as usize);
+            unsafe { values.set_len(count) };
+            requests.append(&mut values);
+            if ret < 0 {
+                return match err.raw_os_error() {
+                    // no more elements
+                    Some(libc::ENOENT) => Ok(()),
+                    _ => Err(err),
+                };
+            }
+            first = false;
+        }
+    }
+
+    fn drain_each(&mut self, requests: &mut Vec<Request>) -> Result<(), anyhow::Error> {
+        let keys: Vec<u64> = self.map.keys().filter_map(Result::ok).collect();
+        for key in keys {
+            if let Ok(request) = self.map.get(&key, 0) {
+                requests.push(request);
+            }
+            let _ = self.map.remove(&key);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt::Display;
+
+use crate::profile::ProgramResult;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+impl Risk {
+    /// Chance the capability leads to a compromise of the system, combined into the score
+    fn weight(self) -> f64 {
+        match self {
+            Risk::Low => 0.05,
+            Risk::Medium => 0.2,
+            Risk::High => 0.5,
+        }
+    }
+}
+
+impl Display for Risk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let risk = match self {
+            Risk::Low => "LOW",
+            Risk::Medium => "MEDIUM",
+            Risk::High => "HIGH",
+        };
+        // pad rather than write!, the report aligns the levels with a width
+        f.pad(risk)
+    }
+}
+
+/// Risk of the capabilities with how to do without them, the others are Low. The
+/// suggestions of the profile give the same remediations, with their settings.
+const RISKS: &[(&str, Risk, &str)] = &[
+    (
+        "CAP_SYS_ADMIN",
+        Risk::High,
+        "find the operation in reason_symbols, most of them have a narrower capability",
+    ),
+    (
+        "CAP_SYS_PTRACE",
+        Risk::High,
+        "debug with a dedicated account or YAMA ptrace_scope instead",
+    ),
+    (
+        "CAP_DAC_OVERRIDE",
+        Risk::High,
+        "grant the access to the files with an ACL (setfacl) or a group",
+    ),
+    (
+        "CAP_DAC_READ_SEARCH",
+        Risk::High,
+        "grant the read access to the files with an ACL (setfacl) or a group",
+    ),
+    (
+        "CAP_SYS_MODULE",
+        Risk::High,
+        "load the modules at boot with modules-load.d",
+    ),
+    (
+        "CAP_SYS_RAWIO",
+        Risk::High,
+        "grant access to the device node with udev rules",
+    ),
+    (
+        "CAP_SETUID",
+        Risk::High,
+        "start the command as the target user instead of switching",
+    ),
+    (
+        "CAP_SETGID",
+        Risk::High,
+        "start the command with the target groups instead of switching",
+    ),
+    (
+        "CAP_SETFCAP",
+        Risk::High,
+        "set the file capabilities at install time",
+    ),
+    (
+        "CAP_SETPCAP",
+        Risk::High,
+        "set the capability bounds in the unit file",
+    ),
+    (
+        "CAP_FOWNER",
+        Risk::High,
+        "own the files with the service account",
+    ),
+    (
+        "CAP_CHOWN",
+        Risk::High,
+        "create the files with their owner at boot with systemd-tmpfiles",
+    ),
+    (
+        "CAP_BPF",
+        Risk::High,
+        "load the eBPF programs from a privileged helper",
+    ),
+    ("CAP_MAC_ADMIN", Risk::High, "load the MAC policy at boot"),
+    (
+        "CAP_MAC_OVERRIDE",
+        Risk::High,
+        "adjust the MAC policy of the command",
+    ),
+    (
+        "CAP_NET_ADMIN",
+        Risk::Medium,
+        "configure the network with systemd-networkd or a privileged helper",
+    ),
+    (
+        "CAP_NET_RAW",
+        Risk::Medium,
+        "use unprivileged ICMP sockets (SOCK_DGRAM) for ping",
+    ),
+    (
+        "CAP_SYS_TIME",
+        Risk::Medium,
+        "let systemd-timesyncd or chrony set the clock",
+    ),
+    (
+        "CAP_SYS_BOOT",
+        Risk::Medium,
+        "request the reboot from systemd with logind",
+    ),
+    (
+        "CAP_SYS_RESOURCE",
+        Risk::Medium,
+        "raise the limits in the unit file",
+    ),
+    (
+        "CAP_KILL",
+        Risk::Medium,
+        "run the signalled processes as the same user",
+    ),
+    (
+        "CAP_PERFMON",
+        Risk::Medium,
+        "lower kernel.perf_event_paranoid for the profiling session",
+    ),
+    (
+        "CAP_AUDIT_CONTROL",
+        Risk::Medium,
+        "load the audit rules at boot",
+    ),
+    (
+        "CAP_SYSLOG",
+        Risk::Medium,
+        "grant access to the logs with the systemd-journal group",
+    ),
+    (
+        "CAP_IPC_OWNER",
+        Risk::Medium,
+        "share the IPC objects with a group",
+    ),
+    (
+        "CAP_LINUX_IMMUTABLE",
+        Risk::Medium,
+        "set the attributes at install time",
+    ),
+];
+
+/// How to do without `capability`, when known
+pub fn remediation(capability: &str) -> Option<&'static str> {
+    RISKS
+        .iter()
+        .find(|(cap, _, _)| *cap == capability)
+        .map(|(_, _, remediation)| *remediation)
+}
+
+/// Capabilities that are equivalent to full root on their own or together
+const ROOT_EQUIVALENTS: &[(&[&str], &str)] = &[
+    (
+        &["CAP_SYS_ADMIN"],
+        "mount over system files, load eBPF programs and change namespaces",
+    ),
+    (&["CAP_SYS_MODULE"], "load arbitrary code in the kernel"),
+    (
+        &["CAP_SYS_RAWIO"],
+        "write kernel memory and raw block devices",
+    ),
+    (
+        &["CAP_SYS_PTRACE"],
+        "inject code in any process, including the root ones",
+    ),
+    (
+        &["CAP_DAC_OVERRIDE"],
+        "write any file, such as /etc/shadow or /etc/sudoers",
+    ),
+    (
+        &["CAP_FOWNER"],
+        "change the mode of any file, such as /etc/shadow",
+    ),
+    (&["CAP_CHOWN"], "take the ownership of any file"),
+    (&["CAP_SETUID"], "become uid 0, owner of the system files"),
+    (
+        &["CAP_SETFCAP"],
+        "grant any capability to a program it then executes",
+    ),
+    (
+        &["CAP_SETGID", "CAP_DAC_READ_SEARCH"],
+        "join the shadow group and read password hashes",
+    ),
+    (
+        &["CAP_BPF", "CAP_PERFMON"],
+        "read and write kernel memory with eBPF programs",
+    ),
+];
+
+/// Risk score of the capabilities of a profile, with the combinations equivalent to root
+/// and remediation hints, akin to systemd-analyze security on the observed needs
+pub struct RiskReport {
+    /// Exposure from 0 to 10
+    score: f64,
+    capabilities: Vec<(String, Risk, Option<&'static str>)>,
+    root_equivalents: Vec<(String, &'static str)>,
+}
+
+impl RiskReport {
+    pub fn new(result: &ProgramResult) -> RiskReport {
+        let mut capabilities: Vec<(String, Risk, Option<&'static str>)> = result
+            .capability_names()
+            .map(|name| {
+                let risk = RISKS
+                    .iter()
+                    .find(|(cap, _, _)| *cap == name)
+                    .map_or(Risk::Low, |(_, risk, _)| *risk);
+                (name.to_string(), risk, remediation(name))
+            })
+            .collect();
+        capabilities.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        // probability that at least one capability leads to a compromise
+        let safe: f64 = capabilities
+            .iter()
+            .map(|(_, risk, _)| 1.0 - risk.weight())
+            .product();
+        let root_equivalents = ROOT_EQUIVALENTS
+            .iter()
+            .filter(|(caps, _)| caps.iter().all(|cap| result.has_capability(cap)))
+            .map(|(caps, reason)| (caps.join(" + "), *reason))
+            .collect();
+        RiskReport {
+            score: 10.0 * (1.0 - safe),
+            capabilities,
+            root_equivalents,
+        }
+    }
+
+    /// Exposure level, with the names of systemd-analyze security
+    fn level(&self) -> &'static str {
+        match self.score {
+            s if s >= 7.0 => "UNSAFE",
+            s if s >= 4.0 => "EXPOSED",
+            s if s >= 1.0 => "MEDIUM",
+            _ => "OK",
+        }
+    }
+}
+
+impl Display for RiskReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Overall exposure: {:.1} {}", self.score, self.level())?;
+        for (name, risk, hint) in &self.capabilities {
+            match hint {
+                Some(hint) => writeln!(f, "  {:<6} {}: {}", risk, name, hint)?,
+                None => writeln!(f, "  {:<6} {}", risk, name)?,
+            }
+        }
+        if !self.root_equivalents.is_empty() {
+            writeln!(f, "Equivalent to full root:")?;
+            for (capabilities, reason) in &self.root_equivalents {
+                writeln!(f, "  {}: {}", capabilities, reason)?;
+            }
+        }
+        Ok(())
+    }
+}
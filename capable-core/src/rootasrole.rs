@@ -0,0 +1,68 @@
+use std::process::Command;
+
+use anyhow::Context;
+use tracing::{debug, warn};
+
+use crate::memlock::{limit_replacing, MEMLOCK_CAPABILITY};
+use crate::profile::ProgramResult;
+
+/// Add `task` to `role` in the RootAsRole policy, allowing the commands with the capabilities
+/// of the profile. chsr is in charge of the policy file, including its locking and immutability.
+pub fn install_role(
+    role_task: &str,
+    commands: &[Vec<String>],
+    result: &ProgramResult,
+) -> Result<(), anyhow::Error> {
+    let (role, task) = role_task
+        .split_once('/')
+        .filter(|(role, task)| !role.is_empty() && !task.is_empty())
+        .ok_or(anyhow::anyhow!("--install-role expects <role>/<task>, got {}", role_task))?;
+    let chsr = which::which("chsr").context("chsr not found in $PATH, is RootAsRole installed?")?;
+    let run = |args: &[&str]| -> Result<bool, anyhow::Error> {
+        debug!("chsr {}", shell_words::join(args));
+        let status = Command::new(&chsr)
+            .args(args)
+            .status()
+            .context("Unable to execute chsr")?;
+        Ok(status.success())
+    };
+
+    // the role or the task may already exist, only the following steps must succeed
+    if !run(&["role", role, "add"])? {
+        warn!("Unable to add role {}, assuming it exists", role);
+    }
+    if !run(&["role", role, "task", task, "add"])? {
+        warn!("Unable to add task {} to role {}, assuming it exists", task, role);
+    }
+    let cmdlines: Vec<String> = commands.iter().map(shell_words::join).collect();
+    for cmdline in &cmdlines {
+        if !run(&["role", role, "task", task, "cmd", "whitelist", "add", cmdline])? {
+            return Err(anyhow::anyhow!("chsr failed to allow command {}", cmdline));
+        }
+    }
+    if !result.capabilities.is_empty() {
+        let caps = result.capability_names().collect::<Vec<_>>().join(",");
+        if !run(&["role", role, "task", task, "cred", "set", "--caps", &caps])? {
+            return Err(anyhow::anyhow!("chsr failed to set capabilities {}", caps));
+        }
+    }
+    // RootAsRole sets no resource limits, the ones of PAM do
+    if let Some(memlock) = limit_replacing(&result.capabilities, result.memlock.as_ref()) {
+        let limit = if memlock.all {
+            String::from("unlimited")
+        } else {
+            memlock.requested.div_ceil(1024).to_string()
+        };
+        eprintln!(
+            "{} is only needed to lock memory, setting memlock to {} in /etc/security/limits.conf for the users of role {} allows removing it",
+            MEMLOCK_CAPABILITY, limit, role
+        );
+    }
+    eprintln!(
+        "Installed task {} in role {} for {}",
+        task,
+        role,
+        cmdlines.join(", ")
+    );
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! snapd interfaces a strictly confined snap would plug to do what a profile observed.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use crate::profile::ProgramResult;
+use crate::syscalls::Access;
+
+/// Files the base snap, the snap itself or the default confinement provide
+const PROVIDED: [&str; 18] = [
+    "/usr/",
+    "/lib/",
+    "/lib32/",
+    "/lib64/",
+    "/bin/",
+    "/sbin/",
+    "/snap/",
+    "/var/snap/",
+    "/tmp/",
+    "/proc/self/",
+    "/proc/thread-self/",
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+    "/dev/pts/",
+];
+
+/// Files of /etc readable by every snap
+const ETC_PROVIDED: [&str; 8] = [
+    "/etc/ld.so.",
+    "/etc/resolv.conf",
+    "/etc/hosts",
+    "/etc/localtime",
+    "/etc/passwd",
+    "/etc/group",
+    "/etc/nsswitch.conf",
+    "/etc/ssl/",
+];
+
+/// Interfaces allowing capabilities
+const CAPABILITY_INTERFACES: [(&str, &str); 12] = [
+    ("CAP_NET_BIND_SERVICE", "network-bind"),
+    ("CAP_NET_ADMIN", "network-control"),
+    ("CAP_NET_RAW", "network-control"),
+    ("CAP_NET_BROADCAST", "network-control"),
+    ("CAP_KILL", "process-control"),
+    ("CAP_SYS_NICE", "process-control"),
+    ("CAP_SYS_RESOURCE", "process-control"),
+    ("CAP_SYS_PTRACE", "system-observe"),
+    ("CAP_SYS_MODULE", "kernel-module-control"),
+    ("CAP_SYS_TIME", "time-control"),
+    ("CAP_SYS_BOOT", "shutdown"),
+    ("CAP_BPF", "system-trace"),
+];
+
+/// Interfaces allowing files, by a prefix of their path. `true` when the interface also
+/// allows writing.
+const FILE_INTERFACES: [(&str, &str, bool); 21] = [
+    ("/tmp/.X11-unix/", "x11", true),
+    ("/dev/dri/", "opengl", true),
+    ("/dev/snd/", "alsa", true),
+    ("/dev/kvm", "kvm", true),
+    ("/dev/ttyS", "serial-port", true),
+    ("/dev/ttyUSB", "serial-port", true),
+    ("/dev/ttyACM", "serial-port", true),
+    ("/dev/bus/usb/", "raw-usb", true),
+    ("/dev/input/", "joystick", true),
+    ("/dev/video", "camera", true),
+    ("/run/udev/", "hardware-observe", false),
+    ("/sys/class/net/", "network-observe", false),
+    ("/sys/", "hardware-observe", false),
+    ("/proc/sys/net/", "network-control", true),
+    ("/proc/", "system-observe", false),
+    ("/var/log/", "log-observe", false),
+    ("/run/log/journal/", "log-observe", false),
+    ("/etc/hostname", "hostname-control", true),
+    ("/etc/timezone", "timezone-control", true),
+    ("/media/", "removable-media", true),
+    ("/mnt/", "removable-media", true),
+];
+
+/// Interfaces allowing the sockets of the session, in /run/user/UID/
+const SESSION_INTERFACES: [(&str, &str); 3] = [
+    ("wayland-", "wayland"),
+    ("pulse/", "audio-playback"),
+    ("pipewire-", "audio-playback"),
+];
+
+/// Interfaces allowing the services of the system bus
+const DBUS_INTERFACES: [(&str, &str); 11] = [
+    ("org.freedesktop.NetworkManager", "network-manager"),
+    ("org.freedesktop.ModemManager1", "modem-manager"),
+    ("org.freedesktop.login1", "login-session-observe"),
+    ("org.freedesktop.hostname1", "hostname-control"),
+    ("org.freedesktop.timedate1", "timeserver-control"),
+    ("org.freedesktop.UPower", "upower-observe"),
+    ("org.freedesktop.UDisks2", "udisks2"),
+    ("org.freedesktop.Avahi", "avahi-observe"),
+    ("org.freedesktop.PolicyKit1", "polkit"),
+    ("org.freedesktop.systemd1", "system-observe"),
+    ("org.bluez", "bluez"),
+];
+
+/// Interfaces a snap would plug, each with the first finding requiring it, and the
+/// findings no interface allows
+pub struct SnapInterfaces {
+    pub plugs: BTreeMap<String, String>,
+    /// Findings outside the available interfaces
+    pub outside: Vec<String>,
+}
+
+impl SnapInterfaces {
+    pub fn new(result: &ProgramResult) -> SnapInterfaces {
+        let mut plugs = BTreeMap::new();
+        let mut outside = Vec::new();
+        let mut plug = |interface: &str, reason: String| {
+            plugs.entry(interface.to_string()).or_insert(reason);
+        };
+        if result.network {
+            plug("network", "internet sockets".to_string());
+        }
+        for name in result.capability_names() {
+            match CAPABILITY_INTERFACES.iter().find(|(cap, _)| *cap == name) {
+                Some((_, interface)) => plug(interface, name.to_string()),
+                None => outside.push(name.to_string()),
+            }
+        }
+        for (path, access) in result.all_files() {
+            let writable = access.contains(Access::W);
+            if PROVIDED.iter().any(|prefix| path.starts_with(prefix))
+                || (!writable && ETC_PROVIDED.iter().any(|prefix| path.starts_with(prefix)))
+            {
+                continue;
+            }
+            if path.starts_with("/home/") || path.starts_with("/root/") {
+                // home only allows the files that are not hidden
+                let hidden = path.split('/').any(|component| component.starts_with('.'));
+                if hidden {
+                    plug("personal-files", path.clone());
+                } else {
+                    plug("home", path.clone());
+                }
+                continue;
+            }
+            let session = path
+                .strip_prefix("/run/user/")
+                .and_then(|rest| rest.split_once('/'))
+                .map(|(_, rest)| rest);
+            if let Some(rest) = session {
+                match SESSION_INTERFACES
+                    .iter()
+                    .find(|(prefix, _)| rest.starts_with(prefix))
+                {
+                    Some((_, interface)) => plug(interface, path.clone()),
+                    None => outside.push(format!("{} ({})", path, access)),
+                }
+                continue;
+            }
+            match FILE_INTERFACES.iter().find(|(prefix, _, allows_write)| {
+                path.starts_with(prefix) && (*allows_write || !writable)
+            }) {
+                Some((_, interface, _)) => plug(interface, path.clone()),
+                None if path.starts_with("/etc/") && !writable => {
+                    plug("system-files", path.clone())
+                }
+                None => outside.push(format!("{} ({})", path, access)),
+            }
+        }
+        for destination in &result.destinations {
+            match DBUS_INTERFACES
+                .iter()
+                .find(|(name, _)| destination.starts_with(name))
+            {
+                Some((_, interface)) => plug(interface, destination.clone()),
+                None => outside.push(format!("D-Bus {}", destination)),
+            }
+        }
+        SnapInterfaces { plugs, outside }
+    }
+}
+
+impl Display for SnapInterfaces {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "plugs:")?;
+        for (interface, reason) in &self.plugs {
+            writeln!(f, "  - {} # {}", interface, reason)?;
+        }
+        if !self.outside.is_empty() {
+            writeln!(f, "# outside the available interfaces:")?;
+            for finding in &self.outside {
+                writeln!(f, "#   {}", finding)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! Sockets the command listened on, from its bind and listen syscalls, and the control
+//! sockets of privileged services it connected to.
+
+use std::collections::BTreeMap;
+use std::fs::metadata;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+use nix::unistd::{Gid, Group};
+
+use crate::profile::{ControlSocket, ListeningSocket};
+use crate::strace::{Parameter, Syscall};
+
+/// Socket bound by the command, listening once it is a datagram one or listen() is called
+struct Bound {
+    socket: ListeningSocket,
+    listening: bool,
+}
+
+/// Text between the quotes of a decoded address, e.g. inet_addr("127.0.0.1")
+fn quoted(value: &str) -> Option<&str> {
+    let (_, rest) = value.split_once('"')?;
+    rest.split_once('"').map(|(address, _)| address)
+}
+
+/// File descriptor of the socket, first argument of bind and listen
+fn socket_fd(syscall: &Syscall) -> Option<i32> {
+    match syscall.args.first() {
+        Some(Parameter::Constant(fd)) => fd.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Sockets listening at some point of the run, in the order they started listening
+pub fn listening_sockets(syscalls: &[Syscall]) -> Vec<ListeningSocket> {
+    let mut kinds: BTreeMap<i32, String> = BTreeMap::new();
+    let mut bound: BTreeMap<i32, Bound> = BTreeMap::new();
+    let mut sockets = Vec::new();
+    for syscall in syscalls {
+        match syscall.syscall.trim() {
+            "socket" if syscall.return_code.code >= 0 => {
+                if let Some(Parameter::Constant(kind)) = syscall.args.get(1) {
+                    let kind = kind.split('|').next().unwrap_or_default();
+                    kinds.insert(syscall.return_code.code, kind.to_string());
+                }
+            }
+            "bind" if syscall.return_code.code == 0 => {
+                let (Some(fd), Some(Parameter::Dict(address))) =
+                    (socket_fd(syscall), syscall.args.get(1))
+                else {
+                    continue;
+                };
+                let (port, ipv6) = match (address.get("sin_port"), address.get("sin6_port")) {
+                    (Some(port), _) => (port, false),
+                    (None, Some(port)) => (port, true),
+                    (None, None) => continue,
+                };
+                let Some(port) = port
+                    .strip_prefix("htons(")
+                    .and_then(|port| port.strip_suffix(')')?.parse().ok())
+                else {
+                    continue;
+                };
+                let datagram = kinds.get(&fd).is_some_and(|kind| kind == "SOCK_DGRAM");
+                let protocol = match (datagram, ipv6) {
+                    (false, false) => "tcp",
+                    (false, true) => "tcp6",
+                    (true, false) => "udp",
+                    (true, true) => "udp6",
+                };
+                let socket = ListeningSocket {
+                    protocol: protocol.to_string(),
+                    address: address
+                        .get("sin_addr")
+                        .and_then(|addr| quoted(addr))
+                        .map(String::from),
+                    port,
+                };
+                if datagram && !sockets.contains(&socket) {
+                    sockets.push(socket.clone());
+                }
+                bound.insert(
+                    fd,
+                    Bound {
+                        socket,
+                        listening: datagram,
+                    },
+                );
+            }
+            "listen" if syscall.return_code.code == 0 => {
+                if let Some(bound) = socket_fd(syscall).and_then(|fd| bound.get_mut(&fd)) {
+                    if !bound.listening && !sockets.contains(&bound.socket) {
+                        sockets.push(bound.socket.clone());
+                    }
+                    bound.listening = true;
+                }
+            }
+            _ => (),
+        }
+    }
+    sockets
+}
+
+/// Well-known control sockets, whose clients can make the service act with its privileges
+const CONTROL_SOCKETS: [(&str, &str); 11] = [
+    ("/run/docker.sock", "docker"),
+    ("/run/containerd/containerd.sock", "containerd"),
+    ("/run/podman/podman.sock", "podman"),
+    ("/run/crio/crio.sock", "cri-o"),
+    ("/run/systemd/private", "systemd"),
+    ("/run/libvirt/libvirt-sock", "libvirt"),
+    ("/run/libvirt/libvirt-sock-ro", "libvirt"),
+    ("/run/libvirt/virtqemud-sock", "libvirt"),
+    ("/run/snapd.socket", "snapd"),
+    ("/var/snap/lxd/common/lxd/unix.socket", "lxd"),
+    ("/var/lib/incus/unix.socket", "incus"),
+];
+
+/// Group owning the socket, and whether its members may connect (write permission)
+fn socket_group(path: &str) -> (Option<String>, bool) {
+    let Ok(metadata) = metadata(path) else {
+        return (None, false);
+    };
+    let group = Group::from_gid(Gid::from_raw(metadata.gid()))
+        .ok()
+        .flatten()
+        .map_or(metadata.gid().to_string(), |group| group.name);
+    (Some(group), metadata.permissions().mode() & 0o020 != 0)
+}
+
+/// Control sockets the command connected to, or tried to, in the order of the run
+pub fn control_sockets(syscalls: &[Syscall]) -> Vec<ControlSocket> {
+    let mut sockets: Vec<ControlSocket> = Vec::new();
+    for syscall in syscalls {
+        if syscall.syscall.trim() != "connect" {
+            continue;
+        }
+        let Some(Parameter::Dict(address)) = syscall.args.get(1) else {
+            continue;
+        };
+        // abstract sockets (@"...") have no file
+        let Some(path) = address
+            .get("sun_path")
+            .and_then(|path| path.strip_prefix('"'))
+            .and_then(|path| path.strip_suffix('"'))
+        else {
+            continue;
+        };
+        // /var/run is a link to /run
+        let path = path
+            .strip_prefix("/var")
+            .filter(|path| path.starts_with("/run/"))
+            .unwrap_or(path);
+        let Some((path, service)) = CONTROL_SOCKETS.iter().find(|(socket, _)| *socket == path)
+        else {
+            continue;
+        };
+        if sockets.iter().any(|socket| socket.path == *path) {
+            continue;
+        }
+        let (group, group_access) = socket_group(path);
+        sockets.push(ControlSocket {
+            path: path.to_string(),
+            service: service.to_string(),
+            group,
+            group_access,
+        });
+    }
+    sockets
+}
@@ -0,0 +1,412 @@
+use std::{borrow::Cow, collections::HashMap, fmt::Display};
+
+use pest::error::InputLocation;
+use pest::iterators::Pairs;
+use pest::Parser;
+use pest_derive::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::explain::EXPLAINED_SYSCALLS;
+use crate::loader::LOADER_SYSCALLS;
+
+/// Syscalls given to strace -e: the file ones, the ones explaining the findings and the
+/// mappings recognizing the libraries
+pub fn trace_filter() -> String {
+    let mut filter = vec!["ptrace", "file"];
+    filter.extend(EXPLAINED_SYSCALLS);
+    filter.extend(LOADER_SYSCALLS);
+    filter.join(",")
+}
+
+/// Minimal number of lines parsed by a single worker
+const CHUNK_LINES: usize = 10_000;
+
+/// Marks a syscall interrupted by the one of another process, it ends after RESUMED
+const UNFINISHED: &str = "<unfinished ...>";
+const RESUMED: &str = "resumed>";
+
+/// Syscalls of the 32-bit processes, with the name of their 64-bit equivalent. strace names
+/// the syscalls after the personality of the process, the analysis knows the 64-bit ones.
+const COMPAT_SYSCALLS: [(&str, &str); 27] = [
+    ("_llseek", "lseek"),
+    ("_newselect", "select"),
+    ("fcntl64", "fcntl"),
+    ("mmap2", "mmap"),
+    ("stat64", "stat"),
+    ("lstat64", "lstat"),
+    ("fstat64", "fstat"),
+    ("fstatat64", "newfstatat"),
+    ("statfs64", "statfs"),
+    ("fstatfs64", "fstatfs"),
+    ("truncate64", "truncate"),
+    ("ftruncate64", "ftruncate"),
+    ("sendfile64", "sendfile"),
+    ("ugetrlimit", "getrlimit"),
+    ("chown32", "chown"),
+    ("lchown32", "lchown"),
+    ("fchown32", "fchown"),
+    ("setuid32", "setuid"),
+    ("setgid32", "setgid"),
+    ("setreuid32", "setreuid"),
+    ("setregid32", "setregid"),
+    ("setresuid32", "setresuid"),
+    ("setresgid32", "setresgid"),
+    ("setgroups32", "setgroups"),
+    ("setfsuid32", "setfsuid"),
+    ("setfsgid32", "setfsgid"),
+    ("utimensat_time64", "utimensat"),
+];
+
+/// Calls multiplexed by socketcall on 32-bit x86, by their number in linux/net.h from 1
+const SOCKETCALL_SUBCALLS: [&str; 20] = [
+    "socket",
+    "bind",
+    "connect",
+    "listen",
+    "accept",
+    "getsockname",
+    "getpeername",
+    "socketpair",
+    "send",
+    "recv",
+    "sendto",
+    "recvfrom",
+    "shutdown",
+    "setsockopt",
+    "getsockopt",
+    "sendmsg",
+    "recvmsg",
+    "accept4",
+    "recvmmsg",
+    "sendmmsg",
+];
+
+#[derive(Serialize)]
+pub struct Syscall {
+    pub syscall: String,
+    pub args: Vec<Parameter>,
+    pub return_code: ReturnCode,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum Parameter {
+    String(String),
+    Array(Vec<String>),
+    Constant(String),
+    Comment(String),
+    Dict(HashMap<String, String>),
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Parameter::String(s) => write!(f, "{}", s),
+            Parameter::Array(a) => write!(f, "{:?}", a),
+            Parameter::Constant(c) => write!(f, "{}", c),
+            Parameter::Comment(c) => write!(f, "{}", c),
+            Parameter::Dict(d) => write!(f, "{:?}", d),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReturnCode {
+    pub code: i32,
+    pub constant: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Parser)]
+#[grammar = "strace.pest"]
+struct StraceParser;
+
+pub fn parse_strace(content: &str) -> Vec<Syscall> {
+    parse_strace_counted(content).0
+}
+
+/// Syscalls of the strace log, with the number of lines the parser rejected and skipped
+pub fn parse_strace_counted(content: &str) -> (Vec<Syscall>, usize) {
+    let content = join_resumed(content);
+    let chunks = split_chunks(&content, CHUNK_LINES);
+    debug!("Parsing strace file in {} chunks", chunks.len());
+    // collecting a parallel iterator keeps the chunks order, so syscalls stay in log order
+    let parsed: Vec<(Vec<Syscall>, usize)> =
+        chunks.par_iter().map(|chunk| parse_chunk(chunk)).collect();
+    let failed = parsed.iter().map(|(_, failed)| failed).sum();
+    let syscalls = parsed.into_iter().flat_map(|(syscalls, _)| syscalls);
+    (syscalls.collect(), failed)
+}
+
+/// Join the syscalls a process left unfinished with their resumed part, in place of the
+/// resumed line, so the lines of the other processes in between parse on their own. The
+/// syscalls never resumed are left unfinished at the end of the log.
+fn join_resumed(content: &str) -> Cow<'_, str> {
+    if !content.contains(UNFINISHED) {
+        return Cow::Borrowed(content);
+    }
+    let mut joined = String::with_capacity(content.len());
+    let mut unfinished: HashMap<&str, String> = HashMap::new();
+    for line in content.split_inclusive('\n') {
+        let pid = line
+            .split_whitespace()
+            .next()
+            .filter(|word| word.bytes().all(|byte| byte.is_ascii_digit()))
+            .unwrap_or_default();
+        let mut line = match line.split_once(RESUMED) {
+            Some((_, rest)) if line.contains("<... ") && unfinished.contains_key(pid) => {
+                unfinished.remove(pid).unwrap_or_default() + rest
+            }
+            _ => line.to_string(),
+        };
+        if let Some(start) = line.trim_end().strip_suffix(UNFINISHED) {
+            line = start.to_string();
+            unfinished.insert(pid, line);
+            continue;
+        }
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        joined.push_str(&line);
+    }
+    for start in unfinished.into_values() {
+        joined.push_str(&start);
+        joined.push_str(UNFINISHED);
+        joined.push('\n');
+    }
+    Cow::Owned(joined)
+}
+
+/// Split the log on line boundaries into chunks of at least `min_lines` lines.
+/// A chunk never ends while a syscall is unfinished, as its resumed part must be parsed with it.
+fn split_chunks(content: &str, min_lines: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    let mut lines = 0;
+    let mut unfinished = 0usize;
+    for line in content.split_inclusive('\n') {
+        end += line.len();
+        lines += 1;
+        if line.contains(UNFINISHED) {
+            unfinished += 1;
+        }
+        if line.contains(RESUMED) {
+            unfinished = unfinished.saturating_sub(1);
+        }
+        if lines >= min_lines && unfinished == 0 {
+            chunks.push(&content[start..end]);
+            start = end;
+            lines = 0;
+        }
+    }
+    if start < content.len() {
+        chunks.push(&content[start..]);
+    }
+    chunks
+}
+
+/// Name of the 64-bit equivalent of a syscall of a 32-bit process
+fn native_name(name: &str) -> &str {
+    COMPAT_SYSCALLS
+        .iter()
+        .find(|(compat, _)| *compat == name)
+        .map_or(name, |(_, native)| native)
+}
+
+/// Rewrite socketcall(SYS_SOCKET, [AF_INET, SOCK_STREAM, IPPROTO_IP]) = 3 as the
+/// socket(AF_INET, SOCK_STREAM, IPPROTO_IP) = 3 of the other architectures. The subcall is
+/// named or numbered, as strace prints it when it does not decode it.
+fn demultiplex_socketcall(line: &str) -> Option<String> {
+    let (prefix, rest) = line.split_once("socketcall(")?;
+    let (call, rest) = rest.split_once(", [")?;
+    let (args, return_code) = rest.rsplit_once("]) =")?;
+    let call = call.split_whitespace().next()?;
+    let subcall = match call.strip_prefix("SYS_") {
+        Some(name) => name.to_lowercase(),
+        None => {
+            let number = match call.strip_prefix("0x") {
+                Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+                None => call.parse().ok()?,
+            };
+            SOCKETCALL_SUBCALLS.get(number.checked_sub(1)?)?.to_string()
+        }
+    };
+    Some(format!("{}{}({}) ={}", prefix, subcall, args, return_code))
+}
+
+/// Parse the lines of `chunk`, skipping the ones the parser rejects. Returns the syscalls
+/// and the number of lines skipped.
+fn parse_chunk(chunk: &str) -> (Vec<Syscall>, usize) {
+    let mut syscalls = Vec::new();
+    let mut failed = 0;
+    let mut rest = chunk;
+    while !rest.is_empty() {
+        let error = match StraceParser::parse(Rule::file, rest) {
+            Ok(pairs) => {
+                failed += parse_pairs(pairs, &mut syscalls);
+                break;
+            }
+            Err(e) => e,
+        };
+        // the lines before the one rejected parse on their own, unless a syscall
+        // unfinished before it is never resumed
+        let end_of_log = rest.trim_end().len();
+        let mut position = match error.location {
+            InputLocation::Pos(position) => position,
+            InputLocation::Span((start, _)) => start,
+        }
+        .min(end_of_log);
+        // rejected at the end, the last unfinished syscall was never resumed
+        if position == end_of_log {
+            if let Some(unfinished) = rest
+                .rfind(UNFINISHED)
+                .filter(|unfinished| !rest[*unfinished..].contains(RESUMED))
+            {
+                position = unfinished;
+            }
+        }
+        let start = rest[..position].rfind('\n').map_or(0, |index| index + 1);
+        let end = rest[position..]
+            .find('\n')
+            .map_or(rest.len(), |index| position + index + 1);
+        let line = rest[start..end].trim();
+        warn!("Unable to parse the strace line {}", line);
+        let (before, skipped) = parse_chunk(&rest[..start]);
+        syscalls.extend(before);
+        failed += skipped + 1;
+        rest = &rest[end..];
+    }
+    (syscalls, failed)
+}
+
+/// Add the syscalls of `pairs`, returns the number of socketcalls that failed to parse
+fn parse_pairs(pairs: Pairs<'_, Rule>, syscalls: &mut Vec<Syscall>) -> usize {
+    let mut failed = 0;
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::syscall_call => match demultiplex_socketcall(pair.as_str()) {
+                Some(line) => match StraceParser::parse(Rule::syscall_call, &line) {
+                    Ok(mut pairs) => {
+                        if let Some(pair) = pairs.next() {
+                            parse_syscall(pair, syscalls);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Unable to parse the socketcall {}: {}", line.trim(), e);
+                        failed += 1;
+                    }
+                },
+                None => parse_syscall(pair, syscalls),
+            },
+            Rule::personality => debug!("{}", pair.as_str().trim()),
+            Rule::EOI | Rule::exit => (),
+            _ => warn!("Unexpected rule: {:?}", pair.as_rule()),
+        }
+    }
+    failed
+}
+
+fn parse_syscall(pair: pest::iterators::Pair<'_, Rule>, syscalls: &mut Vec<Syscall>) {
+    let mut syscall = Syscall {
+        syscall: String::new(),
+        args: Vec::new(),
+        return_code: ReturnCode {
+            code: 0,
+            constant: None,
+            message: None,
+        },
+    };
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::syscall => {
+                if !syscall.syscall.is_empty() {
+                    syscalls.push(syscall);
+                    syscall = Syscall {
+                        syscall: String::new(),
+                        args: Vec::new(),
+                        return_code: ReturnCode {
+                            code: 0,
+                            constant: None,
+                            message: None,
+                        },
+                    };
+                }
+                syscall.syscall = native_name(pair.as_str()).to_string();
+                warn!("Syscall: {:?}", syscall.syscall);
+            },
+            Rule::array => {
+                syscall.args.push(Parameter::Array(
+                    pair.into_inner().map(|x| x.as_str().to_string()).collect(),
+                ));
+            }
+            Rule::string => {
+                syscall
+                    .args
+                    .push(Parameter::String(pair.as_str().to_string()));
+            }
+            // a macro strace decodes, e.g. htons(ETH_P_ALL), kept as written
+            Rule::constant | Rule::call => {
+                syscall
+                    .args
+                    .push(Parameter::Constant(pair.as_str().to_string()));
+            }
+            Rule::comment => {
+                syscall
+                    .args
+                    .push(Parameter::Comment(pair.as_str().to_string()));
+            }
+            Rule::structure => {
+                let mut map = HashMap::new();
+                let mut inner = pair.into_inner();
+                while let Some(inner_pair) = inner.next() {
+                    match inner_pair.as_rule() {
+                        Rule::key => {
+                            let key = inner_pair.as_str().to_string();
+                            let value = inner.next().expect("Unable to get value from key=value structure strace").as_str().to_string();
+                            map.insert(key, value);
+                        }
+                        // a field decoded without its name, e.g. inet_pton(AF_INET6, "::", &sin6_addr)
+                        Rule::call => (),
+                        _ => {
+                            warn!("Unexpected rule: {:?}", inner_pair.as_rule());
+                        }
+                    }
+                }
+                syscall.args.push(Parameter::Dict(map));
+            }
+            Rule::return_code => {
+                for inner_pair in pair.into_inner() {
+                    match inner_pair.as_rule() {
+                        // ? when the result is unknown, the code stays 0
+                        Rule::return_value if inner_pair.as_str() == "?" => (),
+                        Rule::return_value => {
+                            syscall.return_code.code = inner_pair.as_str().trim().parse().expect("Unable to parse return code");
+                        }
+                        Rule::constant => {
+                            syscall.return_code.constant = Some(inner_pair.as_str().to_string())
+                        }
+                        Rule::message => {
+                            syscall.return_code.message = Some(inner_pair.as_str().to_string())
+                        }
+                        _ => {
+                            warn!("Unexpected rule: {:?}", inner_pair.as_rule());
+                        }
+                    }
+                }
+            },
+            Rule::signal => {
+                warn!("Signal: {:?}", pair.as_str());
+            },
+            Rule::pid => {
+                warn!("namespaced PID: {:?}", pair.as_str());
+            },
+            _ => {
+                warn!("Unexpected rule: {:?}", pair.as_rule());
+            }
+        }
+    }
+    syscalls.push(syscall);
+}
@@ -0,0 +1,345 @@
+//! Least-privilege alternatives to the capability findings.
+//!
+//! Most capabilities are requested for a handful of operations that have an unprivileged
+//! way, the suggestions give it with the setting implementing it when the run tells it.
+
+use std::collections::BTreeSet;
+use std::fs::read_to_string;
+
+use crate::explain::{checked_during, io_uring_sq_affinity, io_uring_sqpoll, yama_ptrace_scope};
+use crate::memlock::MEMLOCK_CAPABILITY;
+use crate::profile::{
+    CapabilityFinding, ControlSocket, ListeningSocket, MemoryLock, OwnershipChange, Suggestion,
+};
+use crate::risk::remediation;
+use crate::strace::{Parameter, Syscall};
+
+fn suggestion(capability: &str, alternative: &str, setting: Option<String>) -> Suggestion {
+    Suggestion {
+        capability: capability.to_string(),
+        alternative: alternative.to_string(),
+        setting,
+    }
+}
+
+/// The remediation of the risk report for `capability`, which has one
+fn remediate(capability: &str, setting: Option<String>) -> Suggestion {
+    let alternative = remediation(capability).expect("capability without remediation");
+    suggestion(capability, alternative, setting)
+}
+
+/// Address of a socket unit listening like `socket`, the port alone on any address
+fn listen_address(socket: &ListeningSocket) -> String {
+    match socket.address.as_deref() {
+        Some(address) if address != "0.0.0.0" => format!("{}:{}", address, socket.port),
+        _ => socket.port.to_string(),
+    }
+}
+
+fn bind_service(listening: &[ListeningSocket], suggestions: &mut Vec<Suggestion>) {
+    let cap = "CAP_NET_BIND_SERVICE";
+    let activation = "socket activation, systemd binds the port and passes the socket";
+    let privileged: Vec<&ListeningSocket> = listening
+        .iter()
+        .filter(|socket| socket.is_privileged())
+        .collect();
+    if privileged.is_empty() {
+        suggestions.push(suggestion(cap, activation, None));
+        return;
+    }
+    for socket in &privileged {
+        let directive = if socket.protocol.starts_with("udp") {
+            "ListenDatagram"
+        } else {
+            "ListenStream"
+        };
+        suggestions.push(suggestion(
+            cap,
+            &format!(
+                "socket activation of {}/{}, systemd binds the port and passes the socket",
+                socket.protocol, socket.port
+            ),
+            Some(format!("{}={}", directive, listen_address(socket))),
+        ));
+    }
+    let lowest = privileged
+        .iter()
+        .map(|socket| socket.port)
+        .min()
+        .expect("privileged is not empty");
+    suggestions.push(suggestion(
+        cap,
+        "allow unprivileged binding from the lowest port",
+        Some(format!("net.ipv4.ip_unprivileged_port_start={}", lowest)),
+    ));
+}
+
+/// Directories systemd creates and chowns to the User= of the unit, by prefix
+const MANAGED_DIRECTORIES: [(&str, &str); 4] = [
+    ("/var/lib/", "StateDirectory"),
+    ("/run/", "RuntimeDirectory"),
+    ("/var/cache/", "CacheDirectory"),
+    ("/var/log/", "LogsDirectory"),
+];
+
+/// Files of the system or of the kernel, their directories are never given to a command
+const SYSTEM_PREFIXES: [&str; 4] = ["/usr/", "/dev/", "/proc/", "/sys/"];
+
+/// Whether other programs share `dir`, like /etc, /tmp or /var/lib
+fn shared(dir: &str) -> bool {
+    dir.matches('/').count() < 2
+        || SYSTEM_PREFIXES.iter().any(|prefix| dir.starts_with(prefix))
+        || MANAGED_DIRECTORIES
+            .iter()
+            .any(|(prefix, _)| prefix.trim_end_matches('/') == dir)
+}
+
+/// Directory of a file, the one to own to change the file
+fn directory(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &path[..index],
+    }
+}
+
+/// Owning the directories of the files changed, instead of the capability
+fn ownership(cap: &str, changes: &[OwnershipChange], suggestions: &mut Vec<Suggestion>) {
+    let directories: BTreeSet<&str> = changes
+        .iter()
+        .filter(|change| match cap {
+            "CAP_CHOWN" => change.change.starts_with("chown"),
+            _ => !change.change.starts_with("chown"),
+        })
+        .map(|change| directory(&change.path))
+        .filter(|dir| !shared(dir))
+        .collect();
+    for dir in directories {
+        let managed = MANAGED_DIRECTORIES.iter().find_map(|(prefix, directive)| {
+            let name = dir.strip_prefix(prefix)?;
+            let name = name.split('/').next().filter(|name| !name.is_empty())?;
+            Some(format!("{}={}", directive, name))
+        });
+        let setting = managed.unwrap_or(format!("chown -R USER: {}", dir));
+        suggestions.push(suggestion(
+            cap,
+            &format!(
+                "own {} as the user of the command, changing its files then needs no capability",
+                dir
+            ),
+            Some(setting),
+        ));
+    }
+}
+
+fn mounts(finding: &CapabilityFinding) -> bool {
+    finding
+        .syscalls
+        .iter()
+        .chain(&finding.reason_symbols)
+        .any(|name| {
+            matches!(
+                name.as_str(),
+                "mount" | "fsmount" | "move_mount" | "path_mount"
+            )
+        })
+}
+
+fn ping(syscalls: &[Syscall]) -> bool {
+    syscalls.iter().any(|syscall| {
+        syscall.syscall.trim() == "socket"
+            && matches!(syscall.args.get(2), Some(Parameter::Constant(protocol)) if protocol.starts_with("IPPROTO_ICMP"))
+    })
+}
+
+/// Alternatives to CAP_SYS_PTRACE when Yama restricts ptrace to some tracers, the
+/// capability is not needed to trace a process of the same user the Yama scope allows
+fn yama(cap: &str, suggestions: &mut Vec<Suggestion>) {
+    match yama_ptrace_scope() {
+        Some(1) => {
+            suggestions.push(suggestion(
+                cap,
+                "let the traced process allow the tracer with prctl(PR_SET_PTRACER), or start it from the tracer",
+                None,
+            ));
+            suggestions.push(suggestion(
+                cap,
+                "let any process trace the processes of its user",
+                Some(String::from("kernel.yama.ptrace_scope=0")),
+            ));
+        }
+        Some(2) => suggestions.push(suggestion(
+            cap,
+            "let the ancestors of a process and the tracer it allows with prctl(PR_SET_PTRACER) trace it",
+            Some(String::from("kernel.yama.ptrace_scope=1")),
+        )),
+        _ => (),
+    }
+}
+
+/// Connecting to a Unix socket requires the write permission on it, CAP_DAC_OVERRIDE
+/// passes it. The members of the group of a control socket usually have it.
+fn control_sockets(cap: &str, sockets: &[ControlSocket], suggestions: &mut Vec<Suggestion>) {
+    for socket in sockets {
+        let Some(group) = socket
+            .group
+            .as_ref()
+            .filter(|group| socket.group_access && *group != "root")
+        else {
+            continue;
+        };
+        suggestions.push(suggestion(
+            cap,
+            &format!(
+                "connect to {} ({}) as a member of its group {}",
+                socket.path, socket.service, group
+            ),
+            Some(format!("SupplementaryGroups={}", group)),
+        ));
+    }
+}
+
+/// 1 when io_uring is restricted to CAP_SYS_ADMIN and the members of kernel.io_uring_group
+const IO_URING_DISABLED: &str = "/proc/sys/kernel/io_uring_disabled";
+
+/// Limit on locked memory enough for what the command locked, instead of CAP_IPC_LOCK
+fn memlock(cap: &str, memlock: Option<&MemoryLock>, suggestions: &mut Vec<Suggestion>) {
+    let Some(memlock) = memlock else {
+        suggestions.push(suggestion(
+            cap,
+            "raise the locked memory limit",
+            Some(String::from("LimitMEMLOCK=infinity")),
+        ));
+        return;
+    };
+    let limit = memlock.limit.map_or(String::from("unlimited"), |limit| {
+        format!("{} bytes", limit)
+    });
+    let alternative = if memlock.all {
+        format!(
+            "lift the locked memory limit ({}), the command locked all its memory with mlockall",
+            limit
+        )
+    } else {
+        format!(
+            "raise the locked memory limit ({}) above the {} bytes the command locked",
+            limit, memlock.requested
+        )
+    };
+    suggestions.push(suggestion(
+        cap,
+        &alternative,
+        Some(format!("LimitMEMLOCK={}", memlock.limit_setting())),
+    ));
+}
+
+/// Alternatives to the capabilities io_uring may check, narrower than CAP_SYS_ADMIN
+fn io_uring(finding: &CapabilityFinding, syscalls: &[Syscall], suggestions: &mut Vec<Suggestion>) {
+    let setup = syscalls
+        .iter()
+        .any(|syscall| syscall.syscall.trim() == "io_uring_setup");
+    if !setup || !checked_during(finding, "io_uring_setup") {
+        return;
+    }
+    let cap = finding.name.as_str();
+    let sqpoll = syscalls.iter().any(io_uring_sqpoll);
+    let pinned = syscalls.iter().any(io_uring_sq_affinity);
+    match cap {
+        "CAP_SYS_ADMIN" => {
+            let disabled = read_to_string(IO_URING_DISABLED)
+                .is_ok_and(|disabled| disabled.trim() == "1");
+            if disabled {
+                suggestions.push(suggestion(
+                    cap,
+                    "let the group of the command create io_uring instances",
+                    Some(String::from("kernel.io_uring_group=GID")),
+                ));
+            }
+            if pinned {
+                suggestions.push(suggestion(
+                    cap,
+                    "run on Linux 5.11 or later, where pinning the io_uring polling thread (SQPOLL) to a CPU only needs CAP_SYS_NICE",
+                    Some(String::from("AmbientCapabilities=CAP_SYS_NICE")),
+                ));
+            } else if sqpoll {
+                suggestions.push(suggestion(
+                    cap,
+                    "run on Linux 5.11 or later, where the io_uring polling thread (SQPOLL) needs no capability",
+                    None,
+                ));
+            }
+        }
+        "CAP_SYS_NICE" if pinned => suggestions.push(suggestion(
+            cap,
+            "let the scheduler place the io_uring polling thread (SQPOLL) instead of pinning it to a CPU (SQ_AFF)",
+            None,
+        )),
+        _ => (),
+    }
+}
+
+/// Alternatives to the capabilities of the profile
+pub fn suggest(
+    capabilities: &[CapabilityFinding],
+    syscalls: &[Syscall],
+    listening: &[ListeningSocket],
+    control: &[ControlSocket],
+    ownership_changes: &[OwnershipChange],
+    memory_lock: Option<&MemoryLock>,
+) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+    let names: BTreeSet<&str> = capabilities.iter().map(|cap| cap.name.as_str()).collect();
+    for finding in capabilities {
+        let cap = finding.name.as_str();
+        match cap {
+            "CAP_NET_BIND_SERVICE" => bind_service(listening, &mut suggestions),
+            "CAP_DAC_OVERRIDE" => {
+                suggestions.push(remediate(cap, None));
+                control_sockets(cap, control, &mut suggestions);
+            }
+            "CAP_DAC_READ_SEARCH" => suggestions.push(remediate(cap, None)),
+            "CAP_SYS_ADMIN" => {
+                if mounts(finding) {
+                    suggestions.push(suggestion(
+                        cap,
+                        "mount in a user namespace, e.g. unshare --user --map-root-user --mount",
+                        None,
+                    ));
+                    suggestions.push(suggestion(
+                        cap,
+                        "declare the mounts in the unit file",
+                        Some(String::from("BindPaths=, TemporaryFileSystem=")),
+                    ));
+                }
+                io_uring(finding, syscalls, &mut suggestions);
+            }
+            "CAP_SYS_NICE" => io_uring(finding, syscalls, &mut suggestions),
+            "CAP_NET_RAW" if ping(syscalls) => suggestions.push(remediate(
+                cap,
+                Some(String::from("net.ipv4.ping_group_range=0 2147483647")),
+            )),
+            "CAP_SETUID" => suggestions.push(remediate(cap, Some(String::from("User=")))),
+            "CAP_SETGID" => suggestions.push(remediate(
+                cap,
+                Some(String::from(if names.contains("CAP_SETUID") {
+                    "Group=, SupplementaryGroups="
+                } else {
+                    "SupplementaryGroups="
+                })),
+            )),
+            "CAP_CHOWN" => {
+                suggestions.push(remediate(cap, None));
+                ownership(cap, ownership_changes, &mut suggestions);
+            }
+            "CAP_FOWNER" => ownership(cap, ownership_changes, &mut suggestions),
+            "CAP_IPC_LOCK" => memlock(cap, memory_lock, &mut suggestions),
+            "CAP_SYS_TIME" | "CAP_KILL" => suggestions.push(remediate(cap, None)),
+            "CAP_SYS_PTRACE" => yama(cap, &mut suggestions),
+            _ => (),
+        }
+    }
+    // locking failed without the capability, the command may need more than its limit
+    if memory_lock.is_some() && !names.contains(MEMLOCK_CAPABILITY) {
+        memlock(MEMLOCK_CAPABILITY, memory_lock, &mut suggestions);
+    }
+    suggestions
+}
@@ -4,10 +4,11 @@ use std::{
 
 use bitflags::bitflags;
 use log::warn;
-use serde::Serialize;
 use tracing::debug;
 
-use crate::{dac_read_search_effective, strace::Syscall};
+use crate::{caps::dac_read_search_effective, strace::Syscall};
+
+pub use capable_common::profile::Access;
 
 bitflags! {
     #[derive(PartialEq, Clone)]
@@ -20,9 +21,9 @@ bitflags! {
     }
 }
 
-impl Into<usize> for Pos {
-    fn into(self) -> usize {
-        match self {
+impl From<Pos> for usize {
+    fn from(pos: Pos) -> usize {
+        match pos {
             Pos::One => 0,
             Pos::Two => 1,
             Pos::Three => 2,
@@ -55,50 +56,6 @@ impl Display for Pos {
     }
 }
 
-bitflags! {
-    #[derive(Clone, Copy)]
-    pub struct Access: u8 {
-        const R   = 0b100;
-        const W   = 0b010;
-        const X   = 0b001;
-        const RW  = 0b110;
-        const RX  = 0b101;
-        const WX  = 0b011;
-        const RWX = 0b111;
-    }
-}
-
-impl PartialEq for Access {
-    fn eq(&self, other: &Self) -> bool {
-        self.bits() == other.bits()
-    }
-}
-
-impl std::fmt::Display for Access {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut access = String::new();
-        if self.contains(Access::R) {
-            access.push('R');
-        }
-        if self.contains(Access::W) {
-            access.push('W');
-        }
-        if self.contains(Access::X) {
-            access.push('X');
-        }
-        write!(f, "{}", access)
-    }
-}
-
-impl Serialize for Access {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(self.to_string().as_str())
-    }
-}
-
 pub struct SyscallAccessEntry {
     pub path: String,
     pub access: Access,
@@ -247,7 +204,7 @@ fn check_directories_access<P:AsRef<Path> + Clone>(initial_path: P, syscall: &Sy
     let mut parent = initial_path.as_ref();
     while parent.parent().is_some() {
         parent = parent.parent().expect("No parent found (impossible)");
-        let metadata = match fs::symlink_metadata(&parent) {
+        let metadata = match fs::symlink_metadata(parent) {
             Ok(metadata) => metadata,
             Err(_) => {
                 warn!("Cannot retrieve metadata for path: {}", parent.display());
@@ -285,10 +242,10 @@ pub fn syscall_to_entry(syscall: &Syscall) -> Option<Vec<SyscallAccessEntry>> {
             .clone()
             .into_iter()
             .nth((*pos).clone().into())
-            .expect(&format!("No argument found for syscall {} at position {}", syscall.syscall, pos))
+            .unwrap_or_else(|| panic!("No argument found for syscall {} at position {}", syscall.syscall, pos))
             .to_string();
             let mut create_or_delete = false;
-            let mut access = access.clone();
+            let mut access = *access;
             match *name {
                 "open" | "openat" | "openat2" => {
                     let flags = if syscall.args.len() > 2 {
@@ -324,6 +281,7 @@ pub fn syscall_to_entry(syscall: &Syscall) -> Option<Vec<SyscallAccessEntry>> {
                 continue;
             }
             debug!("{} is requesting {} at {}", name, access, &path);
+            // an unknown result (= ?, the seccomp collector) counts as a success
             if syscall
                 .return_code
                 .constant
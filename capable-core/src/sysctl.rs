@@ -0,0 +1,37 @@
+use std::collections::BTreeMap;
+
+use aya::maps::{HashMap, MapData};
+use capable_common::SysctlRequest;
+
+use crate::profile::SysctlAccess;
+use crate::syscalls::Access;
+
+fn nul_terminated(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// Sysctls accessed by the profiled cgroup, as recorded by the cgroup/sysctl program.
+/// Names use the sysctl dotted notation, e.g. net.ipv4.ip_forward.
+pub fn read_sysctls(
+    requests: &HashMap<MapData, SysctlRequest, u8>,
+) -> BTreeMap<String, SysctlAccess> {
+    let mut sysctls: BTreeMap<String, SysctlAccess> = BTreeMap::new();
+    for request in requests.keys().filter_map(|request| request.ok()) {
+        let name = nul_terminated(&request.name).replace('/', ".");
+        let sysctl = sysctls.entry(name).or_insert(SysctlAccess {
+            access: Access::empty(),
+            values: Vec::new(),
+        });
+        if request.write != 0 {
+            sysctl.access |= Access::W;
+            let value = nul_terminated(&request.value).trim_end().to_string();
+            if !sysctl.values.contains(&value) {
+                sysctl.values.push(value);
+            }
+        } else {
+            sysctl.access |= Access::R;
+        }
+    }
+    sysctls
+}
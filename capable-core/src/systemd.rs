@@ -0,0 +1,137 @@
+//! Sandboxing options of a systemd service allowing what a profile observed, as a drop-in.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+use crate::bwrap::Mounts;
+use crate::memlock::{limit_replacing, MEMLOCK_CAPABILITY};
+use crate::paths::{collapse, home_path, parent};
+use crate::profile::ProgramResult;
+use crate::syscalls::Access;
+
+/// Directories systemd creates and owns for the service, by their setting
+const MANAGED_DIRS: [(&str, &str); 4] = [
+    ("/var/lib/", "StateDirectory"),
+    ("/var/cache/", "CacheDirectory"),
+    ("/var/log/", "LogsDirectory"),
+    ("/run/", "RuntimeDirectory"),
+];
+
+/// Filesystems left to PrivateTmp=, PrivateDevices= and the API filesystems
+const SPECIAL: [&str; 4] = ["/tmp/", "/dev/", "/proc/", "/sys/"];
+
+/// Directories of the system, already read-only under ProtectSystem=strict
+const SYSTEM: [&str; 8] = [
+    "/usr/", "/lib/", "/lib32/", "/lib64/", "/bin/", "/sbin/", "/etc/", "/boot/",
+];
+
+/// [Service] settings of a drop-in
+pub struct ServiceSandbox {
+    pub settings: Vec<(String, String)>,
+}
+
+/// Name below a directory systemd manages, with its setting
+fn managed_dir(path: &str) -> Option<(&'static str, &str)> {
+    MANAGED_DIRS.iter().find_map(|(prefix, setting)| {
+        let rest = path.strip_prefix(prefix)?;
+        let (name, _) = rest.split_once('/')?;
+        Some((*setting, name))
+    })
+}
+
+impl ServiceSandbox {
+    pub fn new(result: &ProgramResult) -> ServiceSandbox {
+        let mut managed: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        let mut grants = BTreeMap::new();
+        let mut home = None;
+        let mut tmp_read = false;
+        for (path, access) in result.all_files() {
+            let writable = access.contains(Access::W);
+            if home_path(path).is_some() {
+                home = Some(home.unwrap_or(false) | writable);
+            }
+            if path.starts_with("/tmp/") && !writable {
+                tmp_read = true;
+            }
+            if SPECIAL.iter().any(|prefix| path.starts_with(prefix)) {
+                continue;
+            }
+            if writable {
+                match managed_dir(path) {
+                    Some((setting, name)) => {
+                        managed.entry(setting).or_default().insert(name);
+                    }
+                    None => {
+                        grants.insert(parent(path).to_string(), true);
+                    }
+                }
+            } else if !SYSTEM.iter().any(|prefix| path.starts_with(prefix)) {
+                grants.entry(parent(path).to_string()).or_insert(false);
+            }
+        }
+
+        let mut settings = Vec::new();
+        let mut set = |name: &str, value: String| settings.push((name.to_string(), value));
+        // a limit on locked memory large enough replaces CAP_IPC_LOCK
+        let memlock = limit_replacing(&result.capabilities, result.memlock.as_ref());
+        let capabilities: Vec<&str> = result
+            .capability_names()
+            .filter(|cap| memlock.is_none() || *cap != MEMLOCK_CAPABILITY)
+            .collect();
+        set("CapabilityBoundingSet", capabilities.join(" "));
+        if !capabilities.is_empty() {
+            set("AmbientCapabilities", capabilities.join(" "));
+        }
+        if let Some(memlock) = memlock {
+            set("LimitMEMLOCK", memlock.limit_setting());
+        }
+        set("ProtectSystem", "strict".to_string());
+        set(
+            "ProtectHome",
+            match home {
+                None => "yes",
+                Some(false) => "read-only",
+                Some(true) => "no",
+            }
+            .to_string(),
+        );
+        // PrivateTmp= hides the files other processes left in /tmp
+        if !tmp_read {
+            set("PrivateTmp", "yes".to_string());
+        }
+        if Mounts::new(result).devices.is_empty() {
+            set("PrivateDevices", "yes".to_string());
+        }
+        if !result.network {
+            set("PrivateNetwork", "yes".to_string());
+        }
+        for (setting, names) in &managed {
+            let names: Vec<&str> = names.iter().copied().collect();
+            set(setting, names.join(" "));
+        }
+        let (read_write, read_only): (Vec<_>, Vec<_>) = collapse(grants)
+            .into_iter()
+            .partition(|(_, writable)| *writable);
+        if !read_write.is_empty() {
+            let paths: Vec<String> = read_write.into_iter().map(|(path, _)| path).collect();
+            set("ReadWritePaths", paths.join(" "));
+        }
+        // read-only already under ProtectSystem=strict, listed to keep them so if it is
+        // relaxed
+        if !read_only.is_empty() {
+            let paths: Vec<String> = read_only.into_iter().map(|(path, _)| path).collect();
+            set("ReadOnlyPaths", paths.join(" "));
+        }
+        ServiceSandbox { settings }
+    }
+}
+
+impl Display for ServiceSandbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[Service]")?;
+        for (name, value) in &self.settings {
+            writeln!(f, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
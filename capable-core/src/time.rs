@@ -0,0 +1,63 @@
+//! Timestamps of the findings.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Current time, in seconds since the Unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Unix time of the boot in nanoseconds, eBPF timestamps are nanoseconds since boot
+pub fn boot_time() -> u64 {
+    let mut realtime: libc::timespec = unsafe { std::mem::zeroed() };
+    let mut monotonic: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_REALTIME, &mut realtime);
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut monotonic);
+    }
+    let nanos = |t: libc::timespec| t.tv_sec as u64 * 1_000_000_000 + t.tv_nsec as u64;
+    nanos(realtime).saturating_sub(nanos(monotonic))
+}
+
+/// Local date and time of a Unix timestamp
+pub fn format_time(timestamp: u64) -> String {
+    let time = timestamp as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let mut buf = [0u8; 32];
+    let len = unsafe {
+        libc::localtime_r(&time, &mut tm);
+        libc::strftime(
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+            c"%Y-%m-%d %H:%M:%S".as_ptr(),
+            &tm,
+        )
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Parse an age such as 90d, 12h or 30m, in seconds without a unit
+pub fn parse_age(s: &str) -> Result<Duration, anyhow::Error> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid age {}, expected e.g. 90d", s))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid age {}, units are s, m, h, d and w",
+                s
+            ))
+        }
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
@@ -0,0 +1,123 @@
+use std::env;
+use std::fs::{set_permissions, File, Permissions};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use anyhow::Context;
+use capctl::CapSet;
+
+use crate::caps::parse_capset_iter;
+use crate::profile::ProgramResult;
+
+/// Environment variables copied to the wrapper, others are left to the caller environment
+const WRAPPER_ENV: [&str; 3] = ["PATH", "LANG", "LC_ALL"];
+
+/// Tool used by the wrapper to start the command with the learned capabilities
+enum Launcher {
+    /// RootAsRole, with the role and task installed by --install-role
+    Sr { role: String, task: String },
+    Setpriv,
+    SystemdRun,
+}
+
+fn launcher(role_task: Option<&str>) -> Result<Launcher, anyhow::Error> {
+    if let Some((role, task)) = role_task.and_then(|rt| rt.split_once('/')) {
+        if which::which("sr").is_ok() {
+            return Ok(Launcher::Sr {
+                role: role.to_string(),
+                task: task.to_string(),
+            });
+        }
+    }
+    if which::which("setpriv").is_ok() {
+        Ok(Launcher::Setpriv)
+    } else if which::which("systemd-run").is_ok() {
+        Ok(Launcher::SystemdRun)
+    } else {
+        Err(anyhow::anyhow!(
+            "No launcher found for the wrapper, install setpriv (util-linux) or systemd-run"
+        ))
+    }
+}
+
+fn launcher_args(launcher: &Launcher, caps: &CapSet) -> Vec<String> {
+    match launcher {
+        Launcher::Sr { role, task } => vec![
+            "sr".to_string(),
+            "-r".to_string(),
+            role.clone(),
+            "-t".to_string(),
+            task.clone(),
+        ],
+        Launcher::Setpriv => {
+            // setpriv names capabilities without the cap_ prefix
+            let list = caps.iter().fold(String::from("-all"), |mut acc, cap| {
+                acc.push_str(",+");
+                acc.push_str(&format!("{:?}", cap).to_lowercase());
+                acc
+            });
+            vec![
+                "setpriv".to_string(),
+                format!("--inh-caps={}", list),
+                format!("--ambient-caps={}", list),
+                format!("--bounding-set={}", list),
+                "--no-new-privs".to_string(),
+                "--".to_string(),
+            ]
+        }
+        Launcher::SystemdRun => {
+            let list = caps
+                .iter()
+                .map(|cap| format!("CAP_{:?}", cap))
+                .collect::<Vec<_>>()
+                .join(" ");
+            vec![
+                "systemd-run".to_string(),
+                "--quiet".to_string(),
+                "--pty".to_string(),
+                "--wait".to_string(),
+                "--collect".to_string(),
+                "--same-dir".to_string(),
+                format!("--property=AmbientCapabilities={}", list),
+                format!("--property=CapabilityBoundingSet={}", list),
+                "--property=NoNewPrivileges=yes".to_string(),
+                "--".to_string(),
+            ]
+        }
+    }
+}
+
+/// Write a shell script starting the command with the capabilities of the profile,
+/// from the current directory and with the current locale and PATH.
+pub fn emit_wrapper<P: AsRef<Path>>(
+    path: P,
+    command: &[String],
+    result: &ProgramResult,
+    role_task: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let path = path.as_ref();
+    let caps = parse_capset_iter(result.capability_names())
+        .context("Invalid capability in profile")?;
+    let launcher = launcher(role_task)?;
+    let mut file = File::create(path)
+        .with_context(|| format!("Unable to create wrapper {}", path.display()))?;
+    writeln!(file, "#!/bin/sh")?;
+    writeln!(
+        file,
+        "# Generated by capable, runs the command with the privileges it was observed to need"
+    )?;
+    let cwd = env::current_dir()?;
+    writeln!(file, "cd {} || exit 1", shell_words::quote(&cwd.display().to_string()))?;
+    for var in WRAPPER_ENV {
+        if let Ok(value) = env::var(var) {
+            writeln!(file, "export {}={}", var, shell_words::quote(&value))?;
+        }
+    }
+    let mut args = launcher_args(&launcher, &caps);
+    args.extend(command.iter().cloned());
+    writeln!(file, "exec {} \"$@\"", shell_words::join(args))?;
+    file.flush()?;
+    set_permissions(path, Permissions::from_mode(0o755))?;
+    Ok(())
+}
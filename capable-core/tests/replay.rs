@@ -0,0 +1,148 @@
+//! Profiles of the fixtures replayed through the aggregation of a live run. Each fixture
+//! directory holds the requests of a run, `requests.jsonl`, and its strace log,
+//! `strace.log`. The seccomp fixture opens the password files of the host, others can neither
+//! read /etc/shadow nor write /etc/passwd.
+
+use std::path::{Path, PathBuf};
+
+use capable_core::policy::Policy;
+use capable_core::profile::{Access, NestedNamespace, ProgramResult};
+use capable_core::replay::Replay;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn replay(name: &str) -> ProgramResult {
+    let dir = fixture(name);
+    let mut replay = Replay::new();
+    replay.requests_file(dir.join("requests.jsonl")).unwrap();
+    replay.strace_file(dir.join("strace.log")).unwrap();
+    let ns = replay.root().expect("fixture without requests");
+    replay.result(ns, None)
+}
+
+#[test]
+fn ping() {
+    let result = replay("ping");
+    let names: Vec<&str> = result.capability_names().collect();
+    assert_eq!(names, ["CAP_NET_ADMIN", "CAP_NET_RAW"]);
+    let net_raw = &result.capabilities[1];
+    assert_eq!(net_raw.count, 1);
+    assert_eq!(net_raw.processes, [2]);
+    assert_eq!(net_raw.reason_symbols, ["inet_create"]);
+    assert!(result.network);
+    let executed: Vec<&str> = result
+        .executed
+        .iter()
+        .map(|file| file.path.as_str())
+        .collect();
+    assert_eq!(executed, ["/usr/bin/ping"]);
+    assert!(result.suggestions.iter().any(|suggestion| {
+        suggestion.capability == "CAP_NET_RAW"
+            && suggestion.setting.as_deref() == Some("net.ipv4.ping_group_range=0 2147483647")
+    }));
+}
+
+#[test]
+fn nested_namespace() {
+    let result = replay("nested");
+    // the capabilities of the nested namespace count in the profile of the command
+    let names: Vec<&str> = result.capability_names().collect();
+    assert_eq!(names, ["CAP_NET_BIND_SERVICE", "CAP_SYS_ADMIN"]);
+    let bind = &result.capabilities[0];
+    assert_eq!(bind.count, 2);
+    assert_eq!(bind.processes, [5]);
+    assert_eq!(result.capabilities[1].processes, [2]);
+    assert!(
+        result.namespaces
+            == [NestedNamespace {
+                ns: 4026532600,
+                parent: 4026532500,
+                depth: 1,
+                capabilities: vec!["CAP_NET_BIND_SERVICE".to_string()],
+            }]
+    );
+}
+
+#[test]
+fn seccomp_log() {
+    // the log of the seccomp collector does not know the results, `= ?` counts as a success
+    let result = replay("seccomp");
+    let names: Vec<&str> = result.capability_names().collect();
+    assert_eq!(names, ["CAP_DAC_OVERRIDE", "CAP_DAC_READ_SEARCH"]);
+    assert!(result.files["/etc/shadow"] == Access::R);
+    assert!(result.files["/etc/passwd"] == Access::W);
+    let executed: Vec<&str> = result
+        .executed
+        .iter()
+        .map(|file| file.path.as_str())
+        .collect();
+    assert_eq!(executed, ["/usr/sbin/chpasswd"]);
+}
+
+#[test]
+fn systemd_policy() {
+    let dir = fixture("nginx");
+    let result = replay("nginx");
+    let log = std::fs::read_to_string(dir.join("strace.log")).unwrap();
+    let command = ["nginx", "-g", "daemon off;"].map(String::from).to_vec();
+    let policy: Policy = "systemd".parse().unwrap();
+    let unit = policy.render(&[command], &[log], &result);
+    let lines: Vec<&str> = unit.lines().collect();
+    assert_eq!(lines[0], "[Service]");
+    assert!(lines.contains(&"CapabilityBoundingSet=CAP_SETGID CAP_SETUID CAP_NET_BIND_SERVICE"));
+    assert!(lines.contains(&"AmbientCapabilities=CAP_SETGID CAP_SETUID CAP_NET_BIND_SERVICE"));
+    assert!(lines.contains(&"ProtectSystem=strict"));
+    // nginx listens on port 80
+    assert!(!lines.iter().any(|line| line.starts_with("PrivateNetwork=")));
+    assert_eq!(result.listening.len(), 1);
+    assert_eq!(result.listening[0].port, 80);
+}
+
+#[test]
+fn keyring() {
+    let result = replay("keyring");
+    assert_eq!(
+        result.annotations["capability:CAP_SYS_ADMIN"],
+        ["keyring: checked during keyctl"]
+    );
+    // CAP_SETUID is a keyring capability, but was not checked during a keyring syscall
+    assert!(!result.annotations.contains_key("capability:CAP_SETUID"));
+}
+
+#[test]
+fn bpf() {
+    let result = replay("bpf");
+    assert!(result.annotations["capability:CAP_BPF"]
+        .iter()
+        .any(|note| note.starts_with("bpf: BPF_MAP_CREATE of a BPF_MAP_TYPE_HASH map \"counts\"")));
+    // CAP_SYS_ADMIN may allow bpf(), but was checked during mount
+    assert_eq!(
+        result.annotations["capability:CAP_SYS_ADMIN"],
+        ["via path_mount: mounting a filesystem"]
+    );
+}
+
+#[test]
+fn io_uring() {
+    let result = replay("io_uring");
+    let note = &result.annotations["capability:CAP_IPC_LOCK"][0];
+    assert!(note.contains("(IORING_SETUP_SQPOLL, CAP_SYS_ADMIN before Linux 5.11)"));
+    assert!(note.contains("(IORING_SETUP_SQ_AFF, CAP_SYS_NICE since Linux 5.11)"));
+    assert_eq!(result.annotations["capability:CAP_SYS_NICE"][0], *note);
+    // CAP_SYS_ADMIN may allow io_uring, but was checked during mount
+    assert_eq!(
+        result.annotations["capability:CAP_SYS_ADMIN"],
+        ["via path_mount: mounting a filesystem"]
+    );
+    let io_uring: Vec<&str> = result
+        .suggestions
+        .iter()
+        .filter(|suggestion| suggestion.alternative.contains("io_uring"))
+        .map(|suggestion| suggestion.capability.as_str())
+        .collect();
+    assert_eq!(io_uring, ["CAP_SYS_NICE"]);
+}
@@ -0,0 +1,59 @@
+//! Parsing of the strace logs of the fixtures, with the lines counted in the statistics.
+
+use std::path::Path;
+
+use capable_core::stats::CollectorStats;
+use capable_core::strace::parse_strace_counted;
+
+fn strace_log(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .join("strace.log");
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn resumed() {
+    let log = strace_log("threads");
+    let (syscalls, failed) = parse_strace_counted(&log);
+    // each unfinished syscall is joined with its resumed part, in place of the resumed line
+    let names: Vec<&str> = syscalls
+        .iter()
+        .map(|syscall| syscall.syscall.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        ["execve", "vfork", "openat", "execve", "read", "openat", "read", "getpid"]
+    );
+    let args: Vec<String> = syscalls[3].args.iter().map(ToString::to_string).collect();
+    assert_eq!(args[0], "/bin/sh");
+    assert_eq!(syscalls[3].return_code.code, 0);
+    assert_eq!(syscalls[5].args[1].to_string(), "main.c");
+    assert_eq!(syscalls[5].return_code.code, 5);
+    assert_eq!(syscalls[6].return_code.code, -1);
+    assert_eq!(syscalls[6].return_code.constant.as_deref(), Some("EIO"));
+    // the line cut by the full disk
+    assert_eq!(failed, 1);
+}
+
+#[test]
+fn never_resumed() {
+    let log = "1 read(3, <unfinished ...>\n2 getpid() = 2\n2 +++ exited with 0 +++\n";
+    let (syscalls, failed) = parse_strace_counted(log);
+    assert_eq!(syscalls.len(), 1);
+    assert_eq!(syscalls[0].syscall, "getpid");
+    assert_eq!(failed, 1);
+}
+
+#[test]
+fn stats() {
+    let log = strace_log("threads");
+    let (syscalls, failed) = parse_strace_counted(&log);
+    let mut stats = CollectorStats::default();
+    stats.strace(&log, failed);
+    // the resumed lines and the exits are not syscalls of their own
+    assert_eq!(stats.strace_parsed, syscalls.len());
+    assert_eq!(stats.strace_skipped, 6);
+    assert_eq!(stats.strace_failed, 1);
+}
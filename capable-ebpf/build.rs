@@ -15,9 +15,37 @@ fn main() {
     let bpf_linker = which("bpf-linker").expect("bpf-linker not found in $PATH");
     let aya_tool = which("aya-tool").expect("aya-tool not found in $PATH");
     println!("cargo:rerun-if-changed={}", bpf_linker.to_str().expect("bpf-linker path is not valid UTF-8"));
+    // The registers of the kprobe arguments are the ones of the kernel architecture.
+    // aya-build passes the target of capable with --cfg bpf_target_arch, a plain build of
+    // this crate targets the build host.
+    let host = std::env::var("HOST").expect("HOST is not set");
+    let host_arch = match host.split('-').next().unwrap_or_default() {
+        arch if arch.starts_with("arm") => "arm".to_string(),
+        arch => arch.to_string(),
+    };
+    let arch = match std::env::var("CARGO_CFG_BPF_TARGET_ARCH") {
+        Ok(arch) => arch,
+        Err(_) => {
+            println!("cargo:rustc-cfg=bpf_target_arch=\"{}\"", host_arch);
+            host_arch.clone()
+        }
+    };
+    // The kernel structures are read with bindings of the BTF of the build host, unless
+    // CAPABLE_BTF gives the one of the target kernel, e.g. to cross compile for aarch64.
+    println!("cargo:rerun-if-env-changed=CAPABLE_BTF");
+    let btf = std::env::var_os("CAPABLE_BTF");
+    // the layouts of the structures differ between architectures, e.g. 32-bit pointers
+    if btf.is_none() && arch != host_arch {
+        eprintln!("building the probes for {} with the kernel structures of this {} host would read them at the wrong offsets, set CAPABLE_BTF to the BTF of the target kernel", arch, host_arch);
+        std::process::exit(1);
+    }
     // run aya-tool to rebuild task_struct bindings
-    let output = std::process::Command::new(aya_tool)
-        .arg("generate")
+    let mut generate = std::process::Command::new(aya_tool);
+    generate.arg("generate");
+    if let Some(btf) = btf {
+        generate.arg("--btf").arg(btf);
+    }
+    let output = generate
         .arg("task_struct")
         .output()
         .expect("Failed to run aya-tool");
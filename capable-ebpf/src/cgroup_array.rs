@@ -0,0 +1,37 @@
+use core::cell::UnsafeCell;
+
+use aya_ebpf::{
+    bindings::{bpf_map_def, bpf_map_type::BPF_MAP_TYPE_CGROUP_ARRAY},
+    helpers::gen::bpf_current_task_under_cgroup,
+};
+
+// BPF_MAP_TYPE_CGROUP_ARRAY, set from userspace with the file descriptors of cgroup
+// directories. aya-ebpf has no type for it.
+#[repr(transparent)]
+pub struct CgroupArray {
+    def: UnsafeCell<bpf_map_def>,
+}
+
+unsafe impl Sync for CgroupArray {}
+
+impl CgroupArray {
+    pub const fn with_max_entries(max_entries: u32, flags: u32) -> CgroupArray {
+        CgroupArray {
+            def: UnsafeCell::new(bpf_map_def {
+                type_: BPF_MAP_TYPE_CGROUP_ARRAY,
+                key_size: core::mem::size_of::<u32>() as u32,
+                value_size: core::mem::size_of::<u32>() as u32,
+                max_entries,
+                map_flags: flags,
+                id: 0,
+                pinning: 0,
+            }),
+        }
+    }
+
+    // 1 when the current task is in the cgroup at `index` or in one of its descendants, 0
+    // when it is not, negative when the index is empty
+    pub unsafe fn current_task_under(&self, index: u32) -> i64 {
+        bpf_current_task_under_cgroup(self.def.get() as *mut _, index)
+    }
+}
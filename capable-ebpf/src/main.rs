@@ -6,53 +6,596 @@
 #[allow(non_camel_case_types)]
 #[allow(dead_code)]
 mod vmlinux;
+mod cgroup_array;
 
 use aya_ebpf::{
-    helpers::{bpf_get_current_task, bpf_get_current_uid_gid, bpf_probe_read_kernel}, macros::{kprobe, map}, maps::stack_trace::StackTrace, programs::ProbeContext
+    helpers::{bpf_get_current_cgroup_id, bpf_get_current_pid_tgid, bpf_get_current_task, bpf_get_current_uid_gid, bpf_get_smp_processor_id, bpf_ktime_get_ns, bpf_probe_read_kernel, bpf_probe_read_kernel_str_bytes}, macros::{cgroup_sysctl, kprobe, kretprobe, lsm, map, tracepoint, uretprobe}, maps::stack_trace::StackTrace, programs::{LsmContext, ProbeContext, RetProbeContext, SysctlContext, TracePointContext}, EbpfContext
 };
-use aya_ebpf::maps::Stack;
-use vmlinux::{ns_common, pid_namespace, task_struct};
-use capable_common::Request;
+use aya_ebpf::maps::{Array, HashMap, LruHashMap, PerCpuArray};
+use core::mem::offset_of;
+use cgroup_array::CgroupArray;
+use vmlinux::{cgroup, cred, css_set, dentry, file, hlist_node, inode, kernfs_node, ns_common, pid_namespace, task_struct};
+use aya_ebpf::helpers::gen::{bpf_get_current_ancestor_cgroup_id, bpf_send_signal, bpf_sysctl_get_name, bpf_sysctl_get_new_value};
+use capable_common::{ApprovalRequest, CgroupPath, CGROUP_COMPONENT_LEN, CGROUP_PATH_COMPONENTS, DacAccess, LibcapCall, LsmDenial, DAC_COMPONENT_LEN, DAC_PATH_COMPONENTS, DAC_PATH_LEN, ModuleRequest, Request, Stats, SysctlRequest, LIBCAP_CAPNG_APPLY, LIBCAP_CAP_SET_PROC, MODULE_NAME_LEN, SYSCTL_NAME_LEN, SYSCTL_VALUE_LEN};
 
 #[kprobe]
 pub fn capable(ctx: ProbeContext) -> u32 {
     try_capable(&ctx).unwrap_or_else(|ret| ret as u32)
 }
 
+#[kretprobe]
+pub fn capable_ret(ctx: RetProbeContext) -> u32 {
+    try_capable_ret(&ctx).unwrap_or_else(|ret| ret as u32)
+}
+
+#[tracepoint]
+pub fn module_request(ctx: TracePointContext) -> u32 {
+    try_module_request(&ctx).unwrap_or_else(|ret| ret as u32)
+}
+
+#[cgroup_sysctl]
+pub fn sysctl(ctx: SysctlContext) -> i32 {
+    let _ = try_sysctl(&ctx);
+    // only observe, the access is always allowed
+    1
+}
+
+#[kprobe]
+pub fn dac_inode(ctx: ProbeContext) -> u32 {
+    try_dac_inode(&ctx).unwrap_or_else(|ret| ret as u32)
+}
+
+#[kprobe]
+pub fn dac_file(ctx: ProbeContext) -> u32 {
+    try_dac_file(&ctx).unwrap_or_else(|ret| ret as u32)
+}
+
+// Only loaded by `capable enforce --lsm`
+#[lsm(hook = "capable")]
+pub fn capable_lsm(ctx: LsmContext) -> i32 {
+    // a failure of the program does not deny anything
+    try_capable_lsm(&ctx).unwrap_or(0)
+}
+
+// Only loaded by `capable enforce --lsm`, with capable_lsm
+#[lsm(hook = "file_open")]
+pub fn cgroup_write_lsm(ctx: LsmContext) -> i32 {
+    try_cgroup_write_lsm(&ctx).unwrap_or(0)
+}
+
+#[uretprobe]
+pub fn cap_set_proc(ctx: RetProbeContext) -> u32 {
+    try_libcap_ret(&ctx, LIBCAP_CAP_SET_PROC).unwrap_or_else(|ret| ret as u32)
+}
+
+#[uretprobe]
+pub fn capng_apply(ctx: RetProbeContext) -> u32 {
+    try_libcap_ret(&ctx, LIBCAP_CAPNG_APPLY).unwrap_or_else(|ret| ret as u32)
+}
 
 pub type TaskStructPtr = *mut task_struct;
 pub const MAX_PID: u32 = 2 * 1024 * 1024;
 pub const EPERM : i32 = 1;
+pub const CAP_DAC_OVERRIDE: u8 = 1;
+pub const CAP_DAC_READ_SEARCH: u8 = 2;
+pub const SIGSTOP: u32 = 19;
+// task_struct flags of the kernel threads, workqueue workers included
+pub const PF_KTHREAD: u32 = 0x00200000;
+// file f_mode of the files opened for writing
+pub const FMODE_WRITE: u32 = 0x2;
+pub const CGROUP2_SUPER_MAGIC: u64 = 0x63677270;
+// Levels of the cgroup hierarchy searched for an enforced cgroup, from the root
+const LSM_MAX_LEVEL: i32 = 16;
+
+// The arguments of the kprobes are read from the registers of bpf_target_arch, which must
+// be the architecture of the kernel the programs are loaded in
+#[cfg(not(any(bpf_target_arch = "x86_64", bpf_target_arch = "aarch64", bpf_target_arch = "arm")))]
+compile_error!("capable-ebpf supports x86_64, aarch64 and arm kernels");
+
+// Index of cred and cap in cap_capable(cred, ns, cap, opts)
+const CAP_CAPABLE_CRED_ARG: usize = 0;
+const CAP_CAPABLE_CAP_ARG: usize = 2;
+
+// Process last seen with a cred, in CRED_ORIGINS
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CredOrigin {
+    pub pid: i32,
+    pub ppid: i32,
+    pub pnsid_nsid: u64,
+    pub cgroup: u64,
+}
+
+// DAC capability check waiting for the LSM hook on its file, in DAC_CHECKS
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DacCheck {
+    pub ns: u32,
+    pub capability: u32,
+    pub time: u64,
+}
+
+// The hooks follow the checks within microseconds, an older check of the thread came from
+// outside a permission check and is reported without its file
+const DAC_HOOK_WINDOW_NS: u64 = 100_000;
+
+// Keyed by cpu << 32 | sequence, a hash map can be drained with batch operations
+#[map]
+static mut ENTRY_MAP: HashMap<u64, Request> = HashMap::with_max_entries(MAX_PID, 0);
+
+#[map]
+static mut ENTRY_SEQ: PerCpuArray<u32> = PerCpuArray::with_max_entries(1, 0);
+
+// Key of the request being checked by each thread, until cap_capable returns
+#[map]
+static mut PENDING: HashMap<u32, u64> = HashMap::with_max_entries(MAX_PID, 0);
+
+// Cgroup of the profiled command, its descendants included. Empty, the requests of every
+// process are recorded.
+#[map]
+static mut CGROUP_FILTER: CgroupArray = CgroupArray::with_max_entries(1, 0);
+
+#[map]
+static mut STATS: PerCpuArray<Stats> = PerCpuArray::with_max_entries(1, 0);
+
+// Process of each cred, by its address. Kernel threads doing deferred work for a process,
+// e.g. async I/O completions or NFS, run with its cred (override_creds), their checks are
+// attributed to the process last seen with it, when it checked a capability or opened a file.
+#[map]
+static mut CRED_ORIGINS: LruHashMap<u64, CredOrigin> = LruHashMap::with_max_entries(16384, 0);
+
+// Set of the modules requested by the kernel, per namespace
+#[map]
+static mut MODULE_REQUESTS: HashMap<ModuleRequest, u8> = HashMap::with_max_entries(1024, 0);
+
+// Set of the sysctl accesses of the profiled cgroup
+#[map]
+static mut SYSCTL_REQUESTS: HashMap<SysctlRequest, u8> = HashMap::with_max_entries(1024, 0);
 
+// Last DAC capability check of each thread, until the LSM hook on its file
+#[map]
+static mut DAC_CHECKS: LruHashMap<u32, DacCheck> = LruHashMap::with_max_entries(16384, 0);
+
+// Set of the paths looked up when a DAC capability was checked, per namespace
+#[map]
+static mut DAC_ACCESSES: HashMap<DacAccess, u8> = HashMap::with_max_entries(4096, 0);
+
+// Path of the cgroup of the requests, by cgroup id, recorded with the first request
+#[map]
+static mut CGROUP_PATHS: LruHashMap<u64, CgroupPath> = LruHashMap::with_max_entries(4096, 0);
 
+// Cgroup path being recorded, too large for the stack
 #[map]
-static mut ENTRY_STACK: Stack<Request> = Stack::with_max_entries(MAX_PID, 0);
+static mut CGROUP_PATH_BUFFER: PerCpuArray<CgroupPath> = PerCpuArray::with_max_entries(1, 0);
+
+// Capability sets after each libcap call, keyed by time to keep them in order
+#[map]
+static mut LIBCAP_CALLS: HashMap<u64, LibcapCall> = HashMap::with_max_entries(4096, 0);
+
+// Parent of each PID namespace of the requests, to follow nested namespaces without requests
+#[map]
+static mut NS_PARENTS: HashMap<u32, u32> = HashMap::with_max_entries(4096, 0);
+
+// Capabilities already decided by the user, per PID namespace of an interactive run
+#[map]
+static mut APPROVALS: HashMap<u32, u64> = HashMap::with_max_entries(64, 0);
+
+// Set of the processes stopped on an undecided capability, until the user decides
+#[map]
+static mut APPROVAL_REQUESTS: HashMap<ApprovalRequest, u8> = HashMap::with_max_entries(1024, 0);
+
+// Capabilities allowed to the processes of each enforced cgroup and of its descendants, the
+// profile enforced by the BPF LSM. The other cgroups are not restricted.
+#[map]
+static mut LSM_PROFILES: HashMap<u64, u64> = HashMap::with_max_entries(1024, 0);
+
+// Number of checks denied by the BPF LSM, per cgroup and capability
+#[map]
+static mut LSM_DENIALS: HashMap<LsmDenial, u64> = HashMap::with_max_entries(4096, 0);
+
+// Levels of PID namespaces recorded above the one of a request
+const NS_DEPTH: usize = 8;
+
+// Offset of the __data_loc name field in the module:module_request tracepoint format, read
+// by userspace from the format file of the tracepoint
+#[map]
+static mut MODULE_REQUEST_NAME_OFFSET: Array<u32> = Array::with_max_entries(1, 0);
 
 #[map]
 static mut STACKTRACE_MAP: StackTrace = StackTrace::with_max_entries(MAX_PID, 0);
 
+// The capability is an int in the third argument register: rdx on x86_64, x2 on aarch64 and
+// r2 on arm. The upper bits of the register are not part of it.
+fn capability_arg(ctx: &ProbeContext) -> Result<u8, i64> {
+    let capability: i32 = ctx.arg(CAP_CAPABLE_CAP_ARG).ok_or(-1i64)?;
+    u8::try_from(capability)
+        .ok()
+        .filter(|capability| *capability < 64)
+        .ok_or(-1i64)
+}
+
+// Whether the cgroup filter excludes the current task
+unsafe fn filtered() -> bool {
+    // an error when the filter is empty
+    CGROUP_FILTER.current_task_under(0) == 0
+}
+
+// Identity of the task, the process a request is attributed to
+unsafe fn task_origin(task: TaskStructPtr) -> Result<CredOrigin, i64> {
+    let nsid: u32 = get_ns_inode(task)?;
+    let cgroup = bpf_get_current_cgroup_id();
+    // the unit is still known once its cgroup is removed
+    let _ = record_cgroup_path(task, cgroup);
+    Ok(CredOrigin {
+        pid: bpf_probe_read_kernel(&(*task).pid)? as i32,
+        ppid: get_ppid(task)?,
+        pnsid_nsid: Into::<u64>::into(get_parent_ns_inode(task)?) << 32 | Into::<u64>::into(nsid),
+        cgroup,
+    })
+}
+
+// Names of the cgroup v2 of the task and its parents, up to the root of the hierarchy which
+// is not named
+unsafe fn record_cgroup_path(task: TaskStructPtr, id: u64) -> Result<(), i64> {
+    if CGROUP_PATHS.get(&id).is_some() {
+        return Ok(());
+    }
+    let path = &mut *CGROUP_PATH_BUFFER.get_ptr_mut(0).ok_or(-1i64)?;
+    let cgroups: *const css_set = bpf_probe_read_kernel(&(*task).cgroups)?;
+    let cgroup: *const cgroup = bpf_probe_read_kernel(&(*cgroups).dfl_cgrp)?;
+    let mut current: *const kernfs_node = bpf_probe_read_kernel(&(*cgroup).kn)?;
+    for i in 0..CGROUP_PATH_COMPONENTS {
+        let start = i * CGROUP_COMPONENT_LEN;
+        path.path[start] = 0;
+        if current.is_null() {
+            continue;
+        }
+        let parent: *const kernfs_node = bpf_probe_read_kernel(&(*current).parent)?;
+        if !parent.is_null() {
+            let name = bpf_probe_read_kernel(&(*current).name)? as *const u8;
+            bpf_probe_read_kernel_str_bytes(name, &mut path.path[start..start + CGROUP_COMPONENT_LEN])?;
+        }
+        current = parent;
+    }
+    CGROUP_PATHS.insert(&id, path, 0)?;
+    Ok(())
+}
+
+unsafe fn is_kernel_thread(task: TaskStructPtr) -> Result<bool, i64> {
+    let flags: u32 = bpf_probe_read_kernel(&(*task).flags)?;
+    Ok(flags & PF_KTHREAD != 0)
+}
+
 pub fn try_capable(ctx: &ProbeContext) -> Result<u32, i64> {
     unsafe {
         let task: TaskStructPtr = bpf_get_current_task() as TaskStructPtr;
         let task = bpf_probe_read_kernel(&task)?;
-        let ppid: i32 = get_ppid(task)?;
-        let pid: i32 = bpf_probe_read_kernel(&(*task).pid)? as i32;
-        let capability: u8 = ctx.arg::<u8>(2).unwrap();
+        let cred: u64 = ctx.arg(CAP_CAPABLE_CRED_ARG).ok_or(-1i64)?;
+        let deferred = if is_kernel_thread(task)? {
+            CRED_ORIGINS.get(&cred).copied()
+        } else {
+            None
+        };
+        // the origin of a deferred check passed the filter when it was recorded
+        if deferred.is_none() && filtered() {
+            return Ok(0);
+        }
+        let cgroup = deferred.map_or_else(bpf_get_current_cgroup_id, |origin| origin.cgroup);
+        let origin = match deferred {
+            Some(origin) => origin,
+            None => {
+                let origin = task_origin(task)?;
+                record_ns_parents(task)?;
+                let _ = CRED_ORIGINS.insert(&cred, &origin, 0);
+                origin
+            }
+        };
+        let capability: u8 = capability_arg(ctx)?;
         let uid_gid: u64 = bpf_get_current_uid_gid();
-        let nsid: u32 = get_ns_inode(task)?;
-        let pnsid_nsid: u64 = Into::<u64>::into(get_parent_ns_inode(task)?) << 32
-            | Into::<u64>::into(nsid);
+        let nsid: u32 = origin.pnsid_nsid as u32;
+        let pnsid_nsid: u64 = origin.pnsid_nsid;
         let stackid = STACKTRACE_MAP.get_stackid(ctx, 0)?;
         let request = Request {
-            pid,
+            pid: origin.pid,
             uid_gid,
-            ppid,
+            ppid: origin.ppid,
             pnsid_nsid,
             capability,
+            denied: 0,
             stackid,
+            time: bpf_ktime_get_ns(),
+            cgroup,
         };
-        ENTRY_STACK.push(&request, 0).expect("Failed to insert request");
+        let seq = ENTRY_SEQ.get_ptr_mut(0).ok_or(-1i64)?;
+        let key = (bpf_get_smp_processor_id() as u64) << 32 | (*seq as u64);
+        *seq = (*seq).wrapping_add(1);
+        let inserted = ENTRY_MAP.insert(&key, &request, 0);
+        if let Some(stats) = STATS.get_ptr_mut(0) {
+            if inserted.is_ok() {
+                (*stats).requests += 1;
+            } else {
+                (*stats).drops += 1;
+            }
+            if capability < 64 {
+                (*stats).capabilities |= 1 << capability;
+            }
+            if deferred.is_some() {
+                (*stats).deferred += 1;
+            }
+        }
+        inserted?;
+        if capability == CAP_DAC_OVERRIDE || capability == CAP_DAC_READ_SEARCH {
+            let check = DacCheck {
+                ns: nsid,
+                capability: capability as u32,
+                time: request.time,
+            };
+            let _ = DAC_CHECKS.insert(&(bpf_get_current_pid_tgid() as u32), &check, 0);
+        }
+        // a kernel thread cannot be stopped for the user to decide
+        if deferred.is_none() {
+            request_approval(nsid, (pnsid_nsid >> 32) as u32, capability)?;
+        }
+        PENDING.insert(&(bpf_get_current_pid_tgid() as u32), &key, 0)?;
+    }
+    Ok(0)
+}
 
+// Stop the process when its namespace, or the one of its parent, is interactive and the
+// capability is not decided yet. The check itself goes on, a kprobe cannot change its
+// result, the process stops on its return to userspace.
+unsafe fn request_approval(nsid: u32, parent_nsid: u32, capability: u8) -> Result<(), i64> {
+    if capability >= 64 {
+        return Ok(());
+    }
+    let (ns, decided) = match APPROVALS.get(&nsid) {
+        Some(decided) => (nsid, *decided),
+        None => match APPROVALS.get(&parent_nsid) {
+            Some(decided) => (parent_nsid, *decided),
+            None => return Ok(()),
+        },
+    };
+    if decided & (1 << capability) != 0 {
+        return Ok(());
+    }
+    let request = ApprovalRequest {
+        pid: (bpf_get_current_pid_tgid() >> 32) as i32,
+        ns,
+        capability: capability as u32,
+    };
+    APPROVAL_REQUESTS.insert(&request, &1, 0)?;
+    bpf_send_signal(SIGSTOP);
+    Ok(())
+}
+
+pub fn try_capable_ret(ctx: &RetProbeContext) -> Result<u32, i64> {
+    unsafe {
+        let tid = bpf_get_current_pid_tgid() as u32;
+        if let Some(key) = PENDING.get_ptr(&tid) {
+            let ret: i32 = ctx.ret().unwrap_or(0);
+            if ret != 0 {
+                // the request may already be drained by userspace
+                if let Some(request) = ENTRY_MAP.get_ptr_mut(&*key) {
+                    (*request).denied = 1;
+                }
+            }
+            PENDING.remove(&tid)?;
+        }
+    }
+    Ok(0)
+}
+
+// security_capable(cred, ns, cap, opts), the last argument of a BPF LSM program is the
+// return value of the previous one
+// Enforced cgroup of the current task and the capabilities it allows. A child cgroup is
+// restricted by the profile of its enforced ancestor, the deepest one when they are nested.
+unsafe fn enforced_cgroup() -> Option<(u64, u64)> {
+    let mut enforced = None;
+    for level in 1..=LSM_MAX_LEVEL {
+        let ancestor = bpf_get_current_ancestor_cgroup_id(level);
+        // below the cgroup of the task
+        if ancestor == 0 {
+            break;
+        }
+        if let Some(allowed) = LSM_PROFILES.get(&ancestor) {
+            enforced = Some((ancestor, *allowed));
+        }
+    }
+    enforced
+}
+
+pub fn try_capable_lsm(ctx: &LsmContext) -> Result<i32, i64> {
+    unsafe {
+        let previous: i32 = ctx.arg(4);
+        if previous != 0 {
+            return Ok(previous);
+        }
+        let Some((cgroup, allowed)) = enforced_cgroup() else {
+            return Ok(0);
+        };
+        let capability: i32 = ctx.arg(2);
+        if !(0..64).contains(&capability) || allowed & (1 << capability) != 0 {
+            return Ok(0);
+        }
+        let denial = LsmDenial {
+            cgroup,
+            capability: capability as u64,
+        };
+        match LSM_DENIALS.get_ptr_mut(&denial) {
+            Some(count) => *count += 1,
+            None => {
+                let _ = LSM_DENIALS.insert(&denial, &1, 0);
+            }
+        }
+    }
+    Ok(-EPERM)
+}
+
+// The processes of an enforced cgroup could leave it by writing their pid to the
+// cgroup.procs of another cgroup, they cannot open the files of the cgroup2 filesystem for
+// writing
+pub fn try_cgroup_write_lsm(ctx: &LsmContext) -> Result<i32, i64> {
+    unsafe {
+        let previous: i32 = ctx.arg(1);
+        if previous != 0 {
+            return Ok(previous);
+        }
+        if enforced_cgroup().is_none() {
+            return Ok(0);
+        }
+        let opened: *const file = ctx.arg(0);
+        let mode: u32 = bpf_probe_read_kernel(&(*opened).f_mode)?;
+        if mode & FMODE_WRITE == 0 {
+            return Ok(0);
+        }
+        let opened_inode: *const inode = bpf_probe_read_kernel(&(*opened).f_inode)?;
+        let sb = bpf_probe_read_kernel(&(*opened_inode).i_sb)?;
+        let magic: u64 = bpf_probe_read_kernel(&(*sb).s_magic)?;
+        if magic != CGROUP2_SUPER_MAGIC {
+            return Ok(0);
+        }
+    }
+    Ok(-EPERM)
+}
+
+pub fn try_module_request(ctx: &TracePointContext) -> Result<u32, i64> {
+    unsafe {
+        let task: TaskStructPtr = bpf_get_current_task() as TaskStructPtr;
+        let task = bpf_probe_read_kernel(&task)?;
+        let mut request = ModuleRequest {
+            ns: get_ns_inode(task)?,
+            name: [0; MODULE_NAME_LEN],
+        };
+        // __data_loc: offset of the string from the start of the record in the low 16 bits
+        let offset = *MODULE_REQUEST_NAME_OFFSET.get(0).ok_or(-1i64)?;
+        let data_loc: u32 = ctx.read_at(offset as usize)?;
+        let name = (ctx.as_ptr() as *const u8).add((data_loc & 0xffff) as usize);
+        bpf_probe_read_kernel_str_bytes(name, &mut request.name)?;
+        MODULE_REQUESTS.insert(&request, &1, 0)?;
+    }
+    Ok(0)
+}
+
+pub fn try_sysctl(ctx: &SysctlContext) -> Result<u32, i64> {
+    unsafe {
+        let mut request = SysctlRequest {
+            write: (*ctx.sysctl).write as u8,
+            name: [0; SYSCTL_NAME_LEN],
+            value: [0; SYSCTL_VALUE_LEN],
+        };
+        // a truncated name is still NUL terminated, -E2BIG is not an error here
+        bpf_sysctl_get_name(
+            ctx.sysctl,
+            request.name.as_mut_ptr() as *mut _,
+            SYSCTL_NAME_LEN as u64,
+            0,
+        );
+        if request.write != 0 {
+            bpf_sysctl_get_new_value(
+                ctx.sysctl,
+                request.value.as_mut_ptr() as *mut _,
+                SYSCTL_VALUE_LEN as u64,
+            );
+        }
+        SYSCTL_REQUESTS.insert(&request, &1, 0)?;
+    }
+    Ok(0)
+}
+
+// security_inode_permission(inode, mask) follows generic_permission, in the same
+// inode_permission call as its DAC capability checks
+pub fn try_dac_inode(ctx: &ProbeContext) -> Result<u32, i64> {
+    unsafe {
+        let Some(check) = take_dac_check() else {
+            return Ok(0);
+        };
+        let checked: *const inode = ctx.arg(0).ok_or(-1i64)?;
+        let alias: *const hlist_node =
+            bpf_probe_read_kernel(&(*checked).__bindgen_anon_2.i_dentry.first)?;
+        if alias.is_null() {
+            return Ok(0);
+        }
+        // d_alias is the first member of d_u
+        let alias = (alias as usize - offset_of!(dentry, d_u)) as *const dentry;
+        record_dac_access(&check, alias)
+    }
+}
+
+// security_file_open(file) follows may_open, the DAC capability checks of the opened file
+pub fn try_dac_file(ctx: &ProbeContext) -> Result<u32, i64> {
+    unsafe {
+        if filtered() {
+            return Ok(0);
+        }
+        // a file keeps the cred of its opener, the one of the work deferred on it
+        let task: TaskStructPtr = bpf_get_current_task() as TaskStructPtr;
+        let task = bpf_probe_read_kernel(&task)?;
+        if !is_kernel_thread(task)? {
+            let cred: *const cred = bpf_probe_read_kernel(&(*task).cred)?;
+            let _ = CRED_ORIGINS.insert(&(cred as u64), &task_origin(task)?, 0);
+        }
+        let Some(check) = take_dac_check() else {
+            return Ok(0);
+        };
+        let opened: *const file = ctx.arg(0).ok_or(-1i64)?;
+        let path = bpf_probe_read_kernel(&(*opened).f_path.dentry)?;
+        record_dac_access(&check, path)
+    }
+}
+
+// The DAC capability check of the thread, unless it is too old to come from the permission
+// check the hook follows
+unsafe fn take_dac_check() -> Option<DacCheck> {
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let check = *DAC_CHECKS.get(&tid)?;
+    let _ = DAC_CHECKS.remove(&tid);
+    if bpf_ktime_get_ns().wrapping_sub(check.time) > DAC_HOOK_WINDOW_NS {
+        return None;
+    }
+    Some(check)
+}
+
+// Names of the dentry and its parents, up to the root of its mount which is its own parent
+unsafe fn record_dac_access(check: &DacCheck, mut current: *const dentry) -> Result<u32, i64> {
+    let mut access = DacAccess {
+        ns: check.ns,
+        capability: check.capability,
+        path: [0; DAC_PATH_LEN],
+    };
+    for i in 0..DAC_PATH_COMPONENTS {
+        let parent: *const dentry = bpf_probe_read_kernel(&(*current).d_parent)?;
+        if parent == current {
+            break;
+        }
+        let name = bpf_probe_read_kernel(&(*current).d_name.name)?;
+        let start = i * DAC_COMPONENT_LEN;
+        bpf_probe_read_kernel_str_bytes(name, &mut access.path[start..start + DAC_COMPONENT_LEN])?;
+        current = parent;
+    }
+    DAC_ACCESSES.insert(&access, &1, 0)?;
+    Ok(0)
+}
+
+pub fn try_libcap_ret(ctx: &RetProbeContext, function: u8) -> Result<u32, i64> {
+    unsafe {
+        if filtered() {
+            return Ok(0);
+        }
+        let task: TaskStructPtr = bpf_get_current_task() as TaskStructPtr;
+        let task = bpf_probe_read_kernel(&task)?;
+        let cred: *const cred = bpf_probe_read_kernel(&(*task).cred)?;
+        let ret: i32 = ctx.ret().unwrap_or(0);
+        let call = LibcapCall {
+            pid: bpf_probe_read_kernel(&(*task).pid)?,
+            ns: get_ns_inode(task)?,
+            function,
+            failed: (ret != 0) as u8,
+            effective: bpf_probe_read_kernel(&(*cred).cap_effective.val)?,
+            permitted: bpf_probe_read_kernel(&(*cred).cap_permitted.val)?,
+            inheritable: bpf_probe_read_kernel(&(*cred).cap_inheritable.val)?,
+            ambient: bpf_probe_read_kernel(&(*cred).cap_ambient.val)?,
+        };
+        LIBCAP_CALLS.insert(&bpf_ktime_get_ns(), &call, 0)?;
     }
     Ok(0)
 }
@@ -72,14 +615,38 @@ unsafe fn get_parent_ns_inode(task: TaskStructPtr) -> Result<u32, i64> {
 }
 
 
-pub unsafe fn get_ns_inode(task: TaskStructPtr) -> Result<u32, i64> {
+unsafe fn get_pid_ns(task: TaskStructPtr) -> Result<*mut pid_namespace, i64> {
     let nsp = bpf_probe_read_kernel(&(*task).nsproxy).map_err(|e| e as u32)?;
-    let pns: *mut pid_namespace =
-        bpf_probe_read_kernel(&(*nsp).pid_ns_for_children).map_err(|e| e as u32)?;
+    bpf_probe_read_kernel(&(*nsp).pid_ns_for_children)
+}
+
+unsafe fn pid_ns_inode(pns: *mut pid_namespace) -> Result<u32, i64> {
     let nsc: ns_common = bpf_probe_read_kernel(&(*pns).ns).map_err(|e| e as u32)?;
     bpf_probe_read_kernel(&nsc.inum)
 }
 
+pub unsafe fn get_ns_inode(task: TaskStructPtr) -> Result<u32, i64> {
+    pid_ns_inode(get_pid_ns(task)?)
+}
+
+// Link the PID namespace of the task to its ancestors, until one already linked
+unsafe fn record_ns_parents(task: TaskStructPtr) -> Result<(), i64> {
+    let mut pns = get_pid_ns(task)?;
+    for _ in 0..NS_DEPTH {
+        let parent: *mut pid_namespace = bpf_probe_read_kernel(&(*pns).parent)?;
+        if parent.is_null() {
+            break;
+        }
+        let inum = pid_ns_inode(pns)?;
+        if NS_PARENTS.get(&inum).is_some() {
+            break;
+        }
+        let _ = NS_PARENTS.insert(&inum, &pid_ns_inode(parent)?, 0);
+        pns = parent;
+    }
+    Ok(())
+}
+
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
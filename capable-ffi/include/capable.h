@@ -0,0 +1,38 @@
+/* C interface of capable, see capable-ffi/src/lib.rs. Link with -lcapable. */
+#ifndef CAPABLE_H
+#define CAPABLE_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct capable_profile capable_profile;
+
+/* Load the probes and start collecting. ns is 0 for all PID namespaces, strace_log may be
+ * NULL. Returns NULL on failure. */
+capable_profile *capable_start_profile(uint32_t ns, const char *strace_log, bool dbus);
+
+/* Next finding as JSON, NULL when none is pending or, once, when the collection failed and
+ * capable_last_error is set. Release it with capable_free_json. */
+char *capable_poll_event(capable_profile *profile);
+
+/* Findings dropped as they were not polled in time, they are in the report. */
+uint64_t capable_dropped_events(const capable_profile *profile);
+
+/* Stop collecting, release the profile and return its report as JSON, NULL on failure.
+ * Release it with capable_free_json. */
+char *capable_finish_profile(capable_profile *profile);
+
+void capable_free_json(char *json);
+
+/* Last error of the calling thread, owned by the library. */
+const char *capable_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CAPABLE_H */
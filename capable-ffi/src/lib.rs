@@ -0,0 +1,153 @@
+//! C interface of capable-core, for tooling that is not written in Rust.
+//!
+//! A profile is started with `capable_start_profile`, its findings are polled with
+//! `capable_poll_event` while the processes run, and `capable_finish_profile` returns the
+//! report. Findings and reports are JSON strings owned by the caller, released with
+//! `capable_free_json`. On failure, NULL is returned and `capable_last_error` describes the
+//! error. The symbols are prefixed with `capable_`, as the Python module exports them to the
+//! interpreter too.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use capable_core::profiler::{Events, Profiler};
+
+#[cfg(feature = "python")]
+mod python;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Profile started by `capable_start_profile`, the opaque `capable_profile` of C
+pub struct Profile {
+    events: Events,
+}
+
+fn set_error(error: anyhow::Error) {
+    let message =
+        CString::new(format!("{:#}", error).replace('\0', "")).expect("nul bytes are removed");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn json<T: serde::Serialize>(value: &T) -> *mut c_char {
+    match serde_json::to_string(value) {
+        // JSON escapes control characters, there is no nul byte
+        Ok(json) => CString::new(json).expect("nul byte in JSON").into_raw(),
+        Err(e) => {
+            set_error(e.into());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Load the probes and start collecting the findings.
+///
+/// `ns` restricts the findings to a PID namespace and its descendants, 0 for all of them.
+/// `strace_log` is the path of a strace log to read the file accesses from, or NULL.
+/// `dbus` monitors the system bus, which requires to run as root.
+/// Returns NULL on failure.
+///
+/// # Safety
+///
+/// `strace_log` must be NULL or a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn capable_start_profile(
+    ns: u32,
+    strace_log: *const c_char,
+    dbus: bool,
+) -> *mut Profile {
+    let mut profiler = Profiler::new().dbus(dbus);
+    if ns != 0 {
+        profiler = profiler.namespace(ns);
+    }
+    if !strace_log.is_null() {
+        let path = CStr::from_ptr(strace_log).to_string_lossy().into_owned();
+        profiler = profiler.strace_log(path);
+    }
+    match profiler.events() {
+        Ok(events) => Box::into_raw(Box::new(Profile { events })),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Next finding as a JSON object, or NULL when none is pending. NULL is also returned once
+/// when the collection failed, `capable_last_error` is then set.
+///
+/// # Safety
+///
+/// `profile` must come from `capable_start_profile` and not be finished.
+#[no_mangle]
+pub unsafe extern "C" fn capable_poll_event(profile: *mut Profile) -> *mut c_char {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    let Some(profile) = profile.as_ref() else {
+        return ptr::null_mut();
+    };
+    match profile.events.try_next() {
+        Some(Ok(event)) => json(&event),
+        Some(Err(e)) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+        None => ptr::null_mut(),
+    }
+}
+
+/// Number of findings dropped as they were not polled in time, they are in the report.
+///
+/// # Safety
+///
+/// `profile` must come from `capable_start_profile` and not be finished.
+#[no_mangle]
+pub unsafe extern "C" fn capable_dropped_events(profile: *const Profile) -> u64 {
+    profile
+        .as_ref()
+        .map_or(0, |profile| profile.events.dropped())
+}
+
+/// Stop collecting and return the report of the profile as JSON, NULL on failure.
+/// The profile is released in both cases.
+///
+/// # Safety
+///
+/// `profile` must come from `capable_start_profile` and not be finished.
+#[no_mangle]
+pub unsafe extern "C" fn capable_finish_profile(profile: *mut Profile) -> *mut c_char {
+    if profile.is_null() {
+        return ptr::null_mut();
+    }
+    let profile = Box::from_raw(profile);
+    match profile.events.finish() {
+        Ok(result) => json(&result),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Release a string returned by this library.
+///
+/// # Safety
+///
+/// `json` must be NULL or come from this library, and not be released yet.
+#[no_mangle]
+pub unsafe extern "C" fn capable_free_json(json: *mut c_char) {
+    if !json.is_null() {
+        drop(CString::from_raw(json));
+    }
+}
+
+/// Last error of the calling thread, valid until its next failing call, or NULL.
+#[no_mangle]
+pub extern "C" fn capable_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
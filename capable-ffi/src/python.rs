@@ -0,0 +1,117 @@
+//! `capable` Python module, built with the `python` feature.
+//!
+//! Reports are returned as the dictionaries of the JSON profiles. The Python process needs the
+//! same capabilities as `capable`.
+
+use std::cell::Cell;
+use std::fs::metadata;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use capable_core::caps::setptrace_effective;
+use capable_core::profile::read_profile;
+use capable_core::profiler::Profiler;
+use capable_core::strace::trace_filter;
+
+/// Interval between two checks of an attached process
+const ATTACH_INTERVAL: Duration = Duration::from_millis(200);
+
+fn to_dict<T: Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(anyhow::Error::from)?;
+    Ok(py
+        .import_bound("json")?
+        .call_method1("loads", (json,))?
+        .unbind())
+}
+
+fn pid_namespace(pid: u32) -> Result<u32, anyhow::Error> {
+    setptrace_effective(true)?;
+    let nspid = metadata(format!("/proc/{}/ns/pid", pid));
+    setptrace_effective(false)?;
+    Ok(nspid?.ino() as u32)
+}
+
+/// Run the command in a new PID namespace and return its profile
+#[pyfunction]
+#[pyo3(signature = (command, dbus = false))]
+fn run(py: Python<'_>, command: Vec<String>, dbus: bool) -> PyResult<PyObject> {
+    let Some(exec) = command.first() else {
+        return Err(PyValueError::new_err("The command is empty"));
+    };
+    let workdir = tempfile::tempdir()?;
+    let strace_log = workdir.path().join("strace.log");
+    let events = Profiler::new()
+        .strace_log(&strace_log)
+        .dbus(dbus)
+        .events()?;
+    let (path, args) = match which::which("strace") {
+        Ok(strace) => {
+            let mut args = vec![
+                "-f".to_string(),
+                "-e".to_string(),
+                trace_filter(),
+                "-o".to_string(),
+                strace_log.display().to_string(),
+            ];
+            args.extend(command.iter().cloned());
+            (strace, args)
+        }
+        Err(_) => (PathBuf::from(exec), command[1..].to_vec()),
+    };
+    let ns = Rc::new(Cell::new(0));
+    let nsclone = ns.clone();
+    let mut child = unshare::Command::new(path)
+        .args(&args)
+        .unshare(&[unshare::Namespace::Pid])
+        .before_unfreeze(move |pid| {
+            nsclone.set(pid_namespace(pid)?);
+            Ok(())
+        })
+        .spawn()
+        .map_err(|e| anyhow!("Unable to run {}: {}", exec, e))?;
+    events.restrict(ns.get());
+    // other Python threads run meanwhile
+    py.allow_threads(|| child.wait())?;
+    let result = py.allow_threads(|| events.finish())?;
+    to_dict(py, &result)
+}
+
+/// Profile the PID namespace of a running process until it exits, or for `timeout` seconds
+#[pyfunction]
+#[pyo3(signature = (pid, timeout = None, dbus = false))]
+fn attach(py: Python<'_>, pid: u32, timeout: Option<f64>, dbus: bool) -> PyResult<PyObject> {
+    let ns = pid_namespace(pid)?;
+    let events = Profiler::new().namespace(ns).dbus(dbus).events()?;
+    let deadline = timeout.map(|timeout| Instant::now() + Duration::from_secs_f64(timeout));
+    let proc = PathBuf::from(format!("/proc/{}", pid));
+    while proc.exists() && !deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        py.allow_threads(|| thread::sleep(ATTACH_INTERVAL));
+        // interrupted by Ctrl-C
+        py.check_signals()?;
+    }
+    let result = py.allow_threads(|| events.finish())?;
+    to_dict(py, &result)
+}
+
+/// Read a profile written by capable
+#[pyfunction]
+fn report(py: Python<'_>, path: PathBuf) -> PyResult<PyObject> {
+    let result = read_profile(path)?;
+    to_dict(py, &result)
+}
+
+#[pymodule]
+fn capable(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(run, module)?)?;
+    module.add_function(wrap_pyfunction!(attach, module)?)?;
+    module.add_function(wrap_pyfunction!(report, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use capable_common::Pid;
+use capctl::{Cap, CapSet};
+use dbus::arg::PropMap;
+use dbus::blocking::Connection;
+use tracing::{debug, warn};
+
+use capable_core::collect::CapSetEntry;
+
+const NOTIFICATIONS_TIMEOUT: Duration = Duration::from_millis(500);
+/// Notification display time, in milliseconds
+const NOTIFICATION_EXPIRE: i32 = 10_000;
+
+/// Alert when a process requests one of the watched capabilities.
+/// Alerts are always logged as warnings, which go to syslog, and shown as desktop
+/// notifications when a session bus is reachable.
+pub struct Alerter {
+    watched: CapSet,
+    seen: HashSet<(Pid, Cap)>,
+    session: Option<Connection>,
+}
+
+impl Alerter {
+    pub fn new(watched: CapSet) -> Alerter {
+        // capable usually runs through sudo, which may not keep the session bus address
+        let session = Connection::new_session()
+            .inspect_err(|e| debug!("no session bus for desktop notifications: {}", e))
+            .ok();
+        Alerter {
+            watched,
+            seen: HashSet::new(),
+            session,
+        }
+    }
+
+    /// Alert once per process for each watched capability it requested
+    pub fn check<'a, I>(&mut self, entries: I)
+    where
+        I: IntoIterator<Item = &'a CapSetEntry>,
+    {
+        for entry in entries {
+            for cap in (entry.capabilities & self.watched).iter() {
+                if self.seen.insert((entry.pid, cap)) {
+                    self.alert(entry, cap);
+                }
+            }
+        }
+    }
+
+    fn alert(&self, entry: &CapSetEntry, cap: Cap) {
+        let body = format!(
+            "process {} (uid {}, ns {}) requested CAP_{:?}",
+            entry.pid, entry.uid, entry.ns, cap
+        );
+        warn!("capable alert: {}", body);
+        if let Some(conn) = &self.session {
+            let proxy = conn.with_proxy(
+                "org.freedesktop.Notifications",
+                "/org/freedesktop/Notifications",
+                NOTIFICATIONS_TIMEOUT,
+            );
+            let res: Result<(u32,), dbus::Error> = proxy.method_call(
+                "org.freedesktop.Notifications",
+                "Notify",
+                (
+                    "capable",
+                    0u32,
+                    "dialog-warning",
+                    format!("CAP_{:?} requested", cap),
+                    body,
+                    Vec::<String>::new(),
+                    PropMap::new(),
+                    NOTIFICATION_EXPIRE,
+                ),
+            );
+            if let Err(e) = res {
+                debug!("desktop notification failed: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Interactive approval (--interactive): the command is paused on the first request of each
+//! capability until the user acknowledges it, and on the first write to each sensitive file
+//! until the user allows or denies it, as desktop firewalls learn their rules.
+//!
+//! Capabilities are paused by the probe with SIGSTOP. The check already happened when the
+//! process stops, it cannot be denied: the capability is shown and stays in the profile.
+//! File writes are paused by the seccomp collector, a denied write fails with EACCES.
+
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::Context;
+use capable_common::Nsid;
+use capctl::CapSet;
+use nix::sys::signal::{kill, Signal};
+use tracing::{debug, warn};
+
+use capable_core::approvals::Approvals;
+use capable_core::caps::get_cap;
+
+const GATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Writes below these locations are asked for
+const SENSITIVE_PREFIXES: [&str; 8] = [
+    "/etc/",
+    "/boot/",
+    "/usr/",
+    "/lib/",
+    "/root/",
+    "/var/lib/",
+    "/proc/sys/",
+    "/sys/",
+];
+
+#[derive(Default)]
+struct Decisions {
+    acknowledged: CapSet,
+    files: BTreeMap<String, bool>,
+}
+
+/// Asks the user on the terminal, each capability and each file is asked once
+pub struct Firewall {
+    tty: Mutex<File>,
+    decisions: Mutex<Decisions>,
+}
+
+fn process_name(pid: u32) -> String {
+    read_to_string(format!("/proc/{}/comm", pid))
+        .map(|comm| comm.trim_end().to_string())
+        .unwrap_or_else(|_| "?".to_string())
+}
+
+/// Waits for the user to press enter
+fn pause(tty: &mut File, message: &str) -> std::io::Result<()> {
+    write!(tty, "{} [enter] ", message)?;
+    tty.flush()?;
+    let mut answer = String::new();
+    BufReader::new(&*tty).read_line(&mut answer)?;
+    Ok(())
+}
+
+/// Denied unless answered yes
+fn prompt(tty: &mut File, question: &str) -> std::io::Result<bool> {
+    write!(tty, "{} [y/N] ", question)?;
+    tty.flush()?;
+    let mut answer = String::new();
+    BufReader::new(&*tty).read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+impl Firewall {
+    /// The terminal of capable, the command may use its stdin
+    pub fn open() -> Result<Firewall, anyhow::Error> {
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .context("--interactive requires a terminal")?;
+        Ok(Firewall {
+            tty: Mutex::new(tty),
+            decisions: Mutex::new(Decisions::default()),
+        })
+    }
+
+    fn ask(&self, question: &str) -> bool {
+        // the gate and the seccomp collector run in their own threads, one question at once
+        let mut tty = self.tty.lock().unwrap();
+        prompt(&mut tty, question).unwrap_or_else(|e| {
+            warn!("unable to ask on the terminal, denied: {}", e);
+            false
+        })
+    }
+
+    /// Show the capability requested by `pid` until the user acknowledges it, once per
+    /// capability
+    pub fn acknowledge_capability(&self, pid: u32, capability: u32) {
+        let Some(cap) = u8::try_from(capability).ok().and_then(get_cap) else {
+            return;
+        };
+        if self.decisions.lock().unwrap().acknowledged.has(cap) {
+            return;
+        }
+        let mut tty = self.tty.lock().unwrap();
+        if let Err(e) = pause(
+            &mut tty,
+            &format!(
+                "{} ({}) requested CAP_{:?}, resume?",
+                process_name(pid),
+                pid,
+                cap
+            ),
+        ) {
+            warn!("unable to ask on the terminal, resumed: {}", e);
+        }
+        self.decisions.lock().unwrap().acknowledged.add(cap);
+    }
+
+    /// Whether `pid` may change `path` with `syscall`, only sensitive paths are asked for
+    pub fn allow_write(&self, pid: u32, syscall: &str, path: &str) -> bool {
+        if !SENSITIVE_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        {
+            return true;
+        }
+        if let Some(allowed) = self.decisions.lock().unwrap().files.get(path) {
+            return *allowed;
+        }
+        let allowed = self.ask(&format!(
+            "{} ({}) writes {} with {}, allow?",
+            process_name(pid),
+            pid,
+            path,
+            syscall
+        ));
+        self.decisions
+            .lock()
+            .unwrap()
+            .files
+            .insert(path.to_string(), allowed);
+        allowed
+    }
+}
+
+/// Asks for the capabilities the processes of the run are stopped on, and resumes them
+pub struct Interactive {
+    firewall: Arc<Firewall>,
+    approvals: Arc<Mutex<Approvals>>,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+fn resume_decided(firewall: &Firewall, approvals: &Mutex<Approvals>) {
+    let pending = approvals.lock().unwrap().pending();
+    for request in pending {
+        firewall.acknowledge_capability(request.pid as u32, request.capability);
+        if let Err(e) = approvals.lock().unwrap().decide(&request) {
+            debug!("unable to record the decision: {}", e);
+        }
+        // stopped once per request, the process may have been killed since
+        let _ = kill(nix::unistd::Pid::from_raw(request.pid), Signal::SIGCONT);
+    }
+}
+
+impl Interactive {
+    pub fn start(approvals: Arc<Mutex<Approvals>>) -> Result<Interactive, anyhow::Error> {
+        let firewall = Arc::new(Firewall::open()?);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::spawn({
+            let firewall = firewall.clone();
+            let approvals = approvals.clone();
+            let stop = stop.clone();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    resume_decided(&firewall, &approvals);
+                    thread::sleep(GATE_INTERVAL);
+                }
+            }
+        });
+        Ok(Interactive {
+            firewall,
+            approvals,
+            stop,
+            handle,
+        })
+    }
+
+    /// Asks for the file writes, given to the seccomp collector
+    pub fn firewall(&self) -> Arc<Firewall> {
+        self.firewall.clone()
+    }
+
+    /// Maps in which the namespace of the command is watched, before it runs
+    pub fn approvals(&self) -> Arc<Mutex<Approvals>> {
+        self.approvals.clone()
+    }
+
+    /// Stop asking for the namespace `ns`
+    pub fn finish(self, ns: Nsid) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+        if let Err(e) = self.approvals.lock().unwrap().unwatch(ns) {
+            debug!("unable to stop watching namespace {}: {}", ns, e);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::read_link;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use capable_common::Nsid;
+use capable_core::caps::parse_capset_iter;
+use capable_core::collect::{
+    build_result, namespace_capabilities, namespace_reasons, nested_namespaces,
+};
+use capable_core::ebpf::Probes;
+use capable_core::explain;
+use capable_core::profile::TimeWindow;
+use capable_core::time::unix_time;
+
+use crate::plugin::Plugins;
+use crate::record::write_report;
+
+const DRAIN_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Inode of the PID namespace of a namespace file, e.g. /proc/1234/ns/pid
+pub fn namespace_of(path: &Path) -> Result<Nsid, anyhow::Error> {
+    // the link of a namespace file is pid:[inode]
+    if let Ok(link) = read_link(path) {
+        let link = link.to_string_lossy();
+        return match link
+            .strip_prefix("pid:[")
+            .and_then(|inode| inode.strip_suffix(']'))
+        {
+            Some(inode) => Ok(inode.parse()?),
+            None => Err(anyhow::anyhow!(
+                "{} is not a PID namespace: {}",
+                path.display(),
+                link
+            )),
+        };
+    }
+    // a bind mount of the namespace
+    let metadata = path
+        .metadata()
+        .with_context(|| format!("Unable to read namespace {}", path.display()))?;
+    Ok(metadata.ino() as Nsid)
+}
+
+/// Settings of the profile of an existing namespace
+pub struct Attach<'a> {
+    pub ns: Nsid,
+    /// Collect for this many seconds, or until Ctrl-C
+    pub sleep: Option<u64>,
+    pub output: Option<PathBuf>,
+    pub labels: &'a BTreeMap<String, String>,
+    pub startup: Duration,
+}
+
+/// capable --nsid: profile the processes of an existing PID namespace and its descendants,
+/// e.g. a LXC or nspawn container or an unshare sandbox. The namespace is not created by
+/// capable, so its syscalls and D-Bus messages are not collected.
+pub fn profile_namespace(
+    attach: Attach,
+    probes: &mut Probes,
+    plugins: &mut Plugins,
+) -> Result<(), anyhow::Error> {
+    let start = unix_time();
+    println!("Collecting namespace {}, waiting for Ctrl-C...", attach.ns);
+    let term = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&term))?;
+    let deadline = attach
+        .sleep
+        .map(|sleep| Instant::now() + Duration::from_secs(sleep));
+    let mut set_entry = HashSet::new();
+    while !term.load(Ordering::Relaxed) && deadline.is_none_or(|deadline| Instant::now() < deadline)
+    {
+        thread::sleep(DRAIN_INTERVAL);
+        probes.drain(&mut set_entry, None)?;
+    }
+    probes.drain(&mut set_entry, None)?;
+    let window = TimeWindow {
+        start,
+        end: unix_time(),
+    };
+
+    let ns = attach.ns;
+    let tree = probes.namespace_tree(&set_entry)?;
+    let mut capset = namespace_capabilities(&tree, ns, &set_entry, |entry| entry.capabilities);
+    let reasons = namespace_reasons(&tree, ns, &set_entry);
+    let dac = probes.dac_accesses(ns)?;
+    capset |= parse_capset_iter(dac.keys().map(String::as_str))?;
+    let mut result = build_result(capset, None, None, ns, Some(window), reasons);
+    explain::explain_requested_modules(&mut result, &probes.requested_modules(ns)?);
+    explain::explain_dac_accesses(&mut result, &dac);
+    result.self_managed = probes.self_managed(ns)?;
+    result.namespaces = nested_namespaces(&tree, ns, &set_entry);
+    result.labels = attach.labels.clone();
+    result
+        .labels
+        .insert("nsid".to_string(), attach.ns.to_string());
+    // the first process of the namespace, its init
+    let pid = set_entry
+        .iter()
+        .filter(|entry| entry.ns == ns)
+        .map(|entry| entry.pid)
+        .min()
+        .unwrap_or_default();
+    write_report(result, pid, ns, attach.output, plugins, attach.startup)
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use anyhow::Context;
+use capctl::CapSet;
+use tracing::debug;
+
+use capable_core::caps::{capset_to_string, parse_capset_iter};
+use capable_core::profile::{read_profile, TimeWindow};
+
+pub const DEFAULT_AUDIT_LOG: &str = "/var/log/audit/audit.log";
+
+/// Records of a single audit event, they share the same `audit(<time>:<serial>)` identifier
+#[derive(Default)]
+struct AuditEvent {
+    time: f64,
+    comm: Option<String>,
+    capabilities: CapSet,
+}
+
+/// Return the value of `key=value` in an audit record, without quotes
+fn field<'a>(record: &'a str, key: &str) -> Option<&'a str> {
+    record
+        .split_whitespace()
+        .find_map(|kv| kv.strip_prefix(key).and_then(|v| v.strip_prefix('=')))
+        .map(|v| v.trim_matches('"'))
+}
+
+/// Parse `msg=audit(1700000000.123:456):` into its time and serial
+fn event_id(record: &str) -> Option<(f64, u64)> {
+    let start = record.find("msg=audit(")? + "msg=audit(".len();
+    let end = start + record[start..].find(')')?;
+    let (time, serial) = record[start..end].split_once(':')?;
+    Some((time.parse().ok()?, serial.parse().ok()?))
+}
+
+fn hex_capset(record: &str, key: &str) -> CapSet {
+    field(record, key)
+        .and_then(|v| u64::from_str_radix(v, 16).ok())
+        .map(CapSet::from_bitmask_truncate)
+        .unwrap_or(CapSet::empty())
+}
+
+/// Capabilities an audit record reports as used or granted
+fn record_capabilities(kind: &str, record: &str) -> CapSet {
+    match kind {
+        // SELinux checks, capability=N with tclass capability or capability2 (N + 32)
+        "AVC" => match (field(record, "capability"), field(record, "tclass")) {
+            (Some(cap), Some(tclass)) if tclass.starts_with("capability") => {
+                let offset = if tclass == "capability2" { 32 } else { 0 };
+                cap.parse::<u64>()
+                    .ok()
+                    .filter(|n| n + offset < 64)
+                    .map(|n| CapSet::from_bitmask_truncate(1 << (n + offset)))
+                    .unwrap_or(CapSet::empty())
+            }
+            _ => CapSet::empty(),
+        },
+        "CAPSET" => hex_capset(record, "cap_pp") | hex_capset(record, "cap_pe"),
+        "BPRM_FCAPS" => hex_capset(record, "new_pp") | hex_capset(record, "new_pe"),
+        _ => CapSet::empty(),
+    }
+}
+
+fn read_audit_events<P: AsRef<Path>>(path: P) -> Result<BTreeMap<u64, AuditEvent>, anyhow::Error> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .with_context(|| format!("Unable to open audit log {}", path.display()))?;
+    let mut events: BTreeMap<u64, AuditEvent> = BTreeMap::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let (Some(kind), Some((time, serial))) = (field(&line, "type"), event_id(&line)) else {
+            continue;
+        };
+        let event = events.entry(serial).or_default();
+        event.time = time;
+        if event.comm.is_none() {
+            event.comm = field(&line, "comm").map(str::to_string);
+        }
+        event.capabilities |= record_capabilities(kind, &line);
+    }
+    Ok(events)
+}
+
+/// Compare the capabilities of a profile with the ones reported by auditd during its run.
+/// `window` overrides the time window recorded in the profile, `comm` restricts audit events
+/// to a process name.
+pub fn audit_correlate<P: AsRef<Path>, A: AsRef<Path>>(
+    profile: P,
+    audit_log: A,
+    window: Option<TimeWindow>,
+    comm: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let profile = read_profile(profile)?;
+    let window = window.or(profile.window).ok_or(anyhow::anyhow!(
+        "The profile has no time window, use --since and --until"
+    ))?;
+    let observed = parse_capset_iter(profile.capability_names())
+        .context("Invalid capability in profile")?;
+    let mut audited = CapSet::empty();
+    let mut count = 0;
+    for event in read_audit_events(audit_log)?.values() {
+        // audit times have sub-second precision, the window is in whole seconds
+        if event.time < window.start as f64 || event.time >= (window.end + 1) as f64 {
+            continue;
+        }
+        if comm.is_some() && event.comm.as_deref() != comm {
+            continue;
+        }
+        count += 1;
+        audited |= event.capabilities;
+    }
+    debug!("{} audit events in [{}, {}]", count, window.start, window.end);
+
+    println!("Audit events in time window: {}", count);
+    let show = |set: CapSet| {
+        if set.is_empty() {
+            String::from("none")
+        } else {
+            capset_to_string(&set)
+        }
+    };
+    println!("Observed by both: {}", show(observed & audited));
+    println!("Observed by capable only: {}", show(observed - audited));
+    println!("Observed by audit only: {}", show(audited - observed));
+    Ok(())
+}
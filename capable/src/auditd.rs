@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::env::current_exe;
+use std::fs::{create_dir_all, read_link, read_to_string, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use capable_common::{Nsid, Pid};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use capable_core::caps::{capset_to_vec, setptrace_effective};
+use capable_core::collect::CapSetEntry;
+use capable_core::ebpf::Probes;
+use capable_core::profile::TimeWindow;
+use capable_core::time::unix_time;
+
+/// Directory of the inventory and the reports, unless --output is given
+pub const DEFAULT_AUDITD_DIR: &str = "/var/lib/capable/auditd";
+/// Period of the reports, unless --interval is given
+pub const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(3600);
+
+const DRAIN_INTERVAL: Duration = Duration::from_millis(400);
+const INVENTORY_NAME: &str = "inventory.json";
+/// Executable of the processes that exited before their requests were drained
+const UNKNOWN_EXECUTABLE: &str = "unknown";
+
+/// Capabilities exercised by an executable
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct ExecutableUsage {
+    capabilities: BTreeSet<String>,
+    /// Requested capabilities the kernel denied
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    denied: BTreeSet<String>,
+    /// Processes of the executable seen requesting a capability
+    processes: u64,
+    /// Users the processes ran as
+    uids: BTreeSet<u32>,
+    /// First and last request, in seconds since the Unix epoch
+    first_seen: u64,
+    last_seen: u64,
+}
+
+impl ExecutableUsage {
+    fn merge(&mut self, other: &ExecutableUsage) {
+        self.capabilities.extend(other.capabilities.iter().cloned());
+        self.denied.extend(other.denied.iter().cloned());
+        self.processes += other.processes;
+        self.uids.extend(other.uids.iter().copied());
+        self.first_seen = self.first_seen.min(other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+    }
+}
+
+/// Usage of every executable since the audit started, kept across restarts
+#[derive(Serialize, Deserialize)]
+struct Inventory {
+    since: u64,
+    executables: BTreeMap<String, ExecutableUsage>,
+}
+
+/// Usage of the executables during a report period
+#[derive(Serialize)]
+struct Report<'a> {
+    window: TimeWindow,
+    executables: &'a BTreeMap<String, ExecutableUsage>,
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), anyhow::Error> {
+    let mut file =
+        File::create(path).with_context(|| format!("Unable to create {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+fn read_inventory(path: &Path) -> Inventory {
+    read_to_string(path)
+        .ok()
+        .and_then(|content| {
+            serde_json::from_str(&content)
+                .inspect_err(|e| warn!("Ignoring invalid inventory {}: {}", path.display(), e))
+                .ok()
+        })
+        .unwrap_or_else(|| Inventory {
+            since: unix_time(),
+            executables: BTreeMap::new(),
+        })
+}
+
+/// Usage of the executables during the current period
+#[derive(Default)]
+struct Period {
+    executables: BTreeMap<String, ExecutableUsage>,
+    /// Processes already counted
+    processes: HashSet<(Nsid, Pid)>,
+}
+
+impl Period {
+    /// Add the drained requests. The processes are read while they run, the ones that
+    /// exited since their requests are counted as UNKNOWN_EXECUTABLE.
+    fn add(&mut self, entries: &HashSet<CapSetEntry>, now: u64) {
+        let own = std::process::id() as Pid;
+        for entry in entries.iter().filter(|entry| entry.pid != own) {
+            let executable = read_link(format!("/proc/{}/exe", entry.pid))
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| UNKNOWN_EXECUTABLE.to_string());
+            let usage = self
+                .executables
+                .entry(executable)
+                .or_insert(ExecutableUsage {
+                    first_seen: now,
+                    ..Default::default()
+                });
+            usage
+                .capabilities
+                .extend(capset_to_vec(&entry.capabilities));
+            usage.denied.extend(capset_to_vec(&entry.denied));
+            usage.uids.insert(entry.uid);
+            usage.last_seen = now;
+            if self.processes.insert((entry.ns, entry.pid)) {
+                usage.processes += 1;
+            }
+        }
+    }
+}
+
+/// Write the report of the period and the updated inventory in `dir`
+fn report(
+    dir: &Path,
+    inventory: &mut Inventory,
+    period: Period,
+    window: TimeWindow,
+) -> Result<PathBuf, anyhow::Error> {
+    for (executable, usage) in &period.executables {
+        match inventory.executables.get_mut(executable) {
+            Some(known) => known.merge(usage),
+            None => {
+                inventory
+                    .executables
+                    .insert(executable.clone(), usage.clone());
+            }
+        }
+    }
+    let path = dir.join(format!("report-{}.json", window.end));
+    write_json(
+        &path,
+        &Report {
+            window,
+            executables: &period.executables,
+        },
+    )?;
+    write_json(&dir.join(INVENTORY_NAME), inventory)?;
+    Ok(path)
+}
+
+/// capable auditd unit: print the service running the audit permanently
+pub fn unit(output: Option<&Path>) -> Result<(), anyhow::Error> {
+    let capable = current_exe().context("Unable to find the capable executable")?;
+    let dir = output.unwrap_or(Path::new(DEFAULT_AUDITD_DIR));
+    println!(
+        "[Unit]
+Description=Inventory of the capabilities used by the executables of the host
+RequiresMountsFor={dir}
+
+[Service]
+ExecStart={capable} auditd --output {dir}
+Restart=on-failure
+
+[Install]
+WantedBy=multi-user.target",
+        dir = dir.display(),
+        capable = capable.display()
+    );
+    Ok(())
+}
+
+/// capable auditd: collect the requests of every process of the host until terminated.
+/// Every `interval`, the usage of each executable during the period is written as a report
+/// in `output`, and merged into the inventory of the host.
+pub fn auditd(
+    output: Option<&Path>,
+    interval: Option<Duration>,
+    bpf_object: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let dir = output.unwrap_or(Path::new(DEFAULT_AUDITD_DIR));
+    create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+    let interval = interval.unwrap_or(DEFAULT_REPORT_INTERVAL);
+    let mut inventory = read_inventory(&dir.join(INVENTORY_NAME));
+    let mut probes = Probes::load(bpf_object)?;
+    // the executables of the processes of the other users
+    if let Err(e) = setptrace_effective(true) {
+        debug!("unable to raise CAP_SYS_PTRACE: {}", e);
+    }
+    info!(
+        "auditing the capabilities of the host, reports every {}s in {}",
+        interval.as_secs(),
+        dir.display()
+    );
+    let term = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&term))?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&term))?;
+    let mut period = Period::default();
+    let mut start = unix_time();
+    let mut period_start = Instant::now();
+    // drained and cleared, memory depends on the executables and not on the uptime
+    let mut set_entry = HashSet::new();
+    loop {
+        let terminated = term.load(Ordering::Relaxed);
+        if !terminated {
+            thread::sleep(DRAIN_INTERVAL);
+        }
+        probes.drain(&mut set_entry, None)?;
+        let now = unix_time();
+        period.add(&set_entry, now);
+        set_entry.clear();
+        if terminated || period_start.elapsed() >= interval {
+            let window = TimeWindow { start, end: now };
+            let path = report(dir, &mut inventory, std::mem::take(&mut period), window)?;
+            debug!("report written to {}", path.display());
+            start = now;
+            period_start = Instant::now();
+        }
+        if terminated {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::env::current_exe;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context;
+use serde::Deserialize;
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+use tracing::warn;
+
+use capable_core::profile::{read_profile, ProgramResult};
+use capable_core::{compress, containers};
+
+/// Command of a job, a shell-quoted string or its words
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JobCommand {
+    Line(String),
+    Words(Vec<String>),
+}
+
+impl JobCommand {
+    fn words(&self) -> Result<Vec<String>, anyhow::Error> {
+        match self {
+            JobCommand::Line(line) => Ok(shell_words::split(line)?),
+            JobCommand::Words(words) => Ok(words.clone()),
+        }
+    }
+}
+
+/// Command of the catalog, profiled on its own
+#[derive(Deserialize)]
+struct Job {
+    /// Name of the job, stored as the job label of its profile. Defaults to the program.
+    name: Option<String>,
+    command: JobCommand,
+    /// User the command runs as, see --user
+    user: Option<String>,
+    #[serde(default)]
+    environment: BTreeMap<String, String>,
+    /// Profile of the job, relative to the jobs file
+    output: PathBuf,
+}
+
+/// Jobs file of capable batch
+#[derive(Deserialize)]
+struct Jobs {
+    jobs: Vec<Job>,
+    /// Profile with what every job requires, relative to the jobs file
+    aggregate: Option<PathBuf>,
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct JobRow {
+    job: String,
+    status: String,
+    output: String,
+}
+
+/// Profile one job with this executable
+fn run(capable: &Path, name: &str, job: &Job, output: &Path) -> Result<(), anyhow::Error> {
+    let command = job.command.words()?;
+    if command.is_empty() {
+        return Err(anyhow::anyhow!("empty command"));
+    }
+    let mut capable = Command::new(capable);
+    capable
+        .arg("--quiet")
+        .arg("--output")
+        .arg(output)
+        .arg("--label")
+        .arg(format!("job={}", name));
+    if let Some(user) = &job.user {
+        capable.arg("--user").arg(user);
+    }
+    for (key, value) in &job.environment {
+        capable.arg("--env").arg(format!("{}={}", key, value));
+    }
+    let exit = capable
+        .arg("--")
+        .args(command)
+        .status()
+        .context("Unable to run capable")?;
+    if !exit.success() {
+        return Err(anyhow::anyhow!("capable {}", exit));
+    }
+    Ok(())
+}
+
+/// capable batch: profile each job of a jobs file, write its profile and the aggregate of
+/// all of them. A failing job does not stop the others.
+pub fn batch(jobs: &Path, output: Option<&Path>) -> Result<(), anyhow::Error> {
+    let content = read_to_string(jobs)
+        .with_context(|| format!("Unable to read jobs file {}", jobs.display()))?;
+    let catalog: Jobs = serde_yaml::from_str(&content)
+        .with_context(|| format!("Invalid jobs file {}", jobs.display()))?;
+    let base = jobs.parent().unwrap_or(Path::new("."));
+    let capable = current_exe().context("Unable to find the capable executable")?;
+    let mut aggregate: Option<ProgramResult> = None;
+    let mut rows = Vec::new();
+    let mut failed = 0;
+    for (index, job) in catalog.jobs.iter().enumerate() {
+        let name = match (&job.name, job.command.words()) {
+            (Some(name), _) => name.clone(),
+            (None, Ok(command)) if !command.is_empty() => command[0].clone(),
+            _ => format!("job {}", index + 1),
+        };
+        let path = base.join(&job.output);
+        let status = run(&capable, &name, job, &path).and_then(|_| read_profile(&path));
+        let status = match status {
+            Ok(result) => {
+                match aggregate.as_mut() {
+                    Some(aggregate) => aggregate.merge(result),
+                    None => aggregate = Some(result),
+                }
+                "ok".to_string()
+            }
+            Err(error) => {
+                warn!("Job {} failed: {:#}", name, error);
+                failed += 1;
+                format!("FAILED: {:#}", error)
+            }
+        };
+        rows.push(JobRow {
+            job: name,
+            status,
+            output: path.display().to_string(),
+        });
+    }
+    if let Some(mut aggregate) = aggregate {
+        aggregate.labels.remove("job");
+        aggregate.containers = containers::compare(&aggregate);
+        let json = serde_json::to_string_pretty(&aggregate)?;
+        match catalog
+            .aggregate
+            .map(|path| base.join(path))
+            .or(output.map(Path::to_path_buf))
+        {
+            Some(path) => {
+                compress::write(&path, &json)?;
+                rows.push(JobRow {
+                    job: "aggregate".to_string(),
+                    status: "ok".to_string(),
+                    output: path.display().to_string(),
+                });
+            }
+            None => println!("{}", json),
+        }
+    }
+    eprintln!("{}", Table::new(&rows).with(Style::modern()));
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} jobs failed",
+            failed,
+            catalog.jobs.len()
+        ));
+    }
+    Ok(())
+}
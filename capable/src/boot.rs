@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashSet};
+use std::env::{current_exe, var_os};
+use std::fs::create_dir_all;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+use tracing::{debug, warn};
+
+use capable_core::collect::CapSetEntry;
+use capable_core::ebpf::Probes;
+use capable_core::profile::TimeWindow;
+use capable_core::time::unix_time;
+
+use crate::cgroup;
+use crate::units::{entries_result, write_profile, UnitCgroups, NO_UNIT};
+
+/// Directory of the per-unit profiles, unless --output is given
+pub const DEFAULT_BOOT_DIR: &str = "/var/lib/capable/boot";
+
+const DRAIN_INTERVAL: Duration = Duration::from_millis(400);
+
+/// cgroup id of capable itself, its own requests are not part of the boot
+fn own_cgroup() -> Option<u64> {
+    let path = cgroup::own_path().ok()?;
+    path.metadata().ok().map(|metadata| metadata.ino())
+}
+
+/// Whether systemd started all the units of the boot transaction
+fn boot_completed() -> Option<bool> {
+    let output = Command::new("systemctl")
+        .arg("is-system-running")
+        .output()
+        .ok()?;
+    let state = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    debug!("system state: {}", state);
+    Some(!matches!(state.as_str(), "initializing" | "starting" | ""))
+}
+
+/// Tell systemd the probes are attached, the units ordered after capable may start
+fn notify_ready() -> Result<(), anyhow::Error> {
+    let Some(socket) = var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let socket = socket.to_string_lossy().into_owned();
+    let address = match socket.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(&socket)?,
+    };
+    UnixDatagram::unbound()?
+        .send_to_addr(b"READY=1", &address)
+        .context("Unable to notify systemd")?;
+    Ok(())
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct UnitRow {
+    unit: String,
+    capabilities: String,
+    profile: String,
+}
+
+/// capable boot-profile unit: print the service starting the boot profiling before the
+/// other units
+pub fn unit(output: Option<&Path>) -> Result<(), anyhow::Error> {
+    let capable = current_exe().context("Unable to find the capable executable")?;
+    let dir = output.unwrap_or(Path::new(DEFAULT_BOOT_DIR));
+    println!(
+        "[Unit]
+Description=Profile the capabilities of the boot sequence
+DefaultDependencies=no
+RequiresMountsFor={dir}
+Before=sysinit.target shutdown.target
+Conflicts=shutdown.target
+
+[Service]
+Type=notify
+ExecStart={capable} boot-profile --output {dir}
+TimeoutStartSec=30
+
+[Install]
+WantedBy=sysinit.target",
+        dir = dir.display(),
+        capable = capable.display()
+    );
+    Ok(())
+}
+
+/// capable boot-profile: collect from early boot until systemd completes the boot, then
+/// `settle` seconds more, and write the profile of each unit in `output`
+pub fn boot_profile(
+    output: Option<&Path>,
+    settle: Option<u64>,
+    bpf_object: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let dir = output.unwrap_or(Path::new(DEFAULT_BOOT_DIR));
+    let mut probes = Probes::load(bpf_object)?;
+    let start = unix_time();
+    notify_ready()?;
+    let term = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&term))?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&term))?;
+    let own = own_cgroup();
+    let mut cgroups = UnitCgroups::new("");
+    let mut set_entry = HashSet::new();
+    let mut completed: Option<Instant> = None;
+    // without systemctl, until terminated
+    let mut follow_state = true;
+    let settle = Duration::from_secs(settle.unwrap_or(0));
+    while !term.load(Ordering::Relaxed) {
+        thread::sleep(DRAIN_INTERVAL);
+        probes.drain(&mut set_entry, None)?;
+        // the oneshot units may be done, their cgroups are the ones recorded by the probes
+        cgroups.record(probes.cgroup_paths()?);
+        if let Some(at) = completed {
+            if at.elapsed() >= settle {
+                break;
+            }
+        } else if follow_state {
+            match boot_completed() {
+                Some(true) => completed = Some(Instant::now()),
+                Some(false) => {}
+                None => {
+                    warn!("Unable to get the system state, collecting until terminated");
+                    follow_state = false;
+                }
+            }
+        }
+    }
+    probes.drain(&mut set_entry, None)?;
+    cgroups.record(probes.cgroup_paths()?);
+    let window = TimeWindow {
+        start,
+        end: unix_time(),
+    };
+
+    let mut units: BTreeMap<&str, Vec<&CapSetEntry>> = BTreeMap::new();
+    for entry in set_entry.iter().filter(|entry| Some(entry.cgroup) != own) {
+        units
+            .entry(cgroups.unit(entry.cgroup).unwrap_or(NO_UNIT))
+            .or_default()
+            .push(entry);
+    }
+    create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+    let mut rows = Vec::new();
+    for (unit, entries) in units {
+        let mut result = entries_result(&entries, window);
+        result.labels.insert("unit".to_string(), unit.to_string());
+        result
+            .labels
+            .insert("phase".to_string(), "boot".to_string());
+        let path = write_profile(dir, unit, &result)?;
+        rows.push(UnitRow {
+            unit: unit.to_string(),
+            capabilities: result.capability_names().collect::<Vec<_>>().join(", "),
+            profile: path.display().to_string(),
+        });
+    }
+    eprintln!("{}", Table::new(&rows).with(Style::modern()));
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::fs::{create_dir, read_to_string, remove_dir, write, File};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tracing::{debug, warn};
+
+/// cgroup v2 hierarchy
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Hint given when the cgroup of capable cannot hold the controllers of the limits
+const DELEGATE_HINT: &str =
+    "run capable in a delegated scope, e.g. systemd-run --scope -p Delegate=yes";
+
+/// Path of the cgroup of capable itself in the cgroup v2 hierarchy
+pub fn own_path() -> Result<PathBuf, anyhow::Error> {
+    let cgroups =
+        read_to_string("/proc/self/cgroup").context("Unable to read the cgroup of capable")?;
+    let path = cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .context("capable is not in a cgroup v2 hierarchy")?;
+    Ok(Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+}
+
+/// Resource limits applied to the profiled command, in the cgroup v2 interface format
+#[derive(Default, Clone)]
+pub struct CgroupLimits {
+    /// memory.max, e.g. 512M
+    pub memory_max: Option<String>,
+    /// cpu.max, e.g. "50000 100000" for half a CPU
+    pub cpu_max: Option<String>,
+}
+
+impl CgroupLimits {
+    fn controllers(&self) -> Vec<&'static str> {
+        let mut controllers = Vec::new();
+        if self.memory_max.is_some() {
+            controllers.push("memory");
+        }
+        if self.cpu_max.is_some() {
+            controllers.push("cpu");
+        }
+        controllers
+    }
+}
+
+/// Controllers prefixed with `sign`, as written to cgroup.subtree_control
+fn subtree_control(controllers: &[&str], sign: char) -> String {
+    controllers
+        .iter()
+        .map(|controller| format!("{}{}", sign, controller))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Controllers enabled by capable in its own cgroup. A cgroup whose children use a controller
+/// cannot hold processes, capable moves itself to a leaf cgroup until they are disabled.
+struct Delegation {
+    parent: PathBuf,
+    leaf: PathBuf,
+    controllers: Vec<&'static str>,
+}
+
+impl Delegation {
+    /// Enable `controllers` for the children of `parent`, the cgroup of capable, unless they
+    /// already are
+    fn enable(
+        parent: &Path,
+        controllers: &[&'static str],
+    ) -> Result<Option<Delegation>, anyhow::Error> {
+        let available = read_to_string(parent.join("cgroup.controllers")).with_context(|| {
+            format!(
+                "Unable to read the controllers of cgroup {}",
+                parent.display()
+            )
+        })?;
+        let available: Vec<&str> = available.split_whitespace().collect();
+        if let Some(missing) = controllers
+            .iter()
+            .find(|controller| !available.contains(controller))
+        {
+            return Err(anyhow::anyhow!(
+                "The {} controller is not delegated to cgroup {}, {}",
+                missing,
+                parent.display(),
+                DELEGATE_HINT
+            ));
+        }
+        let enabled = read_to_string(parent.join("cgroup.subtree_control")).unwrap_or_default();
+        let enabled: Vec<&str> = enabled.split_whitespace().collect();
+        let controllers: Vec<&'static str> = controllers
+            .iter()
+            .filter(|controller| !enabled.contains(controller))
+            .copied()
+            .collect();
+        if controllers.is_empty() {
+            return Ok(None);
+        }
+        if parent == Path::new(CGROUP_ROOT) {
+            return Err(anyhow::anyhow!(
+                "capable runs in the root cgroup, whose controllers it does not change, {}",
+                DELEGATE_HINT
+            ));
+        }
+        let leaf = parent.join(format!("capable-{}-supervisor", std::process::id()));
+        create_dir(&leaf).with_context(|| format!("Unable to create cgroup {}", leaf.display()))?;
+        let delegation = Delegation {
+            parent: parent.to_path_buf(),
+            leaf,
+            controllers,
+        };
+        write(
+            delegation.leaf.join("cgroup.procs"),
+            std::process::id().to_string(),
+        )
+        .with_context(|| {
+            format!(
+                "Unable to move capable to cgroup {}",
+                delegation.leaf.display()
+            )
+        })?;
+        write(
+            parent.join("cgroup.subtree_control"),
+            subtree_control(&delegation.controllers, '+'),
+        )
+        .with_context(|| {
+            format!(
+                "Unable to enable the cgroup controllers of the limits in {}, other processes share it, {}",
+                parent.display(),
+                DELEGATE_HINT
+            )
+        })?;
+        debug!(
+            "enabled the {} controllers in cgroup {}",
+            delegation.controllers.join(" "),
+            parent.display()
+        );
+        Ok(Some(delegation))
+    }
+}
+
+impl Drop for Delegation {
+    fn drop(&mut self) {
+        if let Err(e) = write(
+            self.parent.join("cgroup.subtree_control"),
+            subtree_control(&self.controllers, '-'),
+        ) {
+            warn!(
+                "Unable to disable the controllers of cgroup {}: {}",
+                self.parent.display(),
+                e
+            );
+        }
+        if let Err(e) = write(
+            self.parent.join("cgroup.procs"),
+            std::process::id().to_string(),
+        ) {
+            warn!(
+                "Unable to move capable back to cgroup {}: {}",
+                self.parent.display(),
+                e
+            );
+        }
+        if let Err(e) = remove_dir(&self.leaf) {
+            warn!("Unable to remove cgroup {}: {}", self.leaf.display(), e);
+        }
+    }
+}
+
+/// Transient cgroup holding the profiled command and everything it forks, created below the
+/// cgroup of capable and removed on drop
+pub struct Cgroup {
+    path: PathBuf,
+    /// Dropped after the cgroup is removed
+    _delegation: Option<Delegation>,
+}
+
+impl Cgroup {
+    pub fn create(limits: &CgroupLimits) -> Result<Cgroup, anyhow::Error> {
+        let parent = own_path()?;
+        let controllers = limits.controllers();
+        let delegation = if controllers.is_empty() {
+            None
+        } else {
+            Delegation::enable(&parent, &controllers)?
+        };
+        let path = parent.join(format!("capable-{}", std::process::id()));
+        create_dir(&path)
+            .with_context(|| format!("Unable to create cgroup {}, is cgroup v2 mounted?", path.display()))?;
+        let cgroup = Cgroup {
+            path,
+            _delegation: delegation,
+        };
+        if let Some(memory_max) = &limits.memory_max {
+            write(cgroup.path.join("memory.max"), memory_max).context("Invalid --memory-max")?;
+        }
+        if let Some(cpu_max) = &limits.cpu_max {
+            write(cgroup.path.join("cpu.max"), cpu_max).context("Invalid --cpu-max")?;
+        }
+        debug!("created cgroup {}", cgroup.path.display());
+        Ok(cgroup)
+    }
+
+    /// The cgroup id returned by bpf_get_current_cgroup_id is the inode of its directory
+    pub fn id(&self) -> Result<u64, anyhow::Error> {
+        Ok(std::fs::metadata(&self.path)?.ino())
+    }
+
+    /// Directory of the cgroup, to attach cgroup programs to
+    pub fn open(&self) -> std::io::Result<File> {
+        File::open(&self.path)
+    }
+
+    /// File a process writes "0" to in order to join the cgroup
+    pub fn procs(&self) -> PathBuf {
+        self.path.join("cgroup.procs")
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // the cgroup can only be removed once all its processes have exited
+        if let Err(e) = remove_dir(&self.path) {
+            warn!("Unable to remove cgroup {}: {}", self.path.display(), e);
+        }
+    }
+}
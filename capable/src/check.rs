@@ -0,0 +1,378 @@
+use std::fmt::Display;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use capctl::{Cap, CapSet, CapState};
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+use capable_core::arch::{kernel_arch, PROBES_ARCH};
+use capable_core::caps::capset_to_string;
+
+/// Capabilities capable needs in its permitted set to load the probes and read the
+/// namespaces of the processes, enough for the daemon mode, top and auditd since Linux 5.8
+const PROBE_CAPABILITIES: [Cap; 3] = [Cap::BPF, Cap::PERFMON, Cap::SYS_PTRACE];
+
+/// Capabilities capable also needs to profile a command: its namespaces and cgroup,
+/// toggling its capabilities, reading the files and the D-Bus monitor
+const COMMAND_CAPABILITIES: [Cap; 6] = [
+    Cap::SYS_ADMIN,
+    Cap::NET_ADMIN,
+    Cap::SETPCAP,
+    Cap::DAC_READ_SEARCH,
+    Cap::SETUID,
+    Cap::SYS_RESOURCE,
+];
+
+/// Optional tools, with what is lost without them
+const TOOLS: [(&str, &str); 6] = [
+    (
+        "strace",
+        "install strace to collect the file accesses, or use --collector seccomp",
+    ),
+    ("sh", "install a shell to use --hold"),
+    ("chsr", "install RootAsRole to use --install-role"),
+    (
+        "sr",
+        "install RootAsRole for the wrappers of --emit-wrapper to use sr",
+    ),
+    (
+        "setpriv",
+        "install util-linux for the wrappers of --emit-wrapper to use setpriv",
+    ),
+    (
+        "systemd-run",
+        "install systemd for the wrappers of --emit-wrapper to use systemd-run",
+    ),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    /// capable works, with less findings or features
+    Warning,
+    /// capable cannot profile
+    Failed,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Warning => write!(f, "warning"),
+            Status::Failed => write!(f, "FAILED"),
+        }
+    }
+}
+
+struct Diagnostic {
+    check: String,
+    status: Status,
+    detail: String,
+    /// How to fix the check when it is not ok
+    remediation: String,
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct CheckRow {
+    check: String,
+    status: Status,
+    detail: String,
+}
+
+impl Diagnostic {
+    fn new(check: &str, status: Status, detail: String, remediation: &str) -> Diagnostic {
+        Diagnostic {
+            check: check.to_string(),
+            status,
+            detail,
+            remediation: remediation.to_string(),
+        }
+    }
+}
+
+fn read_sysctl(path: &str) -> Option<String> {
+    read_to_string(path)
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+fn kprobes() -> Diagnostic {
+    let available = [
+        "/sys/bus/event_source/devices/kprobe",
+        "/sys/kernel/tracing/kprobe_events",
+        "/sys/kernel/debug/tracing/kprobe_events",
+    ]
+    .iter()
+    .any(|path| Path::new(path).exists());
+    if available {
+        Diagnostic::new("kprobes", Status::Ok, "available".to_string(), "")
+    } else {
+        Diagnostic::new(
+            "kprobes",
+            Status::Failed,
+            "unavailable".to_string(),
+            "build the kernel with CONFIG_KPROBES and CONFIG_KPROBE_EVENTS, capability checks are observed with kprobes",
+        )
+    }
+}
+
+fn architecture() -> Diagnostic {
+    match kernel_arch() {
+        Some(kernel) if kernel == PROBES_ARCH => {
+            Diagnostic::new("architecture", Status::Ok, kernel, "")
+        }
+        Some(kernel) => Diagnostic::new(
+            "architecture",
+            Status::Failed,
+            format!("probes built for {}, kernel {}", PROBES_ARCH, kernel),
+            "the probes read the registers of the kernel architecture, build capable for it or load probes built for it with --bpf-object",
+        ),
+        None => Diagnostic::new(
+            "architecture",
+            Status::Warning,
+            "unknown".to_string(),
+            "uname failed, the probes are built for the architecture of capable",
+        ),
+    }
+}
+
+fn btf() -> Diagnostic {
+    if Path::new("/sys/kernel/btf/vmlinux").exists() {
+        Diagnostic::new("BTF", Status::Ok, "/sys/kernel/btf/vmlinux".to_string(), "")
+    } else {
+        Diagnostic::new(
+            "BTF",
+            Status::Warning,
+            "no kernel BTF".to_string(),
+            "build the kernel with CONFIG_DEBUG_INFO_BTF, the kernel structures of the probes cannot be regenerated for this kernel",
+        )
+    }
+}
+
+fn bpf_lsm() -> Diagnostic {
+    let lsm = read_sysctl("/sys/kernel/security/lsm").unwrap_or_default();
+    if lsm.split(',').any(|module| module == "bpf") {
+        Diagnostic::new("BPF LSM", Status::Ok, lsm, "")
+    } else {
+        Diagnostic::new(
+            "BPF LSM",
+            Status::Warning,
+            if lsm.is_empty() {
+                "unknown".to_string()
+            } else {
+                lsm
+            },
+            "add bpf to the lsm= boot parameter, with CONFIG_BPF_LSM",
+        )
+    }
+}
+
+fn stack_traces() -> Diagnostic {
+    match read_sysctl("/proc/sys/kernel/perf_event_max_stack") {
+        Some(depth) => Diagnostic::new(
+            "stack trace maps",
+            Status::Ok,
+            format!("up to {} frames", depth),
+            "",
+        ),
+        None => Diagnostic::new(
+            "stack trace maps",
+            Status::Failed,
+            "unavailable".to_string(),
+            "build the kernel with CONFIG_PERF_EVENTS, the reasons of the requests are kernel stacks",
+        ),
+    }
+}
+
+fn perf_event_paranoid() -> Diagnostic {
+    let value = read_sysctl("/proc/sys/kernel/perf_event_paranoid");
+    match value.as_deref().and_then(|value| value.parse::<i32>().ok()) {
+        Some(level) if level > 2 => Diagnostic::new(
+            "perf_event_paranoid",
+            Status::Warning,
+            level.to_string(),
+            "perf events are restricted to privileged processes, keep CAP_PERFMON in the permitted set of capable or set kernel.perf_event_paranoid=2",
+        ),
+        Some(level) => Diagnostic::new("perf_event_paranoid", Status::Ok, level.to_string(), ""),
+        None => Diagnostic::new(
+            "perf_event_paranoid",
+            Status::Warning,
+            "unknown".to_string(),
+            "perf events are likely disabled, build the kernel with CONFIG_PERF_EVENTS",
+        ),
+    }
+}
+
+/// Whether /proc/kallsyms shows the addresses of the symbols to this process
+fn kernel_addresses_visible() -> bool {
+    read_to_string("/proc/kallsyms").is_ok_and(|symbols| {
+        symbols
+            .lines()
+            .take(16)
+            .filter_map(|line| line.split_whitespace().next())
+            .any(|address| address.chars().any(|c| c != '0'))
+    })
+}
+
+fn kptr_restrict() -> Diagnostic {
+    let value = read_sysctl("/proc/sys/kernel/kptr_restrict").unwrap_or("unknown".to_string());
+    if kernel_addresses_visible() {
+        Diagnostic::new("kptr_restrict", Status::Ok, value, "")
+    } else {
+        Diagnostic::new(
+            "kptr_restrict",
+            Status::Warning,
+            format!("{}, kernel addresses hidden", value),
+            "run capable as root or set kernel.kptr_restrict=0, kernel stacks cannot be symbolized and the reasons of the requests are lost",
+        )
+    }
+}
+
+fn lockdown() -> Diagnostic {
+    let Some(state) = read_sysctl("/sys/kernel/security/lockdown") else {
+        return Diagnostic::new("lockdown", Status::Ok, "not supported".to_string(), "");
+    };
+    // the current mode is in brackets, e.g. none [integrity] confidentiality
+    let current = state
+        .split_whitespace()
+        .find(|mode| mode.starts_with('['))
+        .map(|mode| mode.trim_matches(|c| c == '[' || c == ']').to_string())
+        .unwrap_or(state);
+    if current == "confidentiality" {
+        Diagnostic::new(
+            "lockdown",
+            Status::Failed,
+            current,
+            "eBPF cannot read kernel memory in confidentiality mode, boot with lockdown=integrity or without lockdown",
+        )
+    } else {
+        Diagnostic::new("lockdown", Status::Ok, current, "")
+    }
+}
+
+fn setcap_remediation(missing: &CapSet) -> String {
+    format!(
+        "run capable as root, or grant them with setcap {}+p on the capable executable",
+        missing
+            .iter()
+            .map(|cap| format!("cap_{:?}", cap).to_lowercase())
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+fn capabilities(permitted: CapSet) -> Diagnostic {
+    let mut required: CapSet = PROBE_CAPABILITIES.iter().copied().collect();
+    if !Cap::PERFMON.is_supported() {
+        // before Linux 5.8, the eBPF operations require SYS_ADMIN
+        required.drop(Cap::BPF);
+        required.drop(Cap::PERFMON);
+        required.add(Cap::SYS_ADMIN);
+    }
+    let missing = required & !permitted;
+    if missing.is_empty() {
+        Diagnostic::new(
+            "capabilities",
+            Status::Ok,
+            "probes permitted".to_string(),
+            "",
+        )
+    } else {
+        Diagnostic::new(
+            "capabilities",
+            Status::Failed,
+            format!("missing {}", capset_to_string(&missing)),
+            &setcap_remediation(&missing),
+        )
+    }
+}
+
+fn command_capabilities(permitted: CapSet) -> Diagnostic {
+    let required: CapSet = COMMAND_CAPABILITIES.iter().copied().collect();
+    let missing = required & !permitted;
+    if missing.is_empty() {
+        Diagnostic::new(
+            "command capabilities",
+            Status::Ok,
+            "all required are permitted".to_string(),
+            "",
+        )
+    } else {
+        Diagnostic::new(
+            "command capabilities",
+            Status::Warning,
+            format!("missing {}", capset_to_string(&missing)),
+            &format!(
+                "only the daemon mode, top and auditd work, to profile commands {}",
+                setcap_remediation(&missing)
+            ),
+        )
+    }
+}
+
+fn tool(name: &str, remediation: &str) -> Diagnostic {
+    let check = format!("tool {}", name);
+    match which::which(name) {
+        Ok(path) => Diagnostic::new(&check, Status::Ok, path.display().to_string(), ""),
+        Err(_) => Diagnostic::new(
+            &check,
+            Status::Warning,
+            "not found".to_string(),
+            remediation,
+        ),
+    }
+}
+
+/// capable check: verify the kernel prerequisites, the capabilities of capable and the
+/// optional tools, with how to fix each failing check
+pub fn check() -> Result<(), anyhow::Error> {
+    let permitted = CapState::get_current()
+        .map(|state| state.permitted)
+        .unwrap_or_else(|_| CapSet::empty());
+    let mut diagnostics = vec![
+        kprobes(),
+        architecture(),
+        btf(),
+        bpf_lsm(),
+        stack_traces(),
+        perf_event_paranoid(),
+        kptr_restrict(),
+        lockdown(),
+        capabilities(permitted),
+        command_capabilities(permitted),
+    ];
+    diagnostics.extend(
+        TOOLS
+            .iter()
+            .map(|(name, remediation)| tool(name, remediation)),
+    );
+    let rows: Vec<CheckRow> = diagnostics
+        .iter()
+        .map(|diagnostic| CheckRow {
+            check: diagnostic.check.clone(),
+            status: diagnostic.status,
+            detail: diagnostic.detail.clone(),
+        })
+        .collect();
+    println!("{}", Table::new(&rows).with(Style::modern()));
+    for diagnostic in diagnostics.iter().filter(|d| d.status != Status::Ok) {
+        println!(
+            "{} {}: {}",
+            diagnostic.status, diagnostic.check, diagnostic.remediation
+        );
+    }
+    let failed = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.status == Status::Failed)
+        .count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} checks failed, capable cannot profile on this system",
+            failed,
+            diagnostics.len()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::fs::metadata;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use capable_common::{Nsid, Pid};
+use capable_core::caps::setptrace_effective;
+use tracing::{debug, warn};
+
+use crate::procstatus::{namespace_pid, namespace_processes};
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Pid of the init in its namespace
+const INIT_PID: Pid = 1;
+/// Pid in the namespace of the command run by a wrapping init, its first child
+const COMMAND_PID: Pid = 2;
+
+/// When to end the collection of a command that leaves processes behind it
+#[derive(Clone, Copy)]
+pub enum CollectUntil {
+    /// When the command exits. The processes it leaves behind are left running: with
+    /// strace they are still collected, otherwise they end with the namespace.
+    Exit,
+    /// When the namespace has no processes left (--until-ns-empty)
+    NsEmpty,
+    /// When the processes left did not access any file for this long (--wait-for-idle)
+    Idle(Duration),
+}
+
+impl CollectUntil {
+    /// Whether the processes left behind by the command are waited for
+    pub fn waits(self) -> bool {
+        !matches!(self, CollectUntil::Exit)
+    }
+}
+
+/// First process of the PID namespace of the run
+pub enum Init {
+    /// The command itself, its processes end with it
+    Command,
+    /// strace or the --hold shell, which run the command and outlive it
+    Wrapper,
+    /// A shell outliving the command only to keep its processes, it is terminated once they
+    /// are done. It writes the exit code of the command to the file once it exited.
+    Linger(PathBuf),
+}
+
+/// Polls the PID namespace of the run to detect a command exiting while processes it
+/// forked keep running, which is how daemons detach. The init outlives the command, so
+/// the namespace stays alive until all of them exit or the run is terminated. Their
+/// activity is the growth of the log of the collector, strace or seccomp.
+pub struct DaemonWatcher {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl DaemonWatcher {
+    /// Set `term`, which terminates the run, once the collection must end
+    pub fn start(
+        until: CollectUntil,
+        ns: Nsid,
+        init: Init,
+        log: PathBuf,
+        term: Arc<AtomicBool>,
+    ) -> DaemonWatcher {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            if matches!(init, Init::Command) {
+                return;
+            }
+            // capabilities are per thread, the namespaces of other users need SYS_PTRACE
+            if let Err(e) = setptrace_effective(true) {
+                debug!("unable to raise CAP_SYS_PTRACE: {}", e);
+            }
+            let mut log_size = 0;
+            let mut last_activity = Instant::now();
+            let mut started = false;
+            let mut reported = false;
+            while !stopped.load(Ordering::Relaxed) && !term.load(Ordering::Relaxed) {
+                thread::sleep(WATCH_INTERVAL);
+                // the log is created by the collector once started
+                let size = metadata(&log).map_or(0, |log| log.len());
+                if size != log_size {
+                    log_size = size;
+                    last_activity = Instant::now();
+                }
+                let processes: Vec<Pid> = namespace_processes(ns)
+                    .into_iter()
+                    .filter_map(namespace_pid)
+                    .collect();
+                let running = processes.contains(&COMMAND_PID);
+                // a short command may be done before the first poll, its processes follow it
+                started |= running
+                    || processes.iter().any(|pid| *pid > COMMAND_PID)
+                    || matches!(&init, Init::Linger(status) if status.exists());
+                if processes.is_empty() || running || !started {
+                    continue;
+                }
+                let left = processes.iter().any(|pid| *pid != INIT_PID);
+                if !left {
+                    if matches!(init, Init::Linger(_)) {
+                        debug!("the command and its processes are done");
+                        term.store(true, Ordering::Relaxed);
+                    }
+                    continue;
+                }
+                if !reported {
+                    reported = true;
+                    match until {
+                        CollectUntil::Exit => warn!("The command daemonized, its processes are left running and collected until they exit. Use --wait-for-idle to end the collection once they are idle"),
+                        _ => warn!("The command daemonized, collecting until its processes are done"),
+                    }
+                }
+                if let CollectUntil::Idle(idle) = until {
+                    if last_activity.elapsed() >= idle {
+                        debug!("ending the collection of the daemonized command");
+                        term.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        });
+        DaemonWatcher { stop, handle }
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
@@ -0,0 +1,114 @@
+//! --debug-dump: the intermediate artifacts of a run, to find out offline why a result is
+//! wrong. The directory holds the raw capability requests, the strace logs with their parsed
+//! syscalls, the D-Bus messages and the analyzed profiles before the plugins.
+
+use std::collections::HashMap;
+use std::fs::{create_dir_all, write, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use capable_common::{Gid, Nsid, Pid, Uid};
+use serde::Serialize;
+
+use capable_core::bus::{self, DbusMsg};
+use capable_core::caps::get_cap;
+use capable_core::collect::{CapSetEntry, RequestObserver};
+use capable_core::profile::ProgramResult;
+use capable_core::strace::parse_strace;
+
+const REQUESTS_NAME: &str = "requests.jsonl";
+
+/// Line of requests.jsonl, a request as drained from the kernel
+#[derive(Serialize)]
+struct RawRequest<'a> {
+    pid: Pid,
+    ppid: Pid,
+    uid: Uid,
+    gid: Gid,
+    ns: Nsid,
+    parent_ns: Nsid,
+    cgroup: u64,
+    capability: String,
+    denied: bool,
+    time: u64,
+    stack: &'a [String],
+}
+
+pub struct DebugDump {
+    dir: PathBuf,
+    requests: BufWriter<File>,
+}
+
+impl DebugDump {
+    pub fn create<P: AsRef<Path>>(dir: P) -> Result<DebugDump, anyhow::Error> {
+        let dir = dir.as_ref();
+        create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+        let path = dir.join(REQUESTS_NAME);
+        let requests =
+            File::create(&path).with_context(|| format!("Unable to create {}", path.display()))?;
+        Ok(DebugDump {
+            dir: dir.to_path_buf(),
+            requests: BufWriter::new(requests),
+        })
+    }
+
+    fn write_json<T: Serialize>(&self, name: &str, value: &T) -> Result<(), anyhow::Error> {
+        let path = self.dir.join(name);
+        write(&path, serde_json::to_string_pretty(value)?)
+            .with_context(|| format!("Unable to write {}", path.display()))
+    }
+
+    /// strace log of the `index`th command of the run, and the syscalls parsed from it
+    pub fn strace(&self, index: usize, log: &str) -> Result<(), anyhow::Error> {
+        let path = self.dir.join(format!("strace-{}.log", index));
+        write(&path, log).with_context(|| format!("Unable to write {}", path.display()))?;
+        self.write_json(&format!("syscalls-{}.json", index), &parse_strace(log))
+    }
+
+    /// Messages of the D-Bus monitor, per PID namespace
+    pub fn dbus(&self, messages: &HashMap<u32, Vec<DbusMsg>>) -> Result<(), anyhow::Error> {
+        bus::write_dbus_result(self.dir.join("dbus.json"), messages)?;
+        Ok(())
+    }
+
+    /// Profile of the `index`th command as analyzed, before the plugins filter it
+    pub fn analysis(&self, index: usize, result: &ProgramResult) -> Result<(), anyhow::Error> {
+        self.write_json(&format!("analysis-{}.json", index), result)
+    }
+
+    pub fn finish(mut self) -> Result<(), anyhow::Error> {
+        self.requests.flush()?;
+        Ok(())
+    }
+}
+
+impl RequestObserver for DebugDump {
+    fn observe(
+        &mut self,
+        entry: &CapSetEntry,
+        capability: u8,
+        denied: bool,
+        time: u64,
+        stack: &[String],
+    ) -> Result<(), anyhow::Error> {
+        let request = RawRequest {
+            pid: entry.pid,
+            ppid: entry.ppid,
+            uid: entry.uid,
+            gid: entry.gid,
+            ns: entry.ns,
+            parent_ns: entry.parent_ns,
+            cgroup: entry.cgroup,
+            capability: get_cap(capability)
+                .map(|cap| format!("CAP_{:?}", cap))
+                .unwrap_or_else(|| capability.to_string()),
+            denied,
+            time,
+            stack,
+        };
+        serde_json::to_writer(&mut self.requests, &request)?;
+        writeln!(self.requests)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use std::ffi::CString;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+use anyhow::Context;
+use capctl::{ambient, bounding, prctl, Cap, CapState};
+use landlock::{
+    path_beneath_rules, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus, ABI,
+};
+use tracing::{debug, warn};
+
+use capable_core::caps::{capset_to_string, parse_capset_iter, setpcap_effective};
+use capable_core::lsm::LsmEnforcer;
+use capable_core::memlock::{memlock_limit, restore_memlock_limit};
+use capable_core::profile::read_profile;
+use capable_core::syscalls::Access as FileAccess;
+
+use crate::cgroup::{Cgroup, CgroupLimits};
+
+/// Newest Landlock ABI used, older kernels get a best-effort subset
+const LANDLOCK_ABI: ABI = ABI::V3;
+
+/// Locations every program expects to write to, they are not reported by the profiling as
+/// they are writable by anyone
+const LANDLOCK_WRITABLE: [&str; 6] = ["/dev/null", "/dev/zero", "/dev/tty", "/dev/pts", "/tmp", "/dev/shm"];
+
+/// Execute the command with exactly the capabilities of the profile.
+/// Capabilities outside the profile are removed from the bounding set, the profile ones are
+/// raised as ambient so they survive execve(), and no_new_privs prevents regaining anything
+/// through setuid or file capabilities. With `landlock`, writes are restricted to the paths
+/// of the profile. Only returns on error.
+pub fn enforce<P: AsRef<Path>>(
+    profile: P,
+    command: &[String],
+    landlock: bool,
+) -> Result<(), anyhow::Error> {
+    if command.is_empty() {
+        return Err(anyhow::anyhow!("No command to enforce the profile on"));
+    }
+    let profile = read_profile(profile)?;
+    let capabilities = parse_capset_iter(profile.capability_names())
+        .context("Invalid capability in profile")?;
+    debug!("enforcing capabilities: {}", capset_to_string(&capabilities));
+
+    let current = CapState::get_current()?;
+    let missing = capabilities & !current.permitted;
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "capable does not hold {} required by the profile",
+            capset_to_string(&missing)
+        ));
+    }
+
+    // required by Landlock when not holding CAP_SYS_ADMIN
+    prctl::set_no_new_privs()?;
+    if landlock {
+        restrict_files(profile.all_files())?;
+    }
+
+    setpcap_effective(true)?;
+    for cap in Cap::iter() {
+        if !capabilities.has(cap) && bounding::read(cap).unwrap_or(false) {
+            bounding::drop(cap).with_context(|| format!("Unable to drop CAP_{:?} from bounding set", cap))?;
+        }
+    }
+    let mut capstate = CapState::empty();
+    capstate.permitted = capabilities;
+    capstate.inheritable = capabilities;
+    capstate.effective = capabilities;
+    capstate.set_current()?;
+    ambient::clear()?;
+    for cap in capabilities.iter() {
+        ambient::raise(cap).with_context(|| format!("Unable to raise CAP_{:?} as ambient", cap))?;
+    }
+
+    let args = command
+        .iter()
+        .map(|arg| CString::new(arg.as_str()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let err = nix::unistd::execvp(&args[0], &args).unwrap_err();
+    Err(anyhow::anyhow!("Unable to execute {}: {}", command[0], err))
+}
+
+/// Execute the command in a transient cgroup where the BPF LSM denies with EPERM every
+/// capability outside the profile. Unlike `enforce`, the credentials of the command are
+/// left untouched: a capability it holds, e.g. as root, is still denied when outside the
+/// profile. Exits with the exit code of the command.
+pub fn enforce_lsm<P: AsRef<Path>>(
+    profile: P,
+    command: &[String],
+    landlock: bool,
+    bpf_object: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    if command.is_empty() {
+        return Err(anyhow::anyhow!("No command to enforce the profile on"));
+    }
+    let profile = read_profile(profile)?;
+    let capabilities = parse_capset_iter(profile.capability_names())
+        .context("Invalid capability in profile")?;
+    debug!("enforcing capabilities with the BPF LSM: {}", capset_to_string(&capabilities));
+
+    let mut enforcer = LsmEnforcer::load(bpf_object)?;
+    let cgroup = Cgroup::create(&CgroupLimits::default())?;
+    let id = cgroup.id()?;
+    enforcer.restrict(id, capabilities)?;
+    let procs = cgroup.procs();
+    let mut files = profile.files;
+    files.extend(profile.runtime);
+    let memlock = memlock_limit();
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..]);
+    unsafe {
+        cmd.pre_exec(move || {
+            std::fs::write(&procs, "0")?;
+            restore_memlock_limit(&memlock)?;
+            if landlock {
+                prctl::set_no_new_privs()?;
+                restrict_files(&files).map_err(std::io::Error::other)?;
+            }
+            Ok(())
+        })
+    };
+    let status = cmd
+        .status()
+        .with_context(|| format!("Unable to execute {}", command[0]))?;
+    for (capability, count) in enforcer.denials(id) {
+        warn!("{} denied {} times, outside the profile", capability, count);
+    }
+    drop(cgroup);
+    drop(enforcer);
+    exit(status.code().unwrap_or(1));
+}
+
+/// Deny writes outside the paths the profile reported as written.
+/// Reads are left untouched, as the profile only reports privileged reads.
+/// The parent directories of the written files may get them created, removed or renamed,
+/// as editors and atomic writes do. Missing paths are skipped: Landlock only takes rules on
+/// existing files.
+fn restrict_files<'a, I>(files: I) -> Result<(), anyhow::Error>
+where
+    I: IntoIterator<Item = (&'a String, &'a FileAccess)>,
+{
+    let write = AccessFs::from_write(LANDLOCK_ABI);
+    let replace = AccessFs::MakeReg | AccessFs::RemoveFile;
+    let written: Vec<PathBuf> = files
+        .into_iter()
+        .filter(|(_, access)| access.contains(FileAccess::W))
+        .map(|(path, _)| PathBuf::from(path))
+        .collect();
+    let mut parents: Vec<PathBuf> = written
+        .iter()
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+        .filter(|parent| parent.exists())
+        .collect();
+    parents.sort();
+    parents.dedup();
+    let mut writable: Vec<PathBuf> = Vec::new();
+    for path in written {
+        if path.exists() {
+            writable.push(path);
+        } else {
+            warn!("{} does not exist, Landlock cannot allow writing it", path.display());
+        }
+    }
+    writable.extend(
+        LANDLOCK_WRITABLE
+            .iter()
+            .map(PathBuf::from)
+            .filter(|path| path.exists()),
+    );
+    debug!("landlock writable paths: {:?}, parents: {:?}", writable, parents);
+    let status = Ruleset::default()
+        .handle_access(write)?
+        .create()?
+        .add_rules(path_beneath_rules(writable, write))?
+        .add_rules(path_beneath_rules(parents, replace))?
+        .restrict_self()?;
+    if status.ruleset != RulesetStatus::FullyEnforced {
+        warn!("Landlock is not fully enforced on this kernel: {:?}", status.ruleset);
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_dir, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use nix::unistd::geteuid;
+use serde::{Deserialize, Serialize};
+use tabled::settings::object::Columns;
+use tabled::settings::{Modify, Style, Width};
+use tabled::{Table, Tabled};
+use tracing::debug;
+
+use capable_core::compress::{self, Output};
+use capable_core::profile::ProgramResult;
+use capable_core::time::format_time;
+
+/// History of root runs, the other users keep theirs in their XDG data directory
+const SYSTEM_HISTORY: &str = "/var/lib/capable/history";
+
+/// A stored profiling run, in <history>/<id>.json.zst, or <history>/<id>.json when stored
+/// before the history was compressed
+#[derive(Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub command: Vec<String>,
+    /// End of the run, in seconds since the Unix epoch
+    pub timestamp: u64,
+    pub result: ProgramResult,
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct HistoryRow {
+    id: u64,
+    date: String,
+    command: String,
+    labels: String,
+    capabilities: String,
+}
+
+/// A run of the history, by id or as the latest run with a label (key=value)
+pub enum RunSelector {
+    Id(u64),
+    Label(String, String),
+}
+
+impl FromStr for RunSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse() {
+            return Ok(RunSelector::Id(id));
+        }
+        parse_label(s).map(|(key, value)| RunSelector::Label(key, value))
+    }
+}
+
+/// Parse a key=value label
+pub fn parse_label(s: &str) -> Result<(String, String), anyhow::Error> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() && !key.starts_with('-') => {
+            Ok((key.to_string(), value.to_string()))
+        }
+        _ => Err(anyhow::anyhow!("Invalid label {}, expected key=value", s)),
+    }
+}
+
+fn format_labels(labels: &BTreeMap<String, String>) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn history_dir() -> Result<PathBuf, anyhow::Error> {
+    if geteuid().is_root() {
+        return Ok(PathBuf::from(SYSTEM_HISTORY));
+    }
+    let data = match std::env::var_os("XDG_DATA_HOME") {
+        Some(data) => PathBuf::from(data),
+        None => PathBuf::from(std::env::var_os("HOME").ok_or(anyhow::anyhow!(
+            "Unable to locate the history, HOME is not set"
+        ))?)
+        .join(".local/share"),
+    };
+    Ok(data.join("capable/history"))
+}
+
+fn entry_path(dir: &Path, id: u64) -> PathBuf {
+    let path = dir.join(format!("{}.json", id));
+    if path.exists() {
+        return path;
+    }
+    dir.join(format!("{}.json.{}", id, compress::EXTENSION))
+}
+
+fn ids(dir: &Path) -> Result<Vec<u64>, anyhow::Error> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut ids: Vec<u64> = read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .map(|name| name.strip_suffix(".zst").unwrap_or(name))
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|id| id.parse().ok())
+        })
+        .collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Store the result of a run, returns its id
+pub fn save(
+    command: &[String],
+    timestamp: u64,
+    result: &ProgramResult,
+) -> Result<u64, anyhow::Error> {
+    let dir = history_dir()?;
+    create_dir_all(&dir).with_context(|| format!("Unable to create history {}", dir.display()))?;
+    let id = ids(&dir)?.last().map_or(1, |last| last + 1);
+    let entry = HistoryEntry {
+        id,
+        command: command.to_vec(),
+        timestamp,
+        result: result.clone(),
+    };
+    let path = entry_path(&dir, id);
+    let file = File::create_new(&path)
+        .with_context(|| format!("Unable to create history entry {}", path.display()))?;
+    let mut file = Output::new(file, true)?;
+    writeln!(file, "{}", serde_json::to_string_pretty(&entry)?)?;
+    file.finish()?;
+    debug!("run saved as {} in {}", id, dir.display());
+    Ok(id)
+}
+
+pub fn load(id: u64) -> Result<HistoryEntry, anyhow::Error> {
+    let path = entry_path(&history_dir()?, id);
+    let content =
+        compress::read_to_string(&path).with_context(|| format!("No run {} in history", id))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Unable to parse history entry {}", path.display()))
+}
+
+/// Latest run matching the selector
+fn select(selector: &RunSelector) -> Result<HistoryEntry, anyhow::Error> {
+    let (key, value) = match selector {
+        RunSelector::Id(id) => return load(*id),
+        RunSelector::Label(key, value) => (key, value),
+    };
+    for id in ids(&history_dir()?)?.into_iter().rev() {
+        match load(id) {
+            Ok(entry) if entry.result.labels.get(key) == Some(value) => return Ok(entry),
+            Ok(_) => (),
+            Err(e) => debug!("skipping history entry {}: {}", id, e),
+        }
+    }
+    Err(anyhow::anyhow!(
+        "No run labelled {}={} in history",
+        key,
+        value
+    ))
+}
+
+/// capable history: list the stored runs having all the `labels`
+pub fn list(labels: &BTreeMap<String, String>) -> Result<(), anyhow::Error> {
+    let dir = history_dir()?;
+    let mut rows = Vec::new();
+    for id in ids(&dir)? {
+        match load(id) {
+            Ok(entry)
+                if labels
+                    .iter()
+                    .all(|(key, value)| entry.result.labels.get(key) == Some(value)) =>
+            {
+                rows.push(HistoryRow {
+                    id,
+                    date: format_time(entry.timestamp),
+                    command: shell_words::join(&entry.command),
+                    labels: format_labels(&entry.result.labels),
+                    capabilities: entry.result.capability_names().collect::<Vec<_>>().join(", "),
+                })
+            }
+            Ok(_) => (),
+            Err(e) => debug!("skipping history entry {}: {}", id, e),
+        }
+    }
+    println!(
+        "{}",
+        Table::new(&rows)
+            .with(Style::modern())
+            .with(Modify::new(Columns::single(2)).with(Width::wrap(40).keep_words()))
+            .with(Modify::new(Columns::last()).with(Width::wrap(52).keep_words()))
+    );
+    Ok(())
+}
+
+/// capable show: print a stored profile, or what it requires beyond a baseline run
+pub fn show(
+    run: &RunSelector,
+    baseline: Option<&RunSelector>,
+    output: Option<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let entry = select(run)?;
+    if let Some(baseline) = baseline {
+        let baseline = select(baseline)?;
+        print!("{}", entry.result.beyond(&baseline.result));
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(&entry.result)?;
+    if let Some(output) = output {
+        compress::write(output, &json)?;
+    } else {
+        println!("{}", json);
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::fs::read_to_string;
+use std::io::stdin;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
+use nix::sys::wait::{waitid, Id, WaitPidFlag, WaitStatus};
+use tracing::debug;
+use unshare::ExitStatus;
+
+const HOLD_POLL: Duration = Duration::from_millis(200);
+
+/// Run by the namespace init instead of the command: writes the exit code of the command
+/// to the file given as first argument, then sleeps so the namespace stays alive
+const HOLD_SCRIPT: &str = r#"hold=$1; shift; "$@"; echo $? > "$hold"; exec sleep infinity"#;
+
+/// Wrap the command so the init of its namespace outlives it, for --hold or to wait for the
+/// processes it leaves behind
+pub fn hold_command(
+    path: PathBuf,
+    args: Vec<String>,
+    status_file: &Path,
+) -> Result<(PathBuf, Vec<String>), anyhow::Error> {
+    let sh = which::which("sh").context("--hold requires sh in $PATH")?;
+    let mut hold_args = vec![
+        "-c".to_string(),
+        HOLD_SCRIPT.to_string(),
+        "capable-hold".to_string(),
+        status_file.display().to_string(),
+        path.display().to_string(),
+    ];
+    hold_args.extend(args);
+    Ok((sh, hold_args))
+}
+
+/// Whether the process exited, without reaping it
+pub fn exited(pid: i32) -> bool {
+    !matches!(
+        waitid(
+            Id::Pid(nix::unistd::Pid::from_raw(pid)),
+            WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT,
+        ),
+        Ok(WaitStatus::StillAlive)
+    )
+}
+
+/// Wait for the command to exit, then keep its namespace until the user is done with it.
+/// Returns the exit status of the command, not the one of the init holding the namespace.
+pub fn hold(
+    child: &Mutex<unshare::Child>,
+    pid: i32,
+    status_file: &Path,
+) -> Result<ExitStatus, anyhow::Error> {
+    while !status_file.exists() && !exited(pid) {
+        thread::sleep(HOLD_POLL);
+    }
+    if !exited(pid) {
+        eprintln!("The command exited, its namespaces are held for inspection:");
+        eprintln!("  nsenter --target {} --all", pid);
+        eprintln!("Press Enter to tear them down");
+        let mut line = String::new();
+        stdin().read_line(&mut line)?;
+    }
+    let mut child = child.lock().expect("failed to lock execution child");
+    if let Err(e) = child.kill() {
+        debug!("held namespace init already exited: {}", e);
+    }
+    let init_status = child.wait()?;
+    Ok(command_status(status_file, init_status))
+}
+
+/// The init of a wrapped command was killed, the exit code is the one of the command, when
+/// it exited
+pub fn command_status(status_file: &Path, init_status: ExitStatus) -> ExitStatus {
+    match read_to_string(status_file)
+        .ok()
+        .and_then(|code| code.trim().parse::<i32>().ok())
+    {
+        Some(code) => ExitStatus::Exited(code as i8),
+        None => init_status,
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use capable_common::{Nsid, Pid};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use capable_core::profile::{capability_key, dbus_key, file_key};
+use capable_core::syscalls::Access;
+
+/// Finding reported to the --on-event program, serialized as JSON on its stdin
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    Capability {
+        capability: String,
+        pid: Pid,
+        ns: Nsid,
+    },
+    FileWrite {
+        path: &'a str,
+        access: Access,
+    },
+    /// Any privileged file access, only sent to plugins
+    File {
+        path: &'a str,
+        access: Access,
+    },
+    Dbus {
+        method: &'a str,
+    },
+}
+
+impl Event<'_> {
+    /// Identify the finding regardless of the process it was observed in
+    pub fn key(&self) -> String {
+        match self {
+            Event::Capability { capability, .. } => capability_key(capability),
+            Event::FileWrite { path, .. } | Event::File { path, .. } => file_key(path),
+            Event::Dbus { method } => dbus_key(method),
+        }
+    }
+}
+
+/// User program executed with `sh -c` the first time each finding is observed
+pub struct EventHook {
+    program: String,
+    seen: HashSet<String>,
+}
+
+impl EventHook {
+    pub fn new(program: String) -> EventHook {
+        EventHook {
+            program,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Run the hook if the finding is new. The hook is waited for, so a slow hook delays the
+    /// next aggregation but no finding is lost. Hook failures are only reported.
+    pub fn notify(&mut self, event: Event) {
+        if !self.seen.insert(event.key()) {
+            return;
+        }
+        if let Err(e) = self.run(&event) {
+            warn!("on-event hook failed: {}", e);
+        }
+    }
+
+    fn run(&self, event: &Event) -> Result<(), anyhow::Error> {
+        let json = serde_json::to_string(event)?;
+        debug!("on-event: {}", json);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.program)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // the hook may exit without reading its input
+            if let Err(e) = writeln!(stdin, "{}", json) {
+                debug!("on-event hook did not read the event: {}", e);
+            }
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            warn!("on-event hook exited with {}", status);
+        }
+        Ok(())
+    }
+}
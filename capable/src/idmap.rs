@@ -0,0 +1,181 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{metadata, read_to_string};
+use std::os::unix::fs::MetadataExt;
+
+use capable_common::{Nsid, Pid};
+use capable_core::collect::CapSetEntry;
+
+/// Range of ids of a uid_map or gid_map
+struct IdRange {
+    inside: u32,
+    outside: u32,
+    count: u32,
+}
+
+/// Mapping of the ids of a user namespace to the ids of the host, as in /proc/PID/uid_map
+#[derive(Default)]
+struct IdMap {
+    ranges: Vec<IdRange>,
+}
+
+impl IdMap {
+    fn read(path: &str) -> Option<IdMap> {
+        let content = read_to_string(path).ok()?;
+        let ranges = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace().map(|field| field.parse().ok());
+                Some(IdRange {
+                    inside: fields.next()??,
+                    outside: fields.next()??,
+                    count: fields.next()??,
+                })
+            })
+            .collect();
+        Some(IdMap { ranges })
+    }
+
+    /// The host ids are the ids of the namespace, as in the initial user namespace
+    fn is_identity(&self) -> bool {
+        self.ranges
+            .iter()
+            .all(|range| range.inside == range.outside)
+    }
+
+    /// Id in the namespace of a host id, None when the namespace does not map it
+    fn inside(&self, outside: u32) -> Option<u32> {
+        self.ranges.iter().find_map(|range| {
+            let offset = outside.checked_sub(range.outside)?;
+            (offset < range.count).then_some(range.inside + offset)
+        })
+    }
+}
+
+/// Names of the ids of a passwd or group file
+fn read_names(path: &str) -> HashMap<u32, String> {
+    read_to_string(path)
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split(':');
+                    let name = fields.next()?;
+                    let id = fields.nth(1)?.parse().ok()?;
+                    Some((id, name.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Ids of a container: the mapping of its user namespace when it has its own, and the
+/// names of its own /etc/passwd and /etc/group
+struct UserNamespace {
+    uid_map: Option<IdMap>,
+    gid_map: Option<IdMap>,
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+/// Id in the namespace with its name, and the host id, e.g. "root (0) / host 100000".
+/// Without a mapping the ids are the host ones, only a name different from the host one
+/// is shown, e.g. "www-data (33) / host tape".
+fn translate(
+    host: u32,
+    host_name: &str,
+    map: Option<&IdMap>,
+    names: &HashMap<u32, String>,
+) -> String {
+    let inside = match map {
+        Some(map) => match map.inside(host) {
+            Some(inside) => inside,
+            None => return format!("unmapped / host {}", host_name),
+        },
+        None => host,
+    };
+    match names.get(&inside) {
+        Some(name) if map.is_none() && name == host_name => host_name.to_string(),
+        Some(name) => format!("{} ({}) / host {}", name, inside, host_name),
+        None => format!("{} / host {}", inside, host_name),
+    }
+}
+
+/// The process sees another root than capable, e.g. the image of a container
+fn has_own_root(pid: Pid) -> bool {
+    match (
+        metadata(format!("/proc/{}/ns/mnt", pid)),
+        metadata("/proc/self/ns/mnt"),
+    ) {
+        (Ok(target), Ok(own)) => target.ino() != own.ino(),
+        _ => false,
+    }
+}
+
+impl UserNamespace {
+    /// None when the process runs in the user and mount namespaces of the host
+    fn of(pid: Pid) -> Option<UserNamespace> {
+        let uid_map = IdMap::read(&format!("/proc/{}/uid_map", pid))?;
+        let gid_map = IdMap::read(&format!("/proc/{}/gid_map", pid)).unwrap_or_default();
+        let mapped = !uid_map.is_identity() || !gid_map.is_identity();
+        // a container sharing the user namespace of the host still has its own passwd
+        if !mapped && !has_own_root(pid) {
+            return None;
+        }
+        Some(UserNamespace {
+            uid_map: mapped.then_some(uid_map),
+            gid_map: mapped.then_some(gid_map),
+            users: read_names(&format!("/proc/{}/root/etc/passwd", pid)),
+            groups: read_names(&format!("/proc/{}/root/etc/group", pid)),
+        })
+    }
+}
+
+/// Containers of the observed PID namespaces. They are read while their processes
+/// run, a process that exited before its namespace was seen is shown with host ids.
+#[derive(Default)]
+pub struct UserNamespaces {
+    namespaces: HashMap<Nsid, Option<UserNamespace>>,
+}
+
+impl UserNamespaces {
+    /// Read the ids of the PID namespaces not seen yet
+    pub fn observe(&mut self, set_entry: &HashSet<CapSetEntry>) {
+        for entry in set_entry {
+            if self.namespaces.contains_key(&entry.ns) {
+                continue;
+            }
+            // the map cannot be read once the process exited, another one may still run
+            if std::fs::metadata(format!("/proc/{}/uid_map", entry.pid)).is_ok() {
+                self.namespaces
+                    .insert(entry.ns, UserNamespace::of(entry.pid));
+            }
+        }
+    }
+
+    fn get(&self, ns: Nsid) -> Option<&UserNamespace> {
+        self.namespaces.get(&ns).and_then(Option::as_ref)
+    }
+
+    /// User of a host uid, with its uid and name in the container of `ns` if it is one
+    pub fn user(&self, ns: Nsid, uid: u32, host_name: &str) -> String {
+        match self.get(ns) {
+            Some(namespace) => {
+                translate(uid, host_name, namespace.uid_map.as_ref(), &namespace.users)
+            }
+            None => host_name.to_string(),
+        }
+    }
+
+    /// Group of a host gid, with its gid and name in the container of `ns` if it is one
+    pub fn group(&self, ns: Nsid, gid: u32, host_name: &str) -> String {
+        match self.get(ns) {
+            Some(namespace) => translate(
+                gid,
+                host_name,
+                namespace.gid_map.as_ref(),
+                &namespace.groups,
+            ),
+            None => host_name.to_string(),
+        }
+    }
+}
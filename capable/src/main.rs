@@ -1,63 +1,303 @@
-use std::borrow::{Borrow, BorrowMut};
 use std::cell::RefCell;
-use std::collections::HashSet;
-use std::error::Error;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::ffi::CString;
-use std::fs::{canonicalize, metadata, File};
-use std::hash::Hash;
-use std::io::Write;
+use std::fs::{canonicalize, metadata, read_to_string, remove_file, Permissions};
+use std::io::{IsTerminal, Write};
+use std::os::unix::fs::PermissionsExt;
 use std::os::unix::prelude::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::Context;
-use aya::maps::{MapData, Stack, StackTraceMap};
-use aya::programs::KProbe;
-use aya::util::{kernel_symbols, KernelVersion};
-use aya::{include_bytes_aligned, Ebpf};
-use aya_log::EbpfLogger;
-use bus::{run_dbus_monitor, Memory};
-use capable_common::{Nsid, Pid, Request};
-use capctl::{ambient, Cap, CapSet, CapState, ParseCapError};
+use capable_common::{Nsid, Pid};
+use capable_core::bus::{self, run_dbus_monitor, Memory};
+use capable_core::caps::{
+    capset_to_string, parse_capset_iter, setadmin_effective, setbpf_effective,
+    setpcap_effective, setptrace_effective,
+};
+use capable_core::collect::{
+    build_result, namespace_capabilities, CapSetEntry, RequestObserver,
+};
+use capable_core::compress::{self, Output};
+use capable_core::ebpf::{NamespaceCapabilities, Probes};
+use capable_core::memlock::{memlock_limit, restore_memlock_limit};
+use capable_core::policy::Policy;
+use capable_core::profile::{
+    read_profile, CapabilityChange, HeldCapabilities, NestedNamespace, Observation,
+    ProgramResult, SetuidTransition, SysctlAccess, TimeWindow,
+};
+use capable_core::reasons::Reasons;
+use capable_core::recording::Recorder;
+use capable_core::risk::RiskReport;
+use capable_core::stats::SeccompStats;
+use capable_core::strace::{parse_strace_counted, trace_filter};
+use capable_core::syscalls::Access;
+use capable_core::time::{parse_age, unix_time};
+use capable_core::{containers, explain, phase, pinned, rootasrole, wrapper};
+use capctl::{ambient, CapSet, CapState};
+use alert::Alerter;
+use approve::Interactive;
+use attach::Attach;
+use hooks::{Event, EventHook};
+use idmap::UserNamespaces;
+use plugin::Plugins;
+use procstatus::{HeldSampler, HeldSets};
+use runas::RunAs;
+use session::{indexed_path, join_commands, SessionMode};
+use sign::{sign_output, SigningKey};
 use log::{debug, warn};
-use nix::sys::signal::kill;
+use nix::sys::signal::{kill, Signal};
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::{fork, getpid, ForkResult, Uid};
+use nix::unistd::{fork, ForkResult, Uid};
+use cgroup::{Cgroup, CgroupLimits};
+use daemonize::{CollectUntil, DaemonWatcher, Init};
+use dump::DebugDump;
+use history::{parse_label, RunSelector};
+use hold::{command_status, exited, hold, hold_command};
+use setuid::SetuidMode;
+use unotify::{Collector, Listener};
+use units::UnitCgroups;
+use status::StatusLine;
+use table::TableLayout;
+use trial::TrialReport;
 use serde::{Deserialize, Serialize};
 use signal_hook::consts::TERM_SIGNALS;
 use signal_hook::flag;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{env, thread, vec};
-use strace::read_strace;
-use syscalls::SyscallAccessEntry;
 use tabled::settings::object::Columns;
+use tempfile::TempDir;
 use unshare::ExitStatus;
 
 use tabled::settings::{Modify, Style, Width};
 use tabled::{Table, Tabled};
-use tracing::Level;
+use rolling_file::{BasicRollingFileAppender, RollingConditionBasic};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
-
-mod strace;
-mod syscalls;
-mod version;
-mod bus;
+use tracing_subscriber::Layer;
+
+mod status;
+mod setuid;
+mod trial;
+mod unotify;
+mod units;
+mod alert;
+mod approve;
+mod attach;
+mod audit;
+mod auditd;
+mod batch;
+mod boot;
+mod cgroup;
+mod check;
+mod daemonize;
+mod dump;
+mod enforce;
+mod history;
+mod hold;
+mod hooks;
+mod idmap;
+mod plugin;
+mod procstatus;
+mod record;
+mod prune;
+mod runas;
+mod selftest;
+mod session;
+mod sign;
+mod slice;
+mod table;
+mod top;
+
+enum Subcommand {
+    /// Execute the command restricted to a learned profile
+    Enforce { profile: PathBuf },
+    /// Compare a profile with the audit records of the same time window
+    AuditCorrelate { profile: PathBuf },
+    /// Regenerate the report of a recording
+    Report,
+    /// Generate the report of a fixture of requests and a strace log, without probes
+    Replay {
+        requests: PathBuf,
+        strace_log: Option<PathBuf>,
+    },
+    /// List the stored runs
+    History,
+    /// Print a stored run, or what it requires beyond a baseline run
+    Show {
+        run: RunSelector,
+        baseline: Option<RunSelector>,
+    },
+    /// List, or remove, the entries of a learned profile no recent run exercised. The
+    /// profile may also follow the options.
+    Prune { profile: Option<PathBuf> },
+    /// Check that the requirements of built-in workloads are detected
+    Selftest,
+    /// Built-in workload of the self-test, profiled by it
+    SelftestWorkload { name: String, path: PathBuf },
+    /// Diagnose the prerequisites of capable on this system
+    Check,
+    /// Profile the jobs of a jobs file, and their aggregate
+    Batch { jobs: PathBuf },
+    /// Profile each unit started during the boot
+    BootProfile,
+    /// Print the service running boot-profile early in the boot
+    BootProfileUnit,
+    /// Inventory of the capabilities used by each executable of the host, until terminated
+    Auditd,
+    /// Print the service running auditd
+    AuditdUnit,
+    /// Live view of the processes ranked by capability checks per second
+    Top,
+}
 
 struct Cli {
+    /// Run a subcommand instead of profiling
+    subcommand: Option<Subcommand>,
+
     /// Specify a delay before killing the process
     sleep: Option<u64>,
+
+    /// With auditd, period of the reports
+    interval: Option<Duration>,
     /// collecting data on system and print result at the end
     daemon: bool,
 
-    /// Pass all capabilities when executing the command,
+    /// Pass all capabilities when executing the command, minus the ones of --cap-drop
     capabilities: CapSet,
 
     /// Specify a file to write policy result, reactivate stdin/out/err
     output: Option<PathBuf>,
 
+    /// Aggregation and order of the rows of the daemon mode table
+    table_layout: TableLayout,
+
+    /// With enforce, restrict writes to the paths of the profile with Landlock
+    landlock: bool,
+
+    /// With enforce, deny the capabilities outside the profile with the BPF LSM
+    lsm: bool,
+
+    /// Fail when the command requires more than this approved profile
+    assert: Option<PathBuf>,
+
+    /// Learn incrementally: merge the result into this profile, recording the runs in
+    /// which each entry was observed
+    merge_into: Option<PathBuf>,
+    /// minisign secret key signing the profile written to --output
+    sign_key: Option<PathBuf>,
+
+    /// With prune, entries last observed longer ago than this are stale
+    not_seen_since: Option<Duration>,
+    /// With prune, remove the stale entries instead of listing them only
+    remove: bool,
+
+    /// Add the resulting profile as a task of a RootAsRole role, formatted as role/task
+    install_role: Option<String>,
+
+    /// Write a launcher script running the command with the resulting profile
+    emit_wrapper: Option<PathBuf>,
+
+    /// Program receiving each new finding as JSON on stdin
+    on_event: Option<String>,
+
+    /// In daemon mode, alert when one of these capabilities is requested
+    alert: CapSet,
+
+    /// WASM plugins filtering and analyzing the profile
+    plugins: Vec<PathBuf>,
+
+    /// Store the raw events of the run, compressed
+    record: Option<PathBuf>,
+
+    /// Write the intermediate artifacts of the run in this directory, to debug a wrong result
+    debug_dump: Option<PathBuf>,
+
+    /// Also write logs to this file, rotated by size and daily
+    log_file: Option<PathBuf>,
+
+    /// Do not show the status line while the command runs
+    quiet: bool,
+
+    /// Run the command with only these capabilities and report what failed
+    try_caps: Option<CapSet>,
+
+    /// Whether a set-user-ID or set-group-ID command is elevated at exec
+    setuid: SetuidMode,
+
+    /// How the file accesses of the command are collected
+    collector: Collector,
+
+    /// When to end the collection of a command that daemonizes
+    collect_until: CollectUntil,
+
+    /// Keep the namespaces of the command after it exits, until the user is done
+    hold: bool,
+
+    /// Pause the command on each new capability and sensitive file write until the user
+    /// allows or denies it
+    interactive: bool,
+
+    /// Do not store the result in the run history
+    no_history: bool,
+
+    /// Print the risk of the resulting capabilities with remediation hints
+    lint: bool,
+
+    /// Print the permissions of this sandbox allowing what the command did
+    policy: Option<Policy>,
+
+    /// Print the statistics of the collectors, and store them in the profile
+    stats: bool,
+
+    /// Capabilities last requested within this time after the start are startup ones
+    startup: Duration,
+
+    /// Metadata of the run, with history the runs to list
+    labels: BTreeMap<String, String>,
+
+    /// Run the command in a transient cgroup, only its requests are recorded
+    cgroup: bool,
+    cgroup_limits: CgroupLimits,
+
+    /// With report, recording to read
+    from: Option<PathBuf>,
+    /// Pin the probes in this bpffs directory and exit, they keep collecting
+    pin: Option<PathBuf>,
+    /// With report, bpffs directory of the pinned probes to read
+    pinned: Option<PathBuf>,
+    /// eBPF object loaded instead of the embedded probes
+    bpf_object: Option<PathBuf>,
+
+    /// With audit-correlate, audit log to read and time window and process name to consider
+    audit_log: Option<PathBuf>,
+    since: Option<u64>,
+    until: Option<u64>,
+    comm: Option<String>,
+
+    /// Profile several commands one after the other, separated by -- on the command line,
+    /// into one merged profile or one profile per command
+    session: Option<SessionMode>,
+    /// Commands of the session, one per line
+    cmd_file: Option<PathBuf>,
+
+    /// Run the command as this user, without capabilities
+    user: Option<String>,
+    /// Environment variables set for the command
+    env: Vec<(String, String)>,
+
+    /// Collect the requests of the session of this user, per application
+    user_slice: Option<String>,
+    /// Profile this existing PID namespace instead of running a command
+    nsid: Option<Nsid>,
+    /// Commands of the session, the first one is also `command`
+    commands: Vec<Vec<String>>,
+
     /// Specify a command to execute with arguments
     command: Vec<String>,
 }
@@ -65,73 +305,63 @@ struct Cli {
 impl Default for Cli {
     fn default() -> Self {
         Cli {
+            subcommand: None,
             sleep: None,
+            interval: None,
             daemon: false,
             output: None,
+            table_layout: TableLayout::default(),
+            landlock: false,
+            lsm: false,
+            assert: None,
+            merge_into: None,
+            sign_key: None,
+            not_seen_since: None,
+            remove: false,
+            install_role: None,
+            emit_wrapper: None,
+            on_event: None,
+            alert: CapSet::empty(),
+            plugins: Vec::new(),
+            record: None,
+            debug_dump: None,
+            log_file: None,
+            quiet: false,
+            try_caps: None,
+            setuid: SetuidMode::Keep,
+            collector: Collector::Strace,
+            collect_until: CollectUntil::Exit,
+            hold: false,
+            interactive: false,
+            no_history: false,
+            lint: false,
+            policy: None,
+            stats: false,
+            startup: phase::DEFAULT_STARTUP,
+            labels: BTreeMap::new(),
+            cgroup: false,
+            cgroup_limits: CgroupLimits::default(),
+            from: None,
+            pin: None,
+            pinned: None,
+            bpf_object: None,
+            audit_log: None,
+            since: None,
+            until: None,
+            comm: None,
             capabilities: CapSet::empty(),
+            session: None,
+            cmd_file: None,
+            user: None,
+            env: Vec::new(),
+            user_slice: None,
+            nsid: None,
+            commands: Vec::new(),
             command: Vec::new(),
         }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct CapSetEntry {
-    pub pid: Pid,
-    pub ppid: Pid,
-    pub uid: capable_common::Uid,
-    pub gid: capable_common::Gid,
-    pub ns: Nsid,
-    pub parent_ns: Nsid,
-    pub capabilities: CapSet,
-}
-
-impl CapSetEntry {
-    pub fn new(
-        pid: Pid,
-        ppid: Pid,
-        uid: capable_common::Uid,
-        gid: capable_common::Gid,
-        parent_ns: Nsid,
-        ns: Nsid,
-    ) -> CapSetEntry {
-        CapSetEntry {
-            pid,
-            ppid,
-            uid,
-            gid,
-            parent_ns,
-            ns,
-            capabilities: CapSet::empty(),
-        }
-    }
-    pub fn add(&mut self, cap: Cap) {
-        self.capabilities.add(cap);
-    }
-}
-
-impl Hash for CapSetEntry {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.pid.hash(state);
-        self.ppid.hash(state);
-        self.uid.hash(state);
-        self.gid.hash(state);
-        self.parent_ns.hash(state);
-        self.ns.hash(state);
-    }
-}
-
-impl PartialEq for CapSetEntry {
-    fn eq(&self, other: &Self) -> bool {
-        self.pid == other.pid
-            && self.ppid == other.ppid
-            && self.uid == other.uid
-            && self.parent_ns == other.parent_ns
-            && self.ns == other.ns
-    }
-}
-
-impl Eq for CapSetEntry {}
-
 #[derive(Tabled, Serialize, Deserialize)]
 #[tabled(rename_all = "UPPERCASE")]
 struct CapabilitiesTable {
@@ -142,158 +372,61 @@ struct CapabilitiesTable {
     ns: u32,
     parent_ns: u32,
     name: String,
+    /// systemd unit owning the cgroup of the process, empty when unknown
+    #[serde(default)]
+    unit: String,
     capabilities: String,
+    /// Set of `capabilities`, to aggregate and sort the rows
+    #[tabled(skip)]
+    #[serde(skip, default = "CapSet::empty")]
+    capset: CapSet,
 }
 
 const MAX_CHECK: u64 = 10;
 
-pub fn capset_to_vec(set: &CapSet) -> Vec<String> {
-    set.iter().map(|c| format!("CAP_{:?}", c)).collect()
-}
-
-pub fn capset_to_string(set: &CapSet) -> String {
-    if set == &!CapSet::empty() {
-        return String::from("ALL");
-    }
-    set.iter()
-        .fold(String::new(), |mut acc, cap| {
-            acc.push_str(&format!("CAP_{:?} ", cap));
-            acc
-        })
-        .trim_end()
-        .to_string()
-}
-
-fn get_cap(val: u8) -> Option<Cap> {
-    match val {
-        0 => Some(Cap::CHOWN),
-        1 => Some(Cap::DAC_OVERRIDE),
-        2 => Some(Cap::DAC_READ_SEARCH),
-        3 => Some(Cap::FOWNER),
-        4 => Some(Cap::FSETID),
-        5 => Some(Cap::KILL),
-        6 => Some(Cap::SETGID),
-        7 => Some(Cap::SETUID),
-        8 => Some(Cap::SETPCAP),
-        9 => Some(Cap::LINUX_IMMUTABLE),
-        10 => Some(Cap::NET_BIND_SERVICE),
-        11 => Some(Cap::NET_BROADCAST),
-        12 => Some(Cap::NET_ADMIN),
-        13 => Some(Cap::NET_RAW),
-        14 => Some(Cap::IPC_LOCK),
-        15 => Some(Cap::IPC_OWNER),
-        16 => Some(Cap::SYS_MODULE),
-        17 => Some(Cap::SYS_RAWIO),
-        18 => Some(Cap::SYS_CHROOT),
-        19 => Some(Cap::SYS_PTRACE),
-        20 => Some(Cap::SYS_PACCT),
-        21 => Some(Cap::SYS_ADMIN),
-        22 => Some(Cap::SYS_BOOT),
-        23 => Some(Cap::SYS_NICE),
-        24 => Some(Cap::SYS_RESOURCE),
-        25 => Some(Cap::SYS_TIME),
-        26 => Some(Cap::SYS_TTY_CONFIG),
-        27 => Some(Cap::MKNOD),
-        28 => Some(Cap::LEASE),
-        29 => Some(Cap::AUDIT_WRITE),
-        30 => Some(Cap::AUDIT_CONTROL),
-        31 => Some(Cap::SETFCAP),
-        32 => Some(Cap::MAC_OVERRIDE),
-        33 => Some(Cap::MAC_ADMIN),
-        34 => Some(Cap::SYSLOG),
-        35 => Some(Cap::WAKE_ALARM),
-        36 => Some(Cap::BLOCK_SUSPEND),
-        37 => Some(Cap::AUDIT_READ),
-        38 => Some(Cap::PERFMON),
-        39 => Some(Cap::BPF),
-        40 => Some(Cap::CHECKPOINT_RESTORE),
-        _ => None,
-    }
-}
-
-fn union_all_childs(
-    nsinode: u32,
-    graph: &std::collections::HashMap<u32, Vec<u32>>,
-    cap_graph: &std::collections::HashMap<u32, CapSet>,
-) -> CapSet {
-    let mut result = CapSet::empty();
-    for ns in graph.get(&nsinode).unwrap_or(&Vec::new()) {
-        result |= *cap_graph.get(ns).unwrap_or(&CapSet::empty());
-        if graph.contains_key(&ns) && *ns != nsinode {
-            result |= union_all_childs(*ns, graph, cap_graph);
-        }
-    }
-    result
-}
-
-fn program_capabilities<T, V>(
-    nsinode: &u32,
-    request_map: &mut Stack<V, Request>,
-    stacktrace_map: &StackTraceMap<T>,
-    ksyms: &std::collections::BTreeMap<u64, String>,
-) -> Result<CapSet, Box<dyn Error>>
-where
-    T: Borrow<MapData>,
-    V: BorrowMut<MapData>,
-{
-    let mut graph = std::collections::HashMap::new();
-    let mut init = CapSet::empty();
-    setbpf_effective(true)?;
-
-    let mut nsid_caps = std::collections::HashMap::new();
-    let set_entry = aggregate_cap_set_entries(request_map, stacktrace_map, ksyms)?;
-    for CapSetEntry {
-        capabilities,
-        parent_ns,
-        ns,
-        ..
-    } in set_entry
-    {
-        let capset = nsid_caps.entry(ns).or_insert_with(CapSet::empty);
-        *capset |= capabilities;
-        graph.entry(parent_ns).or_insert_with(Vec::new).push(ns);
-    }
-    setbpf_effective(false)?;
-    init |= union_all_childs(*nsinode, &graph, &nsid_caps);
-    Ok(init)
-}
-
-fn find_from_envpath<P>(exe_name: &P) -> Option<PathBuf>
-where
-    P: AsRef<Path>,
-{
-    env::var_os("PATH").and_then(|paths| {
-        env::split_paths(&paths)
-            .filter_map(|dir| {
-                let full_path = dir.join(exe_name);
-                if full_path.is_file() {
-                    Some(full_path)
-                } else {
-                    None
-                }
-            })
-            .next()
-    })
-}
-
-fn get_exec_and_args(command: &mut Vec<String>) -> (PathBuf, Vec<String>) {
+/// Program to execute for the command and its arguments, and whether strace runs the command
+/// as the --user itself. `trace_all` traces every syscall instead of the file ones, for the
+/// seccomp policy of minijail.
+fn get_exec_and_args(
+    command: &mut Vec<String>,
+    strace_log: &Path,
+    collector: Collector,
+    run_as: Option<&RunAs>,
+    trace_all: bool,
+) -> (PathBuf, Vec<String>, bool) {
     let mut exec_path: PathBuf = command[0].parse().expect("Failed to get exec path to PathBuf");
     let mut exec_args;
+    let mut switched = false;
     // encapsulate the command in sh command
     command[0] = canonicalize(exec_path.clone())
         .unwrap_or(exec_path)
         .to_str()
         .expect("Failed to get exec path to string (canonicalize)")
         .to_string();
-    if let Ok(strace) = which::which("strace") {
+    if collector == Collector::Seccomp {
+        // the accesses are notified by the filter installed before exec
+        exec_path = which::which(&command[0]).unwrap_or(PathBuf::from(&command[0]));
+        exec_args = command[1..].to_vec();
+    } else if let Ok(strace) = which::which("strace") {
         exec_path = strace;
+        let filter = if trace_all {
+            String::from("all")
+        } else {
+            trace_filter()
+        };
         exec_args = vec![
             "-f".to_string(),
             "-e".to_string(),
-            "ptrace,file".to_string(),
+            filter,
             "-o".to_string(),
-            format!("/tmp/capable_strace_{}.log", getpid()),
+            strace_log.display().to_string(),
         ];
+        // strace keeps its privileges to trace, it switches the command to the user
+        if let Some(run_as) = run_as {
+            exec_args.push("-u".to_string());
+            exec_args.push(run_as.name.clone());
+            switched = true;
+        }
         exec_args.extend(command.clone());
     } else if let Ok(sh) = which::which("sh") {
         exec_path = sh;
@@ -301,13 +434,7 @@ fn get_exec_and_args(command: &mut Vec<String>) -> (PathBuf, Vec<String>) {
     } else {
         panic!("Failed to find sh or strace in $PATH");
     }
-    (exec_path, exec_args)
-}
-
-fn extract_ns(pinum_inum: u64) -> (u32, u32) {
-    let ns = (pinum_inum & 0xffffffff) as u32;
-    let parent_ns = (pinum_inum >> 32) as u32;
-    (ns, parent_ns)
+    (exec_path, exec_args, switched)
 }
 
 fn read_exe_link(pid: &Pid) -> String {
@@ -328,17 +455,12 @@ fn get_groupname(gid: &u32) -> String {
         .map_or(gid.to_string(), |g| g.map_or(gid.to_string(), |g| g.name))
 }
 
-fn process_data_map<T, V>(
-    data_map: &mut Stack<T, Request>,
+fn process_data_map(
+    set_entry: HashSet<CapSetEntry>,
     capabilities_table: &mut Vec<CapabilitiesTable>,
-    stacktrace_map: &StackTraceMap<V>,
-    ksyms: &std::collections::BTreeMap<u64, String>,
-) -> Result<(), anyhow::Error>
-where
-    T: BorrowMut<MapData>,
-    V: Borrow<MapData>,
-{
-    let set_entry = aggregate_cap_set_entries(data_map, stacktrace_map, ksyms)?;
+    user_namespaces: &UserNamespaces,
+    units: &UnitCgroups,
+) {
     for CapSetEntry {
         pid,
         ppid,
@@ -346,12 +468,15 @@ where
         gid,
         ns,
         parent_ns,
+        cgroup,
         capabilities,
+        ..
     } in set_entry
     {
         let name = read_exe_link(&pid);
-        let username = get_username(&uid);
-        let groupname = get_groupname(&gid);
+        // ids of the host, shown with the ids of the container when it maps them
+        let username = user_namespaces.user(ns, uid, &get_username(&uid));
+        let groupname = user_namespaces.group(ns, gid, &get_groupname(&gid));
         capabilities_table.push(CapabilitiesTable {
             pid,
             ppid,
@@ -360,93 +485,122 @@ where
             ns,
             parent_ns,
             name,
+            unit: units.unit(cgroup).unwrap_or_default().to_string(),
             capabilities: capset_to_string(&capabilities),
+            capset: capabilities,
         });
     }
-    Ok(())
 }
 
-fn aggregate_cap_set_entries<T, V>(
-    data_map: &mut Stack<V, Request>,
-    stacktrace_map: &StackTraceMap<T>,
-    ksyms: &std::collections::BTreeMap<u64, String>,
-) -> Result<HashSet<CapSetEntry>, anyhow::Error>
-where
-    T: Borrow<MapData>,
-    V: BorrowMut<MapData>,
-{
-    let mut set_entry = HashSet::new();
-    while let Ok(Request {
-        pid,
-        ppid,
-        uid_gid,
-        pnsid_nsid,
-        capability,
-        stackid,
-    }) = data_map.pop(0)
-    {
-        assert!(stackid <= i32::MAX as i64); // Inconsistent StackTraceMap key type
-        let (ns, parent_ns) = extract_ns(pnsid_nsid);
-        let uid = uid_gid as u32 as capable_common::Uid;
-        let gid = (uid_gid >> 32) as capable_common::Gid;
-        let mut entry = CapSetEntry::new(pid, ppid, uid, gid, parent_ns, ns);
-        let mut binding = set_entry.take(&entry);
-        let entry = binding.as_mut().unwrap_or(&mut entry);
-        let stack = stacktrace_map.get(&(stackid as u32), 0)?;
-        if !((capability == Cap::SETUID as u8
-            && skip_priv_sym(&stack, ksyms, "cap_bprm_creds_from_file"))
-            || capability == Cap::DAC_OVERRIDE as u8
-            || (capability == Cap::DAC_READ_SEARCH as u8
-            && skip_priv_sym(&stack, ksyms, "may_open"))
-            || capability == Cap::SYS_PTRACE as u8)
-        {
-            entry.add(get_cap(capability).expect(&format!("Unknown capability: {}", capability)));
-            // debug the stack trace
-            for frame in stack.frames() {
-                if let Some(sym) = ksyms.range(..=frame.ip).next_back().map(|(_, s)| s) {
-                    debug!("{}()", sym);
-                }
-            }
-        }
+/// The recorder, if any, as the observer of the raw requests
+fn observer(recorder: &mut Option<Recorder>) -> Option<&mut dyn RequestObserver> {
+    recorder.as_mut().map(|recorder| recorder as &mut dyn RequestObserver)
+}
 
-        //debug!("new entry: {:?}", entry);
+/// The recorder and the debug dump, both observing the raw requests of a command
+struct Observers<'a> {
+    recorder: Option<&'a mut Recorder>,
+    dump: Option<&'a mut DebugDump>,
+}
 
-        set_entry.insert(entry.clone());
+impl Observers<'_> {
+    /// None without any, the stacks are then only symbolized for the reasons
+    fn observer(&mut self) -> Option<&mut dyn RequestObserver> {
+        (self.recorder.is_some() || self.dump.is_some())
+            .then_some(self as &mut dyn RequestObserver)
     }
-    Ok(set_entry)
 }
 
-fn skip_priv_sym(
-    stack: &aya::maps::stack_trace::StackTrace,
-    ksyms: &std::collections::BTreeMap<u64, String>,
-    symbol: &str,
-) -> bool {
-    for frame in stack.frames() {
-        if let Some(sym) = ksyms.range(..=frame.ip).next_back().map(|(_, s)| s) {
-            if sym == symbol {
-                return true;
-            }
+impl RequestObserver for Observers<'_> {
+    fn observe(
+        &mut self,
+        entry: &CapSetEntry,
+        capability: u8,
+        denied: bool,
+        time: u64,
+        stack: &[String],
+    ) -> Result<(), anyhow::Error> {
+        if let Some(recorder) = self.recorder.as_deref_mut() {
+            recorder.observe(entry, capability, denied, time, stack)?;
+        }
+        if let Some(dump) = self.dump.as_deref_mut() {
+            dump.observe(entry, capability, denied, time, stack)?;
         }
+        Ok(())
     }
-    false
 }
 
-fn print_all<T, V>(
-    data_map: &mut Stack<T, Request>,
-    stacktrace_map: &StackTraceMap<V>,
-    ksyms: &std::collections::BTreeMap<u64, String>,
+/// Report capabilities observed for the first time to the --on-event hook
+fn notify_capabilities(hook: &mut Option<EventHook>, set_entry: &HashSet<CapSetEntry>) {
+    let Some(hook) = hook else {
+        return;
+    };
+    for entry in set_entry {
+        for cap in entry.capabilities.iter() {
+            hook.notify(Event::Capability {
+                capability: format!("CAP_{:?}", cap),
+                pid: entry.pid,
+                ns: entry.ns,
+            });
+        }
+    }
+}
+
+/// Report the capabilities of the namespace `ns` and its descendants observed for the first
+/// time to the --on-event hook
+fn notify_namespace_capabilities(
+    hook: &mut EventHook,
+    probes: &Probes,
+    set_entry: &HashSet<CapSetEntry>,
+    ns: Nsid,
+    pid: Pid,
+) -> Result<(), anyhow::Error> {
+    let tree = probes.namespace_tree(set_entry)?;
+    for cap in namespace_capabilities(&tree, ns, set_entry, |entry| entry.capabilities).iter() {
+        hook.notify(Event::Capability {
+            capability: format!("CAP_{:?}", cap),
+            pid,
+            ns,
+        });
+    }
+    Ok(())
+}
+
+fn print_all(
+    set_entry: HashSet<CapSetEntry>,
     output: Option<PathBuf>,
-) -> Result<(), anyhow::Error>
-where
-    T: BorrowMut<MapData>,
-    V: Borrow<MapData>,
-{
+    mut user_namespaces: UserNamespaces,
+    units: &UnitCgroups,
+    layout: &TableLayout,
+) -> Result<(), anyhow::Error> {
     let mut capabilities_table = Vec::new();
-    process_data_map(data_map, &mut capabilities_table, stacktrace_map, ksyms)?;
+    user_namespaces.observe(&set_entry);
+    process_data_map(set_entry, &mut capabilities_table, &user_namespaces, units);
+    if let Some(group_by) = layout.group_by {
+        let groups = table::group(&capabilities_table, group_by, layout.sort);
+        if let Some(output) = output {
+            compress::write(output, &serde_json::to_string(&groups)?)?;
+        } else if let Some(columns) = &layout.columns {
+            println!("\n{}", table::columns_table(&groups, columns)?);
+        } else {
+            println!(
+                "\n{}",
+                Table::new(&groups)
+                    .with(Style::modern())
+                    .with(Modify::new(Columns::last()).with(Width::wrap(52).keep_words()))
+            );
+        }
+        return Ok(());
+    }
+    if let Some(sort) = layout.sort {
+        table::sort(&mut capabilities_table, sort);
+    }
     if let Some(output) = output {
-        let mut file = File::create(output)?;
+        let mut file = Output::create(output)?;
         writeln!(file, "{:?}", serde_json::to_string(&capabilities_table)?)?;
-        file.flush()?;
+        file.finish()?;
+    } else if let Some(columns) = &layout.columns {
+        println!("\n{}", table::columns_table(&capabilities_table, columns)?);
     } else {
         println!(
             "\n{}",
@@ -462,10 +616,36 @@ where
     Ok(())
 }
 
+/// capable report --pinned: print what the pinned probes collected since the previous report
+fn report_pinned(
+    dir: &Path,
+    output: Option<PathBuf>,
+    layout: &TableLayout,
+) -> Result<(), anyhow::Error> {
+    let mut probes = pinned::PinnedProbes::open(dir)?;
+    let mut set_entry = HashSet::new();
+    probes.drain(&mut set_entry)?;
+    let drops = probes.counters().drops;
+    if drops > 0 {
+        warn!(
+            "{} requests were dropped since the probes were pinned, report more often",
+            drops
+        );
+    }
+    let mut units = UnitCgroups::new("");
+    match probes.cgroup_paths()? {
+        Some(paths) => units.record(paths),
+        // pinned without the paths, only the cgroups still there are known
+        None => units.scan(),
+    }
+    print_all(set_entry, output, UserNamespaces::default(), &units, layout)
+}
+
 fn remove_outer_quotes(input: &str) -> String {
-    if input.len() >= 2 && input.starts_with('"') && input.ends_with('"') {
-        remove_outer_quotes(&input[1..input.len() - 1])
-    } else if input.len() >= 2 && input.starts_with('\'') && input.ends_with('\'') {
+    if input.len() >= 2
+        && (input.starts_with('"') && input.ends_with('"')
+            || input.starts_with('\'') && input.ends_with('\''))
+    {
         remove_outer_quotes(&input[1..input.len() - 1])
     } else {
         input.to_string()
@@ -479,104 +659,404 @@ where
     remove_outer_quotes(s.as_ref()).replace("\"", "\\\"")
 }
 
-pub fn parse_capset_iter<'a, I>(iter: I) -> Result<CapSet, ParseCapError>
-where
-    I: Iterator<Item = &'a str>,
-{
-    let mut res = CapSet::empty();
-
-    for part in iter {
-        match part.parse() {
-            Ok(cap) => res.add(cap),
-            Err(error) => {
-                return Err(error);
-            }
-        }
-    }
-    Ok(res)
-}
-
-const CAPABILITIES_ERROR: &str =
-    "You need at least setpcap, sys_admin, bpf, sys_resource, sys_ptrace capabilities to run capable";
-fn cap_effective_error(caplist: &str) -> String {
-    format!(
-        "Unable to toggle {} privilege. {}",
-        caplist, CAPABILITIES_ERROR
-    )
-}
-
-pub fn cap_effective(cap: Cap, enable: bool) -> Result<(), capctl::Error> {
-    let mut current = CapState::get_current()?;
-    current.effective.set_state(cap, enable);
-    current.set_current()
-}
-
-pub fn dac_read_search_effective(enable: bool) -> Result<(), capctl::Error> {
-    cap_effective(Cap::DAC_READ_SEARCH, enable).inspect_err(|_| {
-        eprintln!("{}", cap_effective_error("DAC_READ_SEARCH"));
-    })
-}
-
-fn setpcap_effective(enable: bool) -> Result<(), capctl::Error> {
-    cap_effective(Cap::SETPCAP, enable).inspect_err(|_| {
-        eprintln!("{}", cap_effective_error("SETPCAP"));
-    })
-}
-
-fn setbpf_effective(enable: bool) -> Result<(), capctl::Error> {
-    cap_effective(Cap::BPF, enable).inspect_err(|_| {
-        eprintln!("{}", cap_effective_error("BPF"));
-    })
-}
-
-fn setadmin_effective(enable: bool) -> Result<(), capctl::Error> {
-    cap_effective(Cap::SYS_ADMIN, enable).inspect_err(|_| {
-        eprintln!("{}", cap_effective_error("SYS_ADMIN"));
-    })
-}
-
-fn setresource_effective(enable: bool) -> Result<(), capctl::Error> {
-    cap_effective(Cap::SYS_RESOURCE, enable).inspect_err(|_| {
-        eprintln!("{}", cap_effective_error("SYS_RESOURCE"));
-    })
-}
-
-pub fn setptrace_effective(enable: bool) -> Result<(), capctl::Error> {
-    cap_effective(Cap::SYS_PTRACE, enable).inspect_err(|_| {
-        eprintln!("{}", cap_effective_error("SYS_PTRACE"));
-    })
-}
-
 fn getopt<S, I>(s: I) -> Result<Cli, anyhow::Error>
 where
     I: IntoIterator<Item = S>,
     S: AsRef<str>,
 {
     let mut args = Cli::default();
-    let mut iter = s.into_iter().skip(1);
+    let mut capabilities_given = false;
+    let mut cap_drop = CapSet::empty();
+    let mut iter = s.into_iter().skip(1).peekable();
+    // enforce executes the command itself, its arguments are passed as given
+    let command_arg = |arg: S, enforce: bool| {
+        if enforce {
+            arg.as_ref().to_string()
+        } else {
+            escape_parser_string(arg)
+        }
+    };
+    if iter.peek().is_some_and(|arg| arg.as_ref() == "enforce") {
+        iter.next();
+        let profile = iter
+            .next()
+            .ok_or(anyhow::anyhow!("enforce requires a profile file"))?;
+        args.subcommand = Some(Subcommand::Enforce {
+            profile: PathBuf::from(profile.as_ref()),
+        });
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "report") {
+        iter.next();
+        args.subcommand = Some(Subcommand::Report);
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "replay") {
+        iter.next();
+        let requests = iter
+            .next()
+            .ok_or(anyhow::anyhow!("replay requires a requests fixture"))?;
+        let strace_log = iter
+            .peek()
+            .filter(|log| !log.as_ref().starts_with('-'))
+            .map(|log| PathBuf::from(log.as_ref()));
+        if strace_log.is_some() {
+            iter.next();
+        }
+        args.subcommand = Some(Subcommand::Replay {
+            requests: PathBuf::from(requests.as_ref()),
+            strace_log,
+        });
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "audit-correlate") {
+        iter.next();
+        let profile = iter
+            .next()
+            .ok_or(anyhow::anyhow!("audit-correlate requires a profile file"))?;
+        args.subcommand = Some(Subcommand::AuditCorrelate {
+            profile: PathBuf::from(profile.as_ref()),
+        });
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "history") {
+        iter.next();
+        args.subcommand = Some(Subcommand::History);
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "show") {
+        iter.next();
+        let run = iter
+            .next()
+            .ok_or(anyhow::anyhow!("show requires a run id or key=value label"))?
+            .as_ref()
+            .parse()?;
+        let baseline = iter
+            .peek()
+            .and_then(|baseline| baseline.as_ref().parse().ok());
+        if baseline.is_some() {
+            iter.next();
+        }
+        args.subcommand = Some(Subcommand::Show { run, baseline });
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "prune") {
+        iter.next();
+        let profile = iter
+            .peek()
+            .filter(|profile| !profile.as_ref().starts_with('-'))
+            .map(|profile| PathBuf::from(profile.as_ref()));
+        if profile.is_some() {
+            iter.next();
+        }
+        args.subcommand = Some(Subcommand::Prune { profile });
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "selftest") {
+        iter.next();
+        if iter.peek().is_some_and(|arg| arg.as_ref() == "workload") {
+            iter.next();
+            let name = iter
+                .next()
+                .ok_or(anyhow::anyhow!("selftest workload requires a name and a path"))?;
+            let path = iter
+                .next()
+                .ok_or(anyhow::anyhow!("selftest workload requires a name and a path"))?;
+            args.subcommand = Some(Subcommand::SelftestWorkload {
+                name: name.as_ref().to_string(),
+                path: PathBuf::from(path.as_ref()),
+            });
+        } else {
+            args.subcommand = Some(Subcommand::Selftest);
+        }
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "check") {
+        iter.next();
+        args.subcommand = Some(Subcommand::Check);
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "batch") {
+        iter.next();
+        let jobs = iter
+            .next()
+            .ok_or(anyhow::anyhow!("batch requires a jobs file"))?;
+        args.subcommand = Some(Subcommand::Batch {
+            jobs: PathBuf::from(jobs.as_ref()),
+        });
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "boot-profile") {
+        iter.next();
+        if iter.peek().is_some_and(|arg| arg.as_ref() == "unit") {
+            iter.next();
+            args.subcommand = Some(Subcommand::BootProfileUnit);
+        } else {
+            args.subcommand = Some(Subcommand::BootProfile);
+        }
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "auditd") {
+        iter.next();
+        if iter.peek().is_some_and(|arg| arg.as_ref() == "unit") {
+            iter.next();
+            args.subcommand = Some(Subcommand::AuditdUnit);
+        } else {
+            args.subcommand = Some(Subcommand::Auditd);
+        }
+    } else if iter.peek().is_some_and(|arg| arg.as_ref() == "top") {
+        iter.next();
+        args.subcommand = Some(Subcommand::Top);
+    }
     while let Some(arg) = iter.next() {
         match arg.as_ref() {
             "-s" | "--sleep" => {
                 args.sleep = iter.next().and_then(|s| s.as_ref().parse::<u64>().ok());
             }
+            "--interval" => {
+                let seconds = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--interval requires a number of seconds"))?;
+                args.interval = Some(Duration::from_secs(seconds.as_ref().parse()?));
+            }
             "-d" | "--daemon" => {
                 args.daemon = true;
             }
             "-c" | "--capabilities" => {
+                capabilities_given = true;
                 args.capabilities = iter
                     .next()
-                    .and_then(|s| {
-                        Some(
-                            parse_capset_iter(s.as_ref().split(','))
-                                .ok()
-                                .unwrap_or(CapSet::empty()),
-                        )
+                    .map(|s| {
+                        parse_capset_iter(s.as_ref().split(','))
+                            .ok()
+                            .unwrap_or(CapSet::empty())
                     })
                     .unwrap_or(CapSet::empty());
             }
             "-o" | "--output" => {
                 args.output = iter.next().map(|s| PathBuf::from(s.as_ref()));
             }
+            "--group-by" => {
+                let group_by = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--group-by requires exe, ns, uid or unit"))?;
+                args.table_layout.group_by = Some(group_by.as_ref().parse()?);
+            }
+            "--columns" => {
+                let columns = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--columns requires a list of columns"))?;
+                args.table_layout.columns = Some(
+                    columns
+                        .as_ref()
+                        .split(',')
+                        .map(|column| column.trim().to_lowercase())
+                        .collect(),
+                );
+            }
+            "--sort" => {
+                let sort = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--sort requires caps, pid or name"))?;
+                args.table_layout.sort = Some(sort.as_ref().parse()?);
+            }
+            "--landlock" => {
+                args.landlock = true;
+            }
+            "--lsm" => {
+                args.lsm = true;
+            }
+            "--assert" => {
+                args.assert = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--install-role" => {
+                args.install_role = iter.next().map(|s| s.as_ref().to_string());
+            }
+            "--emit-wrapper" => {
+                args.emit_wrapper = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--on-event" => {
+                args.on_event = iter.next().map(|s| s.as_ref().to_string());
+            }
+            "--cap-drop" => {
+                let caps = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--cap-drop requires a list of capabilities"))?;
+                cap_drop |= parse_capset_iter(caps.as_ref().split(','))
+                    .map_err(|e| anyhow::anyhow!("Invalid capability in --cap-drop: {}", e))?;
+            }
+            "--label" => {
+                let label = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--label requires key=value"))?;
+                let (key, value) = parse_label(label.as_ref())?;
+                args.labels.insert(key, value);
+            }
+            "--merge-into" => {
+                args.merge_into = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--sign-key" => {
+                args.sign_key = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--not-seen-since" => {
+                let age = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--not-seen-since requires an age, e.g. 90d"))?;
+                args.not_seen_since = Some(parse_age(age.as_ref())?);
+            }
+            "--remove" => {
+                args.remove = true;
+            }
+            "--no-history" => {
+                args.no_history = true;
+            }
+            "--lint" => {
+                args.lint = true;
+            }
+            "--stats" => {
+                args.stats = true;
+            }
+            "--hold" => {
+                args.hold = true;
+            }
+            "--interactive" => {
+                args.interactive = true;
+            }
+            "--cgroup" => {
+                args.cgroup = true;
+            }
+            "--memory-max" => {
+                args.cgroup = true;
+                args.cgroup_limits.memory_max = Some(
+                    iter.next()
+                        .ok_or(anyhow::anyhow!("--memory-max requires a limit"))?
+                        .as_ref()
+                        .to_string(),
+                );
+            }
+            "--cpu-max" => {
+                args.cgroup = true;
+                args.cgroup_limits.cpu_max = Some(
+                    iter.next()
+                        .ok_or(anyhow::anyhow!("--cpu-max requires a limit"))?
+                        .as_ref()
+                        .to_string(),
+                );
+            }
+            "--until-ns-empty" => {
+                args.collect_until = CollectUntil::NsEmpty;
+            }
+            "--wait-for-idle" => {
+                let seconds = iter
+                    .next()
+                    .and_then(|s| s.as_ref().parse::<u64>().ok())
+                    .ok_or(anyhow::anyhow!("--wait-for-idle requires a number of seconds"))?;
+                args.collect_until = CollectUntil::Idle(Duration::from_secs(seconds));
+            }
+            "--startup" => {
+                let seconds = iter
+                    .next()
+                    .and_then(|s| s.as_ref().parse::<u64>().ok())
+                    .ok_or(anyhow::anyhow!("--startup requires a number of seconds"))?;
+                args.startup = Duration::from_secs(seconds);
+            }
+            "--policy" => {
+                let policy = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--policy requires a sandbox name"))?;
+                args.policy = Some(policy.as_ref().parse()?);
+            }
+            "--session" => {
+                let mode = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--session requires merge or split"))?;
+                args.session = Some(mode.as_ref().parse()?);
+            }
+            "--cmd-file" => {
+                args.cmd_file = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--user" => {
+                args.user = iter.next().map(|s| s.as_ref().to_string());
+            }
+            "--user-slice" => {
+                args.user_slice = iter.next().map(|s| s.as_ref().to_string());
+            }
+            "--nsid" => {
+                let inode = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--nsid requires a namespace inode"))?;
+                args.nsid = Some(
+                    inode
+                        .as_ref()
+                        .parse()
+                        .with_context(|| format!("Invalid --nsid {}", inode.as_ref()))?,
+                );
+            }
+            "--nspath" => {
+                let path = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--nspath requires a path like /proc/PID/ns/pid"))?;
+                args.nsid = Some(attach::namespace_of(Path::new(path.as_ref()))?);
+            }
+            "--env" => {
+                let var = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--env requires KEY=VALUE"))?;
+                let (key, value) = var
+                    .as_ref()
+                    .split_once('=')
+                    .filter(|(key, _)| !key.is_empty())
+                    .ok_or(anyhow::anyhow!("Invalid --env {}, expected KEY=VALUE", var.as_ref()))?;
+                args.env.push((key.to_string(), value.to_string()));
+            }
+            "--collector" => {
+                let collector = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--collector requires strace or seccomp"))?;
+                args.collector = collector.as_ref().parse()?;
+            }
+            "--setuid" => {
+                let mode = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--setuid requires keep or nullify"))?;
+                args.setuid = mode.as_ref().parse()?;
+            }
+            "--try-caps" => {
+                let caps = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--try-caps requires a list of capabilities"))?;
+                args.try_caps = Some(
+                    parse_capset_iter(caps.as_ref().split(',').filter(|s| !s.is_empty()))
+                        .map_err(|e| anyhow::anyhow!("Invalid capability in --try-caps: {}", e))?,
+                );
+            }
+            "--alert" => {
+                let caps = iter
+                    .next()
+                    .ok_or(anyhow::anyhow!("--alert requires a list of capabilities"))?;
+                args.alert = parse_capset_iter(caps.as_ref().split(','))
+                    .map_err(|e| anyhow::anyhow!("Invalid capability in --alert: {}", e))?;
+            }
+            "--plugin" => {
+                if let Some(path) = iter.next() {
+                    args.plugins.push(PathBuf::from(path.as_ref()));
+                }
+            }
+            "-q" | "--quiet" => {
+                args.quiet = true;
+            }
+            "--log-file" => {
+                args.log_file = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--record" => {
+                args.record = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--debug-dump" => {
+                args.debug_dump = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--from" => {
+                args.from = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--pin" => {
+                args.pin = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--pinned" => {
+                args.pinned = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--bpf-object" => {
+                args.bpf_object = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--audit-log" => {
+                args.audit_log = iter.next().map(|s| PathBuf::from(s.as_ref()));
+            }
+            "--since" => {
+                args.since = iter.next().and_then(|s| s.as_ref().parse::<u64>().ok());
+            }
+            "--until" => {
+                args.until = iter.next().and_then(|s| s.as_ref().parse::<u64>().ok());
+            }
+            "--comm" => {
+                args.comm = iter.next().map(|s| s.as_ref().to_string());
+            }
+            "--" => break,
             "-l" | "--log-level" => {
                 let level = iter.next().map(|s| s.as_ref().to_string()).unwrap_or("info".to_string());
                 env::set_var("RUST_LOG", level);
@@ -585,14 +1065,45 @@ where
                 if arg.as_ref().starts_with('-') {
                     return Err(anyhow::anyhow!("Unknown option: {}", arg.as_ref()));
                 } else {
-                    args.command.push(escape_parser_string(arg));
+                    let enforce = matches!(args.subcommand, Some(Subcommand::Enforce { .. }));
+                    args.command.push(command_arg(arg, enforce));
                     break;
                 }
             }
         }
     }
-    while let Some(arg) = iter.next() {
-        args.command.push(escape_parser_string(arg));
+    let enforce = matches!(args.subcommand, Some(Subcommand::Enforce { .. }));
+    // only the first -- ends the options, the next ones belong to the command
+    for arg in iter {
+        args.command.push(command_arg(arg, enforce));
+    }
+    if enforce && (args.session.is_some() || args.cmd_file.is_some()) {
+        return Err(anyhow::anyhow!(
+            "enforce runs a single command, --session and --cmd-file do not apply"
+        ));
+    }
+    if args.session.is_some() || args.cmd_file.is_some() {
+        args.commands = session::split_commands(&args.command);
+        if let Some(cmd_file) = &args.cmd_file {
+            args.commands.extend(session::read_cmd_file(cmd_file)?);
+        }
+        args.command = args.commands.first().cloned().unwrap_or_default();
+        if args.session.is_none() {
+            args.session = Some(SessionMode::Merge);
+        }
+    }
+    if !cap_drop.is_empty() {
+        // without -c, drop from every capability capable can pass to the command
+        if !capabilities_given {
+            args.capabilities = CapState::get_current()?.permitted;
+        }
+        args.capabilities -= cap_drop;
+    }
+    if let Some(candidate) = args.try_caps {
+        args.capabilities = candidate;
+    }
+    if args.user.is_some() && !args.capabilities.is_empty() {
+        warn!("--user runs the command without capabilities, the ones given are ignored");
     }
     Ok(args)
 }
@@ -601,18 +1112,74 @@ fn run_command(
     cli_args: &mut Cli,
     nsclone: Rc<RefCell<u32>>,
     pid: &mut i32,
-) -> Result<ExitStatus, anyhow::Error> {
-    let (path, args) = get_exec_and_args(&mut cli_args.command);
+    strace_log: &Path,
+    cgroup: Option<&Cgroup>,
+    interactive: Option<&Interactive>,
+    on_drain: &mut dyn FnMut(Pid) -> Result<(), anyhow::Error>,
+) -> Result<(ExitStatus, Option<HeldSets>, Option<SeccompStats>), anyhow::Error> {
+    let run_as = cli_args.user.as_deref().map(RunAs::resolve).transpose()?;
+    let (path, args, switched) = get_exec_and_args(
+        &mut cli_args.command,
+        strace_log,
+        cli_args.collector,
+        run_as.as_ref(),
+        cli_args.policy == Some(Policy::Minijail),
+    );
+    let run_as = run_as.filter(|_| !switched);
+    let traced = which::which("strace").is_ok_and(|strace| strace == path);
+    // without strace the command is the init, its processes would end with it
+    let linger = !traced && !cli_args.hold && cli_args.collect_until.waits();
+    let hold_status = strace_log.with_file_name(HOLD_STATUS_NAME);
+    // written by the previous command of a session
+    let _ = remove_file(&hold_status);
+    let (path, args) = if cli_args.hold || linger {
+        hold_command(path, args, &hold_status)?
+    } else {
+        (path, args)
+    };
+    let init = if linger {
+        Init::Linger(hold_status.clone())
+    } else if traced || cli_args.hold {
+        Init::Wrapper
+    } else {
+        Init::Command
+    };
     let namespaces = vec![&unshare::Namespace::Pid];
-    let capabilities = cli_args.capabilities.clone();
+    let capabilities = cli_args.capabilities;
+    let setuid = cli_args.setuid;
+    // checked before forking, the child cannot report why it failed
+    let securebits = SetuidMode::securebits()?;
+    let cgroup_procs = cgroup.map(Cgroup::procs);
+    let listener = match cli_args.collector {
+        Collector::Seccomp => Some(Listener::new()?),
+        Collector::Strace => None,
+    };
+    let seccomp_socket = listener.as_ref().map(Listener::child).transpose()?;
+    let approvals = interactive.map(Interactive::approvals);
+    // the writes of the processes the collector does not decode cannot be asked for
+    let kill_foreign = interactive.is_some();
+    let memlock = memlock_limit();
     let mut cmd = unshare::Command::new(path);
 
     unsafe {
         cmd.pre_exec(move || {
             let mut capstate = CapState::empty();
-            nix::sys::prctl::set_keepcaps(false).expect("Failed to set keepcaps");
+            if let Some(procs) = &cgroup_procs {
+                std::fs::write(procs, "0").expect("Failed to join the cgroup");
+            }
+            restore_memlock_limit(&memlock).expect("Failed to restore the locked memory limit");
             setpcap_effective(true).expect("Failed to setpcap effective");
+            setuid.apply(securebits).expect("Failed to set securebits");
             ambient::clear().expect("Failed to clear ambiant caps");
+            if let Some(socket) = &seccomp_socket {
+                // last, the syscalls before are capable's, with SYS_ADMIN still effective
+                unotify::install(socket, kill_foreign)
+                    .expect("Failed to install the seccomp filter");
+            }
+            if let Some(run_as) = &run_as {
+                run_as.apply().expect("Failed to switch user");
+                return Ok(());
+            }
             capstate.inheritable = capabilities;
             capstate.permitted = capabilities;
             capstate.effective = capabilities;
@@ -620,18 +1187,37 @@ fn run_command(
             Ok(())
         })
     };
+    for (key, value) in &cli_args.env {
+        cmd.env(key, value);
+    }
+    // the collector must already run, exec is notified and spawn waits for it
+    let collection = listener
+        .map(|listener| {
+            listener.collect(
+                strace_log.to_path_buf(),
+                interactive.map(Interactive::firewall),
+            )
+        })
+        .transpose()?;
     setadmin_effective(true)?;
 
     //avoid output
     let child: Arc<Mutex<unshare::Child>> = Arc::new(Mutex::new(
         cmd.args(&args)
-            .before_unfreeze(move |id| {
-                setptrace_effective(true)?;
-                let fnspid =
-                    metadata(format!("/proc/{}/ns/pid", id)).expect("failed to open pid ns");
-                setptrace_effective(false)?;
-                nsclone.as_ref().replace(fnspid.ino() as u32);
-                Ok(())
+            .before_unfreeze({
+                let nsclone = nsclone.clone();
+                move |id| {
+                    setptrace_effective(true)?;
+                    let fnspid =
+                        metadata(format!("/proc/{}/ns/pid", id)).expect("failed to open pid ns");
+                    setptrace_effective(false)?;
+                    nsclone.as_ref().replace(fnspid.ino() as u32);
+                    // before the command runs, its first capability is already paused
+                    if let Some(approvals) = &approvals {
+                        approvals.lock().unwrap().watch(fnspid.ino() as u32)?;
+                    }
+                    Ok(())
+                }
             })
             .unshare(namespaces)
             .stdout(if cli_args.output.is_none() {
@@ -655,11 +1241,24 @@ fn run_command(
     setadmin_effective(false)?;
     let cloned = child.clone();
     *pid = child.try_lock().expect("failed to lock execution child").id() as i32;
-    let pid_cloned = pid.clone();
+    let pid_cloned = *pid;
     let term = Arc::new(AtomicBool::new(false));
     for sig in TERM_SIGNALS {
         flag::register(*sig, Arc::clone(&term))?;
     }
+    let terminated = Arc::clone(&term);
+    let watcher = DaemonWatcher::start(
+        cli_args.collect_until,
+        *nsclone.as_ref().borrow(),
+        init,
+        strace_log.to_path_buf(),
+        Arc::clone(&term),
+    );
+    // strace is the first process of the namespace, it holds the capabilities to trace
+    let sampler = HeldSampler::start(
+        *nsclone.as_ref().borrow(),
+        (cli_args.collector == Collector::Strace).then_some(*pid),
+    );
 
     thread::spawn(move || {
         while !term.load(Ordering::Relaxed) {
@@ -699,155 +1298,585 @@ fn run_command(
         Ok::<(), ()>(())
     });
 
-    let exit_status = cloned
-        .try_lock()
-        .expect("failed to lock execution child for waiting")
-        .wait()
-        .expect("failed to wait on child");
+    let exit_status = if cli_args.hold {
+        hold(&cloned, *pid, &hold_status)?
+    } else {
+        // drain the requests while the command runs, the findings are reported as they come
+        while !exited(*pid) {
+            thread::sleep(Duration::from_millis(400));
+            on_drain(*pid)?;
+            // the lingering init has no handler, only SIGKILL ends it
+            if linger && terminated.load(Ordering::Relaxed) {
+                if let Err(e) = kill(nix::unistd::Pid::from_raw(*pid), Signal::SIGKILL) {
+                    debug!("lingering init already exited: {}", e);
+                }
+            }
+        }
+        let status = cloned
+            .try_lock()
+            .expect("failed to lock execution child for waiting")
+            .wait()
+            .expect("failed to wait on child");
+        if linger {
+            command_status(&hold_status, status)
+        } else {
+            status
+        }
+    };
+    watcher.stop();
+    let held = sampler.stop();
+    let seccomp = collection.map(unotify::finish);
     debug!("child exited with {:?}", exit_status);
     //print_all(&capabilities_map, &pnsid_nsid_map, &uid_gid_map, &ppid_map)?;
 
-    Ok(exit_status)
+    Ok((exit_status, held, seccomp))
+}
+
+/// What a command of the run requested, read from the probes once it exited
+struct CommandRun {
+    command: Vec<String>,
+    pid: i32,
+    ns: u32,
+    window: TimeWindow,
+    exit: ExitStatus,
+    capset: CapSet,
+    denied: CapSet,
+    reasons: Reasons,
+    nested: Vec<NestedNamespace>,
+    dac: BTreeMap<String, BTreeSet<String>>,
+    strace_log: Option<String>,
+    seccomp: Option<SeccompStats>,
+    modules: BTreeSet<String>,
+    sysctls: BTreeMap<String, SysctlAccess>,
+    self_managed: Vec<CapabilityChange>,
+    setuid: Option<SetuidTransition>,
+    held: Option<HeldCapabilities>,
+}
+
+/// Run the current command of `cli_args` and collect what it requested. The requests are
+/// drained from the probes, so the commands of a session are profiled one after the other.
+/// The capabilities are reported to the --on-event hook while the command runs.
+fn profile_command(
+    cli_args: &mut Cli,
+    probes: &mut Probes,
+    recorder: &mut Option<Recorder>,
+    dump: &mut Option<DebugDump>,
+    hook: &mut Option<EventHook>,
+    strace_log_path: &Path,
+) -> Result<CommandRun, anyhow::Error> {
+    let nsinode: Rc<RefCell<u32>> = Rc::new(0.into());
+    let mut pid = 0;
+    let interactive = cli_args
+        .interactive
+        .then(|| Interactive::start(probes.approvals()))
+        .transpose()?;
+    if interactive.is_some() && cli_args.collector == Collector::Strace {
+        warn!("--interactive only asks for the file writes with --collector seccomp");
+    }
+    // the status line would overwrite the questions
+    let status = (!cli_args.quiet && interactive.is_none() && std::io::stderr().is_terminal())
+        .then(|| StatusLine::start(probes.counters(), strace_log_path.to_path_buf()));
+    let mut setuid = cli_args.command.first().and_then(|exec| setuid::inspect(exec));
+    let cgroup = if cli_args.cgroup {
+        let cgroup = Cgroup::create(&cli_args.cgroup_limits)?;
+        probes.restrict_to_cgroup(cgroup.open()?)?;
+        Some(cgroup)
+    } else {
+        None
+    };
+    let start = unix_time();
+    let mut drained = HashSet::new();
+    let drained_ns = nsinode.clone();
+    let mut on_drain = |pid: Pid| -> Result<(), anyhow::Error> {
+        setbpf_effective(true)?;
+        probes.drain(
+            &mut drained,
+            Observers {
+                recorder: recorder.as_mut(),
+                dump: dump.as_mut(),
+            }
+            .observer(),
+        )?;
+        setbpf_effective(false)?;
+        if let Some(hook) = hook.as_mut() {
+            let ns = *drained_ns.as_ref().borrow();
+            notify_namespace_capabilities(hook, probes, &drained, ns, pid)?;
+        }
+        Ok(())
+    };
+    let (exit, held, seccomp) = run_command(
+        cli_args,
+        nsinode.clone(),
+        &mut pid,
+        strace_log_path,
+        cgroup.as_ref(),
+        interactive.as_ref(),
+        &mut on_drain,
+    )?;
+    drop(cgroup);
+    let end = unix_time();
+    // the credentials observed after exec, the set-ID bits may have been ignored
+    if let Some(setuid) = setuid.as_mut() {
+        setuid.elevated = held.is_some_and(|held| held.set_id);
+    }
+    if let Some(status) = status {
+        status.stop();
+    }
+    if !exit.success() && cli_args.output.is_none() {
+        eprintln!("Command failed with exit status: {}", exit);
+        eprintln!("Please check the command and try again with requested capabilities as you want to reach");
+    }
+
+    let ns = *nsinode.as_ref().borrow();
+    let NamespaceCapabilities {
+        capabilities: mut capset,
+        denied,
+        reasons,
+        nested,
+    } = probes
+        .program_capabilities(
+            ns,
+            drained,
+            Observers {
+                recorder: recorder.as_mut(),
+                dump: dump.as_mut(),
+            }
+            .observer(),
+        )
+        .expect("failed to print capabilities");
+    let dac = probes.dac_accesses(ns)?;
+    capset |= parse_capset_iter(dac.keys().map(String::as_str))?;
+    if let Some(interactive) = interactive {
+        interactive.finish(ns);
+    }
+    let strace_log = if metadata(strace_log_path).is_ok() {
+        let log = read_to_string(strace_log_path)?;
+        // the next command of the session must not find this log
+        remove_file(strace_log_path)?;
+        Some(log)
+    } else {
+        None
+    };
+    Ok(CommandRun {
+        command: cli_args.command.clone(),
+        pid,
+        ns,
+        window: TimeWindow { start, end },
+        exit,
+        capset,
+        denied,
+        reasons,
+        nested,
+        dac,
+        strace_log,
+        seccomp,
+        modules: probes.requested_modules(ns)?,
+        sysctls: probes.sysctls()?,
+        self_managed: probes.self_managed(ns)?,
+        setuid,
+        held: held.map(|held| held.compare(capset)),
+    })
+}
+
+/// Profile written at the end of a run, of a command or of the merged commands of a session
+struct RunProfile {
+    commands: Vec<Vec<String>>,
+    end: u64,
+    strace_logs: Vec<String>,
+    seccomp: Option<SeccompStats>,
+    result: ProgramResult,
+}
+
+impl RunProfile {
+    fn merge(&mut self, other: RunProfile) {
+        self.commands.extend(other.commands);
+        self.end = self.end.max(other.end);
+        self.strace_logs.extend(other.strace_logs);
+        self.seccomp = match (self.seccomp, other.seccomp) {
+            (Some(mut seccomp), Some(other)) => {
+                seccomp.add(&other);
+                Some(seccomp)
+            }
+            (seccomp, other) => seccomp.or(other),
+        };
+        self.result.merge(other.result);
+        self.result.containers = containers::compare(&self.result);
+    }
+}
+
+/// Rotate the --log-file when it exceeds this size, in bytes, or every day
+const LOG_FILE_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// Number of rotated log files kept
+const LOG_FILE_KEEP: usize = 5;
+
+fn log_level() -> LevelFilter {
+    env::var("RUST_LOG")
+        .unwrap_or("info".to_string())
+        .parse::<LevelFilter>()
+        .expect("Failed to parse log level")
+}
+
+/// Tracing output written to `path`, in addition to the standard output or syslog
+fn log_file_layer<S>(path: Option<&Path>) -> Result<Option<impl Layer<S>>, anyhow::Error>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let appender = BasicRollingFileAppender::new(
+        path,
+        RollingConditionBasic::new().daily().max_size(LOG_FILE_MAX_SIZE),
+        LOG_FILE_KEEP,
+    )
+    .with_context(|| format!("Unable to open log file {}", path.display()))?;
+    Ok(Some(
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(Mutex::new(appender))
+            .with_filter(log_level()),
+    ))
 }
 
 #[cfg(debug_assertions)]
-pub fn subsribe(tool: &str) {
+pub fn subsribe(tool: &str, log_file: Option<&Path>) -> Result<(), anyhow::Error> {
     use std::io;
 
-    use tracing::level_filters::LevelFilter;
     let identity = CString::new(tool).expect("Failed to create CString");
     let options = syslog_tracing::Options::LOG_PID;
     let facility = syslog_tracing::Facility::Auth;
     let _syslog = syslog_tracing::Syslog::new(identity, options, facility).expect("Failed to create syslog");
-    tracing_subscriber::fmt()
-        .with_max_level(env::var("RUST_LOG").unwrap_or("info".to_string()).parse::<LevelFilter>().expect("Failed to parse log level"))
-        .with_file(true)
-        .with_line_number(true)
-        .with_writer(io::stdout)
-        .finish()
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_file(true)
+                .with_line_number(true)
+                .with_writer(io::stdout)
+                .with_filter(log_level()),
+        )
+        .with(log_file_layer(log_file)?)
         .init();
+    Ok(())
 }
 
 #[cfg(not(debug_assertions))]
-pub fn subsribe(tool: &str) {
+pub fn subsribe(tool: &str, log_file: Option<&Path>) -> Result<(), anyhow::Error> {
     use std::panic::set_hook;
 
     let identity = CString::new(tool).expect("Failed to create CString");
     let options = syslog_tracing::Options::LOG_PID;
     let facility = syslog_tracing::Facility::Auth;
     let syslog = syslog_tracing::Syslog::new(identity, options, facility).expect("Failed to create syslog");
-    tracing_subscriber::fmt()
-        .compact()
-        .with_max_level(Level::WARN)
-        .with_file(false)
-        .with_timer(false)
-        .with_line_number(false)
-        .with_target(false)
-        .without_time()
-        .with_writer(syslog)
-        .finish()
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .compact()
+                .with_file(false)
+                .with_line_number(false)
+                .with_target(false)
+                .without_time()
+                .with_writer(syslog)
+                .with_filter(LevelFilter::WARN),
+        )
+        .with(log_file_layer(log_file)?)
         .init();
     set_hook(Box::new(|info| {
         if let Some(s) = info.payload().downcast_ref::<String>() {
             println!("{}", s);
         }
     }));
+    Ok(())
 }
 
-#[derive(Serialize)]
-struct ProgramResult {
-    capabilities: Vec<String>,
-    files: std::collections::HashMap<String, syscalls::Access>,
-    dbus: Vec<String>,
+const DBUS_JSON_NAME: &str = "dbus.json";
+/// Exit code when the profile exceeds the --assert baseline
+const ASSERT_EXIT_CODE: i32 = 2;
+const STRACE_LOG_NAME: &str = "strace.log";
+/// Exit code of the command, written by the init holding its namespace (--hold)
+const HOLD_STATUS_NAME: &str = "hold.status";
+
+/// Create the private working directory of a run. It is only accessible by the current user
+/// and removed with all its content when dropped, including on panic.
+fn create_workdir() -> Result<TempDir, anyhow::Error> {
+    tempfile::Builder::new()
+        .prefix("capable-")
+        .permissions(Permissions::from_mode(0o700))
+        .tempdir()
+        .context("Unable to create temporary directory")
 }
 
-const DBUS_JSON_PATH: &str = "/tmp/capable_dbus.json";
+/// Time given to the dbus monitor to write its result before being killed
+const DBUS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ask the dbus monitor to stop, then reap it. If it does not exit in time, it is killed,
+/// so a stuck monitor never blocks the report.
+fn stop_dbus_monitor(child: nix::unistd::Pid) {
+    if let Err(e) = kill(child, Signal::SIGINT) {
+        warn!("failed to send SIGINT to dbus monitor: {}", e);
+    }
+    let start = Instant::now();
+    while start.elapsed() < DBUS_SHUTDOWN_TIMEOUT {
+        match waitpid(child, Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::StillAlive) => thread::sleep(Duration::from_millis(100)),
+            Ok(status) => {
+                debug!("dbus monitor exited with {:?}", status);
+                return;
+            }
+            Err(e) => {
+                warn!("failed to wait dbus monitor: {}", e);
+                return;
+            }
+        }
+    }
+    warn!("dbus monitor did not stop in time, killing it");
+    if let Err(e) = kill(child, Signal::SIGKILL) {
+        warn!("failed to send SIGKILL to dbus monitor: {}", e);
+    }
+    let _ = waitpid(child, None);
+}
 
 fn main() -> Result<(), anyhow::Error> {
     let mut cli_args = getopt(std::env::args()).context("Arguments error")?;
-    subsribe("capable");
+    subsribe("capable", cli_args.log_file.as_deref())?;
     //env_logger::init();
     //ambient::clear().expect("Failed to clear ambiant caps");
     debug!("capable started");
-
-    if KernelVersion::current()?.code() != version::LINUX_VERSION_CODE {
-        let major = version::LINUX_VERSION_CODE >> 16;
-        let minor = (version::LINUX_VERSION_CODE >> 8) & 0xff;
-        let patch = version::LINUX_VERSION_CODE & 0xff;
-        let current = KernelVersion::current().context("Unable to get kernel version")?.code();
-        let current_major = current >> 16;
-        let current_minor = (current >> 8) & 0xff;
-        let current_patch = current & 0xff;
-        warn!("This program was compiled for kernel version {}.{}.{}, but the current kernel version is {}.{}.{}",
-              major, minor, patch, current_major, current_minor, current_patch);
-        warn!("This may cause the program to fail or behave unexpectedly");
-    }
-
-    debug!("setting capabilities");
-
-    // Bump the memlock rlimit. This is needed for older kernels that don't use the
-    // new memcg based accounting, see https://lwn.net/Articles/837122/
-    let rlim = libc::rlimit {
-        rlim_cur: libc::RLIM_INFINITY,
-        rlim_max: libc::RLIM_INFINITY,
-    };
-    setresource_effective(true)?;
-    let ret = unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlim) };
-    setresource_effective(false)?;
-    if ret != 0 {
-        debug!("remove limit on locked memory failed, ret is: {}", ret);
+    let sign_key = cli_args
+        .sign_key
+        .as_deref()
+        .map(SigningKey::read)
+        .transpose()?;
+    let signs_output = cli_args.output.is_some()
+        || cli_args.merge_into.is_some()
+        || matches!(cli_args.subcommand, Some(Subcommand::Prune { .. }));
+    if sign_key.is_some() && !signs_output {
+        return Err(anyhow::anyhow!("--sign-key requires --output"));
     }
 
-    setbpf_effective(true)?;
-    setadmin_effective(true)?;
+    match &cli_args.subcommand {
+        Some(Subcommand::Enforce { profile }) => {
+            if cli_args.lsm {
+                return enforce::enforce_lsm(
+                    profile,
+                    &cli_args.command,
+                    cli_args.landlock,
+                    cli_args.bpf_object.as_deref(),
+                );
+            }
+            return enforce::enforce(profile, &cli_args.command, cli_args.landlock);
+        }
+        Some(Subcommand::AuditCorrelate { profile }) => {
+            let window = match (cli_args.since, cli_args.until) {
+                (Some(start), Some(end)) => Some(TimeWindow { start, end }),
+                (None, None) => None,
+                _ => return Err(anyhow::anyhow!("--since and --until must be used together")),
+            };
+            return audit::audit_correlate(
+                profile,
+                cli_args
+                    .audit_log
+                    .as_deref()
+                    .unwrap_or(Path::new(audit::DEFAULT_AUDIT_LOG)),
+                window,
+                cli_args.comm.as_deref(),
+            );
+        }
+        Some(Subcommand::Report) => {
+            if let Some(dir) = &cli_args.pinned {
+                report_pinned(dir, cli_args.output.clone(), &cli_args.table_layout)?;
+                return sign_output(sign_key.as_ref(), cli_args.output.as_deref());
+            }
+            let from = cli_args
+                .from
+                .as_ref()
+                .ok_or(anyhow::anyhow!(
+                    "report requires --from <recording> or --pinned <directory>"
+                ))?;
+            let mut plugins = Plugins::load(&cli_args.plugins)?;
+            record::report(
+                from,
+                cli_args.output.clone(),
+                &mut plugins,
+                cli_args.startup,
+                &cli_args.table_layout,
+            )?;
+            return sign_output(sign_key.as_ref(), cli_args.output.as_deref());
+        }
+        Some(Subcommand::Replay {
+            requests,
+            strace_log,
+        }) => {
+            let mut plugins = Plugins::load(&cli_args.plugins)?;
+            record::replay(
+                requests,
+                strace_log.as_deref(),
+                cli_args.output.clone(),
+                &mut plugins,
+                cli_args.startup,
+            )?;
+            return sign_output(sign_key.as_ref(), cli_args.output.as_deref());
+        }
+        Some(Subcommand::History) => {
+            return history::list(&cli_args.labels);
+        }
+        Some(Subcommand::Show { run, baseline }) => {
+            history::show(run, baseline.as_ref(), cli_args.output.clone())?;
+            return sign_output(sign_key.as_ref(), cli_args.output.as_deref());
+        }
+        Some(Subcommand::Prune { profile }) => {
+            let profile = profile
+                .clone()
+                .or(cli_args.command.first().map(PathBuf::from))
+                .ok_or(anyhow::anyhow!("prune requires a profile file"))?;
+            let max_age = cli_args
+                .not_seen_since
+                .ok_or(anyhow::anyhow!("prune requires --not-seen-since"))?;
+            prune::prune(&profile, max_age, cli_args.remove, cli_args.output.clone())?;
+            if !cli_args.remove {
+                return Ok(());
+            }
+            return sign_output(
+                sign_key.as_ref(),
+                Some(cli_args.output.as_deref().unwrap_or(&profile)),
+            );
+        }
+        Some(Subcommand::Selftest) => {
+            return selftest::selftest();
+        }
+        Some(Subcommand::SelftestWorkload { name, path }) => {
+            return selftest::workload(name, path);
+        }
+        Some(Subcommand::Check) => {
+            return check::check();
+        }
+        Some(Subcommand::Batch { jobs }) => {
+            return batch::batch(jobs, cli_args.output.as_deref());
+        }
+        Some(Subcommand::BootProfile) => {
+            return boot::boot_profile(
+                cli_args.output.as_deref(),
+                cli_args.sleep,
+                cli_args.bpf_object.as_deref(),
+            );
+        }
+        Some(Subcommand::BootProfileUnit) => {
+            return boot::unit(cli_args.output.as_deref());
+        }
+        Some(Subcommand::Auditd) => {
+            return auditd::auditd(
+                cli_args.output.as_deref(),
+                cli_args.interval,
+                cli_args.bpf_object.as_deref(),
+            );
+        }
+        Some(Subcommand::AuditdUnit) => {
+            return auditd::unit(cli_args.output.as_deref());
+        }
+        Some(Subcommand::Top) => {
+            return top::top(cli_args.bpf_object.as_deref());
+        }
+        None => {}
+    }
+    if let Some(user) = &cli_args.user_slice {
+        return slice::user_slice(
+            user,
+            cli_args.output.as_deref(),
+            cli_args.sleep,
+            cli_args.bpf_object.as_deref(),
+        );
+    }
+    if let Some(dir) = &cli_args.pin {
+        pinned::pin(dir, cli_args.bpf_object.as_deref())?;
+        println!(
+            "Probes pinned to {}, collecting until it is removed",
+            dir.display()
+        );
+        return Ok(());
+    }
 
-    // This will include your eBPF object file as raw bytes at compile-time and load it at
-    // runtime. This approach is recommended for most real-world use cases. If you would
-    // like to specify the eBPF program at runtime rather than at compile-time, you can
-    // reach for `Bpf::load_file` instead.
-    let mut bpf = aya::Ebpf::load(aya::include_bytes_aligned!(concat!(
-        env!("OUT_DIR"),
-        "/capable"
-    )))?;
-
-    if let Err(e) = EbpfLogger::init(&mut bpf) {
-        // This can happen if you remove all log statements from your eBPF program.
-        warn!("failed to initialize eBPF {}", e);
-    }
-    debug!("loading and attaching program {}", "capable");
-    setbpf_effective(true)?;
-    setadmin_effective(true)?;
-    let program: &mut KProbe = bpf.program_mut("capable").expect("failed to get Kprobe capable program").try_into().context("Failed to get Kprobe")?;
-    program.load()?;
-    program.attach("cap_capable", 0)?;
-    setbpf_effective(false)?;
-    setadmin_effective(false)?;
-    debug!("program {} loaded and attached", "capable");
-    let mut requests_map: Stack<_, Request> =
-        Stack::try_from(bpf.take_map("ENTRY_STACK").expect("Unable to obtain Stack requests"))?;
-    let stack_traces = StackTraceMap::try_from(bpf.borrow().map("STACKTRACE_MAP").expect("unable to get Stacktrace map"))?;
-    let ksyms: std::collections::BTreeMap<u64, String> = kernel_symbols()?;
-    setbpf_effective(false)?;
-    setadmin_effective(false)?;
-    
+    let mut probes = Probes::load(cli_args.bpf_object.as_deref())?;
+    let mut hook = cli_args.on_event.clone().map(EventHook::new);
+    let mut plugins = Plugins::load(&cli_args.plugins)?;
+    let mut recorder = cli_args.record.as_ref().map(Recorder::create).transpose()?;
+    let mut dump = cli_args.debug_dump.as_ref().map(DebugDump::create).transpose()?;
     
     {
+        if let Some(ns) = cli_args.nsid {
+            let attach = Attach {
+                ns,
+                sleep: cli_args.sleep,
+                output: cli_args.output.clone(),
+                labels: &cli_args.labels,
+                startup: cli_args.startup,
+            };
+            attach::profile_namespace(attach, &mut probes, &mut plugins)?;
+            return sign_output(sign_key.as_ref(), cli_args.output.as_deref());
+        }
         if cli_args.daemon || cli_args.command.is_empty() {
+            if cli_args.cgroup {
+                warn!("--cgroup only applies to a command, it is ignored");
+            }
+            if dump.is_some() {
+                warn!("--debug-dump only applies to a command, it is ignored");
+            }
             println!("Waiting for Ctrl-C...");
             let term = Arc::new(AtomicBool::new(false));
             signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&term))?;
+            // aggregate while collecting, so memory depends on the observed processes
+            // and not on the capture duration
+            let mut set_entry = HashSet::new();
+            let mut user_namespaces = UserNamespaces::default();
+            // the whole hierarchy, units that stopped during the capture stay attributed
+            let mut units = UnitCgroups::new("");
+            let mut alerter = (!cli_args.alert.is_empty()).then(|| Alerter::new(cli_args.alert));
             while !term.load(Ordering::Relaxed) {
                 thread::sleep(Duration::from_millis(400));
+                probes.drain(&mut set_entry, observer(&mut recorder))?;
+                units.record(probes.cgroup_paths()?);
+                user_namespaces.observe(&set_entry);
+                notify_capabilities(&mut hook, &set_entry);
+                if let Some(alerter) = alerter.as_mut() {
+                    alerter.check(&set_entry);
+                }
+            }
+            probes.drain(&mut set_entry, observer(&mut recorder))?;
+            units.record(probes.cgroup_paths()?);
+            notify_capabilities(&mut hook, &set_entry);
+            if let Some(recorder) = recorder.take() {
+                recorder.finish()?;
             }
-            print_all(&mut requests_map, &stack_traces, &ksyms, cli_args.output)?;
+            if cli_args.stats {
+                eprint!("{}", probes.collector_stats()?);
+            }
+            print_all(
+                set_entry,
+                cli_args.output.clone(),
+                user_namespaces,
+                &units,
+                &cli_args.table_layout,
+            )?;
+            sign_output(sign_key.as_ref(), cli_args.output.as_deref())?;
         } else {
-            let nsinode: Rc<RefCell<u32>> = Rc::new(0.into());
-            let mut pid = 0;
+            if !cli_args.alert.is_empty() {
+                warn!("--alert only applies in daemon mode, it is ignored");
+            }
+            // a single command unless --session or --cmd-file give several
+            let commands = if cli_args.commands.is_empty() {
+                vec![cli_args.command.clone()]
+            } else {
+                std::mem::take(&mut cli_args.commands)
+            };
+            let workdir = create_workdir()?;
+            let dbus_json_path = workdir.path().join(DBUS_JSON_NAME);
+            let strace_log_path = workdir.path().join(STRACE_LOG_NAME);
             //we need to fork
 
             let forked = unsafe { fork().expect("Failed to fork") };
             match forked {
                 ForkResult::Child => {
+                    // the parent owns the working directory, it must not be removed when this
+                    // process unwinds
+                    std::mem::forget(workdir);
                     let term_now = Arc::new(Memory::default());
                     for sig in TERM_SIGNALS {
                         // When terminated by a second term signal, exit with exit code 1.
@@ -859,77 +1888,256 @@ fn main() -> Result<(), anyhow::Error> {
                         flag::register(*sig, Arc::clone(&term_now.cancel))?;
                     }
                     nix::unistd::setuid(nix::unistd::Uid::from_raw(0)).expect("Failed to setuid");
-                    if let Ok(res) = run_dbus_monitor(term_now.clone()) {
-                        //debug!("MEMORY : {:?}", term_now);
-                        let mut file = File::create(DBUS_JSON_PATH)?;
-                        write!(file,"{}",&serde_json::to_string(&res)?)?;
-                        file.flush()?;
-                        
+                    match run_dbus_monitor(term_now.clone()) {
+                        Ok(res) => {
+                            //debug!("MEMORY : {:?}", term_now);
+                            if let Err(e) = bus::write_dbus_result(&dbus_json_path, &res) {
+                                warn!("failed to write dbus result: {}", e);
+                                exit(1);
+                            }
+                        }
+                        Err(e) => {
+                            warn!("dbus monitor failed: {}", e);
+                            exit(1);
+                        }
                     }
                     exit(0);
 
                 }
                 // let's setuid(root)
                 ForkResult::Parent { child } => {
-                    let exit = run_command(&mut cli_args, nsinode.clone(), &mut pid)?;
-                    kill(child, nix::sys::signal::Signal::SIGINT)
-                        .expect("failed to send SIGINT to child");
-                    waitpid(child, Some(WaitPidFlag::empty()))?;
-                    if !exit.success() && cli_args.output.is_none() {
-                        eprintln!("Command failed with exit status: {}", exit);
-                        eprintln!("Please check the command and try again with requested capabilities as you want to reach");
+                    let mut runs = Vec::new();
+                    for command in commands {
+                        cli_args.command = command;
+                        runs.push(profile_command(
+                            &mut cli_args,
+                            &mut probes,
+                            &mut recorder,
+                            &mut dump,
+                            &mut hook,
+                            &strace_log_path,
+                        )?);
                     }
+                    stop_dbus_monitor(child);
 
-                    let mut capset = program_capabilities(
-                        &nsinode.as_ref().borrow(),
-                        &mut requests_map,
-                        &stack_traces,
-                        &ksyms,
-                    )
-                    .expect("failed to print capabilities");
-                    let file_path= format!("/tmp/capable_strace_{}.log", getpid());
-                    let access: Vec<SyscallAccessEntry> = if metadata(&file_path).is_ok() {
-                        read_strace(file_path)?
-                        .iter()
-                        .map(|syscall| {
-                            if syscall.syscall.trim() == "ptrace" {
-                                capset.add(Cap::SYS_PTRACE);
-                            }
-                            syscalls::syscall_to_entry(syscall)
-                        })
-                        .flatten()
-                        .flatten()
-                        .collect()
+                    // dbus filtering
+                    // if the dbus result exists, we will use it to filter the dbus methods
+                    let dbus_result = if metadata(&dbus_json_path).is_ok() {
+                        bus::read_dbus_result(&dbus_json_path)
+                            .inspect_err(|e| {
+                                warn!("Unable to read dbus monitor result, dbus methods are omitted: {}", e)
+                            })
+                            .ok()
                     } else {
-                        vec![]
+                        warn!("No dbus monitor result found, dbus methods are omitted");
+                        None
                     };
-                    let mut map = std::collections::HashMap::new();
-                    for entry in access {
-                        let key = entry.path.clone();
-                        let value = entry.access;
-                        *map.entry(key).or_insert(value) |= entry.access;
+                    let failure = runs.iter().map(|run| run.exit).find(|exit| !exit.success());
+                    let mut profiles = Vec::new();
+                    if let (Some(dump), Some(dbus_result)) = (&dump, &dbus_result) {
+                        dump.dbus(dbus_result)?;
+                    }
+                    for (index, run) in (1..).zip(runs) {
+                        if let (Some(dump), Some(strace_log)) = (&dump, &run.strace_log) {
+                            dump.strace(index, strace_log)?;
+                        }
+                        if let Some(recorder) = recorder.as_mut() {
+                            recorder.record_run(
+                                &run.command,
+                                run.pid,
+                                run.ns,
+                                run.window,
+                                run.strace_log.as_deref(),
+                                dbus_result.as_ref(),
+                            )?;
+                        }
+                        if let Some(candidate) = cli_args.try_caps {
+                            eprint!(
+                                "{}",
+                                TrialReport::new(candidate, run.denied, run.strace_log.as_deref())
+                            );
+                        }
+                        let mut result = build_result(
+                            run.capset,
+                            run.strace_log.as_deref(),
+                            dbus_result.as_ref(),
+                            run.ns,
+                            Some(run.window),
+                            run.reasons,
+                        );
+                        explain::explain_requested_modules(&mut result, &run.modules);
+                        explain::explain_dac_accesses(&mut result, &run.dac);
+                        result.sysctls = run.sysctls;
+                        result.self_managed = run.self_managed;
+                        result.namespaces = run.nested;
+                        result.held = run.held;
+                        result.setuid = run.setuid;
+                        result.labels = cli_args.labels.clone();
+                        if let Some(dump) = &dump {
+                            dump.analysis(index, &result)?;
+                        }
+                        plugins.filter(&mut result, run.pid, run.ns);
+                        result.containers = containers::compare(&result);
+                        phase::classify(&mut result, cli_args.startup);
+                        if let Some(hook) = hook.as_mut() {
+                            for cap in result.capability_names() {
+                                hook.notify(Event::Capability {
+                                    capability: cap.to_string(),
+                                    pid: run.pid,
+                                    ns: run.ns,
+                                });
+                            }
+                            for (path, access) in &result.files {
+                                if access.contains(Access::W) {
+                                    hook.notify(Event::FileWrite { path, access: *access });
+                                }
+                            }
+                            for method in &result.dbus {
+                                hook.notify(Event::Dbus { method });
+                            }
+                        }
+                        profiles.push(RunProfile {
+                            commands: vec![run.command],
+                            end: run.window.end,
+                            strace_logs: run.strace_log.into_iter().collect(),
+                            seccomp: run.seccomp,
+                            result,
+                        });
+                    }
+                    if let Some(recorder) = recorder.take() {
+                        recorder.finish()?;
+                    }
+                    if let Some(dump) = dump.take() {
+                        dump.finish()?;
+                    }
+                    if cli_args.session == Some(SessionMode::Merge) {
+                        profiles = profiles
+                            .into_iter()
+                            .reduce(|mut merged, profile| {
+                                merged.merge(profile);
+                                merged
+                            })
+                            .into_iter()
+                            .collect();
                     }
 
-                    // dbus filtering
-                    // if DBUS_JSON_PATH exists, we will use it to filter the dbus methods
-                    let method_list = if metadata(DBUS_JSON_PATH).is_ok() {
-                        bus::get_dbus_methods(DBUS_JSON_PATH, nsinode.clone())?
-                    } else {
-                        vec![]
-                    };
-                     
-                    let result = ProgramResult {
-                        capabilities: capset_to_vec(&capset),
-                        files: map,
-                        dbus: method_list,
-                    };
-                    if let Some(output) = cli_args.output {
-                        let mut file = File::create(output)?;
-                        writeln!(file, "{}", serde_json::to_string_pretty(&result)?)?;
-                    } else {
-                        println!("{}", serde_json::to_string_pretty(&result)?);
+                    let split = profiles.len() > 1;
+                    for (index, profile) in profiles.iter_mut().enumerate() {
+                        let indexed = |path: &Path| {
+                            if split {
+                                indexed_path(path, index + 1)
+                            } else {
+                                path.to_path_buf()
+                            }
+                        };
+                        let command = join_commands(&profile.commands);
+                        let result = &mut profile.result;
+                        // the statistics tell the syscalls the seccomp collector let through
+                        let uncollected = profile
+                            .seccomp
+                            .is_some_and(|seccomp| seccomp.foreign > 0);
+                        if cli_args.stats || uncollected {
+                            let mut stats = probes.collector_stats()?;
+                            // parsed again to count the lines rejected
+                            for log in &profile.strace_logs {
+                                stats.strace(log, parse_strace_counted(log).1);
+                            }
+                            stats.seccomp = profile.seccomp;
+                            if let Some(messages) = dbus_result.as_ref() {
+                                stats.dbus(messages);
+                            }
+                            result.stats = Some(stats);
+                        }
+                        let run = if cli_args.no_history {
+                            None
+                        } else {
+                            match history::save(&command, profile.end, result) {
+                                Ok(id) => {
+                                    debug!("Run stored in history as {}", id);
+                                    Some(id)
+                                }
+                                Err(e) => {
+                                    warn!("Unable to store the run in history: {}", e);
+                                    None
+                                }
+                            }
+                        };
+                        if let Some(learned) = &cli_args.merge_into {
+                            let learned = indexed(learned);
+                            result.observe(Observation {
+                                run,
+                                date: profile.end,
+                            });
+                            if learned.exists() {
+                                let stats = result.stats.take();
+                                let observed = std::mem::replace(result, read_profile(&learned)?);
+                                result.merge(observed);
+                                result.containers = containers::compare(result);
+                                result.stats = stats;
+                            }
+                        }
+                        // the learned profile is updated unless written elsewhere
+                        let output = cli_args.output.as_ref().or(cli_args.merge_into.as_ref());
+                        if let Some(output) = output {
+                            let output = indexed(output);
+                            compress::write(&output, &serde_json::to_string_pretty(&result)?)?;
+                            sign_output(sign_key.as_ref(), Some(&output))?;
+                        } else {
+                            println!("{}", serde_json::to_string_pretty(&result)?);
+                        }
+                        for report in plugins.analyze(result) {
+                            eprintln!("{}", report);
+                        }
+                        if cli_args.lint {
+                            eprint!("{}", RiskReport::new(result));
+                        }
+                        if let Some(policy) = cli_args.policy {
+                            eprint!(
+                                "{}",
+                                policy.render(&profile.commands, &profile.strace_logs, result)
+                            );
+                        }
+                        if let Some(stats) = &result.stats {
+                            eprint!("{}", stats);
+                        }
+                        if let Some(baseline_path) = &cli_args.assert {
+                            let baseline = read_profile(baseline_path)?;
+                            let diff = result.beyond(&baseline);
+                            if !diff.is_empty() {
+                                eprintln!(
+                                    "The command requires more than the baseline {}:\n{}",
+                                    baseline_path.display(),
+                                    diff
+                                );
+                                drop(workdir);
+                                std::process::exit(ASSERT_EXIT_CODE);
+                            }
+                        }
+                        if let Some(role_task) = &cli_args.install_role {
+                            rootasrole::install_role(role_task, &profile.commands, result)?;
+                        }
+                        if let Some(wrapper) = &cli_args.emit_wrapper {
+                            // a wrapper runs a single command
+                            let wrappers = profile.commands.len() > 1;
+                            for (number, command) in profile.commands.iter().enumerate() {
+                                let path = indexed(wrapper);
+                                let path = if wrappers {
+                                    indexed_path(&path, number + 1)
+                                } else {
+                                    path
+                                };
+                                wrapper::emit_wrapper(
+                                    path,
+                                    command,
+                                    result,
+                                    cli_args.install_role.as_deref(),
+                                )?;
+                            }
+                        }
                     }
-                    if !exit.success() {
+                    if let Some(exit) = failure {
+                        // exit() does not run destructors
+                        drop(workdir);
                         //set the exit code to the command exit code
                         //copy the exit message
                         std::process::exit(exit.code().unwrap_or(-1));
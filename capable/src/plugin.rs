@@ -0,0 +1,205 @@
+//! WASM plugins filtering, annotating and analyzing findings.
+//!
+//! A plugin is a WASM module exporting:
+//! - `memory`: its linear memory
+//! - `alloc(len: i32) -> i32`: return a buffer of `len` bytes where the input is written
+//! - `filter(ptr: i32, len: i32) -> i64` (optional): receives a finding as JSON, as sent to
+//!   --on-event hooks. Returns 0 to keep it, a negative value to drop it, or
+//!   `ptr << 32 | len` of a UTF-8 annotation to keep it annotated.
+//! - `analyze(ptr: i32, len: i32) -> i64` (optional): receives the final profile as JSON.
+//!   Returns 0, or `ptr << 32 | len` of a UTF-8 report, such as a generated policy.
+//!
+//! Plugins have no imports, so they cannot access the system, and each call is bounded
+//! by `PLUGIN_FUEL`.
+
+use std::fs::read;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use capable_common::{Nsid, Pid};
+use tracing::{debug, warn};
+use wasmi::{Config, Engine, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+use capable_core::profile::ProgramResult;
+
+use crate::hooks::Event;
+
+/// Instructions a plugin may execute per call
+const PLUGIN_FUEL: u64 = 100_000_000;
+
+pub enum Verdict {
+    Keep,
+    Drop,
+    Annotate(String),
+}
+
+struct Plugin {
+    path: PathBuf,
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    filter: Option<TypedFunc<(i32, i32), i64>>,
+    analyze: Option<TypedFunc<(i32, i32), i64>>,
+}
+
+impl Plugin {
+    fn load(engine: &Engine, path: &Path) -> Result<Plugin, anyhow::Error> {
+        let wasm = read(path).with_context(|| format!("Unable to read plugin {}", path.display()))?;
+        let module = Module::new(engine, &wasm[..])
+            .map_err(|e| anyhow::anyhow!("Invalid plugin {}: {}", path.display(), e))?;
+        let mut store = Store::new(engine, ());
+        let instance: Instance = Linker::<()>::new(engine)
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| anyhow::anyhow!("Unable to instantiate plugin {}: {}", path.display(), e))?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or(anyhow::anyhow!("Plugin {} does not export its memory", path.display()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|e| anyhow::anyhow!("Plugin {} has no alloc function: {}", path.display(), e))?;
+        let filter = instance.get_typed_func(&store, "filter").ok();
+        let analyze = instance.get_typed_func(&store, "analyze").ok();
+        debug!(
+            "plugin {} loaded, filter: {}, analyze: {}",
+            path.display(),
+            filter.is_some(),
+            analyze.is_some()
+        );
+        Ok(Plugin {
+            path: path.to_path_buf(),
+            store,
+            memory,
+            alloc,
+            filter,
+            analyze,
+        })
+    }
+
+    /// Copy `input` into the plugin memory, call `func` on it and return its raw result
+    fn call(&mut self, func: TypedFunc<(i32, i32), i64>, input: &[u8]) -> Result<i64, anyhow::Error> {
+        let err = |e: &dyn std::fmt::Display| anyhow::anyhow!("{}: {}", self.path.display(), e);
+        self.store.set_fuel(PLUGIN_FUEL).map_err(|e| err(&e))?;
+        let len = i32::try_from(input.len())?;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(|e| err(&e))?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, input)
+            .map_err(|e| err(&e))?;
+        func.call(&mut self.store, (ptr, len)).map_err(|e| err(&e))
+    }
+
+    /// Read the `ptr << 32 | len` string returned by a plugin
+    fn read_string(&self, packed: i64) -> Result<String, anyhow::Error> {
+        let ptr = (packed >> 32) as u32 as usize;
+        let len = packed as u32 as usize;
+        let mut buf = vec![0u8; len];
+        self.memory
+            .read(&self.store, ptr, &mut buf)
+            .map_err(|e| anyhow::anyhow!("{}: {}", self.path.display(), e))?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn filter(&mut self, event: &Event) -> Result<Verdict, anyhow::Error> {
+        let Some(filter) = self.filter else {
+            return Ok(Verdict::Keep);
+        };
+        let ret = self.call(filter, serde_json::to_string(event)?.as_bytes())?;
+        Ok(match ret {
+            0 => Verdict::Keep,
+            r if r < 0 => Verdict::Drop,
+            r => Verdict::Annotate(self.read_string(r)?),
+        })
+    }
+
+    fn analyze(&mut self, result: &ProgramResult) -> Result<Option<String>, anyhow::Error> {
+        let Some(analyze) = self.analyze else {
+            return Ok(None);
+        };
+        match self.call(analyze, serde_json::to_string(result)?.as_bytes())? {
+            0 => Ok(None),
+            r => self.read_string(r).map(Some),
+        }
+    }
+}
+
+/// Plugins given with --plugin, applied in order
+pub struct Plugins {
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    pub fn load(paths: &[PathBuf]) -> Result<Plugins, anyhow::Error> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let plugins = paths
+            .iter()
+            .map(|path| Plugin::load(&engine, path))
+            .collect::<Result<_, _>>()?;
+        Ok(Plugins { plugins })
+    }
+
+    /// Verdict of all the plugins on a finding, the first drop wins and annotations add up.
+    /// A failing plugin keeps the finding, so a broken filter never hides a privilege.
+    fn verdict(&mut self, event: &Event, annotations: &mut Vec<String>) -> bool {
+        for plugin in self.plugins.iter_mut() {
+            match plugin.filter(event) {
+                Ok(Verdict::Keep) => {}
+                Ok(Verdict::Drop) => return false,
+                Ok(Verdict::Annotate(note)) => annotations.push(note),
+                Err(e) => warn!("plugin filter failed: {}", e),
+            }
+        }
+        true
+    }
+
+    /// Remove the findings dropped by the plugins from the profile and record annotations.
+    /// `pid` and `ns` identify the profiled command.
+    pub fn filter(&mut self, result: &mut ProgramResult, pid: Pid, ns: Nsid) {
+        if self.plugins.is_empty() {
+            return;
+        }
+        let mut annotations = std::mem::take(&mut result.annotations);
+        let mut keep = |event: Event| {
+            let mut notes = Vec::new();
+            let kept = self.verdict(&event, &mut notes);
+            if kept && !notes.is_empty() {
+                annotations.entry(event.key()).or_default().extend(notes);
+            }
+            kept
+        };
+        result.capabilities.retain(|cap| {
+            keep(Event::Capability {
+                capability: cap.name.clone(),
+                pid,
+                ns,
+            })
+        });
+        result.files.retain(|path, access| {
+            keep(Event::File {
+                path,
+                access: *access,
+            })
+        });
+        result.dbus.retain(|method| keep(Event::Dbus { method }));
+        let capabilities: Vec<String> = result.capability_names().map(String::from).collect();
+        result
+            .suggestions
+            .retain(|suggestion| capabilities.contains(&suggestion.capability));
+        result.annotations = annotations;
+    }
+
+    /// Reports of the analyzer plugins on the final profile
+    pub fn analyze(&mut self, result: &ProgramResult) -> Vec<String> {
+        self.plugins
+            .iter_mut()
+            .filter_map(|plugin| match plugin.analyze(result) {
+                Ok(report) => report,
+                Err(e) => {
+                    warn!("plugin analyzer failed: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,144 @@
+use std::fs::{metadata, read_dir, read_to_string};
+use std::os::unix::fs::MetadataExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use capable_common::{Nsid, Pid};
+use capable_core::caps::{capset_to_vec, setptrace_effective};
+use capable_core::profile::HeldCapabilities;
+use capctl::CapSet;
+use tracing::debug;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Capability sets of the processes of the profiled namespace, from /proc/PID/status.
+/// Each set is the union over the processes and the samples.
+#[derive(Default, Clone, Copy)]
+pub struct HeldSets {
+    pub effective: CapSet,
+    pub permitted: CapSet,
+    pub bounding: CapSet,
+    /// Whether a process ran with an effective uid or gid other than its real one,
+    /// i.e. a set-ID transition was applied at exec
+    pub set_id: bool,
+    pub samples: usize,
+}
+
+impl HeldSets {
+    /// Compare with the capabilities the processes requested
+    pub fn compare(&self, used: CapSet) -> HeldCapabilities {
+        HeldCapabilities {
+            effective: capset_to_vec(&self.effective),
+            permitted: capset_to_vec(&self.permitted),
+            bounding: capset_to_vec(&self.bounding),
+            unused: capset_to_vec(&(self.permitted - used)),
+            not_held: capset_to_vec(&(used - self.effective)),
+        }
+    }
+}
+
+/// CapEff, CapPrm and CapBnd of a /proc/PID/status
+fn parse_status(status: &str) -> Option<(CapSet, CapSet, CapSet)> {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| u64::from_str_radix(value.trim(), 16).ok())
+            .map(CapSet::from_bitmask_truncate)
+    };
+    Some((field("CapEff:")?, field("CapPrm:")?, field("CapBnd:")?))
+}
+
+/// Whether the effective id of the Uid: or Gid: line differs from the real one
+fn changed_id(status: &str, name: &str) -> bool {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(name))
+        .is_some_and(|ids| {
+            let mut ids = ids.split_whitespace();
+            ids.next() != ids.next()
+        })
+}
+
+/// Processes of the PID namespace `ns`, by their pid seen from capable. Reading the
+/// namespaces of the processes of other users needs SYS_PTRACE.
+pub fn namespace_processes(ns: Nsid) -> Vec<Pid> {
+    let Ok(entries) = read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<Pid>().ok())
+        .filter(|pid| {
+            metadata(format!("/proc/{}/ns/pid", pid))
+                .is_ok_and(|namespace| namespace.ino() as Nsid == ns)
+        })
+        .collect()
+}
+
+/// Pid of the process in its own PID namespace, the last one of NSpid
+pub fn namespace_pid(pid: Pid) -> Option<Pid> {
+    read_to_string(format!("/proc/{}/status", pid))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("NSpid:"))?
+        .split_whitespace()
+        .last()?
+        .parse()
+        .ok()
+}
+
+fn sample(ns: Nsid, exclude: Option<Pid>, held: &mut HeldSets) {
+    for pid in namespace_processes(ns)
+        .into_iter()
+        .filter(|pid| Some(*pid) != exclude)
+    {
+        // the process may exit between both reads
+        let Ok(status) = read_to_string(format!("/proc/{}/status", pid)) else {
+            continue;
+        };
+        let Some((effective, permitted, bounding)) = parse_status(&status) else {
+            continue;
+        };
+        held.set_id |= changed_id(&status, "Uid:") || changed_id(&status, "Gid:");
+        held.effective |= effective;
+        held.permitted |= permitted;
+        held.bounding |= bounding;
+        held.samples += 1;
+    }
+}
+
+/// Samples the capability sets of the processes of the PID namespace `ns` while they run
+pub struct HeldSampler {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<HeldSets>,
+}
+
+impl HeldSampler {
+    /// `exclude` is a process of the namespace not part of the command, e.g. strace
+    pub fn start(ns: Nsid, exclude: Option<Pid>) -> HeldSampler {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut held = HeldSets::default();
+            // capabilities are per thread, the namespaces of other users need SYS_PTRACE
+            if let Err(e) = setptrace_effective(true) {
+                debug!("unable to raise CAP_SYS_PTRACE: {}", e);
+            }
+            while !stopped.load(Ordering::Relaxed) {
+                sample(ns, exclude, &mut held);
+                thread::sleep(SAMPLE_INTERVAL);
+            }
+            held
+        });
+        HeldSampler { stop, handle }
+    }
+
+    /// Stop sampling, None when no process was sampled
+    pub fn stop(self) -> Option<HeldSets> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().ok().filter(|held| held.samples > 0)
+    }
+}
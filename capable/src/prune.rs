@@ -0,0 +1,52 @@
+//! capable prune: entries of a learned profile that no recent run exercised, so that the
+//! policies generated from it shrink over time instead of only growing.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use capable_core::profile::read_profile;
+use capable_core::time::{format_time, unix_time};
+use capable_core::{compress, containers};
+
+/// List the entries of `profile` not observed for `max_age`, and remove them with `remove`.
+/// The pruned profile replaces `profile` unless `output` is given.
+pub fn prune(
+    profile: &Path,
+    max_age: Duration,
+    remove: bool,
+    output: Option<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let mut result = read_profile(profile)?;
+    if result.provenance.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} records no run, learn it with --merge-into",
+            profile.display()
+        ));
+    }
+    let since = unix_time().saturating_sub(max_age.as_secs());
+    // entries without provenance were in the profile before it was learned
+    let stale: Vec<(String, Option<u64>)> = result
+        .entry_keys()
+        .map(|key| {
+            let last = result.last_observed(&key);
+            (key, last)
+        })
+        .filter(|(_, last)| last.is_none_or(|date| date < since))
+        .collect();
+    for (key, last) in &stale {
+        let (kind, name) = key.split_once(':').unwrap_or(("", key));
+        match last {
+            Some(date) => println!("- {} {}, last observed {}", kind, name, format_time(*date)),
+            None => println!("- {} {}, never observed", kind, name),
+        }
+    }
+    if !remove {
+        return Ok(());
+    }
+    for (key, _) in &stale {
+        result.remove_entry(key);
+    }
+    result.containers = containers::compare(&result);
+    let path = output.as_deref().unwrap_or(profile);
+    compress::write(path, &serde_json::to_string_pretty(&result)?)
+}
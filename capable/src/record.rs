@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use capable_common::{Nsid, Pid};
+use tracing::debug;
+
+use capable_core::profile::ProgramResult;
+use capable_core::recording::{read_recording, Recording};
+use capable_core::replay::Replay;
+use capable_core::{compress, containers, phase};
+
+use crate::idmap::UserNamespaces;
+use crate::plugin::Plugins;
+use crate::print_all;
+use crate::table::TableLayout;
+use crate::units::UnitCgroups;
+
+/// Regenerate the report of a recording with the current analysis and plugins
+pub fn report<P: AsRef<Path>>(
+    path: P,
+    output: Option<PathBuf>,
+    plugins: &mut Plugins,
+    startup: Duration,
+    layout: &TableLayout,
+) -> Result<(), anyhow::Error> {
+    let Recording { replay, run } = read_recording(path)?;
+
+    // recordings of daemon mode have no run
+    let Some(run) = run else {
+        // the cgroups of a recording are not those of this boot, they have no unit
+        return print_all(
+            replay.into_entries(),
+            output,
+            UserNamespaces::default(),
+            &UnitCgroups::new(""),
+            layout,
+        );
+    };
+    debug!("replaying run of {}", shell_words::join(&run.command));
+    let result = replay.result(run.ns, Some(run.window));
+    write_report(result, run.pid, run.ns, output, plugins, startup)
+}
+
+/// Generate the report of a fixture of requests, with the strace log of the command if any.
+/// The command is the process of the first request.
+pub fn replay<P: AsRef<Path>>(
+    requests: P,
+    strace_log: Option<&Path>,
+    output: Option<PathBuf>,
+    plugins: &mut Plugins,
+    startup: Duration,
+) -> Result<(), anyhow::Error> {
+    let mut replay = Replay::new();
+    replay.requests_file(&requests)?;
+    if let Some(strace_log) = strace_log {
+        replay.strace_file(strace_log)?;
+    }
+    debug!(
+        "{} requests replayed from {}",
+        replay.requests(),
+        requests.as_ref().display()
+    );
+    let Some(ns) = replay.root() else {
+        return Err(anyhow::anyhow!(
+            "No request in {}",
+            requests.as_ref().display()
+        ));
+    };
+    let pid = replay
+        .entries()
+        .iter()
+        .filter(|entry| entry.ns == ns)
+        .map(|entry| entry.pid)
+        .min()
+        .unwrap_or_default();
+    let result = replay.result(ns, None);
+    write_report(result, pid, ns, output, plugins, startup)
+}
+
+/// Write the profile of the namespace `ns` after the plugins, and print their analyses
+pub fn write_report(
+    mut result: ProgramResult,
+    pid: Pid,
+    ns: Nsid,
+    output: Option<PathBuf>,
+    plugins: &mut Plugins,
+    startup: Duration,
+) -> Result<(), anyhow::Error> {
+    plugins.filter(&mut result, pid, ns);
+    result.containers = containers::compare(&result);
+    phase::classify(&mut result, startup);
+    let json = serde_json::to_string_pretty(&result)?;
+    if let Some(output) = output {
+        compress::write(output, &json)?;
+    } else {
+        println!("{}", json);
+    }
+    for report in plugins.analyze(&result) {
+        eprintln!("{}", report);
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::ffi::CString;
+
+use anyhow::Context;
+use capctl::Cap;
+use nix::unistd::{getgrouplist, setgid, setgroups, setuid, Gid, Uid, User};
+
+use capable_core::caps::cap_effective;
+
+/// User the command runs as (--user). It is resolved before forking, as the switch runs in
+/// pre_exec where nothing must be allocated.
+pub struct RunAs {
+    pub name: String,
+    uid: Uid,
+    gid: Gid,
+    groups: Vec<Gid>,
+}
+
+impl RunAs {
+    /// Resolve a user name or uid from the user database
+    pub fn resolve(user: &str) -> Result<RunAs, anyhow::Error> {
+        let entry = match user.parse::<u32>() {
+            Ok(uid) => User::from_uid(Uid::from_raw(uid))?,
+            Err(_) => User::from_name(user)?,
+        }
+        .with_context(|| format!("Unknown user {}", user))?;
+        let name = CString::new(entry.name.as_str())?;
+        let groups = getgrouplist(&name, entry.gid)
+            .with_context(|| format!("Unable to get the groups of {}", entry.name))?;
+        Ok(RunAs {
+            name: entry.name,
+            uid: entry.uid,
+            gid: entry.gid,
+            groups,
+        })
+    }
+
+    /// Switch to the user and its groups. As outside of capable, the user has no
+    /// capabilities left: the uid change clears them. Runs in pre_exec.
+    pub fn apply(&self) -> std::io::Result<()> {
+        cap_effective(Cap::SETGID, true)?;
+        cap_effective(Cap::SETUID, true)?;
+        setgroups(&self.groups)?;
+        setgid(self.gid)?;
+        setuid(self.uid)?;
+        Ok(())
+    }
+}
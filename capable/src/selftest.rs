@@ -0,0 +1,172 @@
+use std::env::current_exe;
+use std::fs::{canonicalize, write};
+use std::net::TcpListener;
+use std::os::unix::fs::chown;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Context;
+use nix::sys::stat::{makedev, mknod, Mode, SFlag};
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+use tracing::debug;
+
+use capable_core::profile::read_profile;
+
+/// Workload run under capable by the self-test, with what must be detected.
+/// The workloads run without capabilities, a denied request is still reported.
+struct Workload {
+    name: &'static str,
+    description: &'static str,
+    capability: &'static str,
+    /// Whether the path given to the workload must be reported as accessed
+    file: bool,
+}
+
+const WORKLOADS: [Workload; 4] = [
+    Workload {
+        name: "bind",
+        description: "bind port 80",
+        capability: "CAP_NET_BIND_SERVICE",
+        file: false,
+    },
+    Workload {
+        name: "chown",
+        description: "chown a file",
+        capability: "CAP_CHOWN",
+        file: true,
+    },
+    Workload {
+        name: "mknod",
+        description: "create a device node",
+        capability: "CAP_MKNOD",
+        file: true,
+    },
+    Workload {
+        name: "raw-socket",
+        description: "open a raw socket",
+        capability: "CAP_NET_RAW",
+        file: false,
+    },
+];
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct SelftestRow {
+    workload: &'static str,
+    capability: String,
+    file: String,
+}
+
+fn status(detected: bool) -> &'static str {
+    if detected {
+        "ok"
+    } else {
+        "MISSING"
+    }
+}
+
+/// capable selftest workload: perform the privileged operation of a workload on `path`.
+/// It is expected to fail, only the request matters.
+pub fn workload(name: &str, path: &Path) -> Result<(), anyhow::Error> {
+    match name {
+        "bind" => {
+            let bound = TcpListener::bind(("0.0.0.0", 80));
+            debug!("bind: {:?}", bound.map(|_| ()));
+        }
+        "chown" => {
+            write(path, b"")?;
+            debug!("chown: {:?}", chown(path, Some(65534), Some(65534)));
+        }
+        "mknod" => {
+            let node = mknod(
+                path,
+                SFlag::S_IFCHR,
+                Mode::from_bits_truncate(0o600),
+                makedev(1, 3),
+            );
+            debug!("mknod: {:?}", node);
+        }
+        "raw-socket" => {
+            let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_RAW, libc::IPPROTO_ICMP) };
+            if fd >= 0 {
+                unsafe { libc::close(fd) };
+            }
+            debug!("raw socket: {}", fd);
+        }
+        _ => return Err(anyhow::anyhow!("Unknown self-test workload: {}", name)),
+    }
+    Ok(())
+}
+
+/// Profile one workload with this executable and compare with what it requires
+fn check(
+    capable: &Path,
+    workdir: &Path,
+    workload: &Workload,
+) -> Result<SelftestRow, anyhow::Error> {
+    let output = workdir.join(format!("{}.json", workload.name));
+    let path = workdir.join(workload.name);
+    let exit = Command::new(capable)
+        .arg("--no-history")
+        .arg("--quiet")
+        .arg("--output")
+        .arg(&output)
+        .arg("--")
+        .arg(capable)
+        .args(["selftest", "workload", workload.name])
+        .arg(&path)
+        .status()
+        .context("Unable to run capable")?;
+    if !exit.success() {
+        return Err(anyhow::anyhow!(
+            "capable failed on {}: {}",
+            workload.name,
+            exit
+        ));
+    }
+    let result = read_profile(&output)?;
+    let file = if workload.file {
+        status(result.files.contains_key(&path.display().to_string())).to_string()
+    } else {
+        "-".to_string()
+    };
+    Ok(SelftestRow {
+        workload: workload.description,
+        capability: format!(
+            "{} {}",
+            workload.capability,
+            status(result.has_capability(workload.capability))
+        ),
+        file,
+    })
+}
+
+/// capable selftest: validate that the capabilities and file accesses of known workloads are
+/// detected on this kernel
+pub fn selftest() -> Result<(), anyhow::Error> {
+    let capable = current_exe().context("Unable to find the capable executable")?;
+    let workdir = tempfile::Builder::new()
+        .prefix("capable-selftest-")
+        .tempdir()
+        .context("Unable to create temporary directory")?;
+    // strace reports canonical paths
+    let dir = canonicalize(workdir.path())?;
+    let mut rows = Vec::new();
+    for workload in WORKLOADS.iter() {
+        rows.push(check(&capable, &dir, workload)?);
+    }
+    println!("{}", Table::new(&rows).with(Style::modern()));
+    let failed = rows
+        .iter()
+        .filter(|row| row.capability.ends_with("MISSING") || row.file == "MISSING")
+        .count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} workloads are not fully detected",
+            failed,
+            rows.len()
+        ));
+    }
+    Ok(())
+}
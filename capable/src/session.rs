@@ -0,0 +1,85 @@
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+
+use capable_core::compress;
+
+/// Separator of the commands of a session on the command line
+pub const COMMAND_SEPARATOR: &str = "--";
+
+/// Profiles produced by a session of several commands
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SessionMode {
+    /// A single profile with what every command requires
+    Merge,
+    /// One profile per command
+    Split,
+}
+
+impl FromStr for SessionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(SessionMode::Merge),
+            "split" => Ok(SessionMode::Split),
+            _ => Err(anyhow::anyhow!(
+                "--session expects merge or split, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// Commands of the command line, separated by `--`
+pub fn split_commands(args: &[String]) -> Vec<Vec<String>> {
+    args.split(|arg| arg == COMMAND_SEPARATOR)
+        .filter(|command| !command.is_empty())
+        .map(<[String]>::to_vec)
+        .collect()
+}
+
+/// Commands of a --cmd-file, one per line with shell quoting. Empty lines and `#` comments
+/// are skipped.
+pub fn read_cmd_file<P: AsRef<Path>>(path: P) -> Result<Vec<Vec<String>>, anyhow::Error> {
+    let path = path.as_ref();
+    let content = read_to_string(path)
+        .with_context(|| format!("Unable to read command file {}", path.display()))?;
+    let mut commands = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let command = shell_words::split(line)
+            .with_context(|| format!("{}:{}: invalid command", path.display(), number + 1))?;
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// Command of a merged profile, the commands of the session separated by `--`
+pub fn join_commands(commands: &[Vec<String>]) -> Vec<String> {
+    commands.join(&COMMAND_SEPARATOR.to_string())
+}
+
+/// Path of the output of the `index`th command of a split session, e.g. profile.2.json, or
+/// profile.2.json.zst when compressed
+pub fn indexed_path(path: &Path, index: usize) -> PathBuf {
+    if compress::is_compressed(path) {
+        let mut indexed = indexed_path(&path.with_extension(""), index).into_os_string();
+        indexed.push(format!(".{}", compress::EXTENSION));
+        return PathBuf::from(indexed);
+    }
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, index),
+    };
+    path.with_file_name(name)
+}
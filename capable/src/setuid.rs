@@ -0,0 +1,105 @@
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Context;
+use capctl::prctl::{get_securebits, set_no_new_privs, set_securebits, Secbits};
+use tracing::debug;
+
+use capable_core::profile::SetuidTransition;
+
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+
+/// Lock bits of the securebits, with the bit each one locks
+const LOCKS: [(Secbits, Secbits); 4] = [
+    (Secbits::NOROOT_LOCKED, Secbits::NOROOT),
+    (Secbits::NO_SETUID_FIXUP_LOCKED, Secbits::NO_SETUID_FIXUP),
+    (Secbits::KEEP_CAPS_LOCKED, Secbits::KEEP_CAPS),
+    (
+        Secbits::NO_CAP_AMBIENT_RAISE_LOCKED,
+        Secbits::NO_CAP_AMBIENT_RAISE,
+    ),
+];
+
+/// What to do when the command is a set-user-ID or set-group-ID binary
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SetuidMode {
+    /// Let the kernel apply the transition, as it would outside of capable
+    Keep,
+    /// Run with no_new_privs, the kernel ignores the set-ID bits and file capabilities
+    Nullify,
+}
+
+impl FromStr for SetuidMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(SetuidMode::Keep),
+            "nullify" => Ok(SetuidMode::Nullify),
+            _ => Err(anyhow::anyhow!("--setuid expects keep or nullify, got {}", s)),
+        }
+    }
+}
+
+impl SetuidMode {
+    /// Securebits to give to `apply`, read before forking. A locked bit cannot be changed:
+    /// the locks are kept, and fail when NOROOT or NO_SETUID_FIXUP is locked set.
+    pub fn securebits() -> Result<Secbits, anyhow::Error> {
+        let current = get_securebits().context("Unable to read the securebits")?;
+        let mut securebits = Secbits::empty();
+        for (lock, bit) in LOCKS {
+            if !current.contains(lock) {
+                continue;
+            }
+            if current.contains(bit) && (Secbits::NOROOT | Secbits::NO_SETUID_FIXUP).contains(bit) {
+                return Err(anyhow::anyhow!(
+                    "The securebits of capable are locked with {:?}, the uid 0 and set-ID rules of exec cannot apply to the command",
+                    bit
+                ));
+            }
+            securebits |= lock | (current & bit);
+        }
+        Ok(securebits)
+    }
+
+    /// Set the securebits before exec, so the bprm credentials are computed from a known
+    /// state instead of the one inherited from capable. Runs in pre_exec, with CAP_SETPCAP.
+    pub fn apply(self, securebits: Secbits) -> std::io::Result<()> {
+        // no KEEP_CAPS, NOROOT or NO_SETUID_FIXUP: the uid 0 and set-ID rules of exec apply
+        set_securebits(securebits)?;
+        if self == SetuidMode::Nullify {
+            set_no_new_privs()?;
+        }
+        Ok(())
+    }
+}
+
+fn resolve(exec: &str) -> Option<PathBuf> {
+    if exec.contains('/') {
+        Some(PathBuf::from(exec))
+    } else {
+        which::which(exec).ok()
+    }
+}
+
+/// Set-ID bits of the command. Returns None when the command is neither set-user-ID nor
+/// set-group-ID. Whether the kernel elevated it is only known once it ran, strace and
+/// no_new_privs ignore the bits, so `elevated` is false until the credentials are observed.
+pub fn inspect(exec: &str) -> Option<SetuidTransition> {
+    let path = resolve(exec)?;
+    let metadata = std::fs::metadata(&path)
+        .inspect_err(|e| debug!("unable to stat {}: {}", path.display(), e))
+        .ok()?;
+    let uid = (metadata.mode() & S_ISUID != 0).then_some(metadata.uid());
+    let gid = (metadata.mode() & S_ISGID != 0).then_some(metadata.gid());
+    if uid.is_none() && gid.is_none() {
+        return None;
+    }
+    Some(SetuidTransition {
+        uid,
+        gid,
+        elevated: false,
+    })
+}
@@ -0,0 +1,122 @@
+//! --sign-key: detached minisign signatures of the profiles, so that a profile used as the
+//! input of a policy can be verified unmodified since its capture with
+//! `minisign -Vm PROFILE -p KEY.pub`.
+
+use std::ffi::OsString;
+use std::fs::{read, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Digest};
+use ed25519_dalek::Signer;
+use tracing::debug;
+
+use capable_core::time::unix_time;
+
+/// Decoded length of a minisign secret key
+const SECRET_KEY_LEN: usize = 158;
+/// Ed25519 signature of the BLAKE2b-512 hash of the file
+const PREHASHED_ALG: &[u8; 2] = b"ED";
+const KEY_ALG: &[u8; 2] = b"Ed";
+const SIGNATURE_EXTENSION: &str = "minisig";
+
+/// Secret key of minisign, see <https://jedisct1.github.io/minisign/#secret-key-format>
+pub struct SigningKey {
+    key_id: [u8; 8],
+    key: ed25519_dalek::SigningKey,
+}
+
+impl SigningKey {
+    /// Read a minisign secret key. It must not be encrypted, as created by
+    /// `minisign -GW`, since capable runs unattended.
+    pub fn read(path: &Path) -> Result<SigningKey, anyhow::Error> {
+        let content = read_to_string(path)
+            .with_context(|| format!("Unable to read signing key {}", path.display()))?;
+        let encoded = content
+            .lines()
+            .find(|line| !line.trim().is_empty() && !line.starts_with("untrusted comment:"))
+            .unwrap_or_default();
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .filter(|bytes| bytes.len() == SECRET_KEY_LEN && bytes[..2] == *KEY_ALG)
+            .ok_or(anyhow::anyhow!(
+                "{} is not a minisign secret key",
+                path.display()
+            ))?;
+        if bytes[2..4] != [0, 0] {
+            return Err(anyhow::anyhow!(
+                "{} is encrypted, create a key without password with minisign -GW",
+                path.display()
+            ));
+        }
+        let key_id: [u8; 8] = bytes[54..62].try_into()?;
+        let keypair: [u8; 64] = bytes[62..126].try_into()?;
+        let checksum = Blake2b::<U32>::new()
+            .chain_update(KEY_ALG)
+            .chain_update(key_id)
+            .chain_update(keypair)
+            .finalize();
+        if checksum.as_slice() != &bytes[126..] {
+            return Err(anyhow::anyhow!(
+                "{} has an invalid checksum",
+                path.display()
+            ));
+        }
+        let key = ed25519_dalek::SigningKey::from_keypair_bytes(&keypair)
+            .with_context(|| format!("{} holds an invalid key pair", path.display()))?;
+        Ok(SigningKey { key_id, key })
+    }
+
+    /// Write the signature of `path` to `path`.minisig, returns its path
+    pub fn sign(&self, path: &Path) -> Result<PathBuf, anyhow::Error> {
+        let content =
+            read(path).with_context(|| format!("Unable to read {} to sign", path.display()))?;
+        let signature = self.key.sign(&Blake2b512::digest(&content));
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let trusted_comment = format!("timestamp:{}\tfile:{}\thashed", unix_time(), file_name);
+        // the trusted comment is signed with the signature
+        let global_signature = self.key.sign(
+            &[
+                signature.to_bytes().as_slice(),
+                trusted_comment.as_bytes(),
+            ]
+            .concat(),
+        );
+        let signature = [
+            PREHASHED_ALG.as_slice(),
+            &self.key_id,
+            &signature.to_bytes(),
+        ]
+        .concat();
+        let mut signature_path = OsString::from(path);
+        signature_path.push(format!(".{}", SIGNATURE_EXTENSION));
+        let signature_path = PathBuf::from(signature_path);
+        write(
+            &signature_path,
+            format!(
+                "untrusted comment: signature from capable\n{}\ntrusted comment: {}\n{}\n",
+                STANDARD.encode(signature),
+                trusted_comment,
+                STANDARD.encode(global_signature.to_bytes())
+            ),
+        )
+        .with_context(|| format!("Unable to write {}", signature_path.display()))?;
+        Ok(signature_path)
+    }
+}
+
+/// Sign `output` when a key is given, the file written by a subcommand
+pub fn sign_output(key: Option<&SigningKey>, output: Option<&Path>) -> Result<(), anyhow::Error> {
+    if let (Some(key), Some(output)) = (key, output) {
+        let path = key.sign(output)?;
+        debug!("signature written to {}", path.display());
+    }
+    Ok(())
+}
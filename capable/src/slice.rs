@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::create_dir_all;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use nix::unistd::{Uid, User};
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+use capable_core::collect::CapSetEntry;
+use capable_core::ebpf::Probes;
+use capable_core::profile::TimeWindow;
+use capable_core::time::unix_time;
+
+use crate::units::{entries_result, write_profile, UnitCgroups};
+
+const DRAIN_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Application of a unit of the user session. Desktop environments start applications in
+/// units like app-gnome-firefox-4242.scope or app-flatpak-org.gimp.GIMP@1234.service, the
+/// instance part is dropped so every launch is the same application.
+fn application(unit: &str) -> String {
+    let name = unit
+        .rsplit_once('.')
+        .filter(|(_, suffix)| matches!(*suffix, "scope" | "service"))
+        .map_or(unit, |(name, _)| name);
+    let name = name.split_once('@').map_or(name, |(name, _)| name);
+    let name = match name.rsplit_once('-') {
+        Some((application, instance))
+            if !instance.is_empty() && instance.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            application
+        }
+        _ => name,
+    };
+    name.strip_prefix("app-").unwrap_or(name).to_string()
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct ApplicationRow {
+    application: String,
+    units: String,
+    capabilities: String,
+}
+
+/// capable --user-slice USER: collect the requests of every process of the session of a
+/// user, below user-UID.slice, until Ctrl-C or `sleep` seconds. Prints the capabilities
+/// of each application, and writes their profiles in `output`.
+pub fn user_slice(
+    user: &str,
+    output: Option<&Path>,
+    sleep: Option<u64>,
+    bpf_object: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let entry = match user.parse::<u32>() {
+        Ok(uid) => User::from_uid(Uid::from_raw(uid))?,
+        Err(_) => User::from_name(user)?,
+    }
+    .with_context(|| format!("Unknown user {}", user))?;
+    let slice = format!("user.slice/user-{}.slice", entry.uid);
+    let mut cgroups = UnitCgroups::new(&slice);
+    let mut probes = Probes::load(bpf_object)?;
+    let start = unix_time();
+    println!("Collecting {}, waiting for Ctrl-C...", slice);
+    let term = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&term))?;
+    let deadline = sleep.map(|sleep| Instant::now() + Duration::from_secs(sleep));
+    let mut set_entry = HashSet::new();
+    while !term.load(Ordering::Relaxed) && deadline.is_none_or(|deadline| Instant::now() < deadline)
+    {
+        thread::sleep(DRAIN_INTERVAL);
+        probes.drain(&mut set_entry, None)?;
+        cgroups.record(probes.cgroup_paths()?);
+    }
+    probes.drain(&mut set_entry, None)?;
+    cgroups.record(probes.cgroup_paths()?);
+    let window = TimeWindow {
+        start,
+        end: unix_time(),
+    };
+
+    let mut applications: BTreeMap<String, (BTreeSet<&str>, Vec<&CapSetEntry>)> = BTreeMap::new();
+    for entry in &set_entry {
+        // processes outside of the session
+        let Some(unit) = cgroups.unit(entry.cgroup) else {
+            continue;
+        };
+        let (units, entries) = applications.entry(application(unit)).or_default();
+        units.insert(unit);
+        entries.push(entry);
+    }
+    if let Some(dir) = output {
+        create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+    }
+    let mut rows = Vec::new();
+    for (application, (units, entries)) in applications {
+        let mut result = entries_result(&entries, window);
+        result
+            .labels
+            .insert("application".to_string(), application.clone());
+        result.labels.insert("user".to_string(), entry.name.clone());
+        if let Some(dir) = output {
+            write_profile(dir, &application, &result)?;
+        }
+        rows.push(ApplicationRow {
+            application,
+            units: units.into_iter().collect::<Vec<_>>().join("\n"),
+            capabilities: result.capability_names().collect::<Vec<_>>().join(", "),
+        });
+    }
+    println!("{}", Table::new(&rows).with(Style::modern()));
+    Ok(())
+}
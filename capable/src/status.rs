@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use aya::maps::{MapData, PerCpuArray};
+use capable_common::Stats;
+
+use capable_core::ebpf::read_counters;
+use capable_core::explain::EXPLAINED_SYSCALLS;
+use capable_core::loader::LOADER_SYSCALLS;
+
+const STATUS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Single line on stderr, refreshed while the command runs, showing the eBPF counters and
+/// the files seen by strace so far. The eBPF counters are system-wide, before filtering.
+pub struct StatusLine {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// Files of the strace log, read incrementally
+struct FilesSeen {
+    path: PathBuf,
+    offset: u64,
+    files: HashSet<String>,
+}
+
+impl FilesSeen {
+    fn update(&mut self) -> std::io::Result<usize> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        // besides the explained ones, strace only traces file syscalls, their first string
+        // argument is a path
+        while reader.read_line(&mut line)? > 0 {
+            if !line.ends_with('\n') {
+                // being written, read it again on the next update
+                break;
+            }
+            self.offset += line.len() as u64;
+            if is_explained(&line) {
+                line.clear();
+                continue;
+            }
+            if let Some(path) = line.split('"').nth(1) {
+                if !self.files.contains(path) {
+                    self.files.insert(path.to_string());
+                }
+            }
+            line.clear();
+        }
+        Ok(self.files.len())
+    }
+}
+
+/// Whether the strace line is one of the syscalls only traced to explain findings or to
+/// recognize the libraries
+fn is_explained(line: &str) -> bool {
+    // lines are prefixed with the pid
+    let syscall = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|call| call.split('(').next())
+        .unwrap_or_default();
+    EXPLAINED_SYSCALLS
+        .iter()
+        .chain(LOADER_SYSCALLS.iter())
+        .any(|explained| explained.trim_start_matches('?') == syscall)
+}
+
+impl StatusLine {
+    pub fn start(stats: Arc<PerCpuArray<MapData, Stats>>, strace_log: PathBuf) -> StatusLine {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut seen = FilesSeen {
+                path: strace_log,
+                offset: 0,
+                files: HashSet::new(),
+            };
+            let mut stderr = std::io::stderr();
+            while !stopped.load(Ordering::Relaxed) {
+                let current = read_counters(&stats);
+                // the log is created by strace once started
+                let files = seen.update().unwrap_or(seen.files.len());
+                let _ = write!(
+                    stderr,
+                    "\r\x1b[2Kcapable: {} requests, {} capabilities, {} files, {} drops",
+                    current.requests,
+                    current.capabilities.count_ones(),
+                    files,
+                    current.drops
+                );
+                let _ = stderr.flush();
+                thread::sleep(STATUS_INTERVAL);
+            }
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        });
+        StatusLine { stop, handle }
+    }
+
+    /// Stop refreshing and erase the line
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
@@ -0,0 +1,177 @@
+//! Layout of the table of the daemon mode: --group-by aggregates the rows of the processes
+//! sharing an executable, a namespace, a user or a unit, --sort orders the rows and
+//! --columns selects the columns.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use capable_common::Pid;
+use capctl::CapSet;
+use serde::Serialize;
+use tabled::settings::location::ByColumnName;
+use tabled::settings::peaker::PriorityMax;
+use tabled::settings::{Disable, Style, Width};
+use tabled::{Table, Tabled};
+
+use capable_core::caps::capset_to_string;
+
+use crate::CapabilitiesTable;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Exe,
+    Ns,
+    Uid,
+    Unit,
+}
+
+impl FromStr for GroupBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exe" => Ok(GroupBy::Exe),
+            "ns" => Ok(GroupBy::Ns),
+            "uid" => Ok(GroupBy::Uid),
+            "unit" => Ok(GroupBy::Unit),
+            _ => Err(anyhow::anyhow!(
+                "--group-by expects exe, ns, uid or unit, got {}",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// Most capabilities first
+    Caps,
+    Pid,
+    /// By executable, or by group with --group-by
+    Name,
+}
+
+impl FromStr for SortBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "caps" => Ok(SortBy::Caps),
+            "pid" => Ok(SortBy::Pid),
+            "name" => Ok(SortBy::Name),
+            _ => Err(anyhow::anyhow!(
+                "--sort expects caps, pid or name, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// How the rows of the table are aggregated and ordered, one row per process in no
+/// particular order by default
+#[derive(Default)]
+pub struct TableLayout {
+    pub group_by: Option<GroupBy>,
+    pub sort: Option<SortBy>,
+    /// Columns shown, by their lowercase header. None shows them all with fixed widths.
+    pub columns: Option<Vec<String>>,
+}
+
+/// Width of the terminal of the standard output, None when it is not a terminal
+fn terminal_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+/// Table of `rows` with the `columns` only, in their usual order. The table fits the
+/// terminal, the widest columns, usually the capabilities, taking the width left.
+pub fn columns_table<T: Tabled>(rows: &[T], columns: &[String]) -> Result<Table, anyhow::Error> {
+    let headers = T::headers();
+    let names: Vec<String> = headers.iter().map(|header| header.to_lowercase()).collect();
+    if let Some(unknown) = columns.iter().find(|column| !names.contains(column)) {
+        return Err(anyhow::anyhow!(
+            "Unknown column {}, the columns are {}",
+            unknown,
+            names.join(", ")
+        ));
+    }
+    let mut table = Table::new(rows);
+    table.with(Style::modern());
+    for (header, name) in headers.iter().zip(&names) {
+        if !columns.contains(name) {
+            table.with(Disable::column(ByColumnName::new(header)));
+        }
+    }
+    if let Some(width) = terminal_width() {
+        table.with(Width::wrap(width).priority::<PriorityMax>().keep_words());
+    }
+    Ok(table)
+}
+
+/// Processes sharing the key of --group-by, with the union of their capabilities
+#[derive(Tabled, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct GroupRow {
+    group: String,
+    processes: usize,
+    /// Lowest PID of the group, to sort by PID
+    #[tabled(skip)]
+    #[serde(skip)]
+    pid: Pid,
+    capabilities: String,
+    #[tabled(skip)]
+    #[serde(skip)]
+    capset: CapSet,
+}
+
+/// Order the rows of the processes
+pub fn sort(rows: &mut [CapabilitiesTable], sort: SortBy) {
+    match sort {
+        SortBy::Caps => rows.sort_by(|a, b| {
+            b.capset
+                .size()
+                .cmp(&a.capset.size())
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        SortBy::Pid => rows.sort_by_key(|row| row.pid),
+        SortBy::Name => rows.sort_by(|a, b| a.name.cmp(&b.name).then(a.pid.cmp(&b.pid))),
+    }
+}
+
+/// Aggregate the rows of the processes, ordered by group unless `sort` is given
+pub fn group(rows: &[CapabilitiesTable], group_by: GroupBy, sort: Option<SortBy>) -> Vec<GroupRow> {
+    let mut groups: BTreeMap<String, GroupRow> = BTreeMap::new();
+    for row in rows {
+        let key = match group_by {
+            GroupBy::Exe => row.name.clone(),
+            GroupBy::Ns => row.ns.to_string(),
+            GroupBy::Uid => row.uid.clone(),
+            GroupBy::Unit => row.unit.clone(),
+        };
+        let group = groups.entry(key).or_insert_with_key(|key| GroupRow {
+            group: key.clone(),
+            processes: 0,
+            pid: row.pid,
+            capabilities: String::new(),
+            capset: CapSet::empty(),
+        });
+        group.processes += 1;
+        group.pid = group.pid.min(row.pid);
+        group.capset |= row.capset;
+    }
+    let mut groups: Vec<GroupRow> = groups
+        .into_values()
+        .map(|mut group| {
+            group.capabilities = capset_to_string(&group.capset);
+            group
+        })
+        .collect();
+    match sort {
+        Some(SortBy::Caps) => groups.sort_by_key(|group| Reverse(group.capset.size())),
+        Some(SortBy::Pid) => groups.sort_by_key(|group| group.pid),
+        Some(SortBy::Name) | None => (),
+    }
+    groups
+}
@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use capable_common::{Nsid, Pid};
+use capctl::{Cap, CapSet};
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+use capable_core::caps::get_cap;
+use capable_core::collect::{CapSetEntry, RequestObserver};
+use capable_core::ebpf::Probes;
+
+use crate::units::UnitCgroups;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// Processes shown, the most active ones
+const TOP_ROWS: usize = 20;
+/// Processes without any check for this long leave the view
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Capability checks of a process since it was first seen
+struct ProcessActivity {
+    name: String,
+    cgroup: u64,
+    /// Checks since the last refresh
+    checks: u64,
+    total: u64,
+    seen: CapSet,
+    /// Capability the process requested for the first time most recently
+    newest: Option<Cap>,
+    last_check: Instant,
+}
+
+#[derive(Default)]
+struct Activity {
+    processes: HashMap<(Nsid, Pid), ProcessActivity>,
+}
+
+impl RequestObserver for Activity {
+    fn observe(
+        &mut self,
+        entry: &CapSetEntry,
+        capability: u8,
+        _denied: bool,
+        _time: u64,
+        _stack: &[String],
+    ) -> Result<(), anyhow::Error> {
+        let process = self
+            .processes
+            .entry((entry.ns, entry.pid))
+            .or_insert_with(|| ProcessActivity {
+                // read once, the process may be gone at the next refresh
+                name: read_to_string(format!("/proc/{}/comm", entry.pid))
+                    .map(|comm| comm.trim_end().to_string())
+                    .unwrap_or_default(),
+                cgroup: entry.cgroup,
+                checks: 0,
+                total: 0,
+                seen: CapSet::empty(),
+                newest: None,
+                last_check: Instant::now(),
+            });
+        process.checks += 1;
+        process.total += 1;
+        process.last_check = Instant::now();
+        if let Some(cap) = get_cap(capability) {
+            if !process.seen.has(cap) {
+                process.seen.add(cap);
+                process.newest = Some(cap);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "UPPERCASE")]
+struct TopRow {
+    pid: Pid,
+    name: String,
+    #[tabled(rename = "CHECKS/S")]
+    rate: String,
+    total: u64,
+    newest: String,
+    ns: Nsid,
+    unit: String,
+}
+
+/// capable top: processes ranked by capability checks per second, refreshed every second
+/// until Ctrl-C
+pub fn top(bpf_object: Option<&Path>) -> Result<(), anyhow::Error> {
+    let mut probes = Probes::load(bpf_object)?;
+    let mut units = UnitCgroups::new("");
+    let term = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&term))?;
+    let mut activity = Activity::default();
+    let mut set_entry = HashSet::new();
+    let mut refreshed = Instant::now();
+    let mut stdout = std::io::stdout();
+    while !term.load(Ordering::Relaxed) {
+        thread::sleep(REFRESH_INTERVAL);
+        probes.drain(&mut set_entry, Some(&mut activity))?;
+        units.record(probes.cgroup_paths()?);
+        // only the raw requests are used
+        set_entry.clear();
+        let elapsed = refreshed.elapsed().as_secs_f64();
+        refreshed = Instant::now();
+
+        activity
+            .processes
+            .retain(|_, process| process.last_check.elapsed() < IDLE_TIMEOUT);
+        let mut ranked: Vec<_> = activity.processes.iter_mut().collect();
+        ranked.sort_by(|(_, a), (_, b)| b.checks.cmp(&a.checks).then(b.total.cmp(&a.total)));
+        let rate: u64 = ranked.iter().map(|(_, process)| process.checks).sum();
+        let rows: Vec<TopRow> = ranked
+            .iter()
+            .take(TOP_ROWS)
+            .map(|((ns, pid), process)| TopRow {
+                pid: *pid,
+                name: process.name.clone(),
+                rate: format!("{:.1}", process.checks as f64 / elapsed),
+                total: process.total,
+                newest: process
+                    .newest
+                    .map(|cap| format!("CAP_{:?}", cap))
+                    .unwrap_or_default(),
+                ns: *ns,
+                unit: units.unit(process.cgroup).unwrap_or_default().to_string(),
+            })
+            .collect();
+        write!(
+            stdout,
+            "\x1b[H\x1b[2Jcapable top - {:.1} checks/s, {} processes\n\n{}\n",
+            rate as f64 / elapsed,
+            ranked.len(),
+            Table::new(&rows).with(Style::modern())
+        )?;
+        stdout.flush()?;
+        for (_, process) in ranked {
+            process.checks = 0;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::Path;
+
+use capctl::CapSet;
+
+use capable_core::caps::capset_to_string;
+use capable_core::strace::{parse_strace, Parameter};
+
+/// Errors reported as failed operations. ENOENT is only kept when the path exists, as the
+/// command could not see it, most ENOENT are lookups of optional files.
+const FAILURE_ERRORS: [&str; 3] = ["EPERM", "EACCES", "ENOENT"];
+
+/// What failed while running the command with a candidate capability set (--try-caps)
+pub struct TrialReport {
+    candidate: CapSet,
+    denied: CapSet,
+    /// error, syscall and path of each failed operation
+    failures: BTreeSet<(String, String, String)>,
+}
+
+impl TrialReport {
+    pub fn new(candidate: CapSet, denied: CapSet, strace_log: Option<&str>) -> TrialReport {
+        let mut failures = BTreeSet::new();
+        for syscall in strace_log.map(parse_strace).unwrap_or_default() {
+            let Some(error) = syscall
+                .return_code
+                .constant
+                .as_deref()
+                .filter(|c| FAILURE_ERRORS.contains(c))
+            else {
+                continue;
+            };
+            let path = syscall
+                .args
+                .iter()
+                .find_map(|arg| match arg {
+                    Parameter::String(s) => Some(s.trim_matches('"').to_string()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            if error == "ENOENT" && (path.is_empty() || !Path::new(&path).exists()) {
+                continue;
+            }
+            failures.insert((error.to_string(), syscall.syscall.trim().to_string(), path));
+        }
+        TrialReport {
+            candidate,
+            denied,
+            failures,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.denied.is_empty() && self.failures.is_empty()
+    }
+}
+
+impl Display for TrialReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let candidate = if self.candidate.is_empty() {
+            String::from("no capabilities")
+        } else {
+            capset_to_string(&self.candidate)
+        };
+        if self.is_success() {
+            return writeln!(f, "No operation failed with {}", candidate);
+        }
+        writeln!(f, "Failures with {}:", candidate)?;
+        for cap in self.denied.iter() {
+            writeln!(f, "  denied CAP_{:?}", cap)?;
+        }
+        for (error, syscall, path) in &self.failures {
+            writeln!(f, "  {} {} {}", error, syscall, path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fs::{read_dir, File};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use capctl::CapSet;
+
+use capable_core::collect::{build_result, entries_reasons, CapSetEntry};
+use capable_core::containers;
+use capable_core::profile::{ProgramResult, TimeWindow};
+
+use crate::cgroup::CGROUP_ROOT;
+
+/// Suffixes of the units owning a cgroup. Slices only group other units.
+const UNIT_SUFFIXES: [&str; 5] = [".service", ".scope", ".socket", ".mount", ".swap"];
+
+/// Unit of the processes outside of any unit, e.g. kernel threads
+pub const NO_UNIT: &str = "-.slice";
+
+/// Unit of each cgroup id below a cgroup. The paths the probes record with the requests
+/// attribute the cgroups of units that already stopped, the ids seen before are kept.
+pub struct UnitCgroups {
+    root: PathBuf,
+    units: HashMap<u64, String>,
+}
+
+/// Unit owning the cgroup at `relative`, the innermost unit of its path
+fn unit_of(relative: &Path) -> String {
+    relative
+        .iter()
+        .rev()
+        .filter_map(|component| component.to_str())
+        .find(|name| UNIT_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)))
+        .unwrap_or(NO_UNIT)
+        .to_string()
+}
+
+impl UnitCgroups {
+    /// Units below `root`, relative to the cgroup v2 hierarchy, e.g. user.slice
+    pub fn new<P: AsRef<Path>>(root: P) -> UnitCgroups {
+        UnitCgroups {
+            root: Path::new(CGROUP_ROOT).join(root),
+            units: HashMap::new(),
+        }
+    }
+
+    /// Record the cgroups created since the last scan
+    pub fn scan(&mut self) {
+        let hierarchy = Path::new(CGROUP_ROOT);
+        let mut pending = vec![self.root.clone()];
+        while let Some(dir) = pending.pop() {
+            let Ok(metadata) = dir.metadata() else {
+                continue;
+            };
+            let relative = dir.strip_prefix(hierarchy).unwrap_or(&dir);
+            self.units.insert(metadata.ino(), unit_of(relative));
+            let Ok(entries) = read_dir(&dir) else {
+                continue;
+            };
+            pending.extend(
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .map(|entry| entry.path()),
+            );
+        }
+    }
+
+    /// Record the paths of the cgroups of the requests, relative to the hierarchy, by id.
+    /// A cut path is attributed to its innermost unit, only when the root is the hierarchy.
+    pub fn record<I>(&mut self, paths: I)
+    where
+        I: IntoIterator<Item = (u64, PathBuf)>,
+    {
+        let hierarchy = Path::new(CGROUP_ROOT);
+        for (id, relative) in paths {
+            if hierarchy.join(&relative).starts_with(&self.root) {
+                self.units.entry(id).or_insert_with(|| unit_of(&relative));
+            }
+        }
+    }
+
+    /// Unit of the cgroup, None when it is not below the root
+    pub fn unit(&self, cgroup: u64) -> Option<&str> {
+        self.units.get(&cgroup).map(String::as_str)
+    }
+}
+
+/// Profile of the requests of a group of processes, without their syscalls
+pub fn entries_result(entries: &[&CapSetEntry], window: TimeWindow) -> ProgramResult {
+    let capset = entries
+        .iter()
+        .fold(CapSet::empty(), |capset, entry| capset | entry.capabilities);
+    let reasons = entries_reasons(entries.iter().copied());
+    let mut result = build_result(capset, None, None, 0, Some(window), reasons);
+    result.containers = containers::compare(&result);
+    result
+}
+
+/// Write the profile `name`.json in `dir`, returns its path
+pub fn write_profile(
+    dir: &Path,
+    name: &str,
+    result: &ProgramResult,
+) -> Result<PathBuf, anyhow::Error> {
+    let path = dir.join(format!("{}.json", name));
+    let mut file =
+        File::create(&path).with_context(|| format!("Unable to create {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string_pretty(result)?)?;
+    Ok(path)
+}
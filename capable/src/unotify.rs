@@ -0,0 +1,673 @@
+//! File accesses collected with a seccomp user-notification filter instead of strace.
+//!
+//! The filter is installed by the child right before exec and notifies the path-taking
+//! syscalls of the CALLS table. The parent reads their arguments from the memory of the
+//! process and lets the kernel continue the syscall, so the command is not ptraced. The
+//! accesses are written to the log in the strace format, for the same analysis. The result
+//! of a syscall is not known when it is notified, it is written as `= ?` and the analysis
+//! takes it as successful: the accesses that failed, e.g. on a missing file, are part of
+//! the profile. With
+//! --interactive, the writes to sensitive files wait for the user, a denied one fails with
+//! EACCES.
+//!
+//! The syscalls of x32 processes are the x86_64 ones with X32_SYSCALL_BIT set, they are
+//! collected as such. The arguments of the processes of another architecture, e.g. 32-bit
+//! ones, are not decoded: their syscalls run uncollected and are counted in the statistics,
+//! with --interactive such a process is killed as its writes cannot be asked for.
+
+use std::fs::{read_link, File};
+use std::io::{IoSlice, IoSliceMut, Write};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::FileExt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use libc::{c_long, sock_filter, sock_fprog};
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::socket::{
+    recvmsg, sendmsg, socketpair, AddressFamily, ControlMessage, ControlMessageOwned, MsgFlags,
+    SockFlag, SockType,
+};
+use tracing::{debug, warn};
+
+use capable_core::caps::setptrace_effective;
+use capable_core::stats::SeccompStats;
+
+use crate::approve::Firewall;
+
+/// How the file accesses of the command are collected
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Collector {
+    Strace,
+    Seccomp,
+}
+
+impl FromStr for Collector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strace" => Ok(Collector::Strace),
+            "seccomp" => Ok(Collector::Seccomp),
+            _ => Err(anyhow::anyhow!(
+                "--collector expects strace or seccomp, got {}",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000003e);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc00000b7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// Set in the syscall numbers of x32 processes, which are the x86_64 ones otherwise
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+#[cfg(not(target_arch = "x86_64"))]
+const X32_SYSCALL_BIT: u32 = 0;
+
+// linux/seccomp.h, _IOWR('!', 0, struct seccomp_notif), _IOWR('!', 1, struct seccomp_notif_resp)
+// and _IOW('!', 2, __u64)
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc0502100;
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc0182101;
+const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 0x40082102;
+
+/// Interval at which the collector checks whether the command is gone
+const POLL_TIMEOUT_MS: u16 = 200;
+/// Time given to the processes left by the command to release the filter
+const FINISH_TIMEOUT: Duration = Duration::from_secs(1);
+
+const AT_FDCWD: i32 = -100;
+
+/// Notified syscalls: name, number, number of arguments written and the ones that are
+/// strings. Enough arguments are written for the position of the path in CALLS.
+const TRACED: &[(&str, c_long, usize, &[usize])] = &[
+    ("acct", libc::SYS_acct, 1, &[0]),
+    ("chdir", libc::SYS_chdir, 1, &[0]),
+    ("chroot", libc::SYS_chroot, 1, &[0]),
+    ("execve", libc::SYS_execve, 1, &[0]),
+    ("execveat", libc::SYS_execveat, 2, &[1]),
+    ("faccessat", libc::SYS_faccessat, 3, &[1]),
+    ("faccessat2", libc::SYS_faccessat2, 3, &[1]),
+    ("fanotify_mark", libc::SYS_fanotify_mark, 5, &[4]),
+    ("fchmodat", libc::SYS_fchmodat, 3, &[1]),
+    ("fchownat", libc::SYS_fchownat, 2, &[1]),
+    ("fspick", libc::SYS_fspick, 2, &[1]),
+    ("getxattr", libc::SYS_getxattr, 2, &[0, 1]),
+    ("inotify_add_watch", libc::SYS_inotify_add_watch, 2, &[1]),
+    ("lgetxattr", libc::SYS_lgetxattr, 2, &[0, 1]),
+    ("linkat", libc::SYS_linkat, 4, &[1, 3]),
+    ("listxattr", libc::SYS_listxattr, 1, &[0]),
+    ("llistxattr", libc::SYS_llistxattr, 1, &[0]),
+    ("lremovexattr", libc::SYS_lremovexattr, 2, &[0, 1]),
+    ("lsetxattr", libc::SYS_lsetxattr, 2, &[0, 1]),
+    ("mkdirat", libc::SYS_mkdirat, 3, &[1]),
+    ("mknodat", libc::SYS_mknodat, 3, &[1]),
+    ("name_to_handle_at", libc::SYS_name_to_handle_at, 2, &[1]),
+    ("newfstatat", libc::SYS_newfstatat, 2, &[1]),
+    // the decoded flags of the opens follow, at index 2 like strace writes them
+    ("openat", libc::SYS_openat, 2, &[1]),
+    ("openat2", libc::SYS_openat2, 2, &[1]),
+    ("open_tree", libc::SYS_open_tree, 2, &[1]),
+    ("pivot_root", libc::SYS_pivot_root, 2, &[0, 1]),
+    ("readlinkat", libc::SYS_readlinkat, 2, &[1]),
+    ("removexattr", libc::SYS_removexattr, 2, &[0, 1]),
+    ("renameat", libc::SYS_renameat, 4, &[1, 3]),
+    ("renameat2", libc::SYS_renameat2, 4, &[1, 3]),
+    ("setxattr", libc::SYS_setxattr, 2, &[0, 1]),
+    ("statx", libc::SYS_statx, 2, &[1]),
+    ("swapoff", libc::SYS_swapoff, 1, &[0]),
+    ("swapon", libc::SYS_swapon, 1, &[0]),
+    ("symlinkat", libc::SYS_symlinkat, 3, &[0, 2]),
+    ("truncate", libc::SYS_truncate, 1, &[0]),
+    ("unlinkat", libc::SYS_unlinkat, 2, &[1]),
+    ("utimensat", libc::SYS_utimensat, 2, &[1]),
+    #[cfg(target_arch = "x86_64")]
+    ("access", libc::SYS_access, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("chmod", libc::SYS_chmod, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("chown", libc::SYS_chown, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("creat", libc::SYS_creat, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("futimesat", libc::SYS_futimesat, 2, &[1]),
+    #[cfg(target_arch = "x86_64")]
+    ("lchown", libc::SYS_lchown, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("link", libc::SYS_link, 2, &[0, 1]),
+    #[cfg(target_arch = "x86_64")]
+    ("lstat", libc::SYS_lstat, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("mkdir", libc::SYS_mkdir, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("mknod", libc::SYS_mknod, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("open", libc::SYS_open, 2, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("readlink", libc::SYS_readlink, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("rename", libc::SYS_rename, 2, &[0, 1]),
+    #[cfg(target_arch = "x86_64")]
+    ("rmdir", libc::SYS_rmdir, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("symlink", libc::SYS_symlink, 2, &[0, 1]),
+    #[cfg(target_arch = "x86_64")]
+    ("unlink", libc::SYS_unlink, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("utime", libc::SYS_utime, 1, &[0]),
+    #[cfg(target_arch = "x86_64")]
+    ("utimes", libc::SYS_utimes, 1, &[0]),
+];
+
+/// Syscalls changing a file, with the position of its path among the written arguments.
+/// The opens change it when they write.
+const WRITES: &[(&str, usize)] = &[
+    ("chmod", 0),
+    ("chown", 0),
+    ("creat", 0),
+    ("fchmodat", 1),
+    ("fchownat", 1),
+    ("futimesat", 1),
+    ("lchown", 0),
+    ("link", 1),
+    ("linkat", 3),
+    ("mkdir", 0),
+    ("mkdirat", 1),
+    ("mknod", 0),
+    ("mknodat", 1),
+    ("rename", 1),
+    ("renameat", 3),
+    ("renameat2", 3),
+    ("rmdir", 0),
+    ("symlink", 1),
+    ("symlinkat", 2),
+    ("truncate", 0),
+    ("unlink", 0),
+    ("unlinkat", 1),
+    ("utime", 0),
+    ("utimensat", 1),
+    ("utimes", 0),
+];
+
+const OPEN_FLAGS: &[(libc::c_int, &str)] = &[
+    (libc::O_CREAT, "O_CREAT"),
+    (libc::O_EXCL, "O_EXCL"),
+    (libc::O_TRUNC, "O_TRUNC"),
+    (libc::O_APPEND, "O_APPEND"),
+    (libc::O_DIRECTORY, "O_DIRECTORY"),
+    (libc::O_NOFOLLOW, "O_NOFOLLOW"),
+    (libc::O_PATH, "O_PATH"),
+    (libc::O_CLOEXEC, "O_CLOEXEC"),
+];
+
+fn statement(code: u32, k: u32) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Notify the syscalls of TRACED, allow the others and the ones of another architecture
+fn filter(arch: u32, kill_foreign: bool) -> Vec<sock_filter> {
+    // offsets in struct seccomp_data
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+    let count = TRACED.len() as u8;
+    let foreign = if kill_foreign {
+        libc::SECCOMP_RET_KILL_PROCESS
+    } else {
+        libc::SECCOMP_RET_USER_NOTIF
+    };
+    let mut program = vec![
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH),
+        jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, arch, 1, 0),
+        statement(libc::BPF_RET | libc::BPF_K, foreign),
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR),
+        // x32 syscalls are compared as the x86_64 ones
+        statement(
+            libc::BPF_ALU | libc::BPF_AND | libc::BPF_K,
+            !X32_SYSCALL_BIT,
+        ),
+    ];
+    for (i, (_, nr, _, _)) in TRACED.iter().enumerate() {
+        // on a match, jump over the next comparisons and the allow
+        let remaining = count - i as u8;
+        program.push(jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            *nr as u32,
+            remaining,
+            0,
+        ));
+    }
+    program.push(statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW));
+    program.push(statement(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_USER_NOTIF,
+    ));
+    program
+}
+
+/// Sockets passing the listener of the filter from the child to the collector
+pub struct Listener {
+    parent: OwnedFd,
+    child: OwnedFd,
+}
+
+impl Listener {
+    pub fn new() -> Result<Listener, anyhow::Error> {
+        if AUDIT_ARCH.is_none() {
+            return Err(anyhow::anyhow!(
+                "--collector seccomp is not supported on this architecture"
+            ));
+        }
+        let (parent, child) = socketpair(
+            AddressFamily::Unix,
+            SockType::SeqPacket,
+            None,
+            SockFlag::SOCK_CLOEXEC,
+        )?;
+        Ok(Listener { parent, child })
+    }
+
+    /// End kept by the child, to give to `install`
+    pub fn child(&self) -> std::io::Result<OwnedFd> {
+        self.child.try_clone()
+    }
+
+    /// Start collecting to the log. The child end is closed, so the collector does not wait
+    /// forever for a child that failed before installing the filter. With a firewall, the
+    /// writes to sensitive files are asked for.
+    pub fn collect(
+        self,
+        log: PathBuf,
+        firewall: Option<Arc<Firewall>>,
+    ) -> std::io::Result<Collection> {
+        let Listener { parent, child } = self;
+        drop(child);
+        let log = File::create(log)?;
+        let stats = Arc::new(Mutex::new(SeccompStats::default()));
+        let counted = stats.clone();
+        let handle = thread::spawn(move || {
+            let listener = match receive(&parent) {
+                Ok(Some(listener)) => listener,
+                Ok(None) => {
+                    warn!("the command did not install the seccomp filter, no file is collected");
+                    return;
+                }
+                Err(e) => {
+                    warn!("unable to receive the seccomp listener: {}", e);
+                    return;
+                }
+            };
+            // the memory of the command is read with SYS_PTRACE, toggled for this thread
+            if let Err(e) = setptrace_effective(true) {
+                warn!("unable to read the syscall arguments: {}", e);
+            }
+            if let Err(e) = serve(&listener, log, firewall.as_deref(), &counted) {
+                warn!("seccomp collector stopped: {}", e);
+            }
+            let _ = setptrace_effective(false);
+        });
+        Ok(Collection { handle, stats })
+    }
+}
+
+/// Thread collecting the notifications, with their counts
+pub struct Collection {
+    handle: JoinHandle<()>,
+    stats: Arc<Mutex<SeccompStats>>,
+}
+
+/// Wait for the pending notifications to be written and return their counts. Processes the
+/// command left behind still hold the filter, they are no longer collected after
+/// FINISH_TIMEOUT.
+pub fn finish(collection: Collection) -> SeccompStats {
+    let start = Instant::now();
+    while !collection.handle.is_finished() {
+        if start.elapsed() > FINISH_TIMEOUT {
+            debug!("processes of the command still hold the seccomp filter");
+            return *collection.stats.lock().unwrap();
+        }
+        thread::sleep(Duration::from_millis(POLL_TIMEOUT_MS as u64));
+    }
+    let _ = collection.handle.join();
+    let stats = *collection.stats.lock().unwrap();
+    stats
+}
+
+/// Install the filter on the calling process and send its listener to the collector. The
+/// processes of another architecture are killed with `kill_foreign`, notified otherwise.
+/// Runs in pre_exec, the caller needs SYS_ADMIN or no_new_privs.
+pub fn install(socket: &OwnedFd, kill_foreign: bool) -> std::io::Result<()> {
+    let arch = AUDIT_ARCH.ok_or(std::io::ErrorKind::Unsupported)?;
+    let mut program = filter(arch, kill_foreign);
+    let prog = sock_fprog {
+        len: program.len() as u16,
+        filter: program.as_mut_ptr(),
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+            &prog as *const sock_fprog,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let listener = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+    let fds = [listener.as_raw_fd()];
+    sendmsg::<()>(
+        socket.as_raw_fd(),
+        &[IoSlice::new(b"\0")],
+        &[ControlMessage::ScmRights(&fds)],
+        MsgFlags::empty(),
+        None,
+    )?;
+    Ok(())
+}
+
+fn receive(socket: &OwnedFd) -> nix::Result<Option<OwnedFd>> {
+    let mut byte = [0u8; 1];
+    let mut iov = [IoSliceMut::new(&mut byte)];
+    let mut space = nix::cmsg_space!([std::os::fd::RawFd; 1]);
+    let msg = recvmsg::<()>(
+        socket.as_raw_fd(),
+        &mut iov,
+        Some(&mut space),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )?;
+    for cmsg in msg.cmsgs()? {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+            if let Some(fd) = fds.first() {
+                return Ok(Some(unsafe { OwnedFd::from_raw_fd(*fd) }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Handle the notifications until every process holding the filter exited
+fn serve(
+    listener: &OwnedFd,
+    log: File,
+    firewall: Option<&Firewall>,
+    stats: &Mutex<SeccompStats>,
+) -> std::io::Result<()> {
+    let mut log = std::io::BufWriter::new(log);
+    loop {
+        let mut fds = [PollFd::new(listener.as_fd(), PollFlags::POLLIN)];
+        poll(&mut fds, PollTimeout::from(POLL_TIMEOUT_MS))?;
+        let revents = fds[0].revents().unwrap_or(PollFlags::empty());
+        if revents.contains(PollFlags::POLLIN) {
+            let mut notif: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+            if unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_RECV as _, &mut notif) }
+                < 0
+            {
+                // the process may have been killed while the notification was pending
+                debug!("failed to receive a seccomp notification: {}", std::io::Error::last_os_error());
+                stats.lock().unwrap().failed += 1;
+                continue;
+            }
+            if Some(notif.data.arch) != AUDIT_ARCH {
+                // not decoded, the process runs uncollected
+                respond(listener, notif.id);
+                let mut stats = stats.lock().unwrap();
+                if stats.foreign == 0 {
+                    warn!(
+                        "process {} of another architecture is not collected by the seccomp collector",
+                        notif.pid
+                    );
+                }
+                stats.foreign += 1;
+                continue;
+            }
+            let notified = describe(listener, &notif);
+            {
+                let mut stats = stats.lock().unwrap();
+                match notified {
+                    Some(_) => stats.received += 1,
+                    None => stats.invalidated += 1,
+                }
+            }
+            // the process waits in the syscall while the user decides
+            let refused = match (firewall, &notified) {
+                (Some(firewall), Some(notified)) => notified
+                    .changed_path()
+                    .is_some_and(|path| !firewall.allow_write(notif.pid, notified.name, &path)),
+                _ => false,
+            };
+            if refused {
+                refuse(listener, notif.id);
+            } else {
+                respond(listener, notif.id);
+            }
+            if let Some(notified) = notified {
+                let result = if refused {
+                    "-1 EACCES (Permission denied)"
+                } else {
+                    "?"
+                };
+                // written at once, the log is followed while the command runs
+                log.write_all(notified.line(result).as_bytes())?;
+                log.flush()?;
+            }
+        } else if revents.intersects(PollFlags::POLLHUP | PollFlags::POLLERR) {
+            return Ok(());
+        }
+    }
+}
+
+/// Let the kernel run the syscall as if it was not notified
+fn respond(listener: &OwnedFd, id: u64) {
+    let mut resp = libc::seccomp_notif_resp {
+        id,
+        val: 0,
+        error: 0,
+        flags: libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32,
+    };
+    if unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_SEND as _, &mut resp) } < 0 {
+        debug!("failed to continue syscall {}: {}", id, std::io::Error::last_os_error());
+    }
+}
+
+/// Fail the syscall with EACCES without running it
+fn refuse(listener: &OwnedFd, id: u64) {
+    let mut resp = libc::seccomp_notif_resp {
+        id,
+        val: 0,
+        error: -libc::EACCES,
+        flags: 0,
+    };
+    if unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_SEND as _, &mut resp) } < 0 {
+        debug!("failed to refuse syscall {}: {}", id, std::io::Error::last_os_error());
+    }
+}
+
+/// Whether the notification is still pending, so its pid was not reused while reading
+fn valid(listener: &OwnedFd, id: u64) -> bool {
+    let mut id = id;
+    unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ID_VALID as _, &mut id) == 0 }
+}
+
+/// A notified syscall, with its arguments as written in the log
+struct Notified {
+    pid: u32,
+    name: &'static str,
+    args: Vec<String>,
+    /// Position of the path the syscall changes, None when it only reads
+    changed: Option<usize>,
+}
+
+impl Notified {
+    /// The syscall as a line of the strace log
+    fn line(&self, result: &str) -> String {
+        format!("{} {}({}) = {}\n", self.pid, self.name, self.args.join(", "), result)
+    }
+
+    /// Path changed by the syscall, unquoted
+    fn changed(&self) -> Option<&str> {
+        let path = self.args.get(self.changed?)?;
+        Some(path.trim_matches('"'))
+    }
+
+    /// Absolute path changed by the syscall. A relative one is resolved against the
+    /// directory file descriptor before it for the *at syscalls, against the working
+    /// directory of the process otherwise. Symbolic links are not followed.
+    fn changed_path(&self) -> Option<String> {
+        let path = Path::new(self.changed()?);
+        if path.is_absolute() {
+            return Some(normalize(path));
+        }
+        let at = (self.name.ends_with("at") && self.name != "creat") || self.name.ends_with("at2");
+        let dirfd = self.changed?.checked_sub(1).and_then(|i| self.args.get(i));
+        let directory = match dirfd {
+            Some(dirfd) if at && dirfd != "AT_FDCWD" => format!("/proc/{}/fd/{}", self.pid, dirfd),
+            _ => format!("/proc/{}/cwd", self.pid),
+        };
+        let directory = read_link(&directory)
+            .inspect_err(|e| debug!("unable to resolve {}: {}", directory, e))
+            .ok()?;
+        Some(normalize(&directory.join(path)))
+    }
+}
+
+/// `path` without its . and .. components
+fn normalize(path: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized.display().to_string()
+}
+
+fn describe(listener: &OwnedFd, notif: &libc::seccomp_notif) -> Option<Notified> {
+    let nr = (notif.data.nr as u32 & !X32_SYSCALL_BIT) as c_long;
+    let (name, _, arity, strings) = TRACED.iter().find(|(_, traced, _, _)| *traced == nr)?;
+    let mem = File::open(format!("/proc/{}/mem", notif.pid))
+        .inspect_err(|e| debug!("unable to open the memory of {}: {}", notif.pid, e))
+        .ok()?;
+    let args = notif.data.args;
+    let mut written = Vec::with_capacity(*arity + 1);
+    for (i, arg) in args.iter().take(*arity).enumerate() {
+        if strings.contains(&i) {
+            written.push(read_string(&mem, *arg));
+        } else if *arg as i32 == AT_FDCWD {
+            written.push("AT_FDCWD".to_string());
+        } else {
+            written.push((*arg as i64).to_string());
+        }
+    }
+    let mut changed = WRITES
+        .iter()
+        .find(|(call, _)| call == name)
+        .map(|(_, path)| *path);
+    let opened = match *name {
+        "open" => Some((args[1] as libc::c_int, 0)),
+        "openat" => Some((args[2] as libc::c_int, 1)),
+        // struct open_how starts with the flags
+        "openat2" => Some((read_u64(&mem, args[2]).unwrap_or_default() as libc::c_int, 1)),
+        _ => None,
+    };
+    if let Some((flags, path)) = opened {
+        if *name == "openat2" {
+            written.push(format!("{{flags={}}}", open_flags(flags)));
+        } else {
+            written.push(open_flags(flags));
+        }
+        if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+        {
+            changed = Some(path);
+        }
+    }
+    if !valid(listener, notif.id) {
+        return None;
+    }
+    Some(Notified {
+        pid: notif.pid,
+        name,
+        args: written,
+        changed,
+    })
+}
+
+fn open_flags(flags: libc::c_int) -> String {
+    let mut names = vec![match flags & libc::O_ACCMODE {
+        libc::O_WRONLY => "O_WRONLY",
+        libc::O_RDWR => "O_RDWR",
+        _ => "O_RDONLY",
+    }];
+    for (flag, name) in OPEN_FLAGS {
+        if flags & flag == *flag {
+            names.push(name);
+        }
+    }
+    names.join("|")
+}
+
+fn read_u64(mem: &File, address: u64) -> Option<u64> {
+    let mut buf = [0u8; 8];
+    mem.read_exact_at(&mut buf, address).ok()?;
+    Some(u64::from_ne_bytes(buf))
+}
+
+/// Read a NUL-terminated string, page by page as the next page may not be mapped
+fn read_string(mem: &File, address: u64) -> String {
+    const PAGE: u64 = 4096;
+    if address == 0 {
+        return "NULL".to_string();
+    }
+    let mut bytes = Vec::new();
+    let mut offset = address;
+    while bytes.len() < libc::PATH_MAX as usize {
+        let mut buf = vec![0u8; (PAGE - offset % PAGE) as usize];
+        let read = match mem.read_at(&mut buf, offset) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        if let Some(end) = buf[..read].iter().position(|b| *b == 0) {
+            bytes.extend_from_slice(&buf[..end]);
+            break;
+        }
+        bytes.extend_from_slice(&buf[..read]);
+        offset += read as u64;
+    }
+    // escaped as strace does, so the line stays a single line of the log
+    let mut string = String::from("\"");
+    for c in String::from_utf8_lossy(&bytes).chars() {
+        match c {
+            '"' => string.push_str("\\\""),
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            c => string.push(c),
+        }
+    }
+    string.push('"');
+    string
+}